@@ -10,6 +10,7 @@ use std::path::Path;
 fn main() {
     let out_dir = env::var_os("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("ldtk_constants.rs");
+    let fields_dest_path = Path::new(&out_dir).join("ldtk_entity_fields.rs");
 
     // Path to your LDTK project file
     let ldtk_path = "assets/ldtk/project.ldtk"; // Adjust this path as needed
@@ -24,6 +25,14 @@ fn main() {
             std::process::exit(1);
         }
     }
+
+    match generate_entity_fields(ldtk_path, &fields_dest_path) {
+        Ok(_) => println!("Generated LDTK entity fields successfully"),
+        Err(e) => {
+            eprintln!("Failed to generate LDTK entity fields: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -34,14 +43,39 @@ struct LdtkProject {
 
 #[derive(Debug, Deserialize)]
 struct Defs {
+    layers: Vec<LayerDef>,
     entities: Vec<EntityDef>,
     tilesets: Vec<TilesetDef>,
     enums: Vec<EnumDef>,
 }
 
+#[derive(Debug, Deserialize)]
+struct LayerDef {
+    identifier: String,
+    #[serde(rename = "intGridValues")]
+    int_grid_values: Vec<IntGridValueDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IntGridValueDef {
+    value: i64,
+    identifier: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct EntityDef {
     identifier: String,
+    #[serde(rename = "fieldDefs")]
+    field_defs: Vec<FieldDef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FieldDef {
+    identifier: String,
+    #[serde(rename = "__type")]
+    field_type: String,
+    #[serde(rename = "canBeNull")]
+    can_be_null: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -88,6 +122,9 @@ fn generate_constants(
     // Generate layer constants
     generate_layer_constants(&mut output, &project)?;
 
+    // Generate IntGrid value enums
+    generate_int_grid_enums(&mut output, &project)?;
+
     // Generate entity constants
     generate_entity_constants(&mut output, &project)?;
 
@@ -134,6 +171,74 @@ fn generate_layer_constants(
     Ok(())
 }
 
+fn generate_int_grid_enums(
+    output: &mut File,
+    project: &LdtkProject,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(output, "#[allow(dead_code)]")?;
+    writeln!(output, "pub mod int_grid {{")?;
+
+    for layer in &project.defs.layers {
+        // Values without an identifier can't be given a name
+        let values: Vec<_> = layer
+            .int_grid_values
+            .iter()
+            .filter_map(|int_grid_value| {
+                let identifier = int_grid_value.identifier.as_ref()?;
+                Some((to_pascal_case(identifier), int_grid_value.value))
+            })
+            .collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        let enum_name = to_pascal_case(&layer.identifier);
+        writeln!(
+            output,
+            "    /// Values of the `{}` IntGrid layer",
+            layer.identifier
+        )?;
+        writeln!(
+            output,
+            "    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]"
+        )?;
+        writeln!(output, "    #[repr(i64)]")?;
+        writeln!(output, "    pub enum {} {{", enum_name)?;
+        for (variant, value) in &values {
+            writeln!(output, "        {} = {},", variant, value)?;
+        }
+        writeln!(output, "    }}")?;
+        writeln!(output)?;
+
+        writeln!(output, "    impl {} {{", enum_name)?;
+        writeln!(
+            output,
+            "        pub fn from_value(value: i64) -> Option<Self> {{"
+        )?;
+        writeln!(output, "            match value {{")?;
+        for (variant, value) in &values {
+            writeln!(
+                output,
+                "                {} => Some(Self::{}),",
+                value, variant
+            )?;
+        }
+        writeln!(output, "                _ => None,")?;
+        writeln!(output, "            }}")?;
+        writeln!(output, "        }}")?;
+        writeln!(output)?;
+        writeln!(output, "        pub const fn value(self) -> i64 {{")?;
+        writeln!(output, "            self as i64")?;
+        writeln!(output, "        }}")?;
+        writeln!(output, "    }}")?;
+    }
+
+    writeln!(output, "}}")?;
+    writeln!(output)?;
+
+    Ok(())
+}
+
 fn generate_entity_constants(
     output: &mut File,
     project: &LdtkProject,
@@ -223,12 +328,199 @@ fn generate_level_constants(
         )?;
     }
 
+    // Levels in the order they appear in the project
+    writeln!(output)?;
+    writeln!(output, "    pub const ALL: &[&str] = &[")?;
+    for level in &project.levels {
+        writeln!(output, "        \"{}\",", level.identifier)?;
+    }
+    writeln!(output, "    ];")?;
+
     writeln!(output, "}}")?;
     writeln!(output)?;
 
     Ok(())
 }
 
+fn generate_entity_fields(
+    ldtk_path: &str,
+    output_path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let json_content = read_to_string(ldtk_path)?;
+    let project: LdtkProject = serde_json::from_str(&json_content)?;
+
+    let mut output = File::create(output_path)?;
+
+    writeln!(output, "// Auto-generated LDTK entity fields - DO NOT EDIT")?;
+    writeln!(output)?;
+    writeln!(
+        output,
+        "{}",
+        r#"/// Grid cell of a point field, relative to the level
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridPoint {
+    pub cx: i64,
+    pub cy: i64,
+}
+
+impl GridPoint {
+    fn from_value(value: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            cx: value.get("cx")?.as_i64()?,
+            cy: value.get("cy")?.as_i64()?,
+        })
+    }
+}
+
+/// Another entity an entity reference field points to
+#[allow(dead_code)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EntityRef {
+    /// Instance id of the entity pointed to
+    pub entity_iid: String,
+    pub level_iid: String,
+}
+
+impl EntityRef {
+    fn from_value(value: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            entity_iid: value.get("entityIid")?.as_str()?.to_string(),
+            level_iid: value.get("levelIid")?.as_str()?.to_string(),
+        })
+    }
+}
+
+/// Value of one of the LDtk entity's custom fields
+#[allow(dead_code)]
+fn field_value<'a>(
+    entity: &'a ldtk_rust::EntityInstance,
+    identifier: &str,
+) -> Option<&'a serde_json::Value> {
+    entity
+        .field_instances
+        .iter()
+        .find(|field| field.identifier == identifier)
+        .and_then(|field| field.value.as_ref())
+}"#
+    )?;
+
+    for entity in &project.defs.entities {
+        if entity.field_defs.is_empty() {
+            continue;
+        }
+
+        let struct_name = format!("{}Fields", to_pascal_case(&entity.identifier));
+        writeln!(output)?;
+        writeln!(
+            output,
+            "/// Custom fields of the `{}` entity",
+            entity.identifier
+        )?;
+        writeln!(output, "#[allow(dead_code)]")?;
+        writeln!(output, "#[derive(Clone, Debug, PartialEq)]")?;
+        writeln!(output, "pub struct {} {{", struct_name)?;
+        for field in &entity.field_defs {
+            let (rust_type, _) = field_type(&field.field_type);
+            let rust_type = if field.can_be_null {
+                format!("Option<{}>", rust_type)
+            } else {
+                rust_type.to_string()
+            };
+            writeln!(
+                output,
+                "    pub {}: {},",
+                to_snake_case(&field.identifier),
+                rust_type
+            )?;
+        }
+        writeln!(output, "}}")?;
+        writeln!(output)?;
+
+        // Missing fields or values of the wrong type make the whole entity `None`
+        writeln!(output, "impl {} {{", struct_name)?;
+        writeln!(
+            output,
+            "    pub fn from_entity(entity: &ldtk_rust::EntityInstance) -> Option<Self> {{"
+        )?;
+        writeln!(output, "        Some(Self {{")?;
+        for field in &entity.field_defs {
+            let (_, parse) = field_type(&field.field_type);
+            let value = format!("field_value(entity, \"{}\")", field.identifier);
+            let expression = if field.can_be_null {
+                // Null and missing both come out as `None`
+                format!(
+                    "{}.filter(|value| !value.is_null()).and_then(|value| Some({}))",
+                    value,
+                    parse.replace("{}", "value")
+                )
+            } else {
+                parse.replace("{}", &format!("{}?", value))
+            };
+            writeln!(
+                output,
+                "            {}: {},",
+                to_snake_case(&field.identifier),
+                expression
+            )?;
+        }
+        writeln!(output, "        }})")?;
+        writeln!(output, "    }}")?;
+        writeln!(output, "}}")?;
+    }
+
+    Ok(())
+}
+
+/// Rust type of an LDtk field type, and the expression parsing it from a `serde_json::Value`
+/// in place of `{}`
+///
+/// Types without a better match, like enums, are kept as plain JSON.
+fn field_type(ldtk_type: &str) -> (&'static str, &'static str) {
+    match ldtk_type {
+        "Int" => ("i64", "{}.as_i64()?"),
+        "Float" => ("f32", "{}.as_f64()? as f32"),
+        "Bool" => ("bool", "{}.as_bool()?"),
+        "String" | "Multilines" | "FilePath" | "Color" => ("String", "{}.as_str()?.to_string()"),
+        "Point" => ("GridPoint", "GridPoint::from_value({})?"),
+        "EntityRef" => ("EntityRef", "EntityRef::from_value({})?"),
+        "Array<Int>" => (
+            "Vec<i64>",
+            "{}.as_array()?.iter().map(|value| value.as_i64()).collect::<Option<_>>()?",
+        ),
+        "Array<Float>" => (
+            "Vec<f32>",
+            "{}.as_array()?.iter().map(|value| value.as_f64().map(|value| value as f32)).collect::<Option<_>>()?",
+        ),
+        "Array<String>" => (
+            "Vec<String>",
+            "{}.as_array()?.iter().map(|value| value.as_str().map(str::to_string)).collect::<Option<_>>()?",
+        ),
+        "Array<Point>" => (
+            "Vec<GridPoint>",
+            "{}.as_array()?.iter().map(GridPoint::from_value).collect::<Option<_>>()?",
+        ),
+        "Array<EntityRef>" => (
+            "Vec<EntityRef>",
+            "{}.as_array()?.iter().map(EntityRef::from_value).collect::<Option<_>>()?",
+        ),
+        _ => ("serde_json::Value", "{}.clone()"),
+    }
+}
+
+fn to_pascal_case(s: &str) -> String {
+    s.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
 fn to_screaming_snake_case(s: &str) -> String {
     let mut result = String::new();
     let mut prev_was_lower = false;
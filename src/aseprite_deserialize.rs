@@ -59,6 +59,9 @@ pub struct FrameTag {
     pub to: usize,   // inclusive index in frames
     pub direction: String,
     pub color: String,
+    /// User data of the tag, only exported when set
+    #[serde(default)]
+    pub data: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,6 +75,19 @@ pub struct Slice {
 pub struct SliceKey {
     pub frame: usize,
     pub bounds: Rect,
+    /// Pivot point, relative to the top-left of `bounds`. Only exported when set.
+    #[serde(default)]
+    pub pivot: Option<Point>,
+    /// Center part of a 9-patch slice, relative to the top-left of `bounds`. Only exported
+    /// when set.
+    #[serde(default)]
+    pub center: Option<Rect>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
 }
 
 impl Aseprite {
@@ -139,3 +155,33 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_keys_with_optional_pivot() {
+        let slice: Slice = serde_json::from_str(
+            r##"{
+                "name": "gun_barrel",
+                "color": "#0000ffff",
+                "keys": [
+                    { "frame": 0, "bounds": { "x": 40, "y": 20, "w": 8, "h": 4 } },
+                    {
+                        "frame": 3,
+                        "bounds": { "x": 42, "y": 18, "w": 8, "h": 4 },
+                        "pivot": { "x": 8, "y": 2 },
+                        "center": { "x": 1, "y": 1, "w": 6, "h": 2 }
+                    }
+                ]
+            }"##,
+        )
+        .unwrap();
+
+        assert!(slice.keys[0].pivot.is_none());
+        assert!(slice.keys[0].center.is_none());
+        assert_eq!(slice.keys[1].pivot, Some(Point { x: 8, y: 2 }));
+        assert_eq!(slice.keys[1].center.as_ref().unwrap().w, 6);
+    }
+}
@@ -11,11 +11,41 @@ pub struct TileCoords {
     pub y: i64,
 }
 
+/// Role of a single edge of a merged collider rectangle
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum EdgeRole {
+    /// Edge is fully covered by other solid tiles
+    #[default]
+    Interior,
+    /// Top edge exposed to air, i.e. something can stand on it
+    FloorTop,
+    /// Bottom edge exposed to air
+    Ceiling,
+    /// Left or right edge exposed to air
+    Wall,
+}
+
+/// Edge metadata for a merged collider, computed from the original tile set
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq, Reflect)]
+pub struct SurfaceEdges {
+    pub top: EdgeRole,
+    pub bottom: EdgeRole,
+    pub left: EdgeRole,
+    pub right: EdgeRole,
+}
+
+impl SurfaceEdges {
+    pub fn is_walkable_top(&self) -> bool {
+        self.top == EdgeRole::FloorTop
+    }
+}
+
 #[derive(Bundle, Debug)]
 pub struct MergedTileColliderBundle {
     pub rigid_body: RigidBody,
     pub collider: Collider,
     pub transform: Transform,
+    pub surface_edges: SurfaceEdges,
 }
 
 #[derive(Component)]
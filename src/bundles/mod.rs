@@ -2,4 +2,4 @@ pub mod camera;
 pub mod level;
 pub mod platform;
 pub mod player;
-//pub mod spawn_point;
+pub mod spawn_point;
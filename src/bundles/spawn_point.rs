@@ -1,43 +1,99 @@
+use std::time::Duration;
+
+use avian2d::prelude::ColliderAabb;
 use bevy::prelude::*;
-use bevy_ecs_ldtk::LdtkEntity;
+use ldtk_rust::EntityInstance;
 
-use super::player::{Player, PlayerBundle};
+use crate::{
+    collision::child_collider_aabb,
+    constants,
+    health::PlayerDiedEvent,
+    level::{LdtkEntityAppExt, LdtkEntityContext, spawn_trigger_area},
+    player::PlayerSpawnEvent,
+    sprite_effect::FlashWhite,
+    states::GameState,
+    transition::{TransitionEvent, TransitionKind},
+};
 
-#[derive(Component, Debug, Default)]
-pub struct PlayerSpawnPoint;
+use super::player::Player;
 
-#[derive(Default, Bundle, LdtkEntity)]
-pub struct PlayerSpawnBundle {
-    player_spawn_point: PlayerSpawnPoint,
-}
+/// Touching a checkpoint makes the player respawn there instead of at the level start
+#[derive(Component, Debug, Default)]
+pub struct Checkpoint;
 
+/// Where the player respawns after dying
 #[derive(Resource, Default, Clone, Copy, Debug)]
 pub struct CurrentSpawn(pub Option<Transform>);
 
-#[derive(Event, Default)]
-pub struct RespawnPlayer;
-
 #[derive(Event)]
 pub struct SetSpawn(pub Transform);
 
-#[derive(Resource, Clone)]
-pub struct SpawnSettings {}
-impl Default for SpawnSettings {
+/// Deaths the player can take before it's game over
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Lives(pub u32);
+
+pub const STARTING_LIVES: u32 = 3;
+const CHECKPOINT_FLASH: Duration = Duration::from_millis(150);
+
+impl Default for Lives {
     fn default() -> Self {
-        Self {}
+        Self(STARTING_LIVES)
     }
 }
 
+fn spawn_ldtk_checkpoint(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    spawn_trigger_area(commands, context.level_id, entity, Checkpoint);
+}
+
 pub struct PlayerSpawnPlugin;
 
 impl Plugin for PlayerSpawnPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<CurrentSpawn>()
-            .init_resource::<SpawnSettings>()
-            .add_event::<RespawnPlayer>()
+        app.register_ldtk_entity(constants::entities::CHECKPOINT, spawn_ldtk_checkpoint)
+            .init_resource::<CurrentSpawn>()
+            .init_resource::<Lives>()
             .add_event::<SetSpawn>()
-            // Run after your level/scene is loaded (Startup is fine for code-built levels).
-            .add_systems(Update, (apply_set_spawn_events, handle_respawn));
+            .add_systems(
+                Update,
+                (activate_checkpoints, apply_set_spawn_events, handle_respawn)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+    }
+}
+
+/// Move the spawn to any checkpoint the player overlaps, flashing the player to show it
+fn activate_checkpoints(
+    mut commands: Commands,
+    checkpoint_query: Query<(&Transform, &ColliderAabb), With<Checkpoint>>,
+    player_query: Query<(Entity, &Children), With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    current: Res<CurrentSpawn>,
+    mut ev_set: EventWriter<SetSpawn>,
+) {
+    for (player, children) in player_query.iter() {
+        let Some(player_aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        for (transform, checkpoint_aabb) in checkpoint_query.iter() {
+            let already_set = current
+                .0
+                .is_some_and(|spawn| spawn.translation.xy() == transform.translation.xy());
+            if !already_set && checkpoint_aabb.intersects(&player_aabb) {
+                info!("Checkpoint reached at {}", transform.translation.xy());
+                ev_set.write(SetSpawn(
+                    transform.with_translation(transform.translation.xy().extend(1.0)),
+                ));
+                commands
+                    .entity(player)
+                    .insert(FlashWhite::new(CHECKPOINT_FLASH));
+            }
+        }
     }
 }
 
@@ -48,23 +104,28 @@ fn apply_set_spawn_events(mut current: ResMut<CurrentSpawn>, mut ev_set: EventRe
     }
 }
 
-/// Despawn existing players and (re)spawn at CurrentSpawn on `RespawnPlayer`.
+/// Fade in over the respawned player, hiding the camera jumping to them
+const RESPAWN_TRANSITION: Duration = Duration::from_millis(400);
+
+/// Despawn the dead player and spawn a new one at CurrentSpawn, or end the game once the
+/// last life is lost
 fn handle_respawn(
     mut commands: Commands,
-    mut ev_respawn: EventReader<RespawnPlayer>,
+    mut ev_died: EventReader<PlayerDiedEvent>,
     current: Res<CurrentSpawn>,
-    mut player: Query<Entity, With<Player>>,
+    mut lives: ResMut<Lives>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut ev_spawn: EventWriter<PlayerSpawnEvent>,
+    mut ev_transition: EventWriter<TransitionEvent>,
 ) {
-    let mut should = false;
-    for _ in ev_respawn.read() {
-        should = true;
-    }
-    if !should {
-        return;
-    }
+    for event in ev_died.read() {
+        commands.entity(event.player).despawn();
 
-    if let Ok(player) = player.single_mut() {
-        commands.entity(player).despawn();
+        lives.0 = lives.0.saturating_sub(1);
+        if lives.0 == 0 {
+            next_state.set(GameState::GameOver);
+            continue;
+        }
 
         let at = match current.0 {
             Some(t) => t,
@@ -73,10 +134,82 @@ fn handle_respawn(
                 Transform::default()
             }
         };
+        ev_spawn.write(PlayerSpawnEvent(at));
+        ev_transition.write(TransitionEvent::reveal(
+            TransitionKind::Fade,
+            RESPAWN_TRANSITION,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_respawn_at_last_checkpoint() {
+        let mut world = World::new();
+        world.init_resource::<CurrentSpawn>();
+        world.init_resource::<Lives>();
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<Events<SetSpawn>>();
+        world.init_resource::<Events<PlayerDiedEvent>>();
+        world.init_resource::<Events<PlayerSpawnEvent>>();
+        world.init_resource::<Events<TransitionEvent>>();
+
+        let level_start = Transform::from_xyz(0.0, 0.0, 1.0);
+        world.send_event(SetSpawn(level_start));
+        world.run_system_once(apply_set_spawn_events).unwrap();
+
+        world.spawn((
+            Checkpoint,
+            Transform::from_xyz(200.0, 50.0, 0.0),
+            ColliderAabb::new(Vec2::new(200.0, 50.0), Vec2::new(16.0, 32.0)),
+        ));
+        let player = world
+            .spawn(Player)
+            .with_child(ColliderAabb::new(
+                Vec2::new(190.0, 40.0),
+                Vec2::new(8.0, 16.0),
+            ))
+            .id();
+
+        world.run_system_once(activate_checkpoints).unwrap();
+        world.run_system_once(apply_set_spawn_events).unwrap();
+
+        world.send_event(PlayerDiedEvent { player });
+        world.run_system_once(handle_respawn).unwrap();
+
+        assert!(world.get_entity(player).is_err());
+        let spawns: Vec<_> = world
+            .resource_mut::<Events<PlayerSpawnEvent>>()
+            .drain()
+            .map(|event| event.0.translation)
+            .collect();
+        assert_eq!(spawns, vec![Vec3::new(200.0, 50.0, 1.0)]);
+    }
+
+    #[test]
+    fn test_last_life_ends_the_game() {
+        let mut world = World::new();
+        world.insert_resource(CurrentSpawn(Some(Transform::default())));
+        world.insert_resource(Lives(1));
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<Events<PlayerDiedEvent>>();
+        world.init_resource::<Events<PlayerSpawnEvent>>();
+        world.init_resource::<Events<TransitionEvent>>();
+
+        let player = world.spawn(Player).id();
+        world.send_event(PlayerDiedEvent { player });
+        world.run_system_once(handle_respawn).unwrap();
 
-        commands.spawn(PlayerBundle {
-            transform: at,
-            ..Default::default()
-        });
+        assert_eq!(*world.resource::<Lives>(), Lives(0));
+        assert!(world.resource::<Events<PlayerSpawnEvent>>().is_empty());
+        assert!(matches!(
+            *world.resource::<NextState<GameState>>(),
+            NextState::Pending(GameState::GameOver)
+        ));
     }
 }
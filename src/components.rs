@@ -1,4 +1,13 @@
 use bevy::prelude::*;
 
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct GameEntity;
+
+/// Belongs to the loaded levels, despawned when another level is loaded
+#[derive(Component, Default)]
+pub struct LevelEntity;
+
+/// Level an entity was spawned for, so it can be unloaded along with it
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+#[require(GameEntity, LevelEntity)]
+pub struct LevelId(pub String);
@@ -1,4 +1,5 @@
 use avian2d::prelude::PhysicsLayer;
+use serde::Deserialize;
 
 pub const TILE_SIZE: f32 = 16.0;
 
@@ -6,6 +7,28 @@ pub const fn multiply_by_tile_size(value: i64) -> f32 {
     value as f32 * TILE_SIZE
 }
 
+/// Speed measured in tiles per second
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Deserialize)]
+pub struct TilesPerSecond(pub f32);
+
+impl TilesPerSecond {
+    /// Pixels per second, the unit velocities use
+    pub const fn to_pixels(self) -> f32 {
+        self.0 * TILE_SIZE
+    }
+}
+
+/// Acceleration measured in tiles per second squared
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub struct TilesPerSecondSquared(pub f32);
+
+impl TilesPerSecondSquared {
+    /// Pixels per second squared, the unit accelerations and gravity use
+    pub const fn to_pixels(self) -> f32 {
+        self.0 * TILE_SIZE
+    }
+}
+
 pub const PLAYER_WIDTH: f32 = multiply_by_tile_size(2);
 pub const PLAYER_HEIGHT: f32 = multiply_by_tile_size(3);
 
@@ -17,4 +40,26 @@ pub enum GameLayer {
     Default,
     Player,
     LevelGeometry,
+    /// Level geometry that can be jumped through from below and only blocks falling onto it
+    OneWayPlatform,
+    /// Sensor areas that react to the player overlapping them
+    Trigger,
+    Enemy,
+    /// Shots fired by the player, hitting only enemies and level geometry
+    PlayerProjectile,
+    /// Shots fired by enemies, hitting only the player and level geometry
+    EnemyProjectile,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_conversions() {
+        assert_eq!(TilesPerSecond(1.0).to_pixels(), TILE_SIZE);
+        assert_eq!(TilesPerSecond(2.5).to_pixels(), 40.0);
+        assert_eq!(TilesPerSecondSquared(30.0).to_pixels(), 480.0);
+        assert_eq!(TilesPerSecond(0.0).to_pixels(), 0.0);
+    }
 }
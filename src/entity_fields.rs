@@ -0,0 +1,6 @@
+//! Typed custom fields of the LDtk project's entities, generated by build.rs
+//!
+//! Each entity with custom fields gets an `<Entity>Fields` struct with a `from_entity`
+//! constructor reading them out of an entity instance.
+
+include!(concat!(env!("OUT_DIR"), "/ldtk_entity_fields.rs"));
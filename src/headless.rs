@@ -0,0 +1,448 @@
+//! Headless simulation of the player controller
+//!
+//! Runs the player's movement, collision and gravity systems against real physics colliders,
+//! without a window, renderer or egui. Every update is exactly one fixed timestep, and input
+//! is set straight on the player's `ActionState`, or played back from a replay.
+
+use avian2d::prelude::*;
+use bevy::{platform::time::Instant, prelude::*, time::TimeUpdateStrategy};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    bundles::player::Player,
+    constants::{GameLayer, PLAYER_HEIGHT, PLAYER_WIDTH, TILE_SIZE},
+    plugins::{
+        abilities::PlayerAbilities,
+        aim::AimMode,
+        animation::NextAnimation,
+        audio::PlaySfxEvent,
+        character::CharacterPlugin,
+        collision::{CollisionBundle, CollisionPlugin},
+        gravity::GravityPlugin,
+        noclip::{NoclipConfig, fly_noclip},
+        player::{
+            COLLIDER_INSET, PlayerAction, PlayerAnimations, PlayerMovementPlugin, inset_hitbox,
+            player_collision_config, player_controller, player_gravity,
+        },
+        replay::{ReplayBuffer, record_or_replay_input},
+    },
+    schedule::{MovementSet, SchedulePlugin},
+};
+
+/// Size of the player's collider when the sprite has no hitbox slice
+pub fn player_collider_size() -> Vec2 {
+    inset_hitbox(Vec2::new(PLAYER_WIDTH, PLAYER_HEIGHT), COLLIDER_INSET)
+}
+
+pub struct Simulation {
+    app: App,
+    player: Option<Entity>,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            TransformPlugin,
+            // Avian's scene and mesh colliders need the asset server around
+            AssetPlugin::default(),
+            bevy::scene::ScenePlugin,
+            PhysicsPlugins::default().with_length_unit(TILE_SIZE),
+            SchedulePlugin,
+        ))
+        .init_asset::<Mesh>()
+        .add_plugins((
+            CharacterPlugin,
+            CollisionPlugin,
+            GravityPlugin,
+            PlayerMovementPlugin,
+        ))
+        .add_event::<PlaySfxEvent>()
+        .init_resource::<PlayerAbilities>()
+        .init_resource::<ReplayBuffer>()
+        .init_resource::<NoclipConfig>()
+        .add_systems(FixedPreUpdate, record_or_replay_input)
+        .add_systems(FixedUpdate, fly_noclip.in_set(MovementSet::ApplyVelocity))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(
+            Time::<Fixed>::default().timestep(),
+        ));
+        app.finish();
+        app.cleanup();
+
+        // The clocks start on the first update, each one after it runs a single timestep
+        app.update();
+
+        Self { app, player: None }
+    }
+
+    /// Solid level geometry centered at `center`
+    pub fn spawn_block(&mut self, center: Vec2, size: Vec2) -> Entity {
+        self.app
+            .world_mut()
+            .spawn((
+                RigidBody::Static,
+                Collider::rectangle(size.x, size.y),
+                Transform::from_translation(center.extend(0.0)),
+                CollisionLayers::new(
+                    GameLayer::LevelGeometry,
+                    [GameLayer::Player, GameLayer::Default],
+                ),
+            ))
+            .id()
+    }
+
+    /// The player with the same movement and collision setup as in the game, standing with
+    /// its feet at `feet`
+    pub fn spawn_player(&mut self, feet: Vec2) -> Entity {
+        let collider_size = player_collider_size();
+        // A little above the ground, so the collider doesn't start out touching it
+        let position = feet + Vec2::new(0.0, collider_size.y / 2.0 + 0.5);
+
+        let player = self
+            .app
+            .world_mut()
+            .spawn((
+                Player,
+                Transform::from_translation(position.extend(0.0)),
+                RigidBody::Kinematic,
+                LockedAxes::ROTATION_LOCKED,
+                CollisionBundle {
+                    config: player_collision_config(),
+                    ..Default::default()
+                },
+                CollisionLayers::new(
+                    GameLayer::Player,
+                    [GameLayer::LevelGeometry, GameLayer::Default],
+                ),
+                player_gravity(),
+                player_controller(),
+                ActionState::<PlayerAction>::default(),
+                AimMode::default(),
+                Sprite::default(),
+                NextAnimation::<PlayerAnimations> { key: None },
+            ))
+            .with_children(|children| {
+                children.spawn((
+                    Collider::rectangle(collider_size.x, collider_size.y),
+                    Transform::default(),
+                ));
+            })
+            .id();
+        self.player = Some(player);
+        player
+    }
+
+    fn player(&self) -> Entity {
+        self.player.expect("no player spawned")
+    }
+
+    /// A component of the player
+    pub fn get<T: Component>(&self) -> &T {
+        self.app
+            .world()
+            .get::<T>(self.player())
+            .expect("player is missing the component")
+    }
+
+    /// Add components to the player, like `Noclip`
+    pub fn insert(&mut self, bundle: impl Bundle) {
+        let player = self.player();
+        self.app.world_mut().entity_mut(player).insert(bundle);
+    }
+
+    pub fn abilities(&mut self) -> Mut<'_, PlayerAbilities> {
+        self.app.world_mut().resource_mut::<PlayerAbilities>()
+    }
+
+    pub fn replay(&mut self) -> Mut<'_, ReplayBuffer> {
+        self.app.world_mut().resource_mut::<ReplayBuffer>()
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.get::<Transform>().translation.xy()
+    }
+
+    pub fn press(&mut self, action: PlayerAction) {
+        self.action_state().press(&action);
+    }
+
+    pub fn release(&mut self, action: PlayerAction) {
+        self.action_state().release(&action);
+    }
+
+    fn action_state(&mut self) -> Mut<'_, ActionState<PlayerAction>> {
+        let player = self.player();
+        self.app
+            .world_mut()
+            .get_mut::<ActionState<PlayerAction>>(player)
+            .expect("player has no input")
+    }
+
+    /// Run a single fixed timestep
+    pub fn step(&mut self) {
+        self.app.update();
+
+        // Presses are only "just pressed" for the first timestep, like with the input plugin
+        if self.player.is_some() {
+            let now = Instant::now();
+            self.action_state().tick(now, now);
+        }
+    }
+
+    pub fn run(&mut self, steps: u32) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plugins::collision::{IsGrounded, IsTouchingWallRight, Noclip, Velocity};
+
+    use super::*;
+
+    /// Long enough for a freshly spawned player to land and its jump cooldown to pass
+    const SETTLE_STEPS: u32 = 40;
+
+    /// A level that is a single floor, 20 tiles wide with its top at y = 0
+    fn flat_ground() -> Simulation {
+        let mut simulation = Simulation::new();
+        simulation.spawn_block(
+            Vec2::new(0.0, -TILE_SIZE / 2.0),
+            Vec2::new(20.0 * TILE_SIZE, TILE_SIZE),
+        );
+        simulation
+    }
+
+    #[test]
+    fn test_jump_reaches_apex() {
+        let mut simulation = flat_ground();
+        simulation.spawn_player(Vec2::ZERO);
+        simulation.run(SETTLE_STEPS);
+        assert!(simulation.get::<IsGrounded>().grounded);
+        let start = simulation.position().y;
+
+        // Held Jump: full speed up while gravity immune, then slowing down under gravity
+        let controller = player_controller();
+        let gravity = player_gravity().gravity;
+        let expected_apex = controller.jump_force * controller.gravity_immunity.as_secs_f32()
+            + controller.jump_force.powi(2) / (2.0 * gravity);
+
+        simulation.press(PlayerAction::Jump);
+        let mut apex = start;
+        for _ in 0..64 {
+            simulation.step();
+            apex = apex.max(simulation.position().y);
+        }
+
+        assert!(
+            (apex - start - expected_apex).abs() < TILE_SIZE / 4.0,
+            "jumped {} pixels, expected {}",
+            apex - start,
+            expected_apex
+        );
+    }
+
+    #[test]
+    fn test_held_jump_only_jumps_once() {
+        let mut simulation = flat_ground();
+        simulation.spawn_player(Vec2::ZERO);
+        simulation.run(SETTLE_STEPS);
+
+        // Still touching the ground on the step after the jump, which mustn't jump again
+        simulation.press(PlayerAction::Jump);
+        simulation.run(2);
+        let jump_force = player_controller().jump_force;
+        let velocity_y = simulation.get::<Velocity>().0.y;
+        assert!(
+            velocity_y <= jump_force * 1.01,
+            "jumped at {} px/s, a single jump is {}",
+            velocity_y,
+            jump_force
+        );
+    }
+
+    #[test]
+    fn test_player_spawned_in_the_floor_is_pushed_out() {
+        let mut simulation = flat_ground();
+        simulation.spawn_player(Vec2::new(0.0, -6.0));
+        simulation.step();
+
+        let feet = simulation.position().y - player_collider_size().y / 2.0;
+        assert!(feet >= -0.1, "feet still {} pixels in the floor", -feet);
+    }
+
+    #[test]
+    fn test_noclip_flies_through_walls() {
+        let mut simulation = flat_ground();
+        simulation.spawn_player(Vec2::ZERO);
+        // A wall two tiles thick, from x = 2 to 4 tiles
+        let wall_right = 4.0 * TILE_SIZE;
+        simulation.spawn_block(
+            Vec2::new(3.0 * TILE_SIZE, 2.0 * TILE_SIZE),
+            Vec2::new(2.0 * TILE_SIZE, 4.0 * TILE_SIZE),
+        );
+        simulation.run(SETTLE_STEPS);
+
+        // Walking into it stops at the wall
+        simulation.press(PlayerAction::Right);
+        simulation.run(30);
+        assert!(simulation.get::<IsTouchingWallRight>().0);
+        assert!(simulation.position().x < wall_right);
+
+        simulation.insert(Noclip);
+        simulation.run(15);
+        let left_edge = simulation.position().x - player_collider_size().x / 2.0;
+        assert!(
+            left_edge > wall_right,
+            "stuck at {}, the wall ends at {}",
+            left_edge,
+            wall_right
+        );
+    }
+
+    /// Floor with a hole `gap` wide centered on x = 0, and the player settled above the hole
+    fn fall_towards_gap(gap: f32) -> Simulation {
+        let mut simulation = Simulation::new();
+        let floor_width = 8.0 * TILE_SIZE;
+        for side in [-1.0, 1.0] {
+            simulation.spawn_block(
+                Vec2::new(side * (gap + floor_width) / 2.0, -TILE_SIZE / 2.0),
+                Vec2::new(floor_width, TILE_SIZE),
+            );
+        }
+        simulation.spawn_player(Vec2::new(0.0, TILE_SIZE));
+        simulation.run(SETTLE_STEPS);
+        simulation
+    }
+
+    #[test]
+    fn test_player_falls_through_player_wide_gap() {
+        let feet =
+            |simulation: &Simulation| simulation.position().y - player_collider_size().y / 2.0;
+
+        let simulation = fall_towards_gap(PLAYER_WIDTH);
+        assert!(
+            feet(&simulation) < -TILE_SIZE,
+            "caught at {} in a gap as wide as the player",
+            feet(&simulation)
+        );
+
+        // Any narrower and the floor holds
+        let simulation = fall_towards_gap(PLAYER_WIDTH - TILE_SIZE / 2.0);
+        assert!(simulation.get::<IsGrounded>().grounded);
+        assert!(feet(&simulation) > -1.0);
+    }
+
+    #[test]
+    fn test_coyote_jump_after_walking_off_ledge() {
+        let mut simulation = Simulation::new();
+        // Floor ending at x = 0
+        simulation.spawn_block(
+            Vec2::new(-5.0 * TILE_SIZE, -TILE_SIZE / 2.0),
+            Vec2::new(10.0 * TILE_SIZE, TILE_SIZE),
+        );
+        simulation.spawn_player(Vec2::new(-3.0 * TILE_SIZE, 0.0));
+        simulation.run(SETTLE_STEPS);
+        let ledge_height = simulation.position().y;
+
+        simulation.press(PlayerAction::Right);
+        for _ in 0..120 {
+            if !simulation.get::<IsGrounded>().grounded {
+                break;
+            }
+            simulation.step();
+        }
+        assert!(!simulation.get::<IsGrounded>().grounded);
+        simulation.release(PlayerAction::Right);
+
+        // Already falling, but still within the coyote time
+        simulation.run(3);
+        assert!(simulation.position().y < ledge_height);
+        simulation.press(PlayerAction::Jump);
+        simulation.step();
+        assert!(simulation.get::<Velocity>().0.y > 0.0);
+
+        simulation.run(10);
+        assert!(simulation.position().y > ledge_height);
+    }
+
+    #[test]
+    fn test_wall_stops_walking() {
+        let mut simulation = flat_ground();
+        // Wall 5 tiles tall, far taller than a step, with its left side at x = 48
+        let wall_left = 3.0 * TILE_SIZE;
+        simulation.spawn_block(
+            Vec2::new(wall_left + TILE_SIZE, 2.5 * TILE_SIZE),
+            Vec2::new(2.0 * TILE_SIZE, 5.0 * TILE_SIZE),
+        );
+        simulation.spawn_player(Vec2::ZERO);
+        simulation.run(SETTLE_STEPS);
+
+        simulation.press(PlayerAction::Right);
+        simulation.run(90);
+
+        let right_edge = simulation.position().x + player_collider_size().x / 2.0;
+        assert!(
+            right_edge <= wall_left + 0.01 && right_edge > wall_left - 1.0,
+            "player's right edge at {}, wall at {}",
+            right_edge,
+            wall_left
+        );
+        assert!(simulation.get::<IsTouchingWallRight>().0);
+        assert!(simulation.get::<IsGrounded>().grounded);
+    }
+
+    #[test]
+    fn test_double_jump_needs_ability() {
+        let jump_force = player_controller().jump_force;
+        // A short hop, then Jump again on the way down
+        let jump_twice = |double_jump: bool| {
+            let mut simulation = flat_ground();
+            simulation.abilities().double_jump = double_jump;
+            simulation.spawn_player(Vec2::ZERO);
+            simulation.run(SETTLE_STEPS);
+
+            simulation.press(PlayerAction::Jump);
+            simulation.run(4);
+            simulation.release(PlayerAction::Jump);
+            simulation.run(16);
+            assert!(!simulation.get::<IsGrounded>().grounded);
+
+            simulation.press(PlayerAction::Jump);
+            simulation.step();
+            simulation.get::<Velocity>().0.y
+        };
+
+        assert!(jump_twice(false) < jump_force * 0.5);
+        assert!(jump_twice(true) > jump_force * 0.9);
+    }
+
+    #[test]
+    fn test_replay_reproduces_movement() {
+        let mut recording = flat_ground();
+        recording.spawn_player(Vec2::ZERO);
+        recording.replay().start_recording();
+        recording.run(SETTLE_STEPS);
+        recording.press(PlayerAction::Right);
+        recording.run(20);
+        recording.press(PlayerAction::Jump);
+        recording.run(10);
+        recording.release(PlayerAction::Jump);
+        recording.release(PlayerAction::Right);
+        recording.press(PlayerAction::Left);
+        recording.run(30);
+        recording.replay().stop();
+        let frames = recording.replay().frames.clone();
+
+        let mut playback = flat_ground();
+        playback.spawn_player(Vec2::ZERO);
+        playback.replay().frames = frames;
+        playback.replay().start_playback();
+        playback.run(SETTLE_STEPS + 60);
+
+        assert_eq!(playback.position(), recording.position());
+    }
+}
@@ -8,21 +8,28 @@ mod aseprite_deserialize;
 mod bundles;
 mod components;
 mod constants;
+mod entity_fields;
+#[cfg(test)]
+mod headless;
 mod level_enums;
 mod plugins;
+mod schedule;
 mod states;
 mod tile_merger;
 
-use animation_library::AnimationLibraryPlugin;
-use collision::CollisionPlugin;
+use animation_library::{AnimationLibraryPlugin, AnimationManifest};
+use boss::BossManifest;
+use bundles::spawn_point::PlayerSpawnPlugin;
+use collision::{CollisionDebugPlugin, CollisionPlugin};
 pub use constants::multiply_by_tile_size;
 use gravity::GravityPlugin;
 use leafwing_input_manager::plugin::InputManagerPlugin;
-use level::LevelPlugin;
+use level::{LdtkProject, LevelPlugin};
 use player::{PlayerAction, PlayerPlugin};
 use plugins::*;
 use projectile::ProjectilePlugin;
-use states::GameState;
+use schedule::SchedulePlugin;
+use states::{GameState, PauseState};
 
 pub use constants::{entities, enums, layers, levels};
 
@@ -32,20 +39,79 @@ fn main() {
             DefaultPlugins.set(ImagePlugin::default_nearest()),
             PhysicsPlugins::default().with_length_unit(constants::TILE_SIZE),
             PhysicsDebugPlugin::default(),
+            SchedulePlugin,
             EguiPlugin::default(),
             WorldInspectorPlugin::new(),
             JsonAssetPlugin::<Aseprite>::new(&["json"]),
+            JsonAssetPlugin::<LdtkProject>::new(&["ldtk"]),
+            JsonAssetPlugin::<AnimationManifest>::new(&["animations.json"]),
+            JsonAssetPlugin::<BossManifest>::new(&["bosses.json"]),
             InputManagerPlugin::<PlayerAction>::default(),
+        ))
+        .add_plugins((
             AnimationLibraryPlugin,
             PlayerPlugin,
+            AimPlugin,
             CameraPlugin,
             GamePlugin,
             LevelPlugin,
             CollisionPlugin,
             GravityPlugin,
             ProjectilePlugin,
+            TrajectoryPlugin,
+            GoalPlugin,
+            SpeedrunPlugin,
+            EnemyPlugin,
+            SpawnerPlugin,
+            InteractionPlugin,
+        ))
+        .add_plugins((
+            AnimatedTilePlugin,
+            DashPlugin,
+            MovingPlatformPlugin,
+            ForceZonePlugin,
+            ClimbingPlugin,
+            LedgePlugin,
+            CrouchPlugin,
+            HealthPlugin,
+            HazardPlugin,
+            PlayerSpawnPlugin,
+            KillZonePlugin,
+            AiPlugin,
+            MeleePlugin,
+            ChargeShotPlugin,
+            CharacterPlugin,
+        ))
+        .add_plugins((
+            PickupPlugin,
+            HudPlugin,
+            ScreensPlugin,
+            LevelStreamingPlugin,
+            LoadingPlugin,
+            SurfacePlugin,
+            ParallaxPlugin,
+            TransitionPlugin,
+            SliceColliderPlugin,
+            SpriteEffectPlugin,
+            AudioPlugin,
+            SettingsPlugin,
+            SavePlugin,
+            ControlsPlugin,
+            TouchControlsPlugin,
+        ))
+        .add_plugins((AbilitiesPlugin, BossPlugin, BreakablePlugin))
+        // Debug tooling
+        .add_plugins((
+            CollisionDebugPlugin,
+            DebugOverlayPlugin,
+            DevConsolePlugin,
+            NoclipPlugin,
+            InputRecorderPlugin,
+            TimeControlPlugin,
+            HotReloadPlugin,
         ))
         .insert_resource(Gravity(Vec2::NEG_Y * multiply_by_tile_size(10)))
         .init_state::<GameState>()
+        .add_sub_state::<PauseState>()
         .run();
 }
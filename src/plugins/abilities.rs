@@ -0,0 +1,301 @@
+use std::time::Duration;
+
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bundles::player::Player, components::GameEntity, constants, entity_fields::AbilityPickupFields,
+    states::GameState,
+};
+
+use super::{
+    audio::{PlaySfxEvent, SfxId},
+    collision::child_collider_aabb,
+    dev_console::{ConsoleCommandsAppExt, expect_args, parse_arg},
+    level::{LdtkEntityAppExt, LdtkEntityContext, spawn_trigger_area},
+};
+
+/// Movement ability the player starts without, unlocked by finding its pickup
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum Ability {
+    DoubleJump,
+    Dash,
+    WallJump,
+}
+
+impl Ability {
+    pub const ALL: [Self; 3] = [Self::DoubleJump, Self::Dash, Self::WallJump];
+
+    /// Ability matching the `ability` field of LDtk ability pickup entities
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "double_jump" => Some(Self::DoubleJump),
+            "dash" => Some(Self::Dash),
+            "wall_jump" => Some(Self::WallJump),
+            _ => None,
+        }
+    }
+
+    /// Name shown to the player when it's unlocked
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::DoubleJump => "Double jump",
+            Self::Dash => "Dash",
+            Self::WallJump => "Wall jump",
+        }
+    }
+}
+
+/// Abilities unlocked so far, kept across levels and deaths, and in the save
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct PlayerAbilities {
+    pub double_jump: bool,
+    pub dash: bool,
+    pub wall_jump: bool,
+}
+
+/// Dash and wall jump were always part of the moveset, only double jump has to be found
+impl Default for PlayerAbilities {
+    fn default() -> Self {
+        Self {
+            double_jump: false,
+            dash: true,
+            wall_jump: true,
+        }
+    }
+}
+
+impl PlayerAbilities {
+    pub fn has(&self, ability: Ability) -> bool {
+        match ability {
+            Ability::DoubleJump => self.double_jump,
+            Ability::Dash => self.dash,
+            Ability::WallJump => self.wall_jump,
+        }
+    }
+
+    /// Unlock `ability`, returning whether it wasn't unlocked already
+    pub fn unlock(&mut self, ability: Ability) -> bool {
+        let flag = match ability {
+            Ability::DoubleJump => &mut self.double_jump,
+            Ability::Dash => &mut self.dash,
+            Ability::WallJump => &mut self.wall_jump,
+        };
+        !std::mem::replace(flag, true)
+    }
+}
+
+/// Unlocks its ability when the player touches it
+#[derive(Component, Debug, Reflect)]
+#[require(Sprite)]
+pub struct AbilityPickup(pub Ability);
+
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct AbilityUnlockedEvent(pub Ability);
+
+/// Text announcing an unlocked ability, gone once its timer runs out
+#[derive(Component)]
+struct UnlockBanner(Timer);
+
+const ABILITY_PICKUP_SIZE: f32 = 12.0;
+const ABILITY_PICKUP_COLOR: Color = Color::srgb(0.6, 0.4, 0.95);
+const BANNER_DURATION: Duration = Duration::from_millis(2500);
+const BANNER_FONT_SIZE: f32 = 16.0;
+
+/// Pickups of abilities the player already has don't show up again
+fn setup_ability_pickups(
+    mut commands: Commands,
+    mut query: Query<(Entity, &AbilityPickup, &mut Sprite), Added<AbilityPickup>>,
+    abilities: Res<PlayerAbilities>,
+) {
+    for (entity, pickup, mut sprite) in query.iter_mut() {
+        if abilities.has(pickup.0) {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        sprite.color = ABILITY_PICKUP_COLOR;
+        sprite.custom_size = Some(Vec2::splat(ABILITY_PICKUP_SIZE));
+    }
+}
+
+fn collect_ability_pickups(
+    mut commands: Commands,
+    pickup_query: Query<(Entity, &AbilityPickup, &ColliderAabb)>,
+    player_query: Query<&Children, With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut abilities: ResMut<PlayerAbilities>,
+    mut event_writer: EventWriter<AbilityUnlockedEvent>,
+) {
+    for children in player_query.iter() {
+        let Some(player_aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        for (entity, pickup, pickup_aabb) in pickup_query.iter() {
+            if !pickup_aabb.intersects(&player_aabb) {
+                continue;
+            }
+            commands.entity(entity).despawn();
+            if abilities.unlock(pickup.0) {
+                event_writer.write(AbilityUnlockedEvent(pickup.0));
+            }
+        }
+    }
+}
+
+fn show_unlock_banner(
+    mut commands: Commands,
+    mut event_reader: EventReader<AbilityUnlockedEvent>,
+    banner_query: Query<Entity, With<UnlockBanner>>,
+    mut sfx_writer: EventWriter<PlaySfxEvent>,
+) {
+    let Some(AbilityUnlockedEvent(ability)) = event_reader.read().last() else {
+        return;
+    };
+
+    // The newest unlock replaces whatever was still showing
+    for banner in banner_query.iter() {
+        commands.entity(banner).despawn();
+    }
+    commands
+        .spawn((
+            UnlockBanner(Timer::new(BANNER_DURATION, TimerMode::Once)),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(20.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            GameEntity,
+        ))
+        .with_child((
+            Text::new(format!("{} unlocked!", ability.display_name())),
+            TextFont {
+                font_size: BANNER_FONT_SIZE,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        ));
+    sfx_writer.write(PlaySfxEvent(SfxId::Pickup));
+}
+
+fn update_unlock_banners(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut UnlockBanner)>,
+    time: Res<Time>,
+) {
+    for (entity, mut banner) in query.iter_mut() {
+        if banner.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn unlock_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name: String = parse_arg(args, 0, "ability, e.g. 'unlock dash'")?;
+    expect_args(args, 1)?;
+
+    let unlocked = if name == "all" {
+        Ability::ALL.to_vec()
+    } else {
+        vec![Ability::from_name(&name).ok_or_else(|| format!("unknown ability '{}'", name))?]
+    };
+    for ability in unlocked {
+        if world.resource_mut::<PlayerAbilities>().unlock(ability) {
+            world.send_event(AbilityUnlockedEvent(ability));
+        }
+    }
+    Ok(format!("unlocked {}", name))
+}
+
+fn spawn_ldtk_ability_pickup(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    let ability = AbilityPickupFields::from_entity(entity)
+        .and_then(|fields| Ability::from_name(&fields.ability));
+    if let Some(ability) = ability {
+        spawn_trigger_area(commands, context.level_id, entity, AbilityPickup(ability));
+    } else {
+        warn!("invalid ability pickup: {:?}", entity);
+    }
+}
+
+pub struct AbilitiesPlugin;
+
+impl Plugin for AbilitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(
+            constants::entities::ABILITY_PICKUP,
+            spawn_ldtk_ability_pickup,
+        )
+        .init_resource::<PlayerAbilities>()
+        .add_event::<AbilityUnlockedEvent>()
+        .add_console_command("unlock", "<double_jump|dash|wall_jump|all>", unlock_command)
+        .add_systems(
+            Update,
+            (
+                setup_ability_pickups,
+                collect_ability_pickups,
+                show_unlock_banner,
+                update_unlock_banners,
+            )
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        )
+        .register_type::<PlayerAbilities>()
+        .register_type::<AbilityPickup>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_pickup_unlocks_ability_once() {
+        let mut world = World::new();
+        world.init_resource::<PlayerAbilities>();
+        world.init_resource::<Events<AbilityUnlockedEvent>>();
+        let collider = world
+            .spawn(ColliderAabb::new(Vec2::ZERO, Vec2::splat(8.0)))
+            .id();
+        world.spawn(Player).add_child(collider);
+        let spawn_pickup = |world: &mut World, ability| {
+            world
+                .spawn((
+                    AbilityPickup(ability),
+                    ColliderAabb::new(Vec2::new(4.0, 0.0), Vec2::splat(6.0)),
+                ))
+                .id()
+        };
+
+        let pickup = spawn_pickup(&mut world, Ability::DoubleJump);
+        world.run_system_once(collect_ability_pickups).unwrap();
+        assert!(world.get_entity(pickup).is_err());
+        assert_eq!(
+            *world.resource::<PlayerAbilities>(),
+            PlayerAbilities {
+                double_jump: true,
+                ..default()
+            }
+        );
+
+        // A second pickup of the same ability is used up without another unlock
+        spawn_pickup(&mut world, Ability::DoubleJump);
+        world.run_system_once(collect_ability_pickups).unwrap();
+        let unlocks: Vec<_> = world
+            .resource_mut::<Events<AbilityUnlockedEvent>>()
+            .drain()
+            .collect();
+        assert_eq!(unlocks, vec![AbilityUnlockedEvent(Ability::DoubleJump)]);
+    }
+}
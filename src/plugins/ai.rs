@@ -0,0 +1,305 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{prelude::*, time::Stopwatch};
+
+use crate::states::GameState;
+
+use super::{
+    animation::{AnimationKey, AnimationPlugin, NextAnimation},
+    animation_library::AnimationConfig,
+    health::{DamageEvent, Health, apply_damage},
+};
+
+/// What an AI controlled entity is currently doing
+///
+/// Doubles as the animation key, so each state plays its own Aseprite tag.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum AiState {
+    #[default]
+    Idle,
+    Patrol,
+    Chase,
+    Attack,
+    Hurt,
+    Dead,
+}
+impl AnimationKey for AiState {}
+
+/// How an entity's AI picks its next state
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct AiBehaviour {
+    /// Patrol instead of standing still while there is nothing to chase
+    pub patrols: bool,
+    /// Distance in pixels at which a target gets attacked instead of chased
+    pub attack_range: Option<f32>,
+    /// How long taking a hit interrupts everything else
+    pub hurt_duration: Duration,
+}
+
+/// Position the AI is after, set each frame by the owning plugin's sensing systems
+#[derive(Component, Default, Debug)]
+pub struct AiTarget(pub Option<Vec2>);
+
+/// Time spent in the current state
+#[derive(Component, Default)]
+pub struct AiStateTime(pub Stopwatch);
+
+/// Fired when an entity switches states, for hooking up sounds and effects
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct AiStateChangedEvent {
+    pub entity: Entity,
+    pub from: AiState,
+    pub to: AiState,
+}
+
+/// Animations for every state, falling back to `move_tag` for tags the sprite doesn't have
+pub fn ai_animation_configs(move_tag: &'static str) -> HashMap<AiState, AnimationConfig<AiState>> {
+    HashMap::from([
+        (
+            AiState::Idle,
+            AnimationConfig::looping("idle").with_fallback(move_tag),
+        ),
+        (AiState::Patrol, AnimationConfig::looping(move_tag)),
+        (
+            AiState::Chase,
+            AnimationConfig::looping("chase").with_fallback(move_tag),
+        ),
+        (
+            AiState::Attack,
+            AnimationConfig::once("attack")
+                .with_fallback(move_tag)
+                .with_priority(1)
+                .uninterruptible(),
+        ),
+        (
+            AiState::Hurt,
+            AnimationConfig::once("hurt")
+                .with_fallback(move_tag)
+                .with_priority(2)
+                .uninterruptible(),
+        ),
+        (
+            AiState::Dead,
+            AnimationConfig::once("death")
+                .with_fallback(move_tag)
+                .with_priority(3)
+                .uninterruptible(),
+        ),
+    ])
+}
+
+/// State for the next frame
+///
+/// Dying is final, and a hit interrupts everything for `hurt_duration`. Otherwise the AI
+/// attacks a target in range, chases one further away, and patrols or idles without one.
+pub fn next_ai_state(
+    current: AiState,
+    behaviour: &AiBehaviour,
+    target_distance: Option<f32>,
+    time_in_state: Duration,
+    dead: bool,
+    hit: bool,
+) -> AiState {
+    if current == AiState::Dead || dead {
+        return AiState::Dead;
+    }
+    if hit || (current == AiState::Hurt && time_in_state < behaviour.hurt_duration) {
+        return AiState::Hurt;
+    }
+
+    match target_distance {
+        Some(distance)
+            if behaviour
+                .attack_range
+                .is_some_and(|range| distance <= range) =>
+        {
+            AiState::Attack
+        }
+        Some(_) => AiState::Chase,
+        None if behaviour.patrols => AiState::Patrol,
+        None => AiState::Idle,
+    }
+}
+
+pub fn update_ai_state(
+    mut query: Query<(
+        Entity,
+        &mut AiState,
+        &AiBehaviour,
+        &AiTarget,
+        &mut AiStateTime,
+        &Transform,
+        Option<&Health>,
+        Option<&mut NextAnimation<AiState>>,
+    )>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut event_writer: EventWriter<AiStateChangedEvent>,
+    time: Res<Time>,
+) {
+    let hit_entities: Vec<Entity> = damage_events.read().map(|event| event.target).collect();
+
+    for (
+        entity,
+        mut state,
+        behaviour,
+        target,
+        mut time_in_state,
+        transform,
+        health,
+        next_animation,
+    ) in query.iter_mut()
+    {
+        time_in_state.0.tick(time.delta());
+
+        let next = next_ai_state(
+            *state,
+            behaviour,
+            target
+                .0
+                .map(|target| target.distance(transform.translation.xy())),
+            time_in_state.0.elapsed(),
+            health.is_some_and(Health::is_dead),
+            hit_entities.contains(&entity),
+        );
+        if next == *state {
+            continue;
+        }
+
+        event_writer.write(AiStateChangedEvent {
+            entity,
+            from: *state,
+            to: next,
+        });
+        *state = next;
+        time_in_state.0.reset();
+        if let Some(mut next_animation) = next_animation {
+            next_animation.key = Some(next);
+        }
+    }
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AiStateChangedEvent>()
+            .add_plugins(AnimationPlugin::<AiState>::default())
+            .add_systems(
+                Update,
+                update_ai_state
+                    .after(apply_damage)
+                    .run_if(in_state(GameState::Game)),
+            )
+            .register_type::<AiState>()
+            .register_type::<AiBehaviour>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn behaviour() -> AiBehaviour {
+        AiBehaviour {
+            patrols: true,
+            attack_range: Some(16.0),
+            hurt_duration: Duration::from_millis(300),
+        }
+    }
+
+    #[test]
+    fn test_ai_state_transitions() {
+        let behaviour = behaviour();
+        let next = |current, target_distance, millis_in_state, dead, hit| {
+            next_ai_state(
+                current,
+                &behaviour,
+                target_distance,
+                Duration::from_millis(millis_in_state),
+                dead,
+                hit,
+            )
+        };
+
+        assert_eq!(next(AiState::Idle, None, 0, false, false), AiState::Patrol);
+        assert_eq!(
+            next(AiState::Patrol, Some(100.0), 0, false, false),
+            AiState::Chase
+        );
+        assert_eq!(
+            next(AiState::Chase, Some(10.0), 0, false, false),
+            AiState::Attack
+        );
+
+        // A hit interrupts the attack until the hurt time is over
+        assert_eq!(
+            next(AiState::Attack, Some(10.0), 0, false, true),
+            AiState::Hurt
+        );
+        assert_eq!(
+            next(AiState::Hurt, Some(10.0), 100, false, false),
+            AiState::Hurt
+        );
+        assert_eq!(
+            next(AiState::Hurt, Some(10.0), 300, false, false),
+            AiState::Attack
+        );
+
+        // No way back from death
+        assert_eq!(next(AiState::Chase, None, 0, true, true), AiState::Dead);
+        assert_eq!(next(AiState::Dead, None, 1000, false, false), AiState::Dead);
+
+        let idler = AiBehaviour {
+            patrols: false,
+            ..behaviour.clone()
+        };
+        assert_eq!(
+            next_ai_state(AiState::Chase, &idler, None, Duration::ZERO, false, false),
+            AiState::Idle
+        );
+    }
+
+    #[test]
+    fn test_state_changes_emit_events_and_switch_animation() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<Events<DamageEvent>>();
+        world.init_resource::<Events<AiStateChangedEvent>>();
+
+        let entity = world
+            .spawn((
+                AiState::Patrol,
+                behaviour(),
+                AiTarget(Some(Vec2::new(64.0, 0.0))),
+                AiStateTime::default(),
+                Transform::default(),
+                NextAnimation::<AiState> { key: None },
+            ))
+            .id();
+
+        world.run_system_once(update_ai_state).unwrap();
+        assert_eq!(*world.get::<AiState>(entity).unwrap(), AiState::Chase);
+        assert_eq!(
+            world.get::<NextAnimation<AiState>>(entity).unwrap().key,
+            Some(AiState::Chase)
+        );
+        let events: Vec<_> = world
+            .resource_mut::<Events<AiStateChangedEvent>>()
+            .drain()
+            .collect();
+        assert_eq!(
+            events,
+            vec![AiStateChangedEvent {
+                entity,
+                from: AiState::Patrol,
+                to: AiState::Chase,
+            }]
+        );
+
+        // Nothing changed, nothing sent
+        world.run_system_once(update_ai_state).unwrap();
+        assert!(world.resource::<Events<AiStateChangedEvent>>().is_empty());
+    }
+}
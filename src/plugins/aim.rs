@@ -0,0 +1,227 @@
+use std::f32::consts::{FRAC_PI_4, PI, TAU};
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::bundles::player::Player;
+
+use super::player::PlayerAction;
+
+/// Normalized direction the player is currently aiming in
+#[derive(Component, Reflect)]
+pub struct AimDirection(pub Vec2);
+
+impl Default for AimDirection {
+    fn default() -> Self {
+        Self(Vec2::X)
+    }
+}
+
+/// Whether the player runs and guns, or stands in place and uses the directional input for aiming
+#[derive(Component, Default, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub enum AimMode {
+    /// Aim follows the stick or the facing direction while moving
+    #[default]
+    RunAndGun,
+    /// The player stays put and the directional input aims freely
+    AimLock,
+}
+
+/// Settings for turning raw stick input into an aim direction
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct AimAssist {
+    /// Snap the aim to the 8 cardinal/diagonal directions
+    pub snap_to_8: bool,
+    /// Time constant (in seconds) for smoothing the aim rotation, 0.0 disables smoothing
+    pub smoothing: f32,
+    /// Stick deflection below which the stick is ignored
+    pub deadzone: f32,
+}
+
+impl Default for AimAssist {
+    fn default() -> Self {
+        Self {
+            snap_to_8: true,
+            smoothing: 0.05,
+            deadzone: 0.3,
+        }
+    }
+}
+
+/// Rotate `angle` to the nearest multiple of 45 degrees
+fn snap_angle_to_8(angle: f32) -> f32 {
+    (angle / FRAC_PI_4).round() * FRAC_PI_4
+}
+
+/// Resolve the new aim direction from raw stick input
+///
+/// Returns `None` when the stick is inside the deadzone, so the caller can fall back
+/// to e.g. the facing direction.
+pub fn resolve_aim(
+    stick: Vec2,
+    previous: Vec2,
+    assist: &AimAssist,
+    delta_secs: f32,
+) -> Option<Vec2> {
+    if stick.length() < assist.deadzone {
+        return None;
+    }
+
+    let mut target = stick.y.atan2(stick.x);
+    if assist.snap_to_8 {
+        target = snap_angle_to_8(target);
+    }
+
+    let t = if assist.smoothing <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-delta_secs / assist.smoothing).exp()
+    };
+
+    let current = previous.y.atan2(previous.x);
+    // Shortest signed angle between the current and target aim
+    let delta = (target - current + PI).rem_euclid(TAU) - PI;
+    Some(Vec2::from_angle(current + delta * t))
+}
+
+/// Build a stick-like vector from the digital direction actions
+pub fn digital_aim(action_state: &ActionState<PlayerAction>) -> Vec2 {
+    let mut aim = Vec2::ZERO;
+    if action_state.pressed(&PlayerAction::Left) {
+        aim.x -= 1.0;
+    }
+    if action_state.pressed(&PlayerAction::Right) {
+        aim.x += 1.0;
+    }
+    if action_state.pressed(&PlayerAction::Up) {
+        aim.y += 1.0;
+    }
+    if action_state.pressed(&PlayerAction::Down) {
+        aim.y -= 1.0;
+    }
+    aim.normalize_or_zero()
+}
+
+/// Aim from the Up/Down modifiers while the stick is idle, relative to the facing direction
+///
+/// A modifier tilts the aim diagonally while running, and points it straight up or down
+/// when standing still.
+pub fn modified_aim(facing_x: f32, moving: bool, aim_up: bool, aim_down: bool) -> Vec2 {
+    let vertical = match (aim_up, aim_down) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => return Vec2::new(facing_x, 0.0),
+    };
+
+    if moving {
+        Vec2::new(facing_x, vertical).normalize()
+    } else {
+        Vec2::new(0.0, vertical)
+    }
+}
+
+fn toggle_aim_mode(mut query: Query<(&ActionState<PlayerAction>, &mut AimMode), With<Player>>) {
+    for (action_state, mut aim_mode) in query.iter_mut() {
+        if action_state.just_pressed(&PlayerAction::AimLock) {
+            *aim_mode = match *aim_mode {
+                AimMode::RunAndGun => AimMode::AimLock,
+                AimMode::AimLock => AimMode::RunAndGun,
+            };
+        }
+    }
+}
+
+fn update_aim_direction(
+    mut query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &Sprite,
+            &AimMode,
+            &mut AimDirection,
+        ),
+        With<Player>,
+    >,
+    aim_assist: Res<AimAssist>,
+    time: Res<Time>,
+) {
+    for (action_state, sprite, aim_mode, mut aim_direction) in query.iter_mut() {
+        let mut stick = action_state.axis_pair(&PlayerAction::Aim);
+        if *aim_mode == AimMode::AimLock && stick == Vec2::ZERO {
+            stick = digital_aim(action_state);
+        }
+        let moving =
+            action_state.pressed(&PlayerAction::Left) || action_state.pressed(&PlayerAction::Right);
+        aim_direction.0 = resolve_aim(stick, aim_direction.0, &aim_assist, time.delta_secs())
+            .unwrap_or_else(|| {
+                modified_aim(
+                    if sprite.flip_x { -1.0 } else { 1.0 },
+                    moving && *aim_mode == AimMode::RunAndGun,
+                    action_state.pressed(&PlayerAction::AimUp),
+                    action_state.pressed(&PlayerAction::AimDown),
+                )
+            });
+    }
+}
+
+pub struct AimPlugin;
+
+impl Plugin for AimPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AimAssist>()
+            .add_systems(Update, (toggle_aim_mode, update_aim_direction).chain())
+            .register_type::<AimDirection>()
+            .register_type::<AimMode>()
+            .register_type::<AimAssist>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap_to_diagonal() {
+        let assist = AimAssist {
+            snap_to_8: true,
+            smoothing: 0.0,
+            deadzone: 0.3,
+        };
+        let stick = Vec2::from_angle(40f32.to_radians());
+
+        let aim = resolve_aim(stick, Vec2::X, &assist, 1.0 / 60.0).unwrap();
+
+        assert!(aim.angle_to(Vec2::from_angle(FRAC_PI_4)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_digital_aim_diagonal() {
+        let mut action_state = ActionState::<PlayerAction>::default();
+        action_state.press(&PlayerAction::Up);
+        action_state.press(&PlayerAction::Left);
+
+        let aim = digital_aim(&action_state);
+
+        assert!(aim.angle_to(Vec2::from_angle(3.0 * FRAC_PI_4)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_aim_modifiers() {
+        assert_eq!(modified_aim(-1.0, true, false, false), Vec2::NEG_X);
+        assert_eq!(modified_aim(1.0, false, true, false), Vec2::Y);
+        assert_eq!(modified_aim(1.0, false, false, true), Vec2::NEG_Y);
+        assert_eq!(
+            modified_aim(-1.0, true, true, false),
+            Vec2::new(-1.0, 1.0).normalize()
+        );
+        // Both cancel out
+        assert_eq!(modified_aim(1.0, true, true, true), Vec2::X);
+    }
+
+    #[test]
+    fn test_deadzone_ignores_small_input() {
+        let assist = AimAssist::default();
+
+        assert!(resolve_aim(Vec2::new(0.1, 0.1), Vec2::X, &assist, 1.0 / 60.0).is_none());
+    }
+}
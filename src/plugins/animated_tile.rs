@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, sprite::SpriteImageMode};
+
+use crate::constants::{TILE_SIZE, int_grid::LevelGeometry};
+
+/// A looping tile animation, tied to an IntGrid value of the level geometry layer
+pub struct AnimatedTileDef {
+    pub int_grid_value: i64,
+    /// Horizontal strip of `frame_count` tile-sized frames
+    pub sprite_path: &'static str,
+    pub frame_count: usize,
+    pub frame_duration: Duration,
+}
+
+pub const ANIMATED_TILES: &[AnimatedTileDef] = &[AnimatedTileDef {
+    int_grid_value: LevelGeometry::Water.value(),
+    sprite_path: "sprites/water.png",
+    frame_count: 4,
+    frame_duration: Duration::from_millis(150),
+}];
+
+pub fn animated_tile_def(int_grid_value: i64) -> Option<&'static AnimatedTileDef> {
+    ANIMATED_TILES
+        .iter()
+        .find(|def| def.int_grid_value == int_grid_value)
+}
+
+/// Cycles a sprite through the frames of its texture atlas
+///
+/// A much lighter take on the full animation system: no tags, events or transitions,
+/// just a fixed number of frames on a repeating timer.
+#[derive(Component)]
+pub struct AnimatedTile {
+    pub frame_count: usize,
+    pub timer: Timer,
+}
+
+/// Horizontal run of identical tiles, spawned as a single entity
+#[derive(Debug, PartialEq, Eq)]
+pub struct TileRun {
+    pub value: i64,
+    pub x: usize,
+    pub y: usize,
+    pub length: usize,
+}
+
+/// Find the horizontal runs of animated tile values in an IntGrid layer
+pub fn find_animated_tile_runs(int_grid_csv: &[i64], width: usize) -> Vec<TileRun> {
+    let mut runs = Vec::new();
+
+    for (y, row) in int_grid_csv.chunks(width).enumerate() {
+        let mut x = 0;
+        while x < row.len() {
+            let value = row[x];
+            let length = row[x..].iter().take_while(|&&tile| tile == value).count();
+            if animated_tile_def(value).is_some() {
+                runs.push(TileRun {
+                    value,
+                    x,
+                    y,
+                    length,
+                });
+            }
+            x += length;
+        }
+    }
+
+    runs
+}
+
+/// Sprite, animation and position for a run of animated tiles
+///
+/// The position is relative to the level's top left corner. The run's tiles share
+/// a single sprite that repeats the current frame across its width.
+pub fn animated_tile_run_bundle(
+    run: &TileRun,
+    def: &AnimatedTileDef,
+    asset_server: &AssetServer,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+) -> impl Bundle {
+    let layout = texture_atlas_layouts.add(TextureAtlasLayout::from_grid(
+        UVec2::splat(TILE_SIZE as u32),
+        def.frame_count as u32,
+        1,
+        None,
+        None,
+    ));
+
+    (
+        AnimatedTile {
+            frame_count: def.frame_count,
+            timer: Timer::new(def.frame_duration, TimerMode::Repeating),
+        },
+        Sprite {
+            image: asset_server.load(def.sprite_path),
+            texture_atlas: Some(TextureAtlas { layout, index: 0 }),
+            custom_size: Some(Vec2::new(run.length as f32 * TILE_SIZE, TILE_SIZE)),
+            image_mode: SpriteImageMode::Tiled {
+                tile_x: true,
+                tile_y: false,
+                stretch_value: 1.0,
+            },
+            ..default()
+        },
+        Transform::from_xyz(
+            (run.x as f32 + run.length as f32 / 2.0) * TILE_SIZE,
+            -(run.y as f32 + 0.5) * TILE_SIZE, // Flip Y coordinate for Bevy
+            0.5,
+        ),
+    )
+}
+
+pub fn animate_tiles(time: Res<Time>, mut query: Query<(&mut AnimatedTile, &mut Sprite)>) {
+    for (mut animated_tile, mut sprite) in query.iter_mut() {
+        animated_tile.timer.tick(time.delta());
+        let frames_passed = animated_tile.timer.times_finished_this_tick() as usize;
+        if frames_passed == 0 {
+            continue;
+        }
+
+        if let Some(atlas) = &mut sprite.texture_atlas {
+            atlas.index = (atlas.index + frames_passed) % animated_tile.frame_count;
+        }
+    }
+}
+
+pub struct AnimatedTilePlugin;
+
+impl Plugin for AnimatedTilePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, animate_tiles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_animated_tile_advances_frames() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+
+        let tile = world
+            .spawn((
+                AnimatedTile {
+                    frame_count: 4,
+                    timer: Timer::new(Duration::from_millis(100), TimerMode::Repeating),
+                },
+                Sprite {
+                    texture_atlas: Some(TextureAtlas::default()),
+                    ..default()
+                },
+            ))
+            .id();
+
+        let mut frames = Vec::new();
+        for _ in 0..5 {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(100));
+            world.run_system_once(animate_tiles).unwrap();
+            let sprite = world.get::<Sprite>(tile).unwrap();
+            frames.push(sprite.texture_atlas.as_ref().unwrap().index);
+        }
+
+        // Wraps around after the last frame
+        assert_eq!(frames, vec![1, 2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn test_find_animated_tile_runs() {
+        #[rustfmt::skip]
+        let grid = [
+            0, 2, 2, 2, 0,
+            2, 1, 1, 0, 2,
+        ];
+
+        assert_eq!(
+            find_animated_tile_runs(&grid, 5),
+            vec![
+                TileRun {
+                    value: 2,
+                    x: 1,
+                    y: 0,
+                    length: 3
+                },
+                TileRun {
+                    value: 2,
+                    x: 0,
+                    y: 1,
+                    length: 1
+                },
+                TileRun {
+                    value: 2,
+                    x: 4,
+                    y: 1,
+                    length: 1
+                },
+            ]
+        );
+    }
+}
@@ -2,6 +2,8 @@ use std::{collections::HashMap, default, marker::PhantomData, time::Duration};
 
 use bevy::prelude::*;
 
+use crate::schedule::MovementSet;
+
 pub trait AnimationStateKey: Clone + Eq + std::hash::Hash + Send + Sync + 'static {}
 
 pub trait AnimationKey: Clone + Eq + std::hash::Hash + Send + Sync + 'static {}
@@ -32,11 +34,25 @@ impl<K: AnimationKey> CurrentAnimation<K> {
 }
 
 #[derive(Component, Clone, Default)]
-pub struct AnimationTimer(Timer);
+pub struct AnimationTimer {
+    timer: Timer,
+    /// Stepping back through the frames, for reverse and ping-pong playback
+    backwards: bool,
+}
+
+impl AnimationTimer {
+    /// Timer for starting `animation` on its start frame
+    pub fn starting<K: AnimationKey>(animation: &Animation<K>) -> Self {
+        Self {
+            timer: Timer::default(),
+            backwards: animation.direction.starts_backwards(),
+        }
+    }
+}
 
 #[derive(Component, Clone)]
 pub struct AnimationMap<K: AnimationKey> {
-    pub animations: HashMap<K, Animation>,
+    pub animations: HashMap<K, Animation<K>>,
     pub frames: Vec<AnimationFrame>,
 }
 
@@ -53,16 +69,152 @@ impl AnimationFrame {
 }
 
 #[derive(Component, Clone, Debug)]
-pub enum OnAnimationEndAction {
+pub enum OnAnimationEndAction<K: AnimationKey> {
     Loop,
     Stop,
+    /// Stop, then switch to another animation unless a different one was requested already
+    TransitionTo(K),
+    /// Despawn the entity, for effects that play once and are gone
+    Despawn,
+}
+
+/// Order the frames of an animation play in, like the directions of Aseprite tags
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum PlaybackDirection {
+    #[default]
+    Forward,
+    Reverse,
+    /// Forward, then back to the start
+    PingPong,
+    /// Backwards from the end, then forward to the end again
+    PingPongReverse,
+}
+
+impl PlaybackDirection {
+    /// Direction of an Aseprite tag, playing forward for any it doesn't know
+    pub fn from_aseprite(direction: &str) -> Self {
+        match direction {
+            "reverse" => Self::Reverse,
+            "pingpong" => Self::PingPong,
+            "pingpong_reverse" => Self::PingPongReverse,
+            _ => Self::Forward,
+        }
+    }
+
+    fn starts_backwards(self) -> bool {
+        matches!(self, Self::Reverse | Self::PingPongReverse)
+    }
 }
 
 #[derive(Component, Clone)]
-pub struct Animation {
+pub struct Animation<K: AnimationKey> {
     pub first_index: usize,
     pub last_index: usize,
-    pub on_end: OnAnimationEndAction,
+    pub on_end: OnAnimationEndAction<K>,
+    /// Only an animation of a higher priority cuts off one that can't be interrupted
+    pub priority: u8,
+    /// Whether any other animation may replace this one before it has finished
+    pub can_be_interrupted: bool,
+    /// Tags of `AnimationFrameEvent`s to fire on entering frames, counted from `first_index`
+    pub events: Vec<(usize, String)>,
+    /// Scales how fast the frames go by, on top of the entity's `AnimationPlaybackState`
+    pub speed_multiplier: f32,
+    pub direction: PlaybackDirection,
+}
+
+/// Per entity control over how its animations play, e.g. freezing them for hit-stop
+#[derive(Component, Clone, Debug, PartialEq, Reflect)]
+pub struct AnimationPlaybackState {
+    pub paused: bool,
+    /// Multiplies the speed of every animation the entity plays
+    pub speed: f32,
+}
+
+impl Default for AnimationPlaybackState {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+        }
+    }
+}
+
+impl AnimationPlaybackState {
+    /// Frame time passed in `delta` for an animation with `speed_multiplier`
+    pub fn scale(&self, delta: Duration, speed_multiplier: f32) -> Duration {
+        if self.paused {
+            Duration::ZERO
+        } else {
+            delta.mul_f32((self.speed * speed_multiplier).max(0.0))
+        }
+    }
+}
+
+/// Fired when an animation enters a frame that has an event attached, like a footstep
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct AnimationFrameEvent {
+    pub entity: Entity,
+    pub tag: String,
+}
+
+impl<K: AnimationKey> Animation<K> {
+    /// Frame the animation starts on
+    pub fn start_index(&self) -> usize {
+        if self.direction.starts_backwards() {
+            self.last_index
+        } else {
+            self.first_index
+        }
+    }
+
+    /// Frame a played through animation that doesn't loop stays on
+    pub fn end_index(&self) -> usize {
+        match self.direction {
+            PlaybackDirection::Forward | PlaybackDirection::PingPongReverse => self.last_index,
+            PlaybackDirection::Reverse | PlaybackDirection::PingPong => self.first_index,
+        }
+    }
+
+    /// Frame after `index`, with whether the animation is then stepping backwards
+    ///
+    /// `None` once an animation that doesn't loop has played through.
+    pub fn next_frame(&self, index: usize, backwards: bool) -> Option<(usize, bool)> {
+        let step = |backwards: bool| {
+            if backwards {
+                (index > self.first_index).then(|| index - 1)
+            } else {
+                (index < self.last_index).then(|| index + 1)
+            }
+        };
+        if let Some(next) = step(backwards) {
+            return Some((next, backwards));
+        }
+
+        let loops = matches!(self.on_end, OnAnimationEndAction::Loop);
+        match self.direction {
+            PlaybackDirection::Forward | PlaybackDirection::Reverse => {
+                loops.then(|| (self.start_index(), backwards))
+            }
+            // Turning around halfway through, or when starting over
+            PlaybackDirection::PingPong | PlaybackDirection::PingPongReverse => {
+                let halfway = backwards == self.direction.starts_backwards();
+                (halfway || loops).then(|| (step(!backwards).unwrap_or(index), !backwards))
+            }
+        }
+    }
+
+    /// Tags of the events attached to sprite sheet frame `frame_index`
+    pub fn frame_events(&self, frame_index: usize) -> impl Iterator<Item = &str> {
+        self.events
+            .iter()
+            .filter(move |(offset, _)| self.first_index + offset == frame_index)
+            .map(|(_, tag)| tag.as_str())
+    }
+
+    /// Whether `next` may replace this animation, which is `finished` once played through
+    pub fn gives_way_to(&self, next: &Animation<K>, finished: bool) -> bool {
+        self.can_be_interrupted || finished || next.priority > self.priority
+    }
 }
 
 impl<K: AnimationKey> AnimationBundle<K> {
@@ -70,7 +222,7 @@ impl<K: AnimationKey> AnimationBundle<K> {
         &self,
         sprite_sheet: Handle<Image>,
         atlas: Handle<TextureAtlasLayout>,
-        animations: HashMap<K, Animation>,
+        animations: HashMap<K, Animation<K>>,
         frames: Vec<AnimationFrame>,
         default_animation: CurrentAnimation<K>,
     ) -> Self {
@@ -87,81 +239,160 @@ impl<K: AnimationKey> AnimationBundle<K> {
         AnimationBundle {
             current_animation: default_animation,
             next_animation: NextAnimation { key: None },
-            timer: AnimationTimer(timer),
+            timer: AnimationTimer {
+                timer,
+                backwards: false,
+            },
             animations: AnimationMap { animations, frames },
             sprite,
         }
     }
 }
 
-pub fn update_animations<K: AnimationKey>(
+/// Whether `animation` has played through and stays on its end frame
+fn is_finished<K: AnimationKey>(
+    animation: &Animation<K>,
+    frames: &[AnimationFrame],
+    atlas_index: usize,
+    timer: &AnimationTimer,
+) -> bool {
+    !matches!(animation.on_end, OnAnimationEndAction::Loop)
+        && frames
+            .get(animation.end_index())
+            .is_some_and(|frame| frame.index == atlas_index)
+        && timer.timer.finished()
+}
+
+/// Switch to the requested animation, if the current one lets it
+///
+/// A request the current animation doesn't give way to is kept, and gets applied once the
+/// current animation has finished.
+pub fn apply_next_animation<K: AnimationKey>(
     mut query: Query<(
+        Entity,
         &mut CurrentAnimation<K>,
         &mut NextAnimation<K>,
         &mut Sprite,
         &mut AnimationTimer,
         &AnimationMap<K>,
     )>,
-    time: Res<Time>,
+    mut event_writer: EventWriter<AnimationFrameEvent>,
 ) {
-    for (mut current_animation, mut next_animation, mut sprite, mut timer, animation_map) in
+    for (entity, mut current_animation, mut next_animation, mut sprite, mut timer, animation_map) in
         query.iter_mut()
     {
-        let is_starting_next_animation =
-            if let Some(next_animation_key) = next_animation.key.clone() {
-                if next_animation_key != current_animation.key {
-                    let next_animation_clip = animation_map
-                        .animations
-                        .get(&next_animation_key)
-                        .expect("Current animation key should always exist in map");
-                    println!(
-                        "Next animation: {:?} - {:?}",
-                        next_animation_clip.first_index, next_animation_clip.last_index
-                    );
-                    current_animation.key = next_animation_key;
-                    next_animation.key = None;
-                    timer.0.reset();
-                    true
-                } else {
-                    false
-                }
-            } else {
-                false
-            };
+        let Some(next_animation_key) = next_animation.key.clone() else {
+            continue;
+        };
+        if next_animation_key == current_animation.key {
+            next_animation.key = None;
+            continue;
+        }
+
+        let current_clip = animation_map
+            .animations
+            .get(&current_animation.key)
+            .expect("Current animation key should always exist in map");
+        let next_clip = animation_map
+            .animations
+            .get(&next_animation_key)
+            .expect("Next animation key should always exist in map");
+        let atlas = sprite
+            .texture_atlas
+            .as_mut()
+            .expect("Texture atlas not found");
+
+        let finished = is_finished(current_clip, &animation_map.frames, atlas.index, &timer);
+        if !current_clip.gives_way_to(next_clip, finished) {
+            continue;
+        }
 
-        timer.0.tick(time.delta());
-        if timer.0.just_finished() || is_starting_next_animation {
-            // Get the current animation from the map using the key
-            let animation = animation_map
-                .animations
-                .get(&current_animation.key)
-                .expect("Current animation key should always exist in map");
-
-            let next_frame = if let Some(atlas) = &mut sprite.texture_atlas {
-                let next_frame_index = if is_starting_next_animation {
-                    animation.first_index
-                } else {
-                    atlas.index + 1
-                };
-                if next_frame_index > animation.last_index {
-                    match animation.on_end {
-                        OnAnimationEndAction::Loop => {
-                            animation_map.frames.get(animation.first_index)
-                        }
-                        OnAnimationEndAction::Stop => {
-                            animation_map.frames.get(animation.last_index)
-                        }
+        let start_index = next_clip.start_index();
+        let first_frame = &animation_map.frames[start_index];
+        atlas.index = first_frame.index;
+        timer.timer = Timer::new(first_frame.duration, TimerMode::Once);
+        timer.backwards = next_clip.direction.starts_backwards();
+        current_animation.key = next_animation_key;
+        next_animation.key = None;
+
+        for tag in next_clip.frame_events(start_index) {
+            event_writer.write(AnimationFrameEvent {
+                entity,
+                tag: tag.to_string(),
+            });
+        }
+    }
+}
+
+pub fn update_animations<K: AnimationKey>(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &CurrentAnimation<K>,
+        Option<&mut NextAnimation<K>>,
+        &mut Sprite,
+        &mut AnimationTimer,
+        &AnimationMap<K>,
+        Option<&AnimationPlaybackState>,
+    )>,
+    time: Res<Time>,
+    mut event_writer: EventWriter<AnimationFrameEvent>,
+) {
+    for (
+        entity,
+        current_animation,
+        next_animation,
+        mut sprite,
+        mut timer,
+        animation_map,
+        playback,
+    ) in query.iter_mut()
+    {
+        // Get the current animation from the map using the key
+        let animation = animation_map
+            .animations
+            .get(&current_animation.key)
+            .expect("Current animation key should always exist in map");
+
+        let playback = playback.cloned().unwrap_or_default();
+        timer
+            .timer
+            .tick(playback.scale(time.delta(), animation.speed_multiplier));
+        if !timer.timer.just_finished() {
+            continue;
+        }
+        let atlas = sprite
+            .texture_atlas
+            .as_mut()
+            .expect("Texture atlas not found");
+
+        // The timer is left finished once played through, marking the animation as done
+        let Some((next_frame_index, backwards)) =
+            animation.next_frame(atlas.index, timer.backwards)
+        else {
+            match &animation.on_end {
+                OnAnimationEndAction::TransitionTo(key) => {
+                    if let Some(mut next_animation) = next_animation {
+                        next_animation.key.get_or_insert_with(|| key.clone());
                     }
-                } else {
-                    animation_map.frames.get(next_frame_index)
                 }
-            } else {
-                panic!("Texture atlas not found")
-            };
+                OnAnimationEndAction::Despawn => commands.entity(entity).despawn(),
+                OnAnimationEndAction::Loop | OnAnimationEndAction::Stop => {}
+            }
+            continue;
+        };
 
-            sprite.texture_atlas.as_mut().unwrap().index = next_frame.unwrap().index;
-            timer.0.reset();
-            timer.0.set_duration(next_frame.unwrap().duration);
+        let next_frame = &animation_map.frames[next_frame_index];
+        atlas.index = next_frame.index;
+        timer.backwards = backwards;
+        timer.timer.reset();
+        timer.timer.set_duration(next_frame.duration);
+
+        for tag in animation.frame_events(next_frame_index) {
+            event_writer.write(AnimationFrameEvent {
+                entity,
+                tag: tag.to_string(),
+            });
         }
     }
 }
@@ -180,6 +411,290 @@ impl<K: AnimationKey> Default for AnimationPlugin<K> {
 
 impl<K: AnimationKey> Plugin for AnimationPlugin<K> {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, update_animations::<K>);
+        app.add_event::<AnimationFrameEvent>()
+            .add_systems(
+                Update,
+                (apply_next_animation::<K>, update_animations::<K>)
+                    .chain()
+                    .in_set(MovementSet::Animation),
+            )
+            .register_type::<AnimationPlaybackState>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    enum TestAnimation {
+        Run,
+        Hurt,
+        Death,
+    }
+
+    impl AnimationKey for TestAnimation {}
+
+    fn clip(
+        first_index: usize,
+        priority: u8,
+        can_be_interrupted: bool,
+    ) -> Animation<TestAnimation> {
+        Animation {
+            first_index,
+            last_index: first_index + 1,
+            on_end: OnAnimationEndAction::Stop,
+            priority,
+            can_be_interrupted,
+            events: Vec::new(),
+            speed_multiplier: 1.0,
+            direction: PlaybackDirection::Forward,
+        }
+    }
+
+    #[test]
+    fn test_uninterruptible_animation_plays_out() {
+        let mut world = World::new();
+        world.init_resource::<Events<AnimationFrameEvent>>();
+        let entity = world
+            .spawn((
+                CurrentAnimation::new(TestAnimation::Run),
+                NextAnimation {
+                    key: Some(TestAnimation::Hurt),
+                },
+                Sprite {
+                    texture_atlas: Some(TextureAtlas::default()),
+                    ..default()
+                },
+                AnimationTimer::default(),
+                AnimationMap {
+                    animations: HashMap::from([
+                        (TestAnimation::Run, clip(0, 0, true)),
+                        (TestAnimation::Hurt, clip(2, 1, false)),
+                        (TestAnimation::Death, clip(4, 2, false)),
+                    ]),
+                    frames: (0..6)
+                        .map(|index| AnimationFrame::new(index, Duration::from_millis(100)))
+                        .collect(),
+                },
+            ))
+            .id();
+        let current = |world: &World| {
+            world
+                .get::<CurrentAnimation<TestAnimation>>(entity)
+                .unwrap()
+                .key
+        };
+        let request = |world: &mut World, key| {
+            world
+                .get_mut::<NextAnimation<TestAnimation>>(entity)
+                .unwrap()
+                .key = Some(key);
+            world
+                .run_system_once(apply_next_animation::<TestAnimation>)
+                .unwrap();
+        };
+
+        world
+            .run_system_once(apply_next_animation::<TestAnimation>)
+            .unwrap();
+        assert_eq!(current(&world), TestAnimation::Hurt);
+        assert_eq!(
+            world
+                .get::<Sprite>(entity)
+                .unwrap()
+                .texture_atlas
+                .as_ref()
+                .unwrap()
+                .index,
+            2
+        );
+
+        // Running doesn't cut the hurt animation short, but is kept for when it's done
+        request(&mut world, TestAnimation::Run);
+        assert_eq!(current(&world), TestAnimation::Hurt);
+
+        let mut sprite = world.get_mut::<Sprite>(entity).unwrap();
+        sprite.texture_atlas.as_mut().unwrap().index = 3;
+        let mut timer = world.get_mut::<AnimationTimer>(entity).unwrap();
+        timer.timer.tick(Duration::from_millis(100));
+        world
+            .run_system_once(apply_next_animation::<TestAnimation>)
+            .unwrap();
+        assert_eq!(current(&world), TestAnimation::Run);
+
+        // Dying goes over getting hurt
+        request(&mut world, TestAnimation::Hurt);
+        request(&mut world, TestAnimation::Death);
+        assert_eq!(current(&world), TestAnimation::Death);
+    }
+
+    #[test]
+    fn test_frame_events_fire_on_entering_frames() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.init_resource::<Events<AnimationFrameEvent>>();
+
+        let entity = world
+            .spawn((
+                CurrentAnimation::new(TestAnimation::Run),
+                Sprite {
+                    texture_atlas: Some(TextureAtlas {
+                        index: 2,
+                        ..default()
+                    }),
+                    ..default()
+                },
+                AnimationTimer::default(),
+                AnimationMap {
+                    animations: HashMap::from([(
+                        TestAnimation::Run,
+                        Animation {
+                            on_end: OnAnimationEndAction::Loop,
+                            events: vec![(0, "footstep".to_string())],
+                            ..clip(2, 0, true)
+                        },
+                    )]),
+                    frames: (0..6)
+                        .map(|index| AnimationFrame::new(index, Duration::from_millis(100)))
+                        .collect(),
+                },
+            ))
+            .id();
+
+        // Frame 3 has nothing attached, frame 2 is entered again by looping around
+        let mut tags = Vec::new();
+        for _ in 0..2 {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(100));
+            world
+                .run_system_once(update_animations::<TestAnimation>)
+                .unwrap();
+            tags.extend(
+                world
+                    .resource_mut::<Events<AnimationFrameEvent>>()
+                    .drain()
+                    .map(|event| (event.entity, event.tag)),
+            );
+        }
+        assert_eq!(tags, vec![(entity, "footstep".to_string())]);
+    }
+
+    #[test]
+    fn test_end_actions_transition_and_despawn() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.init_resource::<Events<AnimationFrameEvent>>();
+
+        // Both on the last frame of their animation
+        let spawn = |world: &mut World, on_end| {
+            world
+                .spawn((
+                    CurrentAnimation::new(TestAnimation::Hurt),
+                    NextAnimation::<TestAnimation> { key: None },
+                    Sprite {
+                        texture_atlas: Some(TextureAtlas {
+                            index: 3,
+                            ..default()
+                        }),
+                        ..default()
+                    },
+                    AnimationTimer::default(),
+                    AnimationMap {
+                        animations: HashMap::from([(
+                            TestAnimation::Hurt,
+                            Animation {
+                                on_end,
+                                ..clip(2, 0, true)
+                            },
+                        )]),
+                        frames: (0..4)
+                            .map(|index| AnimationFrame::new(index, Duration::from_millis(100)))
+                            .collect(),
+                    },
+                ))
+                .id()
+        };
+        let recovering = spawn(
+            &mut world,
+            OnAnimationEndAction::TransitionTo(TestAnimation::Run),
+        );
+        let effect = spawn(&mut world, OnAnimationEndAction::Despawn);
+
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(100));
+        world
+            .run_system_once(update_animations::<TestAnimation>)
+            .unwrap();
+
+        assert_eq!(
+            world
+                .get::<NextAnimation<TestAnimation>>(recovering)
+                .unwrap()
+                .key,
+            Some(TestAnimation::Run)
+        );
+        assert!(world.get_entity(effect).is_err());
+    }
+
+    #[test]
+    fn test_playback_directions() {
+        let play = |direction, on_end| {
+            let animation = Animation {
+                first_index: 2,
+                last_index: 4,
+                on_end,
+                direction,
+                ..clip(2, 0, true)
+            };
+            let mut frames = vec![animation.start_index()];
+            let mut backwards = direction.starts_backwards();
+            while frames.len() < 8
+                && let Some((next, next_backwards)) =
+                    animation.next_frame(*frames.last().unwrap(), backwards)
+            {
+                frames.push(next);
+                backwards = next_backwards;
+            }
+            frames
+        };
+        use OnAnimationEndAction::{Loop, Stop};
+
+        assert_eq!(play(PlaybackDirection::Forward, Stop), vec![2, 3, 4]);
+        assert_eq!(play(PlaybackDirection::Reverse, Stop), vec![4, 3, 2]);
+        assert_eq!(
+            play(PlaybackDirection::Reverse, Loop),
+            vec![4, 3, 2, 4, 3, 2, 4, 3]
+        );
+        assert_eq!(play(PlaybackDirection::PingPong, Stop), vec![2, 3, 4, 3, 2]);
+        assert_eq!(
+            play(PlaybackDirection::PingPong, Loop),
+            vec![2, 3, 4, 3, 2, 3, 4, 3]
+        );
+        assert_eq!(
+            play(PlaybackDirection::PingPongReverse, Stop),
+            vec![4, 3, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn test_playback_state_scales_frame_time() {
+        let delta = Duration::from_millis(100);
+        let playback = AnimationPlaybackState {
+            paused: false,
+            speed: 2.0,
+        };
+        assert_eq!(playback.scale(delta, 0.5), delta);
+        assert_eq!(playback.scale(delta, 1.5), Duration::from_millis(300));
+
+        let paused = AnimationPlaybackState {
+            paused: true,
+            ..playback
+        };
+        assert_eq!(paused.scale(delta, 1.0), Duration::ZERO);
     }
 }
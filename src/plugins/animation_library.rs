@@ -1,12 +1,13 @@
 use std::{collections::HashMap, time::Duration};
 
 use bevy::prelude::*;
+use serde::Deserialize;
 
-use crate::aseprite_deserialize::{Aseprite, Slice};
+use crate::aseprite_deserialize::{Aseprite, Slice, SliceKey};
 
 use super::animation::{
     Animation, AnimationBundle, AnimationFrame, AnimationKey, AnimationMap, AnimationTimer,
-    CurrentAnimation, NextAnimation, OnAnimationEndAction,
+    CurrentAnimation, NextAnimation, OnAnimationEndAction, PlaybackDirection,
 };
 
 /// Complete animation metadata for a sprite
@@ -20,9 +21,42 @@ pub struct AnimationData {
     pub sheet_size: UVec2,
     /// Individual frame size
     pub frame_size: UVec2,
-    /// Slices from Aseprite (e.g., hitboxes)
-    pub slices: Vec<Slice>,
+    /// Slices from Aseprite (e.g., hitboxes), by name
     pub slice_map: HashMap<String, Slice>,
+    /// Path of the sprite sheet image the frames are in
+    pub image_path: String,
+}
+
+impl AnimationData {
+    /// Key of the slice called `name` on sprite sheet frame `frame`
+    ///
+    /// Only frames with a key of their own count, so a slice can be limited to
+    /// e.g. the active frames of an attack.
+    pub fn slice_key_at(&self, name: &str, frame: usize) -> Option<&SliceKey> {
+        self.slice_map
+            .get(name)?
+            .keys
+            .iter()
+            .find(|key| key.frame == frame)
+    }
+
+    /// Size and offset from the sprite's center of a slice key, mirrored for flipped sprites
+    pub fn slice_rect(&self, key: &SliceKey, flip_x: bool) -> (Vec2, Vec2) {
+        let size = Vec2::new(key.bounds.w as f32, key.bounds.h as f32);
+        let center = Vec2::new(key.bounds.x as f32, key.bounds.y as f32) + size / 2.0;
+        // Aseprite has the origin at the top left, with y growing downwards
+        let frame_center = self.frame_size.as_vec2() / 2.0;
+        let mut offset = Vec2::new(center.x - frame_center.x, frame_center.y - center.y);
+        if flip_x {
+            offset.x = -offset.x;
+        }
+        (size, offset)
+    }
+
+    /// First key of the slice called `name`, for slices that don't change between frames
+    pub fn first_slice_key(&self, name: &str) -> Option<&SliceKey> {
+        self.slice_map.get(name)?.keys.first()
+    }
 }
 
 /// Metadata for a named animation (from Aseprite frame tags)
@@ -32,28 +66,58 @@ pub struct AnimationTag {
     pub from: usize,
     pub to: usize,
     pub direction: String,
+    /// Frame events from the tag's user data, see `parse_frame_events`
+    pub events: Vec<(usize, String)>,
+}
+
+/// Frame events written in Aseprite tag user data, like `0:footstep, 3:footstep`
+///
+/// Frames are counted from the start of the tag. Entries that don't parse are skipped.
+pub fn parse_frame_events(data: &str) -> Vec<(usize, String)> {
+    data.split(',')
+        .filter_map(|entry| {
+            let (frame, tag) = entry.split_once(':')?;
+            let frame = frame.trim().parse().ok()?;
+            let tag = tag.trim();
+            (!tag.is_empty()).then(|| (frame, tag.to_string()))
+        })
+        .collect()
 }
 
 /// Configuration for an animation, allowing Rust code to override behavior
 #[derive(Clone, Debug)]
-pub struct AnimationConfig {
+pub struct AnimationConfig<K: AnimationKey> {
     /// The name of the Aseprite tag to use
     pub tag_name: &'static str,
     /// What to do when the animation ends
-    pub on_end: OnAnimationEndAction,
-    // Future extensibility:
-    // pub speed_multiplier: f32,
-    // pub can_be_interrupted: bool,
-    // pub priority: u8,
-    // pub events: Vec<(usize, AnimationEvent)>,
+    pub on_end: OnAnimationEndAction<K>,
+    /// Tag to use instead while the sprite has no frames for `tag_name` yet
+    pub fallback_tag: Option<&'static str>,
+    /// Lets the animation cut off uninterruptible animations of a lower priority
+    pub priority: u8,
+    /// Whether other animations may replace this one before it has finished
+    pub can_be_interrupted: bool,
+    /// Events to fire on entering frames, counted from the start of the tag. Added to any
+    /// events in the tag's user data.
+    pub events: Vec<(usize, &'static str)>,
+    /// Scales how fast the frames go by, 2.0 plays twice as fast as authored
+    pub speed_multiplier: f32,
+    /// Overrides the direction of the Aseprite tag
+    pub direction: Option<PlaybackDirection>,
 }
 
-impl AnimationConfig {
+impl<K: AnimationKey> AnimationConfig<K> {
     /// Create a looping animation configuration
     pub fn looping(tag_name: &'static str) -> Self {
         Self {
             tag_name,
             on_end: OnAnimationEndAction::Loop,
+            fallback_tag: None,
+            priority: 0,
+            can_be_interrupted: true,
+            events: Vec::new(),
+            speed_multiplier: 1.0,
+            direction: None,
         }
     }
 
@@ -62,23 +126,112 @@ impl AnimationConfig {
         Self {
             tag_name,
             on_end: OnAnimationEndAction::Stop,
+            fallback_tag: None,
+            priority: 0,
+            can_be_interrupted: true,
+            events: Vec::new(),
+            speed_multiplier: 1.0,
+            direction: None,
+        }
+    }
+
+    /// Create a one-shot animation configuration switching to `next` once done
+    pub fn once_then(tag_name: &'static str, next: K) -> Self {
+        Self {
+            on_end: OnAnimationEndAction::TransitionTo(next),
+            ..Self::once(tag_name)
         }
     }
+
+    /// Create a one-shot animation configuration despawning the entity once done
+    pub fn once_then_despawn(tag_name: &'static str) -> Self {
+        Self {
+            on_end: OnAnimationEndAction::Despawn,
+            ..Self::once(tag_name)
+        }
+    }
+
+    /// Play the frames of `fallback_tag` if the Aseprite data has no `tag_name` tag
+    pub fn with_fallback(mut self, fallback_tag: &'static str) -> Self {
+        self.fallback_tag = Some(fallback_tag);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_speed(mut self, speed_multiplier: f32) -> Self {
+        self.speed_multiplier = speed_multiplier;
+        self
+    }
+
+    pub fn with_direction(mut self, direction: PlaybackDirection) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Fire an `AnimationFrameEvent` tagged `tag` when entering frame `frame` of the animation
+    pub fn with_event(mut self, frame: usize, tag: &'static str) -> Self {
+        self.events.push((frame, tag));
+        self
+    }
+
+    /// Keep playing until finished, unless an animation of a higher priority comes along
+    pub fn uninterruptible(mut self) -> Self {
+        self.can_be_interrupted = false;
+        self
+    }
+}
+
+/// Sprite sheets to load, listed in `assets/animations.json`
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct AnimationManifest {
+    /// Files of each sprite, by the name its animations are looked up with
+    pub animations: HashMap<String, AnimationSource>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AnimationSource {
+    /// Aseprite JSON export with the frames, tags and slices
+    pub data: String,
+    /// Sprite sheet image
+    pub image: String,
 }
 
 /// Resource that holds pre-loaded animation data for all entities
 #[derive(Resource, Default)]
 pub struct AnimationLibrary {
-    pub player: Option<AnimationData>,
-    // Add more entity types here as needed
-    // pub enemy_bat: Option<AnimationData>,
-    // pub boss: Option<AnimationData>,
+    animations: HashMap<String, AnimationData>,
+    /// Every name in the manifest, once it has loaded
+    requested: Vec<String>,
 }
 
 impl AnimationLibrary {
+    pub const PLAYER: &'static str = "player";
+    pub const ENEMY_WALKER: &'static str = "walker";
+    /// Not in the manifest until there's a sprite sheet, bats are placeholder boxes until then
+    pub const ENEMY_BAT: &'static str = "bat";
+    /// Likewise for the warden, its look and patterns are in `assets/bosses.json` meanwhile
+    pub const BOSS_WARDEN: &'static str = "warden";
+
+    /// Animation data listed under `name` in the manifest, if it has loaded
+    pub fn get(&self, name: &str) -> Option<&AnimationData> {
+        self.animations.get(name)
+    }
+
+    pub fn insert(&mut self, name: &str, anim_data: AnimationData) {
+        self.animations.insert(name.to_string(), anim_data);
+    }
+
+    /// Whether everything in the manifest has loaded
     pub fn is_ready(&self) -> bool {
-        self.player.is_some()
-        // && self.enemy_bat.is_some() // etc.
+        !self.requested.is_empty()
+            && self
+                .requested
+                .iter()
+                .all(|name| self.animations.contains_key(name))
     }
 
     /// Helper function to create an AnimationBundle from library data
@@ -111,7 +264,7 @@ impl AnimationLibrary {
     pub fn create_animation_bundle<K: AnimationKey>(
         anim_data: &AnimationData,
         sprite_path: &str,
-        animation_configs: HashMap<K, AnimationConfig>,
+        animation_configs: HashMap<K, AnimationConfig<K>>,
         default_animation: K,
         asset_server: &AssetServer,
         texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
@@ -126,12 +279,20 @@ impl AnimationLibrary {
         let texture_atlas_layout = texture_atlas_layouts.add(layout);
 
         // Map custom animation keys to Aseprite tag ranges with config overrides
-        let animations: HashMap<K, Animation> = animation_configs
+        let animations: HashMap<K, Animation<K>> = animation_configs
             .into_iter()
             .map(|(key, config)| {
                 let tag = anim_data
                     .animations
                     .get(config.tag_name)
+                    .or_else(|| {
+                        let fallback_tag = config.fallback_tag?;
+                        warn!(
+                            "Animation tag '{}' not found, using '{}' instead",
+                            config.tag_name, fallback_tag
+                        );
+                        anim_data.animations.get(fallback_tag)
+                    })
                     .unwrap_or_else(|| {
                         panic!(
                             "Animation tag '{}' not found in Aseprite data",
@@ -145,15 +306,39 @@ impl AnimationLibrary {
                         first_index: tag.from,
                         last_index: tag.to,
                         on_end: config.on_end,
+                        priority: config.priority,
+                        can_be_interrupted: config.can_be_interrupted,
+                        events: tag
+                            .events
+                            .iter()
+                            .cloned()
+                            .chain(
+                                config
+                                    .events
+                                    .iter()
+                                    .map(|&(frame, event)| (frame, event.to_string())),
+                            )
+                            .collect(),
+                        speed_multiplier: config.speed_multiplier,
+                        direction: config
+                            .direction
+                            .unwrap_or_else(|| PlaybackDirection::from_aseprite(&tag.direction)),
                     },
                 )
             })
             .collect();
 
+        // Start on the first frame of the default animation instead of the first frame of the sheet
+        let (start_index, timer) = animations
+            .get(&default_animation)
+            .map_or((0, AnimationTimer::default()), |animation| {
+                (animation.start_index(), AnimationTimer::starting(animation))
+            });
+
         AnimationBundle {
             current_animation: CurrentAnimation::new(default_animation),
             next_animation: NextAnimation { key: None },
-            timer: AnimationTimer::default(),
+            timer,
             animations: AnimationMap {
                 animations,
                 frames: anim_data.frames.clone(),
@@ -162,22 +347,23 @@ impl AnimationLibrary {
                 texture,
                 TextureAtlas {
                     layout: texture_atlas_layout,
-                    index: 0,
+                    index: start_index,
                 },
             ),
         }
     }
 }
 
-/// Resource holding handles to Aseprite JSON files during loading
+/// Resource holding handles to the manifest and the Aseprite JSON files it lists during loading
 #[derive(Resource)]
 pub struct AnimationDataHandles {
-    pub player: Handle<Aseprite>,
-    // Add more handles as needed
+    pub manifest: Handle<AnimationManifest>,
+    /// Filled in once the manifest has loaded
+    pub data: HashMap<String, Handle<Aseprite>>,
 }
 
 /// Converts Aseprite data into AnimationData
-pub fn aseprite_to_animation_data(aseprite: &Aseprite) -> AnimationData {
+pub fn aseprite_to_animation_data(aseprite: &Aseprite, image_path: &str) -> AnimationData {
     let frames: Vec<AnimationFrame> = aseprite
         .frames
         .iter()
@@ -199,6 +385,11 @@ pub fn aseprite_to_animation_data(aseprite: &Aseprite) -> AnimationData {
                     from: tag.from,
                     to: tag.to,
                     direction: tag.direction.clone(),
+                    events: tag
+                        .data
+                        .as_deref()
+                        .map(parse_frame_events)
+                        .unwrap_or_default(),
                 },
             )
         })
@@ -211,32 +402,29 @@ pub fn aseprite_to_animation_data(aseprite: &Aseprite) -> AnimationData {
         .map(|f| UVec2::new(f.frame.w as u32, f.frame.h as u32))
         .unwrap_or(UVec2::ZERO);
 
-    let slice_map = HashMap::from_iter(aseprite.meta.slices.iter().map(|slice| {
-        (
-            slice.name.clone(),
-            Slice {
-                name: slice.name.clone(),
-                color: slice.color.clone(),
-                keys: slice.keys.clone(),
-            },
-        )
-    }));
+    let slice_map = HashMap::from_iter(
+        aseprite
+            .meta
+            .slices
+            .iter()
+            .map(|slice| (slice.name.clone(), slice.clone())),
+    );
 
     AnimationData {
         frames,
         animations,
         sheet_size: UVec2::new(aseprite.meta.size.w as u32, aseprite.meta.size.h as u32),
         frame_size,
-        slices: aseprite.meta.slices.clone(),
         slice_map,
+        image_path: image_path.to_string(),
     }
 }
 
 /// Startup system to begin loading animation data
 pub fn load_animation_data(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(AnimationDataHandles {
-        player: asset_server.load("sprites/player.json"),
-        // Add more loads as needed
+        manifest: asset_server.load("animations.json"),
+        data: HashMap::new(),
     });
     commands.insert_resource(AnimationLibrary::default());
 }
@@ -244,32 +432,50 @@ pub fn load_animation_data(mut commands: Commands, asset_server: Res<AssetServer
 /// System that checks if assets are loaded and builds the animation library
 pub fn build_animation_library(
     mut library: ResMut<AnimationLibrary>,
+    mut handles: ResMut<AnimationDataHandles>,
+    manifests: Res<Assets<AnimationManifest>>,
     aseprite_assets: Res<Assets<Aseprite>>,
-    handles: Res<AnimationDataHandles>,
+    asset_server: Res<AssetServer>,
 ) {
     // Only run if library isn't ready yet
     if library.is_ready() {
         return;
     }
+    let Some(manifest) = manifests.get(&handles.manifest) else {
+        return;
+    };
 
-    // Check if player animation data is loaded
-    if library.player.is_none() {
-        if let Some(player_data) = aseprite_assets.get(&handles.player) {
-            let anim_data = aseprite_to_animation_data(player_data);
-            info!(
-                "Loaded player animations: {} frames, {} tags",
-                anim_data.frames.len(),
-                anim_data.animations.len()
-            );
-            for (name, tag) in &anim_data.animations {
-                info!("  - '{}': frames {}-{}", name, tag.from, tag.to);
-            }
-            library.player = Some(anim_data);
+    if library.requested.is_empty() {
+        for (name, source) in &manifest.animations {
+            handles
+                .data
+                .insert(name.clone(), asset_server.load(&source.data));
         }
+        library.requested = manifest.animations.keys().cloned().collect();
     }
 
-    // Add more entity types here as they're loaded
-    // if library.enemy_bat.is_none() { ... }
+    for (name, handle) in &handles.data {
+        if library.animations.contains_key(name) {
+            continue;
+        }
+        let (Some(aseprite), Some(source)) =
+            (aseprite_assets.get(handle), manifest.animations.get(name))
+        else {
+            continue;
+        };
+
+        let anim_data = aseprite_to_animation_data(aseprite, &source.image);
+        info!(
+            "Loaded '{}' animations: {} frames, {} tags",
+            name,
+            anim_data.frames.len(),
+            anim_data.animations.len()
+        );
+        for (tag_name, tag) in &anim_data.animations {
+            info!("  - '{}': frames {}-{}", tag_name, tag.from, tag.to);
+        }
+        library.insert(name, anim_data);
+    }
 
     if library.is_ready() {
         info!("Animation library fully loaded!");
@@ -284,3 +490,57 @@ impl Plugin for AnimationLibraryPlugin {
             .add_systems(Update, build_animation_library);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_names_sprites() {
+        let manifest: AnimationManifest = serde_json::from_str(
+            r#"{ "animations": { "player": { "data": "sprites/player.json", "image": "sprites/player.png" } } }"#,
+        )
+        .unwrap();
+        let player = &manifest.animations[AnimationLibrary::PLAYER];
+        assert_eq!(player.data, "sprites/player.json");
+        assert_eq!(player.image, "sprites/player.png");
+
+        // Nothing is ready before the manifest lists what to wait for
+        assert!(!AnimationLibrary::default().is_ready());
+    }
+
+    #[test]
+    fn test_manifest_only_lists_existing_sprites() {
+        let assets = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let manifest: AnimationManifest =
+            serde_json::from_str(&std::fs::read_to_string(assets.join("animations.json")).unwrap())
+                .unwrap();
+        for (name, source) in &manifest.animations {
+            for path in [&source.data, &source.image] {
+                assert!(assets.join(path).exists(), "'{}' is missing {}", name, path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_walker_sheet_has_its_walk_cycle() {
+        let path =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/sprites/walker.json");
+        let aseprite: Aseprite =
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let anim_data = aseprite_to_animation_data(&aseprite, "sprites/walker.png");
+
+        assert!(anim_data.animations.contains_key("walk"));
+        // Drawn at the size of the walker's collider
+        assert_eq!(anim_data.frame_size, UVec2::splat(32));
+    }
+
+    #[test]
+    fn test_parse_frame_events() {
+        assert_eq!(
+            parse_frame_events("0:footstep, 3: footstep,junk, 2:"),
+            vec![(0, "footstep".to_string()), (3, "footstep".to_string())]
+        );
+        assert!(parse_frame_events("").is_empty());
+    }
+}
@@ -0,0 +1,437 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{
+    asset::io::file::FileAssetReader,
+    audio::{SpatialScale, Volume},
+    prelude::*,
+};
+
+use crate::{
+    bundles::{camera::MainCamera, player::Player},
+    states::GameState,
+};
+
+use super::{collision::IsGrounded, pickup::PickupCollectedEvent};
+
+/// Sound effects the game can play
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum SfxId {
+    Jump,
+    Land,
+    Shoot,
+    Hit,
+    /// A projectile hitting a wall
+    Impact,
+    Pickup,
+    UiClick,
+    /// Something jumping into or climbing out of water
+    Splash,
+}
+
+impl SfxId {
+    const ALL: [Self; 8] = [
+        Self::Jump,
+        Self::Land,
+        Self::Shoot,
+        Self::Hit,
+        Self::Impact,
+        Self::Pickup,
+        Self::UiClick,
+        Self::Splash,
+    ];
+
+    fn path(self) -> &'static str {
+        match self {
+            Self::Jump => "audio/sfx/jump.ogg",
+            Self::Land => "audio/sfx/land.ogg",
+            Self::Shoot => "audio/sfx/shoot.ogg",
+            Self::Hit => "audio/sfx/hit.ogg",
+            Self::Impact => "audio/sfx/impact.ogg",
+            Self::Pickup => "audio/sfx/pickup.ogg",
+            Self::UiClick => "audio/sfx/ui_click.ogg",
+            Self::Splash => "audio/sfx/splash.ogg",
+        }
+    }
+}
+
+/// Play a sound effect once
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlaySfxEvent(pub SfxId);
+
+/// Play a sound effect once at a point in the world, quieter the further it is from the
+/// camera and panned to the side it's on
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct PlaySfxAtEvent {
+    pub id: SfxId,
+    pub position: Vec2,
+}
+
+/// Looping background music
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum MusicTrack {
+    Menu,
+    Level,
+}
+
+impl MusicTrack {
+    const ALL: [Self; 2] = [Self::Menu, Self::Level];
+
+    fn path(self) -> &'static str {
+        match self {
+            Self::Menu => "audio/music/menu.ogg",
+            Self::Level => "audio/music/level.ogg",
+        }
+    }
+
+    /// Music playing in a state, if any
+    pub fn for_state(state: &GameState) -> Option<Self> {
+        match state {
+            GameState::Loading => None,
+            GameState::Game => Some(Self::Level),
+            GameState::Menu | GameState::GameOver | GameState::LevelComplete => Some(Self::Menu),
+        }
+    }
+}
+
+/// Volumes from 0.0 to 1.0, the music and sound effects ones scaled by `master`
+#[derive(Resource, Clone, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 0.5,
+            sfx: 0.8,
+        }
+    }
+}
+
+impl AudioSettings {
+    pub fn music_volume(&self) -> f32 {
+        self.master * self.music
+    }
+
+    pub fn sfx_volume(&self) -> f32 {
+        self.master * self.sfx
+    }
+}
+
+/// Sounds whose files are in the assets folder
+///
+/// The audio files aren't part of the repository, so any of them may be missing. Those are
+/// left out here and simply don't play, rather than every play logging a failed load.
+#[derive(Resource, Default)]
+struct AudioAssets {
+    sfx: HashMap<SfxId, Handle<AudioSource>>,
+    music: HashMap<MusicTrack, Handle<AudioSource>>,
+}
+
+/// A music track fading in, playing, or fading out to be replaced by another
+#[derive(Component)]
+struct Music {
+    track: MusicTrack,
+    /// How far faded in the track is, from 0.0 to 1.0
+    fade: f32,
+    fading_out: bool,
+}
+
+/// Time for one track to fade out while the next one fades in
+const MUSIC_CROSSFADE: Duration = Duration::from_millis(1500);
+/// Distance between the listener's ears, how far apart sounds have to be to pan fully
+const EAR_GAP: f32 = 320.0;
+/// Sounds closer than this are at full volume, further ones fall off with the square of
+/// the distance in these units
+const FULL_VOLUME_DISTANCE: f32 = 160.0;
+/// Sounds further away than this aren't played at all
+const MAX_HEARING_DISTANCE: f32 = 960.0;
+
+/// Where to put the emitter for a sound at `position`, `None` when it's too far away from
+/// the listener to be heard
+fn emitter_transform(position: Vec2, listener: Vec2) -> Option<Transform> {
+    (position.distance(listener) <= MAX_HEARING_DISTANCE)
+        .then(|| Transform::from_translation(position.extend(0.0)))
+}
+
+fn load_audio(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let assets_dir = FileAssetReader::get_base_path().join("assets");
+    let mut load = |path: &str| {
+        if assets_dir.join(path).exists() {
+            Some(asset_server.load(path.to_string()))
+        } else {
+            info!("No audio file at {}, playing nothing instead", path);
+            None
+        }
+    };
+
+    let sfx = SfxId::ALL
+        .into_iter()
+        .filter_map(|id| Some((id, load(id.path())?)))
+        .collect();
+    let music = MusicTrack::ALL
+        .into_iter()
+        .filter_map(|track| Some((track, load(track.path())?)))
+        .collect();
+    commands.insert_resource(AudioAssets { sfx, music });
+}
+
+fn play_sfx(
+    mut commands: Commands,
+    mut event_reader: EventReader<PlaySfxEvent>,
+    settings: Res<AudioSettings>,
+    audio_assets: Res<AudioAssets>,
+) {
+    for PlaySfxEvent(id) in event_reader.read() {
+        let Some(sound) = audio_assets.sfx.get(id) else {
+            continue;
+        };
+        commands.spawn((
+            AudioPlayer::new(sound.clone()),
+            PlaybackSettings::DESPAWN.with_volume(Volume::Linear(settings.sfx_volume())),
+        ));
+    }
+}
+
+fn play_positional_sfx(
+    mut commands: Commands,
+    mut event_reader: EventReader<PlaySfxAtEvent>,
+    listener_query: Query<&GlobalTransform, With<SpatialListener>>,
+    settings: Res<AudioSettings>,
+    audio_assets: Res<AudioAssets>,
+) {
+    let Ok(listener) = listener_query.single() else {
+        event_reader.clear();
+        return;
+    };
+
+    for event in event_reader.read() {
+        let Some(sound) = audio_assets.sfx.get(&event.id) else {
+            continue;
+        };
+        let Some(transform) = emitter_transform(event.position, listener.translation().xy()) else {
+            continue;
+        };
+        commands.spawn((
+            AudioPlayer::new(sound.clone()),
+            PlaybackSettings::DESPAWN
+                .with_volume(Volume::Linear(settings.sfx_volume()))
+                .with_spatial(true)
+                .with_spatial_scale(SpatialScale::new_2d(1.0 / FULL_VOLUME_DISTANCE)),
+            transform,
+        ));
+    }
+}
+
+/// The camera hears the positional sounds
+fn add_listener(mut commands: Commands, camera_query: Query<Entity, Added<MainCamera>>) {
+    for camera in camera_query.iter() {
+        commands
+            .entity(camera)
+            .insert(SpatialListener::new(EAR_GAP));
+    }
+}
+
+/// The player touching down after being in the air
+fn play_landing_sounds(
+    player_query: Query<&IsGrounded, With<Player>>,
+    mut event_writer: EventWriter<PlaySfxEvent>,
+    // Whether the player was in the air last frame
+    mut airborne: Local<bool>,
+) {
+    let Ok(is_grounded) = player_query.single() else {
+        return;
+    };
+    if is_grounded.grounded && *airborne {
+        event_writer.write(PlaySfxEvent(SfxId::Land));
+    }
+    *airborne = !is_grounded.grounded;
+}
+
+fn play_pickup_sounds(
+    mut pickup_events: EventReader<PickupCollectedEvent>,
+    mut event_writer: EventWriter<PlaySfxEvent>,
+) {
+    for _ in pickup_events.read() {
+        event_writer.write(PlaySfxEvent(SfxId::Pickup));
+    }
+}
+
+/// Fade out whatever doesn't belong to the new state and fade in the track that does
+fn switch_music(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    mut music_query: Query<&mut Music>,
+    audio_assets: Res<AudioAssets>,
+) {
+    let track = MusicTrack::for_state(state.get());
+    let mut already_playing = false;
+    for mut music in music_query.iter_mut() {
+        if Some(music.track) == track {
+            // Coming back to a track that was on its way out picks it back up
+            music.fading_out = false;
+            already_playing = true;
+        } else {
+            music.fading_out = true;
+        }
+    }
+
+    if let Some(track) = track
+        && !already_playing
+        && let Some(sound) = audio_assets.music.get(&track)
+    {
+        commands.spawn((
+            Music {
+                track,
+                fade: 0.0,
+                fading_out: false,
+            },
+            AudioPlayer::new(sound.clone()),
+            PlaybackSettings::LOOP.with_volume(Volume::SILENT),
+        ));
+    }
+}
+
+fn update_music(
+    mut commands: Commands,
+    mut music_query: Query<(Entity, &mut Music, Option<&mut AudioSink>)>,
+    settings: Res<AudioSettings>,
+    time: Res<Time<Real>>,
+) {
+    let step = time.delta_secs() / MUSIC_CROSSFADE.as_secs_f32();
+    for (entity, mut music, sink) in music_query.iter_mut() {
+        music.fade = if music.fading_out {
+            music.fade - step
+        } else {
+            music.fade + step
+        }
+        .clamp(0.0, 1.0);
+
+        if music.fading_out && music.fade == 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        // The sink only shows up once the track has started playing
+        if let Some(mut sink) = sink {
+            sink.set_volume(Volume::Linear(music.fade * settings.music_volume()));
+        }
+    }
+}
+
+/// Music for each game state and sound effects played through `PlaySfxEvent` and
+/// `PlaySfxAtEvent`
+///
+/// The files go under `assets/audio`, see `SfxId::path` and `MusicTrack::path`. Missing ones
+/// are skipped, so the game runs silently without them.
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySfxEvent>()
+            .add_event::<PlaySfxAtEvent>()
+            .init_resource::<AudioSettings>()
+            .init_resource::<AudioAssets>()
+            .add_systems(PreStartup, load_audio)
+            .add_systems(
+                Update,
+                (
+                    (
+                        play_landing_sounds.run_if(in_state(GameState::Game)),
+                        play_pickup_sounds,
+                    ),
+                    (play_sfx, play_positional_sfx),
+                )
+                    .chain(),
+            )
+            .add_systems(Update, add_listener)
+            .add_systems(
+                Update,
+                (
+                    switch_music.run_if(state_changed::<GameState>),
+                    update_music,
+                )
+                    .chain(),
+            )
+            .register_type::<AudioSettings>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_music_crossfades() {
+        let mut world = World::new();
+        world.init_resource::<AudioSettings>();
+        world.init_resource::<Time<Real>>();
+        let old = world
+            .spawn(Music {
+                track: MusicTrack::Menu,
+                fade: 1.0,
+                fading_out: true,
+            })
+            .id();
+        let new = world
+            .spawn(Music {
+                track: MusicTrack::Level,
+                fade: 0.0,
+                fading_out: false,
+            })
+            .id();
+
+        world
+            .resource_mut::<Time<Real>>()
+            .advance_by(MUSIC_CROSSFADE / 2);
+        world.run_system_once(update_music).unwrap();
+        assert_eq!(world.get::<Music>(old).unwrap().fade, 0.5);
+        assert_eq!(world.get::<Music>(new).unwrap().fade, 0.5);
+
+        world
+            .resource_mut::<Time<Real>>()
+            .advance_by(MUSIC_CROSSFADE / 2);
+        world.run_system_once(update_music).unwrap();
+        assert!(world.get_entity(old).is_err());
+        assert_eq!(world.get::<Music>(new).unwrap().fade, 1.0);
+    }
+
+    #[test]
+    fn test_missing_sounds_are_skipped() {
+        let mut world = World::new();
+        world.init_resource::<AudioSettings>();
+        world.init_resource::<Events<PlaySfxEvent>>();
+        world.insert_resource(AudioAssets {
+            sfx: HashMap::from([(SfxId::Jump, Handle::default())]),
+            ..default()
+        });
+
+        world.send_event(PlaySfxEvent(SfxId::Jump));
+        world.send_event(PlaySfxEvent(SfxId::Land));
+        world.run_system_once(play_sfx).unwrap();
+        let mut query = world.query::<&AudioPlayer>();
+        assert_eq!(query.iter(&world).count(), 1);
+    }
+
+    #[test]
+    fn test_far_away_sounds_are_not_played() {
+        let listener = Vec2::new(100.0, 50.0);
+
+        assert_eq!(
+            emitter_transform(Vec2::new(400.0, 50.0), listener),
+            Some(Transform::from_xyz(400.0, 50.0, 0.0))
+        );
+        assert_eq!(
+            emitter_transform(
+                Vec2::new(100.0 + MAX_HEARING_DISTANCE + 1.0, 50.0),
+                listener
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,703 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    bundles::player::Player,
+    components::GameEntity,
+    constants::{self, TILE_SIZE, TilesPerSecond},
+    entity_fields::{BossArenaFields, BossFields},
+    schedule::MovementSet,
+    states::GameState,
+};
+
+use super::{
+    animation_library::AnimationLibrary,
+    audio::{PlaySfxEvent, SfxId},
+    camera::{CameraShakeEvent, CameraZone, CameraZoneMode},
+    collision::{IsTouchingWallLeft, IsTouchingWallRight, Velocity},
+    enemy::{EnemyKind, EnemySpawnEvent},
+    health::{Health, apply_damage},
+    interaction::SignalEvent,
+    level::{LdtkEntityAppExt, LdtkEntityContext, entity_world_rect},
+    projectile::{Faction, ProjectileSpawnEvent, ProjectileVelocity},
+    sprite_effect::FlashWhite,
+};
+
+/// The bosses there are, placed in the level editor by name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum BossKind {
+    Warden,
+}
+
+impl BossKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "warden" => Some(Self::Warden),
+            _ => None,
+        }
+    }
+
+    /// Name shown above the boss health bar
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::Warden => "The Warden",
+        }
+    }
+
+    /// Name the boss is listed under in `assets/bosses.json`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Warden => "warden",
+        }
+    }
+
+    /// Name its animations are looked up with in the `AnimationLibrary`
+    pub fn animation(self) -> &'static str {
+        match self {
+            Self::Warden => AnimationLibrary::BOSS_WARDEN,
+        }
+    }
+}
+
+/// Bosses to fight, listed in `assets/bosses.json`
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct BossManifest {
+    /// Definitions by `BossKind::name`
+    pub bosses: HashMap<String, BossDefinition>,
+}
+
+/// Resource holding the handle to the boss manifest
+#[derive(Resource)]
+pub struct BossDefinitions(pub Handle<BossManifest>);
+
+impl BossDefinitions {
+    /// Definition of `kind`, once the manifest has loaded
+    pub fn get<'a>(
+        &self,
+        kind: BossKind,
+        manifests: &'a Assets<BossManifest>,
+    ) -> Option<&'a BossDefinition> {
+        manifests.get(&self.0)?.bosses.get(kind.name())
+    }
+}
+
+/// Everything that sets one boss apart from another
+#[derive(Clone, Debug, Deserialize)]
+pub struct BossDefinition {
+    pub health: u32,
+    /// Width and height in tiles
+    size: [f32; 2],
+    pub contact_damage: u32,
+    /// sRGB placeholder color until the sprite sheet has loaded
+    color: [f32; 3],
+    /// In order of decreasing `health_threshold`, starting at 1.0
+    pub phases: Vec<BossPhase>,
+}
+
+impl BossDefinition {
+    pub fn size(&self) -> Vec2 {
+        Vec2::from(self.size) * TILE_SIZE
+    }
+
+    pub fn color(&self) -> Color {
+        let [red, green, blue] = self.color;
+        Color::srgb(red, green, blue)
+    }
+
+    /// Check what `Boss` relies on, bosses.json being edited by hand
+    pub fn validate(&self) -> Result<(), String> {
+        let Some(first) = self.phases.first() else {
+            return Err("no phases".to_string());
+        };
+        if first.health_threshold != 1.0 {
+            return Err(format!(
+                "first phase starts at {} instead of 1.0",
+                first.health_threshold
+            ));
+        }
+        if let Some(index) = self
+            .phases
+            .iter()
+            .position(|phase| phase.pattern.is_empty())
+        {
+            return Err(format!("phase {} has no attacks", index));
+        }
+        Ok(())
+    }
+}
+
+/// Durations are written in milliseconds
+fn deserialize_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+/// Stretch of a boss fight with an attack pattern of its own
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct BossPhase {
+    /// Fraction of max health at or below which the phase takes over
+    pub health_threshold: f32,
+    /// Attacks gone through in order, starting over after the last one
+    pub pattern: Vec<BossAttack>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BossAttack {
+    /// Stand still for `wind_up`, then fire `count` shots fanned out at the player, `spread`
+    /// radians apart
+    Volley {
+        count: u32,
+        spread: f32,
+        speed: TilesPerSecond,
+        #[serde(deserialize_with = "deserialize_millis")]
+        wind_up: Duration,
+    },
+    /// Run at where the player was when it started, stopping at walls
+    Charge {
+        speed: TilesPerSecond,
+        #[serde(deserialize_with = "deserialize_millis")]
+        duration: Duration,
+    },
+    /// Stand still, the opening to hit back in
+    Rest(#[serde(deserialize_with = "deserialize_millis")] Duration),
+}
+
+impl BossAttack {
+    fn duration(&self) -> Duration {
+        match self {
+            Self::Volley { wind_up, .. } => *wind_up,
+            Self::Charge { duration, .. } => *duration,
+            Self::Rest(duration) => *duration,
+        }
+    }
+}
+
+/// Enemy fighting through its phases' attack patterns once its arena's fight starts
+#[derive(Component, Debug, Reflect)]
+pub struct Boss {
+    pub kind: BossKind,
+    #[reflect(ignore)]
+    pub phases: Vec<BossPhase>,
+    pub phase: usize,
+    /// Position in the current phase's pattern
+    step: usize,
+    step_timer: Timer,
+    /// 1.0 facing right, -1.0 facing left, kept for the length of a charge
+    facing: f32,
+    /// Stands idle until the player walks into its arena
+    pub active: bool,
+}
+
+impl Boss {
+    pub fn new(kind: BossKind, phases: Vec<BossPhase>) -> Self {
+        let mut boss = Self {
+            kind,
+            phases,
+            phase: 0,
+            step: 0,
+            step_timer: Timer::default(),
+            facing: -1.0,
+            active: false,
+        };
+        boss.start_phase(0);
+        boss
+    }
+
+    pub fn attack(&self) -> &BossAttack {
+        &self.phases[self.phase].pattern[self.step]
+    }
+
+    /// Start `phase`'s pattern from its first attack
+    pub fn start_phase(&mut self, phase: usize) {
+        self.phase = phase;
+        self.step = 0;
+        self.step_timer = Timer::new(self.attack().duration(), TimerMode::Once);
+    }
+
+    fn next_attack(&mut self) {
+        self.step = (self.step + 1) % self.phases[self.phase].pattern.len();
+        self.step_timer = Timer::new(self.attack().duration(), TimerMode::Once);
+    }
+}
+
+/// Phase for a boss with `health_fraction` of its health left: the last one whose threshold
+/// has been reached
+pub fn phase_for_health(phases: &[BossPhase], health_fraction: f32) -> usize {
+    phases
+        .iter()
+        .rposition(|phase| health_fraction <= phase.health_threshold)
+        .unwrap_or(0)
+}
+
+/// Directions of a volley's shots, fanned out evenly around `aim`
+fn volley_directions(aim: Vec2, count: u32, spread: f32) -> impl Iterator<Item = Vec2> {
+    let aim = aim.try_normalize().unwrap_or(Vec2::X);
+    let middle = (count as f32 - 1.0) / 2.0;
+    (0..count).map(move |shot| Vec2::from_angle((shot as f32 - middle) * spread).rotate(aim))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum ArenaState {
+    #[default]
+    Waiting,
+    Fighting,
+    Cleared,
+}
+
+/// Area the player fights the bosses inside it in, shut in by its doors until they're beaten
+#[derive(Component, Debug, Reflect)]
+pub struct BossArena {
+    /// World space area of the arena
+    pub rect: Rect,
+    /// `LdtkIid`s of the doors closed during the fight
+    pub doors: Vec<String>,
+    pub state: ArenaState,
+}
+
+#[derive(Component)]
+struct BossHealthBar;
+
+#[derive(Component)]
+struct BossHealthBarFill;
+
+/// Beats any camera zone placed in the level editor, so the arena always frames the fight
+const ARENA_CAMERA_PRIORITY: i32 = 100;
+const BOSS_PROJECTILE_DAMAGE: u32 = 1;
+const BOSS_PROJECTILE_SIZE: Vec2 = Vec2::splat(6.0);
+const PHASE_FLASH_DURATION: Duration = Duration::from_millis(300);
+const PHASE_SHAKE: CameraShakeEvent = CameraShakeEvent {
+    amplitude: 5.0,
+    duration: Duration::from_millis(400),
+    frequency: 20.0,
+};
+const HEALTH_BAR_SIZE: Vec2 = Vec2::new(240.0, 8.0);
+const HEALTH_BAR_BACKGROUND: Color = Color::srgb(0.15, 0.1, 0.1);
+const HEALTH_BAR_COLOR: Color = Color::srgb(0.75, 0.15, 0.35);
+const HEALTH_BAR_FONT_SIZE: f32 = 12.0;
+
+fn health_fraction(health: &Health) -> f32 {
+    if health.max == 0 {
+        0.0
+    } else {
+        health.current as f32 / health.max as f32
+    }
+}
+
+fn run_boss_attacks(
+    mut boss_query: Query<(
+        Entity,
+        &mut Boss,
+        &Health,
+        &Transform,
+        &mut Velocity,
+        &IsTouchingWallLeft,
+        &IsTouchingWallRight,
+        Option<&mut Sprite>,
+    )>,
+    player_query: Query<&Transform, With<Player>>,
+    asset_server: Res<AssetServer>,
+    mut projectile_writer: EventWriter<ProjectileSpawnEvent>,
+    time: Res<Time>,
+) {
+    let player = player_query
+        .iter()
+        .next()
+        .map(|player| player.translation.xy());
+
+    for (entity, mut boss, health, transform, mut velocity, wall_left, wall_right, sprite) in
+        boss_query.iter_mut()
+    {
+        let Some(player) = player.filter(|_| boss.active && !health.is_dead()) else {
+            velocity.0.x = 0.0;
+            continue;
+        };
+        let position = transform.translation.xy();
+        let to_player = player - position;
+
+        let attack = *boss.attack();
+        velocity.0.x = match attack {
+            BossAttack::Charge { speed, .. } => {
+                let blocked =
+                    (boss.facing < 0.0 && wall_left.0) || (boss.facing > 0.0 && wall_right.0);
+                if blocked {
+                    0.0
+                } else {
+                    boss.facing * speed.to_pixels()
+                }
+            }
+            _ => {
+                // Keeps an eye on the player between charges
+                if to_player.x != 0.0 {
+                    boss.facing = to_player.x.signum();
+                }
+                0.0
+            }
+        };
+
+        if boss.step_timer.tick(time.delta()).finished() {
+            if let BossAttack::Volley {
+                count,
+                spread,
+                speed,
+                ..
+            } = attack
+            {
+                for direction in volley_directions(to_player, count, spread) {
+                    projectile_writer.write(ProjectileSpawnEvent {
+                        transform: Transform::from_translation(transform.translation),
+                        velocity: ProjectileVelocity(direction * speed.to_pixels()),
+                        sprite: asset_server.load("sprites/bullet.png"),
+                        animation: None,
+                        gravity: 0.0,
+                        damage: BOSS_PROJECTILE_DAMAGE,
+                        size: BOSS_PROJECTILE_SIZE,
+                        faction: Faction::Enemy,
+                        owner: Some(entity),
+                    });
+                }
+            }
+            boss.next_attack();
+        }
+
+        if let Some(mut sprite) = sprite {
+            sprite.flip_x = boss.facing < 0.0;
+        }
+    }
+}
+
+/// Move on to the next phase once health drops to its threshold, with a flash and a shake
+/// to let the player know
+fn update_boss_phases(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Boss, &Health), Changed<Health>>,
+    mut shake_writer: EventWriter<CameraShakeEvent>,
+) {
+    for (entity, mut boss, health) in query.iter_mut() {
+        let phase = phase_for_health(&boss.phases, health_fraction(health));
+        if phase <= boss.phase || health.is_dead() {
+            continue;
+        }
+
+        boss.start_phase(phase);
+        commands
+            .entity(entity)
+            .insert(FlashWhite::new(PHASE_FLASH_DURATION));
+        shake_writer.write(PHASE_SHAKE);
+    }
+}
+
+/// Shut the arena and lock the camera on it when the player walks in, and open it back up
+/// once its bosses are beaten
+///
+/// A player leaving mid-fight, by dying and respawning outside, resets the fight.
+fn update_boss_arenas(
+    mut commands: Commands,
+    mut arena_query: Query<(Entity, &mut BossArena)>,
+    mut boss_query: Query<(&mut Boss, &mut Health, &Transform)>,
+    player_query: Query<&Transform, With<Player>>,
+    mut signal_writer: EventWriter<SignalEvent>,
+    mut sfx_writer: EventWriter<PlaySfxEvent>,
+) {
+    let Some(player) = player_query
+        .iter()
+        .next()
+        .map(|player| player.translation.xy())
+    else {
+        return;
+    };
+
+    for (entity, mut arena) in arena_query.iter_mut() {
+        let player_inside = arena.rect.contains(player);
+        let mut bosses: Vec<_> = boss_query
+            .iter_mut()
+            .filter(|(_, _, transform)| arena.rect.contains(transform.translation.xy()))
+            .collect();
+        let bosses_alive = bosses.iter().any(|(_, health, _)| !health.is_dead());
+        let mut set_doors = |open: bool| {
+            for door in arena.doors.iter() {
+                signal_writer.write(SignalEvent {
+                    target: door.clone(),
+                    on: open,
+                });
+            }
+        };
+
+        match arena.state {
+            ArenaState::Waiting if player_inside && bosses_alive => {
+                set_doors(false);
+                for (boss, _, _) in bosses.iter_mut() {
+                    boss.active = true;
+                }
+                commands.entity(entity).insert(CameraZone {
+                    rect: arena.rect,
+                    mode: CameraZoneMode::Lock,
+                    zoom: 1.0,
+                    priority: ARENA_CAMERA_PRIORITY,
+                });
+                sfx_writer.write(PlaySfxEvent(SfxId::Impact));
+                arena.state = ArenaState::Fighting;
+            }
+            ArenaState::Fighting if !bosses_alive => {
+                set_doors(true);
+                commands.entity(entity).remove::<CameraZone>();
+                arena.state = ArenaState::Cleared;
+            }
+            ArenaState::Fighting if !player_inside => {
+                set_doors(true);
+                for (boss, health, _) in bosses.iter_mut() {
+                    boss.active = false;
+                    boss.start_phase(0);
+                    health.current = health.max;
+                }
+                commands.entity(entity).remove::<CameraZone>();
+                arena.state = ArenaState::Waiting;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Bar along the bottom of the screen while a boss is being fought
+fn update_boss_health_bar(
+    mut commands: Commands,
+    boss_query: Query<(&Boss, &Health)>,
+    bar_query: Query<Entity, With<BossHealthBar>>,
+    mut fill_query: Query<&mut Node, With<BossHealthBarFill>>,
+) {
+    let fought = boss_query
+        .iter()
+        .find(|(boss, health)| boss.active && !health.is_dead());
+    let Some((boss, health)) = fought else {
+        for bar in bar_query.iter() {
+            commands.entity(bar).despawn();
+        }
+        return;
+    };
+
+    let width = Val::Percent(health_fraction(health) * 100.0);
+    if !bar_query.is_empty() {
+        for mut node in fill_query.iter_mut() {
+            node.width = width;
+        }
+        return;
+    }
+
+    commands
+        .spawn((
+            BossHealthBar,
+            Node {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(16.0),
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            GameEntity,
+        ))
+        .with_children(|bar| {
+            bar.spawn((
+                Text::new(boss.kind.display_name()),
+                TextFont {
+                    font_size: HEALTH_BAR_FONT_SIZE,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            ));
+            bar.spawn((
+                Node {
+                    width: Val::Px(HEALTH_BAR_SIZE.x),
+                    height: Val::Px(HEALTH_BAR_SIZE.y),
+                    ..default()
+                },
+                BackgroundColor(HEALTH_BAR_BACKGROUND),
+            ))
+            .with_child((
+                BossHealthBarFill,
+                Node {
+                    width,
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(HEALTH_BAR_COLOR),
+            ));
+        });
+}
+
+fn load_boss_definitions(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BossDefinitions(asset_server.load("bosses.json")));
+}
+
+fn spawn_ldtk_boss(entity: &EntityInstance, context: &LdtkEntityContext, commands: &mut Commands) {
+    let kind = BossFields::from_entity(entity).and_then(|fields| BossKind::from_name(&fields.boss));
+    let Some(kind) = kind else {
+        warn!("invalid boss: {:?}", entity);
+        return;
+    };
+    let (center, _) = entity_world_rect(entity);
+    commands.send_event(EnemySpawnEvent {
+        kind: EnemyKind::Boss(kind),
+        transform: Transform::from_translation(center.extend(1.0)),
+        spawned_by: None,
+        level_id: Some(context.level_id.clone()),
+        flight: None,
+    });
+}
+
+fn spawn_ldtk_boss_arena(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    let doors = BossArenaFields::from_entity(entity)
+        .map(|fields| fields.doors)
+        .unwrap_or_default();
+    let (center, size) = entity_world_rect(entity);
+    commands.spawn((
+        BossArena {
+            rect: Rect::from_center_size(center, size),
+            doors: doors.into_iter().map(|door| door.entity_iid).collect(),
+            state: ArenaState::Waiting,
+        },
+        Transform::from_translation(center.extend(0.0)),
+        context.level_id.clone(),
+    ));
+}
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::BOSS, spawn_ldtk_boss)
+            .register_ldtk_entity(constants::entities::BOSS_ARENA, spawn_ldtk_boss_arena)
+            .add_systems(Startup, load_boss_definitions)
+            .add_systems(
+                Update,
+                (
+                    update_boss_phases.after(apply_damage),
+                    update_boss_arenas,
+                    update_boss_health_bar,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(FixedUpdate, run_boss_attacks.in_set(MovementSet::Input))
+            .register_type::<BossKind>()
+            .register_type::<Boss>()
+            .register_type::<BossArena>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn load_manifest() -> BossManifest {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/bosses.json");
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn warden() -> BossDefinition {
+        load_manifest().bosses[BossKind::Warden.name()].clone()
+    }
+
+    #[test]
+    fn test_every_boss_has_a_definition() {
+        let manifest = load_manifest();
+        for kind in [BossKind::Warden] {
+            let definition = &manifest.bosses[kind.name()];
+            assert_eq!(BossKind::from_name(kind.name()), Some(kind));
+            assert_eq!(definition.validate(), Ok(()), "{:?}", kind);
+        }
+
+        let mut definition = warden();
+        assert_eq!(definition.size(), Vec2::splat(TILE_SIZE * 3.0));
+        assert_eq!(
+            definition.phases[0].pattern[1],
+            BossAttack::Rest(Duration::from_millis(800))
+        );
+
+        // Broken edits are caught before they reach a `Boss`
+        definition.phases[1].pattern.clear();
+        assert!(definition.validate().is_err());
+        definition.phases[0].health_threshold = 0.9;
+        assert!(definition.validate().is_err());
+        definition.phases.clear();
+        assert!(definition.validate().is_err());
+    }
+
+    #[test]
+    fn test_phases_take_over_at_health_thresholds() {
+        let phases = warden().phases;
+        assert_eq!(phase_for_health(&phases, 1.0), 0);
+        assert_eq!(phase_for_health(&phases, 0.61), 0);
+        assert_eq!(phase_for_health(&phases, 0.6), 1);
+        assert_eq!(phase_for_health(&phases, 0.3), 2);
+        assert_eq!(phase_for_health(&phases, 0.0), 2);
+
+        // Volleys fan out evenly around the aim
+        let directions: Vec<_> = volley_directions(Vec2::new(0.0, -10.0), 3, 0.5).collect();
+        assert!(directions[1].abs_diff_eq(Vec2::NEG_Y, 1e-6));
+        assert!((directions[0].angle_to(directions[1]) - 0.5).abs() < 1e-5);
+        assert!((directions[1].angle_to(directions[2]) - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_arena_shuts_during_fight_and_opens_on_defeat() {
+        let mut world = World::new();
+        world.init_resource::<Events<SignalEvent>>();
+        world.init_resource::<Events<PlaySfxEvent>>();
+        let arena = world
+            .spawn(BossArena {
+                rect: Rect::new(0.0, 0.0, 320.0, 192.0),
+                doors: vec!["door".to_string()],
+                state: ArenaState::Waiting,
+            })
+            .id();
+        let definition = warden();
+        let boss = world
+            .spawn((
+                Boss::new(BossKind::Warden, definition.phases),
+                Health::new(definition.health),
+                Transform::from_xyz(240.0, 48.0, 0.0),
+            ))
+            .id();
+        let player = world
+            .spawn((Player, Transform::from_xyz(-32.0, 48.0, 0.0)))
+            .id();
+        let mut run = |world: &mut World| {
+            world.run_system_once(update_boss_arenas).unwrap();
+            world
+                .resource_mut::<Events<SignalEvent>>()
+                .drain()
+                .map(|event| (event.target, event.on))
+                .collect::<Vec<_>>()
+        };
+
+        // Nothing happens before the player walks in
+        assert!(run(&mut world).is_empty());
+        assert!(!world.get::<Boss>(boss).unwrap().active);
+
+        world.get_mut::<Transform>(player).unwrap().translation.x = 32.0;
+        assert_eq!(run(&mut world), vec![("door".to_string(), false)]);
+        assert!(world.get::<Boss>(boss).unwrap().active);
+        assert_eq!(
+            world.get::<CameraZone>(arena).unwrap().mode,
+            CameraZoneMode::Lock
+        );
+
+        world.get_mut::<Health>(boss).unwrap().current = 0;
+        assert_eq!(run(&mut world), vec![("door".to_string(), true)]);
+        assert!(world.get::<CameraZone>(arena).is_none());
+        assert_eq!(
+            world.get::<BossArena>(arena).unwrap().state,
+            ArenaState::Cleared
+        );
+    }
+}
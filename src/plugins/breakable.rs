@@ -0,0 +1,159 @@
+use std::{f32::consts::PI, time::Duration};
+
+use bevy::prelude::*;
+
+use crate::{
+    constants::{TILE_SIZE, TilesPerSecond, TilesPerSecondSquared},
+    states::GameState,
+};
+
+use super::{
+    audio::{PlaySfxAtEvent, SfxId},
+    sprite_effect::{FadeOut, FlashWhite},
+};
+
+/// Block of level geometry destroyed by enough hits from shots or melee attacks
+#[derive(Component, Debug, Reflect)]
+pub struct Breakable {
+    /// Damage left to take before it breaks
+    pub hp: u32,
+}
+
+impl Default for Breakable {
+    fn default() -> Self {
+        Self { hp: BREAKABLE_HP }
+    }
+}
+
+/// Fired when a shot or a melee attack hits a breakable block
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct BreakableHitEvent {
+    pub target: Entity,
+    pub damage: u32,
+}
+
+/// Piece of a broken block flying off until it fades out
+#[derive(Component)]
+struct Debris {
+    velocity: Vec2,
+}
+
+pub const BREAKABLE_HP: u32 = 3;
+/// Placeholder color until breakable blocks get a tile of their own
+pub const BREAKABLE_COLOR: Color = Color::srgb(0.54, 0.42, 0.29);
+const HIT_FLASH_DURATION: Duration = Duration::from_millis(80);
+const DEBRIS_PIECES: u32 = 8;
+const DEBRIS_SIZE: f32 = 4.0;
+const DEBRIS_SPEED: TilesPerSecond = TilesPerSecond(6.0);
+const DEBRIS_GRAVITY: TilesPerSecondSquared = TilesPerSecondSquared(40.0);
+const DEBRIS_DURATION: Duration = Duration::from_millis(500);
+
+fn spawn_debris(commands: &mut Commands, position: Vec3, color: Color) {
+    for piece in 0..DEBRIS_PIECES {
+        // All around, starting from the block's corners, with an extra kick upwards
+        let angle = PI / 4.0 + piece as f32 / DEBRIS_PIECES as f32 * 2.0 * PI;
+        let direction = Vec2::from_angle(angle);
+        commands.spawn((
+            Debris {
+                velocity: (direction + Vec2::Y) * DEBRIS_SPEED.to_pixels(),
+            },
+            Sprite::from_color(color, Vec2::splat(DEBRIS_SIZE)),
+            Transform::from_translation(position + (direction * TILE_SIZE / 4.0).extend(0.0)),
+            FadeOut::new(DEBRIS_DURATION).then_despawn(),
+        ));
+    }
+}
+
+/// Flash blocks that get hit, and break them apart once they're out of hp
+pub fn damage_breakables(
+    mut commands: Commands,
+    mut event_reader: EventReader<BreakableHitEvent>,
+    mut query: Query<(&mut Breakable, &GlobalTransform, Option<&Sprite>)>,
+    mut sfx_writer: EventWriter<PlaySfxAtEvent>,
+) {
+    for event in event_reader.read() {
+        let Ok((mut breakable, transform, sprite)) = query.get_mut(event.target) else {
+            continue;
+        };
+        // Already breaking, from an earlier hit this frame
+        if breakable.hp == 0 {
+            continue;
+        }
+
+        breakable.hp = breakable.hp.saturating_sub(event.damage);
+        sfx_writer.write(PlaySfxAtEvent {
+            id: SfxId::Impact,
+            position: transform.translation().xy(),
+        });
+        if breakable.hp > 0 {
+            commands
+                .entity(event.target)
+                .insert(FlashWhite::new(HIT_FLASH_DURATION));
+        } else {
+            let color = sprite.map_or(BREAKABLE_COLOR, |sprite| sprite.color);
+            spawn_debris(&mut commands, transform.translation(), color);
+            commands.entity(event.target).despawn();
+        }
+    }
+}
+
+fn move_debris(mut query: Query<(&mut Debris, &mut Transform)>, time: Res<Time>) {
+    for (mut debris, mut transform) in query.iter_mut() {
+        debris.velocity.y -= DEBRIS_GRAVITY.to_pixels() * time.delta_secs();
+        transform.translation += (debris.velocity * time.delta_secs()).extend(0.0);
+    }
+}
+
+pub struct BreakablePlugin;
+
+impl Plugin for BreakablePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BreakableHitEvent>()
+            .add_systems(
+                Update,
+                (damage_breakables, move_debris).run_if(in_state(GameState::Game)),
+            )
+            .register_type::<Breakable>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_block_breaks_after_enough_hits() {
+        let mut world = World::new();
+        world.init_resource::<Events<BreakableHitEvent>>();
+        world.init_resource::<Events<PlaySfxAtEvent>>();
+        let block = world
+            .spawn((
+                Breakable::default(),
+                GlobalTransform::default(),
+                Sprite::from_color(BREAKABLE_COLOR, Vec2::splat(TILE_SIZE)),
+            ))
+            .id();
+        let hit = |world: &mut World, damage: u32| {
+            world.send_event(BreakableHitEvent {
+                target: block,
+                damage,
+            });
+            world.run_system_once(damage_breakables).unwrap();
+            world.resource_mut::<Events<BreakableHitEvent>>().clear();
+        };
+
+        hit(&mut world, 1);
+        assert_eq!(world.get::<Breakable>(block).unwrap().hp, BREAKABLE_HP - 1);
+        assert!(world.get::<FlashWhite>(block).is_some());
+
+        hit(&mut world, BREAKABLE_HP);
+        assert!(world.get_entity(block).is_err());
+        let debris = world
+            .query_filtered::<(), With<Debris>>()
+            .iter(&world)
+            .count();
+        assert_eq!(debris, DEBRIS_PIECES as usize);
+    }
+}
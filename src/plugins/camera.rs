@@ -1,21 +1,240 @@
+use std::{f32::consts::TAU, time::Duration};
+
 use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
 
 use crate::bundles::camera::{self, CameraBundle, MainCamera};
 use crate::bundles::player::Player;
+use crate::constants::{self, multiply_by_tile_size};
+use crate::entity_fields::CameraZoneFields;
 use crate::states::GameState;
 
+use super::{
+    collision::{IsGrounded, Velocity},
+    health::DamageEvent,
+    level::{
+        LdtkEntityAppExt, LdtkEntityContext, LevelBounds, LevelStartedEvent, entity_world_rect,
+    },
+    player::PlayerShootEvent,
+};
+
+/// How far the main camera moved this frame, for whatever follows it at its own pace
+#[derive(Resource, Default, Debug)]
+pub struct CameraDelta(pub Vec2);
+
+/// How the camera follows the player
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CameraFollowConfig {
+    /// How quickly the camera catches up with its target, as the fraction of the remaining
+    /// distance covered per second on an exponential curve. Zero locks onto the target.
+    pub smoothing: f32,
+    /// Size of the box around the camera's focus the player can move in without moving it
+    pub deadzone: Vec2,
+    /// How far ahead of the player the camera looks horizontally
+    pub look_ahead: f32,
+    /// How quickly the look-ahead swings over after turning around, like `smoothing`
+    pub look_ahead_smoothing: f32,
+    /// Horizontal speed under which the player counts as standing, looking where they face
+    pub look_ahead_min_speed: f32,
+    /// Offset of the camera from the focus, showing more of what's above the player
+    pub offset: Vec2,
+}
+
+impl Default for CameraFollowConfig {
+    fn default() -> Self {
+        Self {
+            smoothing: 8.0,
+            deadzone: Vec2::new(multiply_by_tile_size(2), multiply_by_tile_size(3)),
+            look_ahead: multiply_by_tile_size(3),
+            look_ahead_smoothing: 2.0,
+            look_ahead_min_speed: multiply_by_tile_size(1),
+            offset: Vec2::new(0.0, 64.0),
+        }
+    }
+}
+
+/// Camera shake can be turned off, for anyone it makes feel sick
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct CameraShakeConfig {
+    pub enabled: bool,
+}
+
+impl Default for CameraShakeConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Where the camera is looking, kept apart from where it actually is as it's still catching up
+#[derive(Component, Default)]
+pub struct CameraFollow {
+    /// Follows the player around the deadzone, `None` until it's snapped onto them
+    focus: Option<Vec2>,
+    look_ahead: f32,
+    /// Where following puts the camera, before any shaking is added on top
+    position: Vec2,
+}
+
+/// How a camera zone overrides following the player
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum CameraZoneMode {
+    /// Keep following, only changing the zoom
+    Follow,
+    /// Hold still on the center of the zone
+    Lock,
+    /// Keep following, but never show anything outside the zone
+    #[default]
+    Clamp,
+}
+
+impl CameraZoneMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "follow" => Some(Self::Follow),
+            "lock" => Some(Self::Lock),
+            "clamp" => Some(Self::Clamp),
+            _ => None,
+        }
+    }
+}
+
+/// Area overriding the camera while the player is in it, like a boss arena
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct CameraZone {
+    /// World space area of the zone
+    pub rect: Rect,
+    pub mode: CameraZoneMode,
+    /// Above 1.0 zooms in, below zooms out
+    pub zoom: f32,
+    /// Where zones overlap the highest priority wins, then the smallest zone
+    pub priority: i32,
+}
+
+/// The zone in charge of the camera for a player at `position`, if they're in any
+///
+/// Ties that priority and size can't break go to the entity spawned first, so overlapping
+/// zones always resolve the same way.
+pub fn active_camera_zone<'a>(
+    zones: impl Iterator<Item = (Entity, &'a CameraZone)>,
+    position: Vec2,
+) -> Option<&'a CameraZone> {
+    zones
+        .filter(|(_, zone)| zone.rect.contains(position))
+        .max_by(|(a_entity, a), (b_entity, b)| {
+            let area = |zone: &CameraZone| zone.rect.width() * zone.rect.height();
+            a.priority
+                .cmp(&b.priority)
+                .then(area(b).total_cmp(&area(a)))
+                .then(b_entity.cmp(a_entity))
+        })
+        .map(|(_, zone)| zone)
+}
+
+/// Shake the camera, fading out over `duration`
+#[derive(Event, Clone, Debug)]
+pub struct CameraShakeEvent {
+    /// Largest offset in pixels, right at the start
+    pub amplitude: f32,
+    pub duration: Duration,
+    /// Back and forth movements per second
+    pub frequency: f32,
+}
+
+impl CameraShakeEvent {
+    pub const SHOT: Self = Self {
+        amplitude: 1.5,
+        duration: Duration::from_millis(100),
+        frequency: 30.0,
+    };
+    pub const HARD_LANDING: Self = Self {
+        amplitude: 4.0,
+        duration: Duration::from_millis(200),
+        frequency: 20.0,
+    };
+    pub const PLAYER_HIT: Self = Self {
+        amplitude: 6.0,
+        duration: Duration::from_millis(300),
+        frequency: 25.0,
+    };
+}
+
+/// Shakes currently playing on the camera, added on top of where following puts it
+#[derive(Component, Default)]
+pub struct CameraShake {
+    shakes: Vec<(CameraShakeEvent, Timer)>,
+}
+
+/// Falling faster than this makes the landing shake the camera
+const HARD_LANDING_SPEED: f32 = multiply_by_tile_size(14);
+
+fn camera_zone_from_entity(entity: &EntityInstance) -> Option<CameraZone> {
+    let fields = CameraZoneFields::from_entity(entity)?;
+    let Some(mode) = CameraZoneMode::from_name(&fields.mode) else {
+        warn!("unknown camera zone mode: {:?}", fields.mode);
+        return None;
+    };
+    if fields.zoom <= 0.0 {
+        warn!("camera zone zoom has to be positive: {:?}", fields.zoom);
+        return None;
+    }
+    let (center, size) = entity_world_rect(entity);
+
+    Some(CameraZone {
+        rect: Rect::from_center_size(center, size),
+        mode,
+        zoom: fields.zoom,
+        priority: fields.priority as i32,
+    })
+}
+
+fn spawn_ldtk_camera_zone(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    if let Some(zone) = camera_zone_from_entity(entity) {
+        commands.spawn((
+            Transform::from_translation(zone.rect.center().extend(0.0)),
+            zone,
+            context.level_id.clone(),
+        ));
+    }
+}
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_camera)
-            .add_systems(Update, update_camera.run_if(in_state(GameState::Game)));
+        app.register_ldtk_entity(constants::entities::CAMERA_ZONE, spawn_ldtk_camera_zone)
+            .init_resource::<CameraDelta>()
+            .init_resource::<CameraFollowConfig>()
+            .init_resource::<CameraShakeConfig>()
+            .add_systems(Startup, setup_camera)
+            .add_systems(
+                Update,
+                (
+                    snap_camera_on_level_start,
+                    update_camera,
+                    shake_on_gameplay_events,
+                    shake_camera,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_event::<CameraShakeEvent>()
+            .register_type::<CameraFollowConfig>()
+            .register_type::<CameraShakeConfig>()
+            .register_type::<CameraZone>();
     }
 }
 
 fn setup_camera(mut commands: Commands) {
     commands.spawn((
         CameraBundle::default(),
+        CameraFollow::default(),
+        CameraShake::default(),
         Projection::from(OrthographicProjection {
             scaling_mode: bevy::render::camera::ScalingMode::FixedVertical {
                 viewport_height: 400.0,
@@ -25,19 +244,303 @@ fn setup_camera(mut commands: Commands) {
     ));
 }
 
-fn update_camera(
-    player_query: Query<&Transform, With<Player>>,
-    mut camera_query: Query<&mut Transform, (With<MainCamera>, Without<Player>)>,
+/// Camera position keeping a view of `half_size` inside `bounds`
+///
+/// Along an axis the bounds are smaller than the view on, the view is centered on them.
+pub fn clamp_to_bounds(position: Vec2, half_size: Vec2, bounds: Rect) -> Vec2 {
+    let clamp_axis = |position: f32, half_size: f32, min: f32, max: f32| {
+        if max - min <= half_size * 2.0 {
+            (min + max) / 2.0
+        } else {
+            position.clamp(min + half_size, max - half_size)
+        }
+    };
+    Vec2::new(
+        clamp_axis(position.x, half_size.x, bounds.min.x, bounds.max.x),
+        clamp_axis(position.y, half_size.y, bounds.min.y, bounds.max.y),
+    )
+}
+
+/// `current` moved towards `target` by the fraction given by `rate` per second, on an
+/// exponential curve so the result doesn't depend on the frame rate
+pub fn smooth_toward(current: Vec2, target: Vec2, rate: f32, delta_secs: f32) -> Vec2 {
+    if rate <= 0.0 {
+        return target;
+    }
+    current.lerp(target, 1.0 - (-rate * delta_secs).exp())
+}
+
+/// Focus point after the player moved to `position`, only dragged along once they push
+/// against the edges of the deadzone around it
+pub fn deadzone_follow(focus: Vec2, position: Vec2, deadzone: Vec2) -> Vec2 {
+    let half = deadzone / 2.0;
+    focus.clamp(position - half, position + half)
+}
+
+/// Jump straight to the player in a new level instead of sweeping over from the old one
+fn snap_camera_on_level_start(
+    mut event_reader: EventReader<LevelStartedEvent>,
+    mut camera_query: Query<&mut CameraFollow>,
+) {
+    if event_reader.read().last().is_none() {
+        return;
+    }
+    for mut follow in camera_query.iter_mut() {
+        follow.focus = None;
+    }
+}
+
+pub fn update_camera(
+    player_query: Query<(&Transform, &Sprite, Option<&Velocity>), With<Player>>,
+    mut camera_query: Query<
+        (&mut Transform, &mut CameraFollow, &mut Projection),
+        (With<MainCamera>, Without<Player>),
+    >,
+    zone_query: Query<(Entity, &CameraZone)>,
+    config: Res<CameraFollowConfig>,
+    level_bounds: Option<Res<LevelBounds>>,
+    mut camera_delta: ResMut<CameraDelta>,
+    time: Res<Time>,
+) {
+    camera_delta.0 = Vec2::ZERO;
+    let Some((player_transform, sprite, velocity)) = player_query.iter().next() else {
+        return;
+    };
+    let Some((mut camera_transform, mut follow, mut projection)) = camera_query.iter_mut().next()
+    else {
+        return;
+    };
+
+    let player_position = player_transform.translation.xy();
+    let snap = follow.focus.is_none();
+    let focus = follow.focus.map_or(player_position, |focus| {
+        deadzone_follow(focus, player_position, config.deadzone)
+    });
+    follow.focus = Some(focus);
+
+    // Look the way the player is running, or facing when standing still
+    let velocity_x = velocity.map_or(0.0, |velocity| velocity.0.x);
+    let direction = if velocity_x.abs() > config.look_ahead_min_speed {
+        velocity_x.signum()
+    } else if sprite.flip_x {
+        -1.0
+    } else {
+        1.0
+    };
+    let look_ahead = direction * config.look_ahead;
+    follow.look_ahead = if snap {
+        look_ahead
+    } else {
+        smooth_toward(
+            Vec2::new(follow.look_ahead, 0.0),
+            Vec2::new(look_ahead, 0.0),
+            config.look_ahead_smoothing,
+            time.delta_secs(),
+        )
+        .x
+    };
+
+    let zone = active_camera_zone(zone_query.iter(), player_position);
+    let before = follow.position;
+    let target = match zone {
+        Some(zone) if zone.mode == CameraZoneMode::Lock => zone.rect.center(),
+        _ => focus + config.offset + Vec2::new(follow.look_ahead, 0.0),
+    };
+    let mut position = if snap {
+        target
+    } else {
+        smooth_toward(before, target, config.smoothing, time.delta_secs())
+    };
+
+    if let Projection::Orthographic(orthographic) = projection.as_mut() {
+        let zoom = zone.map_or(1.0, |zone| zone.zoom);
+        let scale = smooth_toward(
+            Vec2::splat(orthographic.scale),
+            Vec2::splat(1.0 / zoom),
+            config.smoothing,
+            time.delta_secs(),
+        );
+        orthographic.scale = scale.x;
+
+        // Never show anything outside the level, or the zone clamping the camera
+        let bounds = match zone {
+            Some(zone) if zone.mode == CameraZoneMode::Clamp => Some(zone.rect),
+            _ => level_bounds.map(|level_bounds| level_bounds.0),
+        };
+        if let Some(bounds) = bounds {
+            position = clamp_to_bounds(position, orthographic.area.half_size(), bounds);
+        }
+    }
+    follow.position = position;
+    camera_transform.translation.x = position.x;
+    camera_transform.translation.y = position.y;
+
+    camera_delta.0 = position - before;
+}
+
+/// Offset of a shake `elapsed` into it, decaying with the square of the time left
+///
+/// The two axes swing at slightly different rates so the shake doesn't run along a line.
+pub fn shake_offset(shake: &CameraShakeEvent, elapsed: f32) -> Vec2 {
+    let remaining = 1.0 - (elapsed / shake.duration.as_secs_f32()).clamp(0.0, 1.0);
+    let strength = shake.amplitude * remaining * remaining;
+    let phase = elapsed * shake.frequency * TAU;
+    Vec2::new(phase.sin(), (phase * 1.3 + 1.0).sin()) * strength
+}
+
+fn shake_on_gameplay_events(
+    mut shoot_events: EventReader<PlayerShootEvent>,
+    mut damage_events: EventReader<DamageEvent>,
+    player_query: Query<(Entity, &IsGrounded, &Velocity), With<Player>>,
+    mut shake_writer: EventWriter<CameraShakeEvent>,
+    // Fall speed from the frames the player was in the air
+    mut fall_speed: Local<f32>,
 ) {
-    let Some(player_transform) = player_query.iter().next() else {
+    if shoot_events.read().count() > 0 {
+        shake_writer.write(CameraShakeEvent::SHOT);
+    }
+
+    let Ok((player, is_grounded, velocity)) = player_query.single() else {
         return;
     };
-    let Some(mut camera_transform) = camera_query.iter_mut().next() else {
+    if damage_events.read().any(|event| event.target == player) {
+        shake_writer.write(CameraShakeEvent::PLAYER_HIT);
+    }
+
+    if is_grounded.grounded {
+        if *fall_speed > HARD_LANDING_SPEED {
+            shake_writer.write(CameraShakeEvent::HARD_LANDING);
+        }
+        *fall_speed = 0.0;
+    } else {
+        *fall_speed = -velocity.0.y;
+    }
+}
+
+fn shake_camera(
+    mut event_reader: EventReader<CameraShakeEvent>,
+    mut camera_query: Query<(&mut Transform, &mut CameraShake), With<MainCamera>>,
+    config: Res<CameraShakeConfig>,
+    time: Res<Time>,
+) {
+    let Ok((mut transform, mut camera_shake)) = camera_query.single_mut() else {
         return;
     };
 
-    let offset_y = 64.0;
+    for event in event_reader.read().filter(|_| config.enabled) {
+        camera_shake
+            .shakes
+            .push((event.clone(), Timer::new(event.duration, TimerMode::Once)));
+    }
+
+    let mut offset = Vec2::ZERO;
+    camera_shake.shakes.retain_mut(|(shake, timer)| {
+        timer.tick(time.delta());
+        offset += shake_offset(shake, timer.elapsed_secs());
+        !timer.finished()
+    });
+    transform.translation += offset.extend(0.0);
+}
 
-    camera_transform.translation.x = player_transform.translation.x;
-    camera_transform.translation.y = player_transform.translation.y + offset_y;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadzone_and_smoothing() {
+        let deadzone = Vec2::new(32.0, 48.0);
+        // Moving around inside the deadzone leaves the focus alone
+        assert_eq!(
+            deadzone_follow(Vec2::ZERO, Vec2::new(10.0, -20.0), deadzone),
+            Vec2::ZERO
+        );
+        // Past its edge the focus gets dragged along
+        assert_eq!(
+            deadzone_follow(Vec2::ZERO, Vec2::new(40.0, 0.0), deadzone),
+            Vec2::new(24.0, 0.0)
+        );
+
+        let target = Vec2::new(100.0, 0.0);
+        assert_eq!(smooth_toward(Vec2::ZERO, target, 0.0, 0.016), target);
+        // Two half frames end up where one full frame does
+        let half_steps = smooth_toward(
+            smooth_toward(Vec2::ZERO, target, 8.0, 0.008),
+            target,
+            8.0,
+            0.008,
+        );
+        let full_step = smooth_toward(Vec2::ZERO, target, 8.0, 0.016);
+        assert!(half_steps.distance(full_step) < 0.001);
+        assert!(full_step.x > 0.0 && full_step.x < 100.0);
+    }
+
+    #[test]
+    fn test_overlapping_camera_zones_resolve_by_priority_then_size() {
+        let zone = |rect: Rect, priority| CameraZone {
+            rect,
+            mode: CameraZoneMode::Clamp,
+            zoom: 1.0,
+            priority,
+        };
+        let arena = zone(Rect::new(0.0, 0.0, 100.0, 100.0), 0);
+        let reveal = zone(Rect::new(50.0, 0.0, 80.0, 30.0), 0);
+        let boss = zone(Rect::new(0.0, 0.0, 200.0, 200.0), 1);
+        let mut world = World::new();
+        let [a, b, c] = [(); 3].map(|_| world.spawn_empty().id());
+
+        let zones = || [(a, &arena), (b, &reveal), (c, &boss)].into_iter();
+        // Only in the big one
+        assert_eq!(
+            active_camera_zone(zones(), Vec2::new(150.0, 150.0)).map(|zone| zone.rect),
+            Some(boss.rect)
+        );
+        // Higher priority beats the smaller zones
+        assert_eq!(
+            active_camera_zone(zones(), Vec2::new(60.0, 10.0)).map(|zone| zone.rect),
+            Some(boss.rect)
+        );
+        // Same priority, smaller wins
+        let zones = || [(a, &arena), (b, &reveal)].into_iter();
+        assert_eq!(
+            active_camera_zone(zones(), Vec2::new(60.0, 10.0)).map(|zone| zone.rect),
+            Some(reveal.rect)
+        );
+        assert!(active_camera_zone(zones(), Vec2::new(-10.0, 10.0)).is_none());
+    }
+
+    #[test]
+    fn test_shake_fades_out() {
+        let shake = CameraShakeEvent {
+            amplitude: 4.0,
+            duration: Duration::from_secs(1),
+            frequency: 10.0,
+        };
+        let strength = |elapsed| shake_offset(&shake, elapsed).length();
+
+        assert!(strength(0.9) < strength(0.12));
+        assert_eq!(shake_offset(&shake, 1.0), Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_camera_stays_inside_the_level() {
+        let half_size = Vec2::new(100.0, 50.0);
+        let level = Rect::new(0.0, -400.0, 800.0, 0.0);
+
+        // Well inside
+        assert_eq!(
+            clamp_to_bounds(Vec2::new(400.0, -200.0), half_size, level),
+            Vec2::new(400.0, -200.0)
+        );
+        // Up against the top left corner
+        assert_eq!(
+            clamp_to_bounds(Vec2::new(20.0, 30.0), half_size, level),
+            Vec2::new(100.0, -50.0)
+        );
+        // Level narrower than the view gets centered
+        let narrow = Rect::new(0.0, -400.0, 150.0, 0.0);
+        assert_eq!(
+            clamp_to_bounds(Vec2::new(20.0, -200.0), half_size, narrow),
+            Vec2::new(75.0, -200.0)
+        );
+    }
 }
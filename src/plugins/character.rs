@@ -0,0 +1,308 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::Stopwatch};
+
+use crate::schedule::MovementSet;
+
+/// Jump higher when running: a fraction of the horizontal speed is added to the jump force
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct SpeedJump {
+    /// Fraction of the horizontal speed added, 0.0 disables the bonus
+    pub factor: f32,
+    /// Upper limit for the added jump force
+    pub max_bonus: f32,
+}
+
+/// How a wall jump treats the vertical velocity the character already has
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum WallJumpMode {
+    /// Zero the vertical velocity before the jump, so every wall jump reaches the same height
+    #[default]
+    ResetVertical,
+    /// Add the jump on top of the current vertical velocity, so momentum carries over
+    AddVertical,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct WallJump {
+    /// Horizontal speed away from the wall
+    pub horizontal_force: f32,
+    pub vertical_force: f32,
+    pub mode: WallJumpMode,
+}
+
+/// How something walks and jumps, shared by the player and walking enemies
+///
+/// Speeds are in pixels per second, accelerations in pixels per second squared. What the
+/// character is doing right now lives in `CharacterState`.
+#[derive(Component, Clone, Debug, Reflect)]
+#[reflect(Component)]
+#[require(CharacterState)]
+pub struct CharacterController {
+    pub walk_speed: f32,
+    pub walk_acceleration: f32,
+    pub ground_deceleration: f32,
+    /// Upwards speed right after jumping, 0.0 for characters that can't jump
+    pub jump_force: f32,
+    /// How long after walking off a ledge a jump still works
+    pub coyote_time: Duration,
+    /// Shortest time between two jumps, unless jumping right off the ground
+    pub jump_cooldown: Duration,
+    /// How long gravity stays off after jumping, as long as Jump is held
+    pub gravity_immunity: Duration,
+    /// How long a Jump press just before landing is remembered, so it still jumps on the
+    /// frame the character touches down
+    pub jump_buffer: Duration,
+    /// Releasing Jump while still rising multiplies the upwards velocity by this, so short
+    /// taps give short hops
+    pub jump_cut: Option<f32>,
+    pub speed_jump: SpeedJump,
+    /// Stop dead when idle on the ground, so the character can't drift off a ledge from
+    /// leftover velocity
+    pub sticky_feet: bool,
+    pub wall_jump: Option<WallJump>,
+    /// Like `coyote_time`, but for wall jumps: how long after leaving a wall it can still be
+    /// jumped off
+    pub wall_coyote: Duration,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self::new(0.0, 0.0)
+    }
+}
+
+impl CharacterController {
+    /// Starts and stops walking instantly, with none of the jump assists
+    pub fn new(walk_speed: f32, jump_force: f32) -> Self {
+        Self {
+            walk_speed,
+            walk_acceleration: f32::MAX,
+            ground_deceleration: f32::MAX,
+            jump_force,
+            coyote_time: Duration::ZERO,
+            jump_cooldown: Duration::ZERO,
+            gravity_immunity: Duration::ZERO,
+            jump_buffer: Duration::ZERO,
+            jump_cut: None,
+            speed_jump: SpeedJump::default(),
+            sticky_feet: false,
+            wall_jump: None,
+            wall_coyote: Duration::ZERO,
+        }
+    }
+
+    pub fn with_acceleration(self, walk_acceleration: f32, ground_deceleration: f32) -> Self {
+        Self {
+            walk_acceleration,
+            ground_deceleration,
+            ..self
+        }
+    }
+
+    pub fn with_coyote_time(self, coyote_time: Duration) -> Self {
+        Self {
+            coyote_time,
+            ..self
+        }
+    }
+
+    pub fn with_jump_cooldown(self, jump_cooldown: Duration) -> Self {
+        Self {
+            jump_cooldown,
+            ..self
+        }
+    }
+
+    pub fn with_gravity_immunity(self, gravity_immunity: Duration) -> Self {
+        Self {
+            gravity_immunity,
+            ..self
+        }
+    }
+
+    pub fn with_jump_buffer(self, jump_buffer: Duration) -> Self {
+        Self {
+            jump_buffer,
+            ..self
+        }
+    }
+
+    pub fn with_jump_cut(self, multiplier: f32) -> Self {
+        Self {
+            jump_cut: Some(multiplier),
+            ..self
+        }
+    }
+
+    pub fn with_speed_jump(self, speed_jump: SpeedJump) -> Self {
+        Self { speed_jump, ..self }
+    }
+
+    pub fn with_sticky_feet(self) -> Self {
+        Self {
+            sticky_feet: true,
+            ..self
+        }
+    }
+
+    pub fn with_wall_jump(self, wall_jump: WallJump, wall_coyote: Duration) -> Self {
+        Self {
+            wall_jump: Some(wall_jump),
+            wall_coyote,
+            ..self
+        }
+    }
+}
+
+/// Timers behind a `CharacterController`, updated as the character moves
+#[derive(Component, Default, Debug, Reflect)]
+#[reflect(Component)]
+pub struct CharacterState {
+    /// Time since the last jump off the ground, for the jump cooldown
+    pub since_jump: Stopwatch,
+    /// Gravity immunity left from the last jump
+    pub gravity_immunity_left: Duration,
+    /// Time since Jump was pressed, while the press is still buffered
+    since_jump_press: Option<Stopwatch>,
+    /// Whether the midair jump has been used since last touching the ground
+    air_jumped: bool,
+}
+
+impl CharacterState {
+    pub fn tick(&mut self, delta: Duration, controller: &CharacterController) {
+        self.since_jump.tick(delta);
+        self.gravity_immunity_left = self.gravity_immunity_left.saturating_sub(delta);
+        if let Some(stopwatch) = &mut self.since_jump_press {
+            stopwatch.tick(delta);
+            if stopwatch.elapsed() > controller.jump_buffer {
+                self.since_jump_press = None;
+            }
+        }
+    }
+
+    pub fn buffer_jump(&mut self) {
+        self.since_jump_press = Some(Stopwatch::new());
+    }
+
+    pub fn is_jump_buffered(&self) -> bool {
+        self.since_jump_press.is_some()
+    }
+
+    pub fn jump_cooldown_finished(&self, controller: &CharacterController) -> bool {
+        self.since_jump.elapsed() >= controller.jump_cooldown
+    }
+
+    /// Whether the last jump off the ground was this or the previous timestep, when the
+    /// ground check hasn't seen the character leave the ground yet
+    pub fn jumped_recently(&self, delta: Duration) -> bool {
+        self.since_jump.elapsed() <= delta
+    }
+
+    /// Jump off the ground, using up the buffered press so it only triggers a single jump
+    pub fn jump(&mut self, controller: &CharacterController) {
+        self.since_jump.reset();
+        self.since_jump_press = None;
+        self.gravity_immunity_left = controller.gravity_immunity;
+    }
+
+    /// Jump again in midair, once until the character lands
+    pub fn air_jump(&mut self, controller: &CharacterController) {
+        self.since_jump_press = None;
+        self.gravity_immunity_left = controller.gravity_immunity;
+        self.air_jumped = true;
+    }
+
+    pub fn can_air_jump(&self) -> bool {
+        !self.air_jumped
+    }
+
+    /// Touching the ground gives the midair jump back
+    pub fn land(&mut self) {
+        self.air_jumped = false;
+    }
+
+    /// Jump off a wall, which doesn't count towards the jump cooldown
+    pub fn wall_jump(&mut self, controller: &CharacterController) {
+        self.gravity_immunity_left = controller.gravity_immunity;
+    }
+
+    pub fn is_gravity_immune(&self) -> bool {
+        !self.gravity_immunity_left.is_zero()
+    }
+
+    /// Let gravity back in early, like when bumping into a ceiling
+    pub fn end_gravity_immunity(&mut self) {
+        self.gravity_immunity_left = Duration::ZERO;
+    }
+}
+
+pub fn tick_character_states(
+    mut query: Query<(&CharacterController, &mut CharacterState)>,
+    time: Res<Time>,
+) {
+    for (controller, mut state) in query.iter_mut() {
+        state.tick(time.delta(), controller);
+    }
+}
+
+pub struct CharacterPlugin;
+
+impl Plugin for CharacterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            tick_character_states.in_set(MovementSet::Input),
+        )
+        .register_type::<CharacterController>()
+        .register_type::<CharacterState>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jump_buffer_window() {
+        let controller =
+            CharacterController::new(100.0, 300.0).with_jump_buffer(Duration::from_millis(100));
+        let mut state = CharacterState::default();
+        assert!(!state.is_jump_buffered());
+
+        // Pressed 80ms before landing
+        state.buffer_jump();
+        state.tick(Duration::from_millis(80), &controller);
+        assert!(state.is_jump_buffered());
+
+        // Pressed too early
+        state.tick(Duration::from_millis(40), &controller);
+        assert!(!state.is_jump_buffered());
+
+        // A buffered press only jumps once
+        state.buffer_jump();
+        state.jump(&controller);
+        assert!(!state.is_jump_buffered());
+    }
+
+    #[test]
+    fn test_jump_timers() {
+        let controller = CharacterController::new(100.0, 300.0)
+            .with_jump_cooldown(Duration::from_millis(500))
+            .with_gravity_immunity(Duration::from_millis(300));
+        let mut state = CharacterState::default();
+
+        state.jump(&controller);
+        assert!(state.jumped_recently(Duration::from_millis(16)));
+        state.tick(Duration::from_millis(200), &controller);
+        assert!(!state.jumped_recently(Duration::from_millis(16)));
+        assert!(state.is_gravity_immune());
+        assert!(!state.jump_cooldown_finished(&controller));
+
+        state.end_gravity_immunity();
+        assert!(!state.is_gravity_immune());
+
+        state.tick(Duration::from_millis(300), &controller);
+        assert!(state.jump_cooldown_finished(&controller));
+    }
+}
@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::Stopwatch};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::bundles::player::Player;
+
+use super::player::{BarrelPosition, PlayerAction, PlayerShootEvent, shoot};
+
+/// Holding Shoot charges up a stronger shot, fired on release
+#[derive(Component, Reflect)]
+pub struct ChargeShot {
+    /// Holding time to reach a full charge
+    pub full_charge: Duration,
+    /// Multipliers at full charge, scaling linearly from 1.0 for a tap
+    pub max_damage_multiplier: f32,
+    pub max_size_multiplier: f32,
+    pub max_speed_multiplier: f32,
+}
+
+/// How much a charged shot is scaled compared to a normal shot
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChargeMultipliers {
+    pub damage: f32,
+    pub size: f32,
+    pub speed: f32,
+}
+
+impl Default for ChargeMultipliers {
+    fn default() -> Self {
+        Self {
+            damage: 1.0,
+            size: 1.0,
+            speed: 1.0,
+        }
+    }
+}
+
+impl ChargeShot {
+    /// Charge after holding Shoot for `held`, from 0.0 to 1.0
+    pub fn charge(&self, held: Duration) -> f32 {
+        (held.as_secs_f32() / self.full_charge.as_secs_f32()).clamp(0.0, 1.0)
+    }
+
+    pub fn multipliers(&self, charge: f32) -> ChargeMultipliers {
+        let scale = |max_multiplier: f32| 1.0 + (max_multiplier - 1.0) * charge;
+        ChargeMultipliers {
+            damage: scale(self.max_damage_multiplier),
+            size: scale(self.max_size_multiplier),
+            speed: scale(self.max_speed_multiplier),
+        }
+    }
+}
+
+/// Present while Shoot is held
+#[derive(Component)]
+pub struct Charging {
+    held: Stopwatch,
+    effect: Entity,
+}
+
+/// Glow at the barrel growing with the charge
+#[derive(Component)]
+struct ChargeEffect;
+
+/// Charge below which the effect stays hidden, so taps don't flash it
+const EFFECT_MIN_CHARGE: f32 = 0.15;
+const EFFECT_MAX_SIZE: f32 = 10.0;
+const EFFECT_COLOR: Color = Color::srgb(0.6, 0.9, 1.0);
+/// Pulses per second at full charge
+const EFFECT_PULSE_RATE: f32 = 8.0;
+
+/// Start charging on pressing Shoot and fire on releasing it
+fn update_charge(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            Option<&ChargeShot>,
+            Option<&mut Charging>,
+        ),
+        With<Player>,
+    >,
+    mut event_writer: EventWriter<PlayerShootEvent>,
+    time: Res<Time>,
+) {
+    for (entity, action_state, charge_shot, charging) in query.iter_mut() {
+        // Without a charge shot every press is a plain shot
+        let Some(charge_shot) = charge_shot else {
+            if action_state.just_pressed(&PlayerAction::Shoot) {
+                event_writer.write(PlayerShootEvent { charge: 0.0 });
+            }
+            continue;
+        };
+
+        match charging {
+            None if action_state.just_pressed(&PlayerAction::Shoot) => {
+                let effect = commands
+                    .spawn((
+                        ChargeEffect,
+                        Sprite {
+                            color: EFFECT_COLOR,
+                            custom_size: Some(Vec2::ZERO),
+                            ..default()
+                        },
+                        Transform::from_xyz(0.0, 0.0, 1.0),
+                        Visibility::Hidden,
+                        ChildOf(entity),
+                    ))
+                    .id();
+                commands.entity(entity).insert(Charging {
+                    held: Stopwatch::new(),
+                    effect,
+                });
+            }
+            None => {}
+            Some(mut charging) => {
+                charging.held.tick(time.delta());
+                if action_state.pressed(&PlayerAction::Shoot) {
+                    continue;
+                }
+
+                event_writer.write(PlayerShootEvent {
+                    charge: charge_shot.charge(charging.held.elapsed()),
+                });
+                commands.entity(charging.effect).despawn();
+                commands.entity(entity).remove::<Charging>();
+            }
+        }
+    }
+}
+
+/// Keep the effect on the barrel, growing and pulsing faster as the charge builds
+fn update_charge_effect(
+    player_query: Query<(&Charging, &ChargeShot, &BarrelPosition), With<Player>>,
+    mut effect_query: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<ChargeEffect>>,
+) {
+    for (charging, charge_shot, barrel_position) in player_query.iter() {
+        let Ok((mut sprite, mut transform, mut visibility)) = effect_query.get_mut(charging.effect)
+        else {
+            continue;
+        };
+
+        let charge = charge_shot.charge(charging.held.elapsed());
+        if charge < EFFECT_MIN_CHARGE {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        *visibility = Visibility::Inherited;
+        transform.translation = barrel_position.0.extend(transform.translation.z);
+        sprite.custom_size = Some(Vec2::splat(EFFECT_MAX_SIZE * charge));
+        let pulse =
+            (charging.held.elapsed_secs() * EFFECT_PULSE_RATE * charge * std::f32::consts::TAU)
+                .sin();
+        sprite.color = EFFECT_COLOR.with_alpha(0.75 + 0.25 * pulse);
+    }
+}
+
+pub struct ChargeShotPlugin;
+
+impl Plugin for ChargeShotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (update_charge, update_charge_effect).chain().before(shoot),
+        )
+        .register_type::<ChargeShot>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charge_scales_shot() {
+        let charge_shot = ChargeShot {
+            full_charge: Duration::from_secs(1),
+            max_damage_multiplier: 3.0,
+            max_size_multiplier: 2.0,
+            max_speed_multiplier: 1.5,
+        };
+
+        assert_eq!(charge_shot.charge(Duration::ZERO), 0.0);
+        assert_eq!(charge_shot.charge(Duration::from_millis(500)), 0.5);
+        assert_eq!(charge_shot.charge(Duration::from_secs(3)), 1.0);
+
+        assert_eq!(charge_shot.multipliers(0.0), ChargeMultipliers::default());
+        assert_eq!(
+            charge_shot.multipliers(0.5),
+            ChargeMultipliers {
+                damage: 2.0,
+                size: 1.5,
+                speed: 1.25,
+            }
+        );
+    }
+}
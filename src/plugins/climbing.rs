@@ -0,0 +1,207 @@
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{bundles::player::Player, constants, schedule::MovementSet};
+
+use super::{
+    animation::NextAnimation,
+    character::CharacterController,
+    collision::{IsGrounded, Velocity, child_collider_aabb},
+    gravity::EntityGravity,
+    level::{LdtkEntityAppExt, LdtkEntityContext, spawn_trigger_area},
+    player::{PlayerAction, PlayerAnimations},
+};
+
+/// Area the player can climb in, like a ladder or a vine
+#[derive(Component, Default, Reflect)]
+pub struct Climbable;
+
+/// Ability to grab onto `Climbable` areas
+#[derive(Component, Reflect)]
+pub struct Climber {
+    /// Vertical speed while climbing, in pixels per second
+    pub speed: f32,
+}
+
+/// Present while the entity is attached to a climbable area
+#[derive(Component)]
+pub struct Climbing;
+
+/// Vertical climbing velocity for the pressed directions, cancelling out when both are held
+fn climb_velocity(up: bool, down: bool, speed: f32) -> f32 {
+    match (up, down) {
+        (true, false) => speed,
+        (false, true) => -speed,
+        _ => 0.0,
+    }
+}
+
+/// Attach to climbable areas on Up/Down, and let go on a jump or when leaving the area
+pub fn update_climbing(
+    mut commands: Commands,
+    mut player_query: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &Climber,
+            &Children,
+            &mut Velocity,
+            &mut EntityGravity,
+            &IsGrounded,
+            Option<&CharacterController>,
+            Option<&mut NextAnimation<PlayerAnimations>>,
+            Has<Climbing>,
+        ),
+        With<Player>,
+    >,
+    climbable_query: Query<&ColliderAabb, With<Climbable>>,
+    aabb_query: Query<&ColliderAabb>,
+) {
+    for (
+        entity,
+        action_state,
+        climber,
+        children,
+        mut velocity,
+        mut entity_gravity,
+        is_grounded,
+        controller,
+        next_animation,
+        is_climbing,
+    ) in player_query.iter_mut()
+    {
+        let in_climbable = child_collider_aabb(children, &aabb_query).is_some_and(|player_aabb| {
+            climbable_query
+                .iter()
+                .any(|climbable_aabb| climbable_aabb.intersects(&player_aabb))
+        });
+        let up = action_state.pressed(&PlayerAction::Up);
+        let down = action_state.pressed(&PlayerAction::Down);
+
+        if !is_climbing {
+            if in_climbable && (up || down) && !(down && is_grounded.grounded) {
+                commands.entity(entity).insert(Climbing);
+            } else {
+                continue;
+            }
+        } else if action_state.just_pressed(&PlayerAction::Jump) {
+            commands.entity(entity).remove::<Climbing>();
+            entity_gravity.enabled = true;
+            velocity.0.y = controller.map_or(0.0, |controller| controller.jump_force);
+            continue;
+        } else if !in_climbable || down && is_grounded.grounded {
+            commands.entity(entity).remove::<Climbing>();
+            entity_gravity.enabled = true;
+            continue;
+        }
+
+        entity_gravity.enabled = false;
+        velocity.0 = Vec2::new(0.0, climb_velocity(up, down, climber.speed));
+        if let Some(mut next_animation) = next_animation {
+            next_animation.key = Some(PlayerAnimations::Climb);
+        }
+    }
+}
+
+fn spawn_ldtk_ladder(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    spawn_trigger_area(commands, context.level_id, entity, Climbable);
+}
+
+pub struct ClimbingPlugin;
+
+impl Plugin for ClimbingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::LADDER, spawn_ldtk_ladder)
+            .add_systems(
+                FixedUpdate,
+                update_climbing
+                    .after(MovementSet::Gravity)
+                    .before(MovementSet::CollisionChecks),
+            )
+            .register_type::<Climbable>()
+            .register_type::<Climber>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn spawn_climber(world: &mut World, x: f32) -> Entity {
+        let aabb = ColliderAabb::new(Vec2::new(x, 0.0), Vec2::new(8.0, 16.0));
+        world
+            .spawn((
+                Player,
+                ActionState::<PlayerAction>::default(),
+                Climber { speed: 100.0 },
+                Velocity(Vec2::new(50.0, -200.0)),
+                EntityGravity {
+                    gravity: 480.0,
+                    max_fall_speed: 240.0,
+                    enabled: true,
+                },
+                IsGrounded::default(),
+                CharacterController::new(100.0, 300.0),
+            ))
+            .with_child(aabb)
+            .id()
+    }
+
+    fn press(world: &mut World, player: Entity, action: PlayerAction) {
+        let mut action_state = world.get_mut::<ActionState<PlayerAction>>(player).unwrap();
+        action_state.release_all();
+        action_state.press(&action);
+    }
+
+    #[test]
+    fn test_climbing_attaches_and_detaches() {
+        let mut world = World::new();
+        world.spawn((
+            Climbable,
+            ColliderAabb::new(Vec2::ZERO, Vec2::new(8.0, 64.0)),
+        ));
+        let player = spawn_climber(&mut world, 0.0);
+
+        press(&mut world, player, PlayerAction::Up);
+        world.run_system_once(update_climbing).unwrap();
+
+        assert!(world.get::<Climbing>(player).is_some());
+        assert!(!world.get::<EntityGravity>(player).unwrap().enabled);
+        assert_eq!(
+            world.get::<Velocity>(player).unwrap().0,
+            Vec2::new(0.0, 100.0)
+        );
+
+        // Jumping lets go
+        press(&mut world, player, PlayerAction::Jump);
+        world.run_system_once(update_climbing).unwrap();
+
+        assert!(world.get::<Climbing>(player).is_none());
+        assert!(world.get::<EntityGravity>(player).unwrap().enabled);
+        assert_eq!(world.get::<Velocity>(player).unwrap().0.y, 300.0);
+    }
+
+    #[test]
+    fn test_cannot_climb_outside_climbable_area() {
+        let mut world = World::new();
+        world.spawn((
+            Climbable,
+            ColliderAabb::new(Vec2::ZERO, Vec2::new(8.0, 64.0)),
+        ));
+        let player = spawn_climber(&mut world, 100.0);
+
+        press(&mut world, player, PlayerAction::Up);
+        world.run_system_once(update_climbing).unwrap();
+
+        assert!(world.get::<Climbing>(player).is_none());
+        assert!(world.get::<EntityGravity>(player).unwrap().enabled);
+    }
+}
@@ -1,15 +1,45 @@
-use std::f32::{INFINITY, NEG_INFINITY};
+use std::{
+    collections::HashSet,
+    f32::{INFINITY, NEG_INFINITY},
+};
 
+use avian2d::collision::collider::contact_query;
 use avian2d::prelude::*;
 use avian2d::spatial_query::ShapeCastConfig;
 use bevy::prelude::*;
 use bevy::time::Stopwatch;
 use bevy_inspector_egui::InspectorOptions;
 
-use super::player::AfterJumpGravityImmunityTimer;
+use crate::{
+    constants::{GameLayer, TILE_SIZE},
+    schedule::MovementSet,
+};
 
+use super::character::CharacterState;
+
+#[derive(Component)]
+pub struct IsGrounded {
+    pub grounded: bool,
+    /// Normal of the ground under the entity, straight up while airborne
+    pub normal: Vec2,
+}
+
+impl Default for IsGrounded {
+    fn default() -> Self {
+        Self {
+            grounded: false,
+            normal: Vec2::Y,
+        }
+    }
+}
+
+/// What the ground check last found under the entity, if anything
+#[derive(Component, Default)]
+pub struct GroundEntity(pub Option<Entity>);
+
+/// How far a piece of level geometry moved this frame. Entities standing on it move along.
 #[derive(Component, Default)]
-pub struct IsGrounded(pub bool);
+pub struct MovementDelta(pub Vec2);
 
 #[derive(Component, Default)]
 pub struct IsTouchingWallLeft(pub bool);
@@ -20,34 +50,211 @@ pub struct IsTouchingWallRight(pub bool);
 #[derive(Component, Default)]
 pub struct IsTouchingCeiling(pub bool);
 
+/// Grabbable ledges next to the entity, as the top corner of the wall on each side
+#[derive(Component, Default, Debug)]
+pub struct IsTouchingLedge {
+    pub left: Option<Vec2>,
+    pub right: Option<Vec2>,
+}
+
+/// Marker for entities that ignore level collisions and gravity, see the noclip plugin
+#[derive(Component, Default)]
+pub struct Noclip;
+
+/// Platform that can be jumped through from below, on the `GameLayer::OneWayPlatform` layer
+///
+/// Left out of collision filters, it's only checked for by the casts going down onto it.
+#[derive(Component, Default)]
+pub struct OneWayPlatform;
+
+/// Upper limit for horizontal speed from all sources combined (input, conveyors, wind...)
+#[derive(Component, Default, Reflect)]
+pub struct MaxHorizontalSpeed(pub f32);
+
+/// Lets an entity go past its `MaxHorizontalSpeed`, e.g. while dashing or being knocked back
+#[derive(Component, Default)]
+pub struct IgnoreSpeedCap;
+
+/// Per-frame record of how the collision systems resolved an entity's movement
+///
+/// Only kept up to date while `CollisionDiagnosticsConfig::enabled` is set.
+#[derive(Component, Default, Debug, Reflect)]
+pub struct CollisionDiagnostics {
+    /// Movement the velocity asked for this frame
+    pub intended: Vec2,
+    /// Movement that actually happened
+    pub actual: Vec2,
+    pub blocked_x: bool,
+    pub blocked_y: bool,
+    /// Distances to every surface hit while moving
+    pub hit_distances: Vec<f32>,
+    /// Where the moving shape touched those surfaces, in world space
+    pub hit_points: Vec<Vec2>,
+    pub grounded: bool,
+    pub touching_wall_left: bool,
+    pub touching_wall_right: bool,
+    pub touching_ceiling: bool,
+}
+
+impl CollisionDiagnostics {
+    /// Moves shorter than intended by more than this count as blocked
+    const BLOCKED_EPSILON: f32 = 0.01;
+
+    pub fn record_move(
+        &mut self,
+        intended: Vec2,
+        actual: Vec2,
+        hit_distances: Vec<f32>,
+        hit_points: Vec<Vec2>,
+    ) {
+        self.intended = intended;
+        self.actual = actual;
+        self.blocked_x = (intended.x - actual.x).abs() > Self::BLOCKED_EPSILON;
+        self.blocked_y = (intended.y - actual.y).abs() > Self::BLOCKED_EPSILON;
+        self.hit_distances = hit_distances;
+        self.hit_points = hit_points;
+    }
+}
+
+#[derive(Resource, Default, Reflect)]
+#[reflect(Resource)]
+pub struct CollisionDiagnosticsConfig {
+    pub enabled: bool,
+    /// Log the diagnostics of every blocked move
+    pub log_blocked: bool,
+}
+
+const COLLISION_DIAGNOSTICS_TOGGLE_KEY: KeyCode = KeyCode::F3;
+const SHAPE_CAST_GIZMOS_TOGGLE_KEY: KeyCode = KeyCode::F4;
+
+const CAST_MISS_COLOR: Color = Color::srgb(0.2, 0.9, 0.2);
+const CAST_HIT_COLOR: Color = Color::srgb(0.9, 0.2, 0.2);
+
+/// A shape cast made by the collision systems, kept around to draw it
+#[derive(Clone, Debug, PartialEq, Reflect)]
+pub struct DebugCast {
+    pub origin: Vec2,
+    pub direction: Vec2,
+    pub distance: f32,
+    /// Half the size of the cast shape's bounds
+    pub half_size: Vec2,
+    /// Distance travelled before hitting something and the point hit
+    pub hit: Option<(f32, Vec2)>,
+}
+
+impl DebugCast {
+    /// Where the shape ended up, at the surface it hit or at the full distance
+    pub fn end(&self) -> Vec2 {
+        let travelled = self.hit.map_or(self.distance, |(distance, _)| distance);
+        self.origin + self.direction * travelled
+    }
+}
+
+/// Shape casts of the latest timestep, drawn with gizmos while `enabled`
+///
+/// Covers the ground checks and the casts moving entities in `apply_velocity`.
+#[derive(Resource, Default, Reflect)]
+#[reflect(Resource)]
+pub struct ShapeCastGizmos {
+    pub enabled: bool,
+    pub casts: Vec<DebugCast>,
+}
+
+impl ShapeCastGizmos {
+    pub fn record(
+        &mut self,
+        origin: Vec2,
+        direction: Vec2,
+        distance: f32,
+        collider: &Collider,
+        hit: Option<&ShapeHitData>,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        self.casts.push(DebugCast {
+            origin,
+            direction,
+            distance,
+            half_size: collider.aabb(Vec2::ZERO, 0.0).size() / 2.0,
+            hit: hit.map(|hit| (hit.distance, hit.point1)),
+        });
+    }
+}
+
+/// Steepest ground, in radians from flat, that can be walked on. Leaves some leeway over
+/// the 45 degree slope tiles.
+pub const DEFAULT_MAX_SLOPE_ANGLE: f32 = 0.87;
+
 #[derive(Component, Default)]
 pub struct CollisionConfig {
     pub ground_check_distance: f32,
     pub wall_check_distance: f32,
     pub ceiling_check_distance: f32,
+    /// Ground steeper than this, in radians, doesn't count as ground and is slid down instead
+    pub max_slope_angle: f32,
+    /// Tallest step, in pixels, the entity walks up onto instead of being stopped by it.
+    /// 0.0 disables stepping up.
+    pub max_step_height: f32,
+    /// Furthest the entity is nudged sideways, in pixels, to slip past the corner of a ceiling
+    /// it would hit while moving up. 0.0 disables the nudge.
+    pub corner_correction: f32,
     pub collision_filter: SpatialQueryFilter,
 }
 
+impl CollisionConfig {
+    /// Whether a surface with this normal is flat enough to stand on
+    pub fn is_walkable(&self, normal: Vec2) -> bool {
+        normal.angle_to(Vec2::Y).abs() <= self.max_slope_angle
+    }
+}
+
+/// Pixels per second, applied to the transform by `apply_velocity` every fixed timestep
+///
+/// The transform is interpolated between timesteps so movement stays smooth at any frame rate.
 #[derive(Component, Default, Reflect, Resource, InspectorOptions)]
 #[reflect(Resource)]
+#[require(TransformInterpolation)]
 pub struct Velocity(pub Vec2);
 
 #[derive(Component, Default, Reflect, Resource, InspectorOptions)]
 #[reflect(Resource)]
 pub struct GroundedStopwatch(pub Stopwatch);
 
+/// Time since the entity last touched a wall on its left
+#[derive(Component, Default, Reflect)]
+pub struct WallLeftStopwatch(pub Stopwatch);
+
+/// Time since the entity last touched a wall on its right
+#[derive(Component, Default, Reflect)]
+pub struct WallRightStopwatch(pub Stopwatch);
+
 #[derive(Bundle, Default)]
 pub struct CollisionBundle {
     pub is_grounded: IsGrounded,
+    pub ground_entity: GroundEntity,
     pub is_touching_wall_left: IsTouchingWallLeft,
     pub is_touching_wall_right: IsTouchingWallRight,
     pub is_touching_ceiling: IsTouchingCeiling,
     pub grounded_stopwatch: GroundedStopwatch,
+    pub wall_left_stopwatch: WallLeftStopwatch,
+    pub wall_right_stopwatch: WallRightStopwatch,
     pub config: CollisionConfig,
     pub velocity: Velocity,
 }
 
-fn shape_cast(
+/// World-space bounds of an entity's collider, which lives on one of its children
+pub fn child_collider_aabb(
+    children: &Children,
+    aabb_query: &Query<&ColliderAabb>,
+) -> Option<ColliderAabb> {
+    children
+        .iter()
+        .find_map(|child| aabb_query.get(child).ok())
+        .copied()
+}
+
+pub fn shape_cast(
     spatial_query: &SpatialQuery,
     origin: Vec2,
     direction: Vec2,
@@ -72,6 +279,171 @@ fn shape_cast(
     None
 }
 
+/// Cast onto the tops of one-way platforms, which only block shapes coming from above
+///
+/// Shapes already overlapping a platform, like halfway through jumping up through it,
+/// go on through.
+pub fn one_way_platform_cast(
+    spatial_query: &SpatialQuery,
+    origin: Vec2,
+    direction: Vec2,
+    distance: f32,
+    collider: &Collider,
+) -> Option<ShapeHitData> {
+    if direction.y >= 0.0 {
+        return None;
+    }
+    let dir = Dir2::new(direction).ok()?;
+    spatial_query
+        .cast_shape(
+            collider,
+            origin,
+            0.0,
+            dir,
+            &ShapeCastConfig {
+                max_distance: distance,
+                ignore_origin_penetration: true,
+                ..Default::default()
+            },
+            &SpatialQueryFilter::from_mask(GameLayer::OneWayPlatform),
+        )
+        .filter(|hit| hit.normal1.y > 0.0)
+}
+
+/// Whichever of the hits is closer
+pub fn nearest_hit(a: Option<ShapeHitData>, b: Option<ShapeHitData>) -> Option<ShapeHitData> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.distance < a.distance { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Max number of times a single move can be redirected along a surface, so corners can't loop forever
+const MAX_SLIDE_ITERATIONS: usize = 4;
+/// Distance kept between a moving shape and the surface it hits
+const SKIN_WIDTH: f32 = 0.1;
+
+/// Where a cast stopped and the normal of the surface it hit
+pub struct SlideHit {
+    pub distance: f32,
+    pub normal: Vec2,
+}
+
+/// Velocity redirected along the ground, so walking keeps the entity on slopes instead
+/// of bumping into them on the way up or launching off them on the way down
+///
+/// Upward velocity (i.e. jumping) is left alone.
+pub fn project_on_ground(velocity: Vec2, ground_normal: Vec2) -> Vec2 {
+    if velocity.y > 0.0 {
+        return velocity;
+    }
+    // Along the ground, pointing right
+    let tangent = -ground_normal.perp();
+    tangent * velocity.x
+}
+
+/// `velocity` without the part going into a surface with this normal, so it keeps moving
+/// along the surface instead of stopping
+pub fn clip_velocity(velocity: Vec2, normal: Vec2) -> Vec2 {
+    let into_surface = velocity.dot(normal);
+    if into_surface < 0.0 {
+        velocity - normal * into_surface
+    } else {
+        velocity
+    }
+}
+
+/// Move from `origin` by `motion`, sliding along any surfaces hit on the way
+///
+/// On a hit the move stops just short of the surface, the part of the remaining motion
+/// going into the surface is dropped and the rest continues along the surface with
+/// another cast. Returns the final position.
+pub fn move_and_slide(
+    origin: Vec2,
+    motion: Vec2,
+    mut cast: impl FnMut(Vec2, Dir2, f32) -> Option<SlideHit>,
+) -> Vec2 {
+    let mut position = origin;
+    let mut remaining = motion;
+
+    for _ in 0..MAX_SLIDE_ITERATIONS {
+        let Ok((direction, distance)) = Dir2::new_and_length(remaining) else {
+            break;
+        };
+
+        let Some(hit) = cast(position, direction, distance) else {
+            position += remaining;
+            break;
+        };
+
+        let travelled = (hit.distance - SKIN_WIDTH).max(0.0);
+        position += direction * travelled;
+
+        remaining = clip_velocity(direction * (distance - travelled), hit.normal);
+    }
+
+    position
+}
+
+/// Sideways nudge that lets a shape moving up by `distance` slip past the corner of a ceiling
+///
+/// Tries nudges of 1, 2, .. up to `max_nudge` pixels to both sides, smallest first, and returns
+/// the first one that has room both for the nudge itself and for the whole move up. Ceilings
+/// hit further in than `max_nudge` from the shape's edge can't be cleared and give `None`.
+pub fn corner_correction(
+    origin: Vec2,
+    distance: f32,
+    max_nudge: f32,
+    mut cast: impl FnMut(Vec2, Dir2, f32) -> Option<SlideHit>,
+) -> Option<f32> {
+    cast(origin, Dir2::Y, distance)?;
+
+    (1..=max_nudge.floor() as u32)
+        .flat_map(|pixels| [pixels as f32, -(pixels as f32)])
+        .find(|&nudge| {
+            let side = if nudge < 0.0 { Dir2::NEG_X } else { Dir2::X };
+            cast(origin, side, nudge.abs()).is_none()
+                && cast(origin + Vec2::X * nudge, Dir2::Y, distance).is_none()
+        })
+}
+
+/// Where a grounded shape ends up after walking `motion_x` into a step it can get up onto
+///
+/// The step has to be a wall too steep to walk up, with its top at most
+/// `config.max_step_height` above the bottom of the shape and room above the shape to rise
+/// over it. Returns `None` for anything else, which then blocks the move as usual.
+pub fn step_up(
+    origin: Vec2,
+    motion_x: f32,
+    config: &CollisionConfig,
+    mut cast: impl FnMut(Vec2, Dir2, f32) -> Option<SlideHit>,
+) -> Option<Vec2> {
+    if config.max_step_height <= 0.0 {
+        return None;
+    }
+    let side = if motion_x < 0.0 { Dir2::NEG_X } else { Dir2::X };
+    let distance = motion_x.abs();
+
+    let wall = cast(origin, side, distance)?;
+    if config.is_walkable(wall.normal) {
+        return None;
+    }
+
+    let rise = cast(origin, Dir2::Y, config.max_step_height)
+        .map_or(config.max_step_height, |hit| {
+            (hit.distance - SKIN_WIDTH).max(0.0)
+        });
+    let raised = origin + Vec2::Y * rise;
+    // Still blocked when raised, so it's taller than a step
+    if cast(raised, side, distance).is_some() {
+        return None;
+    }
+
+    let ahead = raised + side * distance;
+    let step_top = cast(ahead, Dir2::NEG_Y, rise)?;
+    Some(ahead - Vec2::Y * (step_top.distance - SKIN_WIDTH).max(0.0))
+}
+
 pub fn check_grounded_state(
     spatial_query: SpatialQuery,
     mut query: Query<
@@ -82,14 +454,23 @@ pub fn check_grounded_state(
             &Children,
             Option<&mut GroundedStopwatch>,
             &mut Velocity,
+            Option<&mut GroundEntity>,
         ),
-        Without<Collider>,
+        (Without<Collider>, Without<Noclip>),
     >,
     collider_query: Query<(&Collider, &Transform)>,
+    mut cast_gizmos: ResMut<ShapeCastGizmos>,
     time: Res<Time>,
 ) {
-    for (mut is_grounded, config, transform, children, grounded_stopwatch, mut velocity) in
-        query.iter_mut()
+    for (
+        mut is_grounded,
+        config,
+        transform,
+        children,
+        grounded_stopwatch,
+        mut velocity,
+        ground_entity,
+    ) in query.iter_mut()
     {
         // Find the collider and its transform from children
         let collider_data = children
@@ -100,26 +481,51 @@ pub fn check_grounded_state(
             continue;
         };
 
-        let hit = shape_cast(
-            &spatial_query,
-            Vec2 {
-                x: transform.translation.x + collider_transform.translation.x,
-                y: transform.translation.y + collider_transform.translation.y,
-            },
+        let origin = transform.translation.xy() + collider_transform.translation.xy();
+        let hit = nearest_hit(
+            shape_cast(
+                &spatial_query,
+                origin,
+                Vec2::NEG_Y,
+                config.ground_check_distance,
+                collider,
+                &config.collision_filter,
+            ),
+            one_way_platform_cast(
+                &spatial_query,
+                origin,
+                Vec2::NEG_Y,
+                config.ground_check_distance,
+                collider,
+            ),
+        );
+
+        cast_gizmos.record(
+            origin,
             Vec2::NEG_Y,
             config.ground_check_distance,
             collider,
-            &config.collision_filter,
+            hit.as_ref(),
         );
 
-        if let Some(_hit) = hit {
-            *is_grounded = IsGrounded(true);
+        // Too steep to stand on, leave the entity falling so it slides down
+        let hit = hit.filter(|hit| config.is_walkable(hit.normal1));
+
+        if let Some(mut ground_entity) = ground_entity {
+            ground_entity.0 = hit.as_ref().map(|hit| hit.entity);
+        }
+
+        if let Some(hit) = hit {
+            *is_grounded = IsGrounded {
+                grounded: true,
+                normal: hit.normal1,
+            };
             velocity.0.y = velocity.0.y.clamp(0.0, INFINITY);
             if let Some(mut stopwatch) = grounded_stopwatch {
                 stopwatch.0.reset();
             }
         } else {
-            *is_grounded = IsGrounded(false);
+            *is_grounded = IsGrounded::default();
             if let Some(mut stopwatch) = grounded_stopwatch {
                 stopwatch.0.tick(time.delta());
             }
@@ -127,6 +533,91 @@ pub fn check_grounded_state(
     }
 }
 
+/// How far below the top of the collider a wall has to reach to be grabbed
+const LEDGE_HAND_DEPTH: f32 = 4.0;
+/// Empty space needed above the top of the collider, next to the wall
+const LEDGE_CLEARANCE: f32 = 2.0;
+
+/// Top corner of a ledge the collider spanning `min` to `max` can grab in `direction`
+///
+/// A ledge is a wall within `reach` near the top of the collider with nothing right above
+/// it. `cast` returns the distance to the first surface along a ray, if any.
+pub fn find_ledge(
+    min: Vec2,
+    max: Vec2,
+    direction: f32,
+    reach: f32,
+    mut cast: impl FnMut(Vec2, Dir2, f32) -> Option<f32>,
+) -> Option<Vec2> {
+    let side = if direction < 0.0 {
+        Dir2::NEG_X
+    } else {
+        Dir2::X
+    };
+    let edge_x = if direction < 0.0 { min.x } else { max.x };
+
+    // Something to hold on to
+    let wall_distance = cast(Vec2::new(edge_x, max.y - LEDGE_HAND_DEPTH), side, reach)?;
+
+    // And room to climb up over it
+    if cast(
+        Vec2::new(edge_x, max.y + LEDGE_CLEARANCE),
+        side,
+        reach + 1.0,
+    )
+    .is_some()
+    {
+        return None;
+    }
+
+    let wall_x = edge_x + side.x * wall_distance;
+    let probe = Vec2::new(wall_x + side.x, max.y + LEDGE_CLEARANCE);
+    let top_distance = cast(probe, Dir2::NEG_Y, LEDGE_CLEARANCE + LEDGE_HAND_DEPTH)?;
+
+    Some(Vec2::new(wall_x, probe.y - top_distance))
+}
+
+pub fn check_ledge_state(
+    spatial_query: SpatialQuery,
+    mut query: Query<
+        (
+            &mut IsTouchingLedge,
+            &CollisionConfig,
+            &Children,
+            &IsTouchingWallLeft,
+            &IsTouchingWallRight,
+        ),
+        (Without<Collider>, Without<Noclip>),
+    >,
+    aabb_query: Query<&ColliderAabb>,
+) {
+    for (mut is_touching_ledge, config, children, is_touching_wall_left, is_touching_wall_right) in
+        query.iter_mut()
+    {
+        let Some(aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        let mut cast = |origin: Vec2, direction: Dir2, distance: f32| {
+            spatial_query
+                .cast_ray(origin, direction, distance, true, &config.collision_filter)
+                .map(|hit| hit.distance)
+        };
+        let reach = config.wall_check_distance + 1.0;
+
+        is_touching_ledge.left = if is_touching_wall_left.0 {
+            find_ledge(aabb.min, aabb.max, -1.0, reach, &mut cast)
+        } else {
+            None
+        };
+        is_touching_ledge.right = if is_touching_wall_right.0 {
+            find_ledge(aabb.min, aabb.max, 1.0, reach, &mut cast)
+        } else {
+            None
+        };
+    }
+}
+
 pub fn check_ceiling_state(
     spatial_query: SpatialQuery,
     mut query: Query<
@@ -136,20 +627,14 @@ pub fn check_ceiling_state(
             &Transform,
             &Children,
             &mut Velocity,
-            Option<&mut AfterJumpGravityImmunityTimer>,
+            Option<&mut CharacterState>,
         ),
-        Without<Collider>,
+        (Without<Collider>, Without<Noclip>),
     >,
     collider_query: Query<(&Collider, &Transform)>,
 ) {
-    for (
-        mut is_touching_ceiling,
-        config,
-        transform,
-        children,
-        mut velocity,
-        after_jump_gravity_immunity_timer,
-    ) in query.iter_mut()
+    for (mut is_touching_ceiling, config, transform, children, mut velocity, character_state) in
+        query.iter_mut()
     {
         // Find the collider and its transform from children
         let collider_data = children
@@ -175,10 +660,9 @@ pub fn check_ceiling_state(
             *is_touching_ceiling = IsTouchingCeiling(true);
             velocity.0.y = velocity.0.y.clamp(NEG_INFINITY, -1.0);
             // If the entity (i.e. the player) has immunity to gravity after jumping for a set time,
-            // finish the timer manually here
-            if let Some(mut timer) = after_jump_gravity_immunity_timer {
-                let duration = timer.0.duration();
-                timer.0.set_elapsed(duration);
+            // end it early here
+            if let Some(mut character_state) = character_state {
+                character_state.end_gravity_immunity();
             }
         } else {
             *is_touching_ceiling = IsTouchingCeiling(false);
@@ -195,12 +679,16 @@ pub fn check_wall_left_state(
             &Transform,
             &Children,
             &mut Velocity,
+            Option<&mut WallLeftStopwatch>,
         ),
-        Without<Collider>,
+        (Without<Collider>, Without<Noclip>),
     >,
     collider_query: Query<(&Collider, &Transform)>,
+    time: Res<Time>,
 ) {
-    for (mut is_touching_wall_left, config, transform, children, mut velocity) in query.iter_mut() {
+    for (mut is_touching_wall_left, config, transform, children, mut velocity, wall_stopwatch) in
+        query.iter_mut()
+    {
         // Find the collider and its transform from children
         let collider_data = children
             .iter()
@@ -221,12 +709,19 @@ pub fn check_wall_left_state(
             collider,
             &config.collision_filter,
         );
+        // Walkable slopes aren't walls
+        let hit = hit.filter(|hit| !config.is_walkable(hit.normal1));
         if let Some(_hit) = hit {
             *is_touching_wall_left = IsTouchingWallLeft(true);
             velocity.0.x = velocity.0.x.clamp(0.0, INFINITY);
-            println!("touching wall LEFT");
+            if let Some(mut stopwatch) = wall_stopwatch {
+                stopwatch.0.reset();
+            }
         } else {
             *is_touching_wall_left = IsTouchingWallLeft(false);
+            if let Some(mut stopwatch) = wall_stopwatch {
+                stopwatch.0.tick(time.delta());
+            }
         }
     }
 }
@@ -240,12 +735,15 @@ pub fn check_wall_right_state(
             &Transform,
             &Children,
             &mut Velocity,
+            Option<&mut WallRightStopwatch>,
         ),
-        Without<Collider>,
+        (Without<Collider>, Without<Noclip>),
     >,
     collider_query: Query<(&Collider, &Transform)>,
+    time: Res<Time>,
 ) {
-    for (mut is_touching_wall_right, config, transform, children, mut velocity) in query.iter_mut()
+    for (mut is_touching_wall_right, config, transform, children, mut velocity, wall_stopwatch) in
+        query.iter_mut()
     {
         // Find the collider and its transform from children
         let collider_data = children
@@ -267,16 +765,138 @@ pub fn check_wall_right_state(
             collider,
             &config.collision_filter,
         );
+        // Walkable slopes aren't walls
+        let hit = hit.filter(|hit| !config.is_walkable(hit.normal1));
         if let Some(_hit) = hit {
             *is_touching_wall_right = IsTouchingWallRight(true);
-            velocity.0.x = velocity.0.x.clamp(0.0, INFINITY);
-            println!("touching wall RIGHT");
+            velocity.0.x = velocity.0.x.clamp(NEG_INFINITY, 0.0);
+            if let Some(mut stopwatch) = wall_stopwatch {
+                stopwatch.0.reset();
+            }
         } else {
             *is_touching_wall_right = IsTouchingWallRight(false);
+            if let Some(mut stopwatch) = wall_stopwatch {
+                stopwatch.0.tick(time.delta());
+            }
+        }
+    }
+}
+
+/// Move grounded entities along with whatever they are standing on, e.g. moving platforms
+pub fn carry_grounded_entities(
+    mut query: Query<(&GroundEntity, &mut Transform), Without<Noclip>>,
+    ground_query: Query<&MovementDelta>,
+) {
+    for (ground_entity, mut transform) in query.iter_mut() {
+        if let Some(ground) = ground_entity.0
+            && let Ok(movement_delta) = ground_query.get(ground)
+        {
+            transform.translation += movement_delta.0.extend(0.0);
+        }
+    }
+}
+
+/// Furthest anything moves in one timestep, so fast movers at low tick rates can't skip
+/// through thin geometry
+const MAX_MOVE_PER_STEP: f32 = TILE_SIZE;
+/// Furthest an entity stuck inside level geometry is pushed to get it out
+const MAX_DEPENETRATION: f32 = TILE_SIZE;
+
+pub fn clamp_max_speed(mut query: Query<&mut Velocity>, time: Res<Time>) {
+    let max_speed = MAX_MOVE_PER_STEP / time.delta_secs();
+    for mut velocity in query.iter_mut() {
+        if velocity.0.length() > max_speed {
+            velocity.0 = velocity.0.clamp_length_max(max_speed);
+        }
+    }
+}
+
+/// Minimum translation that pushes a shape out of everything it overlaps
+///
+/// `contacts` are the normals pointing out of the shape towards what it overlaps, with how
+/// deep in it is along each. Along each axis the deepest push either way wins, so a shape
+/// wedged into a corner is pushed out of both walls, and one squeezed from opposite sides
+/// isn't pushed at all.
+pub fn minimum_translation(contacts: impl IntoIterator<Item = (Vec2, f32)>) -> Vec2 {
+    let (most, least) = contacts.into_iter().fold(
+        (Vec2::ZERO, Vec2::ZERO),
+        |(most, least), (normal, penetration)| {
+            let push = -normal * penetration;
+            (most.max(push), least.min(push))
+        },
+    );
+    most + least
+}
+
+/// Push entities stuck in level geometry, like after being shoved by a moving platform or
+/// teleported, back out along the minimum translation vector of their contacts
+///
+/// Entities that can't be pushed out are warned about once, until they get free again.
+pub fn depenetrate(
+    spatial_query: SpatialQuery,
+    mut query: Query<
+        (Entity, &CollisionConfig, &Children, &mut Transform),
+        (Without<Collider>, Without<Noclip>),
+    >,
+    collider_query: Query<(&Collider, &Transform)>,
+    geometry_query: Query<(&Collider, &Position, &Rotation)>,
+    mut stuck: Local<HashSet<Entity>>,
+) {
+    for (entity, config, children, mut transform) in query.iter_mut() {
+        let Some((collider, collider_transform)) = children
+            .iter()
+            .find_map(|child| collider_query.get(child).ok())
+        else {
+            continue;
+        };
+
+        let origin = transform.translation.xy() + collider_transform.translation.xy();
+        let contacts = spatial_query
+            .shape_intersections(collider, origin, 0.0, &config.collision_filter)
+            .into_iter()
+            .filter_map(|other| geometry_query.get(other).ok())
+            .filter_map(|(other_collider, position, rotation)| {
+                contact_query::contact(
+                    collider,
+                    origin,
+                    0.0,
+                    other_collider,
+                    *position,
+                    *rotation,
+                    0.0,
+                )
+                .ok()
+                .flatten()
+            })
+            .map(|contact| (contact.normal1, contact.penetration))
+            .filter(|(_, penetration)| *penetration > 0.0)
+            .collect::<Vec<_>>();
+        if contacts.is_empty() {
+            stuck.remove(&entity);
+            continue;
+        }
+
+        let push = minimum_translation(contacts);
+        if push != Vec2::ZERO && push.length() <= MAX_DEPENETRATION {
+            transform.translation += push.extend(0.0);
+            stuck.remove(&entity);
+        } else if stuck.insert(entity) {
+            warn!("{} is stuck inside level geometry", entity);
         }
     }
 }
 
+pub fn clamp_horizontal_speed(
+    mut query: Query<(&mut Velocity, &MaxHorizontalSpeed), Without<IgnoreSpeedCap>>,
+) {
+    for (mut velocity, max_horizontal_speed) in query.iter_mut() {
+        velocity.0.x = velocity
+            .0
+            .x
+            .clamp(-max_horizontal_speed.0, max_horizontal_speed.0);
+    }
+}
+
 pub fn apply_velocity(
     spatial_query: SpatialQuery,
     time: Res<Time>,
@@ -289,10 +909,13 @@ pub fn apply_velocity(
             Option<&IsTouchingWallLeft>,
             Option<&IsTouchingWallRight>,
             Option<&IsTouchingCeiling>,
+            Option<&IsGrounded>,
+            Option<&mut CollisionDiagnostics>,
         ),
-        Without<Collider>,
+        (Without<Collider>, Without<Noclip>),
     >,
     collider_query: Query<(&Collider, &Transform)>,
+    mut cast_gizmos: ResMut<ShapeCastGizmos>,
 ) {
     for (
         config,
@@ -302,6 +925,8 @@ pub fn apply_velocity(
         is_touching_wall_left,
         is_touching_wall_right,
         is_touching_ceiling,
+        is_grounded,
+        mut diagnostics,
     ) in query.iter_mut()
     {
         // Find the collider and its transform from children
@@ -317,6 +942,48 @@ pub fn apply_velocity(
             velocity.0.x = 0.0;
         }
 
+        let cast_hit = |origin: Vec2, direction: Dir2, distance: f32| {
+            nearest_hit(
+                shape_cast(
+                    &spatial_query,
+                    origin,
+                    direction.as_vec2(),
+                    distance,
+                    collider,
+                    &config.collision_filter,
+                ),
+                one_way_platform_cast(
+                    &spatial_query,
+                    origin,
+                    direction.as_vec2(),
+                    distance,
+                    collider,
+                ),
+            )
+        };
+        let cast = |origin: Vec2, direction: Dir2, distance: f32| {
+            cast_hit(origin, direction, distance).map(|hit| SlideHit {
+                distance: hit.distance,
+                normal: hit.normal1,
+            })
+        };
+
+        let mut origin = transform.translation.xy() + collider_transform.translation.xy();
+
+        // Walk up over small steps instead of stopping at them like at a wall
+        let grounded = is_grounded.is_some_and(|is_grounded| is_grounded.grounded);
+        if grounded
+            && velocity.0.x != 0.0
+            && let Some(stepped) = step_up(origin, velocity.0.x * time.delta_secs(), config, &cast)
+        {
+            transform.translation += (stepped - origin).extend(0.0);
+            if let Some(diagnostics) = diagnostics.as_mut() {
+                let intended = Vec2::X * velocity.0.x * time.delta_secs();
+                diagnostics.record_move(intended, stepped - origin, Vec::new(), Vec::new());
+            }
+            continue;
+        }
+
         if let Some(is_touching_wall_left) = is_touching_wall_left {
             if is_touching_wall_left.0 && velocity.0.x < 0.0 {
                 velocity.0.x = 0.0;
@@ -335,28 +1002,69 @@ pub fn apply_velocity(
             }
         }
 
+        if let Some(diagnostics) = diagnostics.as_mut() {
+            diagnostics.grounded = grounded;
+            diagnostics.touching_wall_left = is_touching_wall_left.is_some_and(|wall| wall.0);
+            diagnostics.touching_wall_right = is_touching_wall_right.is_some_and(|wall| wall.0);
+            diagnostics.touching_ceiling = is_touching_ceiling.is_some_and(|ceiling| ceiling.0);
+        }
+
         if velocity.0.length() == 0.0 || velocity.0.length() == INFINITY {
+            if let Some(diagnostics) = diagnostics.as_mut() {
+                diagnostics.record_move(Vec2::ZERO, Vec2::ZERO, Vec::new(), Vec::new());
+            }
             continue;
         }
 
-        let target_distance = velocity.0.length() * time.delta_secs();
-        let hit = shape_cast(
-            &spatial_query,
-            Vec2 {
-                x: transform.translation.x + collider_transform.translation.x,
-                y: transform.translation.y + collider_transform.translation.y,
-            },
-            velocity.0.normalize(),
-            target_distance,
-            collider,
-            &config.collision_filter,
-        );
-        let actual_distance = hit.map_or(target_distance, |hit| hit.distance - 0.1);
-        *transform = transform.with_translation(Vec3 {
-            x: transform.translation.x + (velocity.0.normalize() * actual_distance).x,
-            y: transform.translation.y + (velocity.0.normalize() * actual_distance).y,
-            z: transform.translation.z,
+        // Clipping the corner of a ceiling on the way up shouldn't end the jump
+        if velocity.0.y > 0.0
+            && let Some(nudge) = corner_correction(
+                origin,
+                velocity.0.y * time.delta_secs(),
+                config.corner_correction,
+                &cast,
+            )
+        {
+            transform.translation.x += nudge;
+            origin.x += nudge;
+        }
+
+        let motion_velocity = match is_grounded {
+            Some(is_grounded) if is_grounded.grounded => {
+                project_on_ground(velocity.0, is_grounded.normal)
+            }
+            _ => velocity.0,
+        };
+        let intended = motion_velocity * time.delta_secs();
+        let mut hit_distances = Vec::new();
+        let mut hit_points = Vec::new();
+        let mut slid_velocity = velocity.0;
+        let end = move_and_slide(origin, intended, |origin, direction, distance| {
+            let hit = cast_hit(origin, direction, distance);
+            cast_gizmos.record(
+                origin,
+                direction.as_vec2(),
+                distance,
+                collider,
+                hit.as_ref(),
+            );
+            let hit = hit?;
+            hit_distances.push(hit.distance);
+            hit_points.push(hit.point1);
+            // Carry the slide over to the next timestep, e.g. falling diagonally into a
+            // wall keeps falling at the same speed instead of pushing into the wall
+            slid_velocity = clip_velocity(slid_velocity, hit.normal1);
+            Some(SlideHit {
+                distance: hit.distance,
+                normal: hit.normal1,
+            })
         });
+        velocity.0 = slid_velocity;
+        transform.translation += (end - origin).extend(0.0);
+
+        if let Some(diagnostics) = diagnostics.as_mut() {
+            diagnostics.record_move(intended, end - origin, hit_distances, hit_points);
+        }
     }
 }
 
@@ -364,19 +1072,491 @@ pub fn apply_velocity(
 
 pub struct CollisionPlugin;
 
+fn toggle_collision_diagnostics(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut config: ResMut<CollisionDiagnosticsConfig>,
+    query: Query<Entity, (With<CollisionConfig>, Without<Collider>)>,
+) {
+    if !keys.just_pressed(COLLISION_DIAGNOSTICS_TOGGLE_KEY) {
+        return;
+    }
+
+    config.enabled = !config.enabled;
+    for entity in query.iter() {
+        if config.enabled {
+            commands
+                .entity(entity)
+                .insert(CollisionDiagnostics::default());
+        } else {
+            commands.entity(entity).remove::<CollisionDiagnostics>();
+        }
+    }
+}
+
+fn toggle_shape_cast_gizmos(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut cast_gizmos: ResMut<ShapeCastGizmos>,
+) {
+    if keys.just_pressed(SHAPE_CAST_GIZMOS_TOGGLE_KEY) {
+        cast_gizmos.enabled = !cast_gizmos.enabled;
+    }
+}
+
+/// Forget the previous timestep's casts, so only the latest ones are drawn
+fn clear_shape_casts(mut cast_gizmos: ResMut<ShapeCastGizmos>) {
+    cast_gizmos.casts.clear();
+}
+
+/// Draw each cast as a line from its origin, the shape where it stopped and the point it hit,
+/// red for hits and green for misses
+fn draw_shape_casts(cast_gizmos: Res<ShapeCastGizmos>, mut gizmos: Gizmos) {
+    if !cast_gizmos.enabled {
+        return;
+    }
+
+    for cast in &cast_gizmos.casts {
+        let color = if cast.hit.is_some() {
+            CAST_HIT_COLOR
+        } else {
+            CAST_MISS_COLOR
+        };
+        gizmos.line_2d(
+            cast.origin,
+            cast.origin + cast.direction * cast.distance,
+            color,
+        );
+        gizmos.rect_2d(
+            Isometry2d::from_translation(cast.end()),
+            cast.half_size * 2.0,
+            color,
+        );
+        if let Some((_, point)) = cast.hit {
+            gizmos.circle_2d(Isometry2d::from_translation(point), 1.5, color);
+        }
+    }
+}
+
+fn log_collision_diagnostics(
+    config: Res<CollisionDiagnosticsConfig>,
+    query: Query<(Entity, &CollisionDiagnostics)>,
+) {
+    if !config.log_blocked {
+        return;
+    }
+
+    for (entity, diagnostics) in query.iter() {
+        if diagnostics.blocked_x || diagnostics.blocked_y {
+            info!("{} collision: {:?}", entity, diagnostics);
+        }
+    }
+}
+
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
-            Update,
+            FixedUpdate,
             (
-                check_grounded_state,
-                check_wall_left_state,
-                check_wall_right_state,
-                check_ceiling_state,
-                apply_velocity,
+                clear_shape_casts.before(MovementSet::Input),
+                (
+                    (check_grounded_state, carry_grounded_entities).chain(),
+                    (
+                        (check_wall_left_state, check_wall_right_state),
+                        check_ledge_state,
+                    )
+                        .chain(),
+                    check_ceiling_state,
+                )
+                    .in_set(MovementSet::CollisionChecks),
+                (
+                    clamp_horizontal_speed,
+                    clamp_max_speed,
+                    apply_velocity,
+                    depenetrate,
+                    log_collision_diagnostics,
+                )
+                    .chain()
+                    .in_set(MovementSet::ApplyVelocity),
             ),
         )
+        .init_resource::<CollisionDiagnosticsConfig>()
+        .init_resource::<ShapeCastGizmos>()
+        .register_type::<CollisionDiagnostics>()
+        .register_type::<CollisionDiagnosticsConfig>()
         .register_type::<GroundedStopwatch>()
+        .register_type::<MaxHorizontalSpeed>()
+        .register_type::<ShapeCastGizmos>()
+        .register_type::<WallLeftStopwatch>()
+        .register_type::<WallRightStopwatch>()
         .register_type::<Velocity>();
     }
 }
+
+/// Keys for the collision diagnostics and drawing the shape casts, kept apart from
+/// `CollisionPlugin` so the collision systems also run without a window or renderer
+pub struct CollisionDebugPlugin;
+
+impl Plugin for CollisionDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                toggle_collision_diagnostics,
+                (toggle_shape_cast_gizmos, draw_shape_casts).chain(),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_speed_cap_limits_combined_velocity() {
+        let mut world = World::new();
+
+        // Walking at full speed on a conveyor going the same way
+        let walk_speed = 160.0;
+        let conveyor_speed = 140.0;
+        let capped = world
+            .spawn((
+                Velocity(Vec2::new(walk_speed + conveyor_speed, 0.0)),
+                MaxHorizontalSpeed(200.0),
+            ))
+            .id();
+        let exempt = world
+            .spawn((
+                Velocity(Vec2::new(walk_speed + conveyor_speed, 0.0)),
+                MaxHorizontalSpeed(200.0),
+                IgnoreSpeedCap,
+            ))
+            .id();
+        let leftwards = world
+            .spawn((
+                Velocity(Vec2::new(-(walk_speed + conveyor_speed), -50.0)),
+                MaxHorizontalSpeed(200.0),
+            ))
+            .id();
+
+        world.run_system_once(clamp_horizontal_speed).unwrap();
+
+        assert_eq!(world.get::<Velocity>(capped).unwrap().0.x, 200.0);
+        assert_eq!(world.get::<Velocity>(exempt).unwrap().0.x, 300.0);
+        assert_eq!(
+            world.get::<Velocity>(leftwards).unwrap().0,
+            Vec2::new(-200.0, -50.0)
+        );
+    }
+
+    #[test]
+    fn test_minimum_translation_out_of_overlaps() {
+        // 3 pixels into a floor
+        assert_eq!(
+            minimum_translation([(Vec2::NEG_Y, 3.0)]),
+            Vec2::new(0.0, 3.0)
+        );
+
+        // Into a corner of a floor and a wall on the right, the two pushes combine rather
+        // than the deeper one overriding the other
+        assert_eq!(
+            minimum_translation([(Vec2::NEG_Y, 2.0), (Vec2::X, 5.0), (Vec2::NEG_Y, 1.0)]),
+            Vec2::new(-5.0, 2.0)
+        );
+
+        // Squeezed between walls from both sides, with nowhere to go
+        assert_eq!(
+            minimum_translation([(Vec2::X, 4.0), (Vec2::NEG_X, 4.0)]),
+            Vec2::ZERO
+        );
+    }
+
+    #[test]
+    fn test_debug_cast_ends_at_hit() {
+        let mut cast = DebugCast {
+            origin: Vec2::new(10.0, 20.0),
+            direction: Vec2::NEG_Y,
+            distance: 4.0,
+            half_size: Vec2::new(8.0, 16.0),
+            hit: None,
+        };
+        assert_eq!(cast.end(), Vec2::new(10.0, 16.0));
+
+        cast.hit = Some((1.5, Vec2::new(10.0, 2.5)));
+        assert_eq!(cast.end(), Vec2::new(10.0, 18.5));
+    }
+
+    /// Cast against an infinite wall at `x = wall_x`, treating the moving shape as a point
+    fn cast_against_wall(wall_x: f32) -> impl FnMut(Vec2, Dir2, f32) -> Option<SlideHit> {
+        move |origin, direction, distance| {
+            if direction.x <= 0.0 {
+                return None;
+            }
+            let hit_distance = (wall_x - origin.x) / direction.x;
+            (hit_distance <= distance).then_some(SlideHit {
+                distance: hit_distance,
+                normal: Vec2::NEG_X,
+            })
+        }
+    }
+
+    #[test]
+    fn test_diagonal_move_slides_along_wall() {
+        let end = move_and_slide(Vec2::ZERO, Vec2::new(20.0, 20.0), cast_against_wall(10.0));
+
+        // Stopped at the wall, but kept moving up along it
+        assert!(end.x < 10.0 && end.x > 9.8);
+        assert!(end.y > 19.0);
+    }
+
+    #[test]
+    fn test_velocity_slides_along_surface() {
+        // Falling diagonally into a wall on the right keeps falling
+        assert_eq!(
+            clip_velocity(Vec2::new(100.0, -50.0), Vec2::NEG_X),
+            Vec2::new(0.0, -50.0)
+        );
+        // Moving away from the wall is left alone
+        assert_eq!(
+            clip_velocity(Vec2::new(-100.0, -50.0), Vec2::NEG_X),
+            Vec2::new(-100.0, -50.0)
+        );
+        // Landing on a slope keeps the part along it
+        let slope_normal = Vec2::new(-1.0, 1.0).normalize();
+        let clipped = clip_velocity(Vec2::new(0.0, -100.0), slope_normal);
+        assert!(clipped.x < 0.0 && clipped.y < 0.0);
+        assert!(clipped.dot(slope_normal).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_diagnostics_record_blocked_axis() {
+        let intended = Vec2::new(20.0, 20.0);
+        let mut hit_distances = Vec::new();
+        let mut cast = cast_against_wall(10.0);
+        let end = move_and_slide(Vec2::ZERO, intended, |origin, direction, distance| {
+            let hit = cast(origin, direction, distance);
+            if let Some(hit) = &hit {
+                hit_distances.push(hit.distance);
+            }
+            hit
+        });
+
+        let mut diagnostics = CollisionDiagnostics::default();
+        diagnostics.record_move(intended, end, hit_distances, Vec::new());
+
+        assert!(diagnostics.blocked_x);
+        assert!(!diagnostics.blocked_y);
+        assert!(diagnostics.actual.x < diagnostics.intended.x);
+        assert_eq!(diagnostics.hit_distances.len(), 1);
+    }
+
+    /// Cast for a shape `half_width` wide on each side, against a ceiling block spanning
+    /// `block_min_x` to `block_max_x` with its bottom at `ceiling_y`
+    fn cast_against_ceiling_block(
+        half_width: f32,
+        block_min_x: f32,
+        block_max_x: f32,
+        ceiling_y: f32,
+    ) -> impl FnMut(Vec2, Dir2, f32) -> Option<SlideHit> {
+        move |origin, direction, distance| {
+            if direction != Dir2::Y {
+                return None;
+            }
+            let overlaps =
+                origin.x + half_width > block_min_x && origin.x - half_width < block_max_x;
+            let to_ceiling = ceiling_y - origin.y;
+            (overlaps && to_ceiling >= 0.0 && to_ceiling <= distance).then_some(SlideHit {
+                distance: to_ceiling,
+                normal: Vec2::NEG_Y,
+            })
+        }
+    }
+
+    #[test]
+    fn test_corner_correction_nudges_past_ceiling_edge() {
+        // Head overlapping the block's left corner by 3 pixels
+        let cast = cast_against_ceiling_block(8.0, 5.0, 100.0, 10.0);
+        assert_eq!(corner_correction(Vec2::ZERO, 20.0, 4.0, cast), Some(-3.0));
+
+        // Overlapping its right corner
+        let cast = cast_against_ceiling_block(8.0, -100.0, -6.0, 10.0);
+        assert_eq!(corner_correction(Vec2::ZERO, 20.0, 4.0, cast), Some(2.0));
+
+        // Too far under the block to slip past
+        let cast = cast_against_ceiling_block(8.0, -2.0, 100.0, 10.0);
+        assert_eq!(corner_correction(Vec2::ZERO, 20.0, 4.0, cast), None);
+
+        // Ceiling out of reach this step, nothing to correct
+        let cast = cast_against_ceiling_block(8.0, 5.0, 100.0, 10.0);
+        assert_eq!(corner_correction(Vec2::ZERO, 5.0, 4.0, cast), None);
+    }
+
+    #[test]
+    fn test_slide_stops_in_corner() {
+        // Every direction is blocked right away
+        let end = move_and_slide(Vec2::ZERO, Vec2::new(5.0, -5.0), |_, direction, _| {
+            Some(SlideHit {
+                distance: 0.0,
+                normal: -direction.as_vec2(),
+            })
+        });
+
+        assert_eq!(end, Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_grounded_entity_is_carried_by_ground() {
+        let mut world = World::new();
+        let platform = world.spawn(MovementDelta(Vec2::new(3.0, -2.0))).id();
+        let static_ground = world.spawn_empty().id();
+
+        let carried = world
+            .spawn((
+                GroundEntity(Some(platform)),
+                Transform::from_xyz(10.0, 10.0, 1.0),
+            ))
+            .id();
+        let on_static_ground = world
+            .spawn((GroundEntity(Some(static_ground)), Transform::default()))
+            .id();
+        let airborne = world.spawn((GroundEntity(None), Transform::default())).id();
+
+        world.run_system_once(carry_grounded_entities).unwrap();
+
+        assert_eq!(
+            world.get::<Transform>(carried).unwrap().translation,
+            Vec3::new(13.0, 8.0, 1.0)
+        );
+        assert_eq!(
+            world
+                .get::<Transform>(on_static_ground)
+                .unwrap()
+                .translation,
+            Vec3::ZERO
+        );
+        assert_eq!(
+            world.get::<Transform>(airborne).unwrap().translation,
+            Vec3::ZERO
+        );
+    }
+
+    #[test]
+    fn test_walking_follows_slope() {
+        let config = CollisionConfig {
+            max_slope_angle: DEFAULT_MAX_SLOPE_ANGLE,
+            ..default()
+        };
+        let slope_normal = Vec2::new(-1.0, 1.0).normalize();
+        assert!(config.is_walkable(Vec2::Y));
+        assert!(config.is_walkable(slope_normal));
+        assert!(!config.is_walkable(Vec2::NEG_X));
+        assert!(!config.is_walkable(Vec2::new(-2.0, 1.0).normalize()));
+
+        // Up and to the right along a slope rising to the right, at the same speed
+        let uphill = project_on_ground(Vec2::new(100.0, 0.0), slope_normal);
+        assert!((uphill.x - uphill.y).abs() < 1e-3);
+        assert!((uphill.length() - 100.0).abs() < 1e-3);
+
+        let downhill = project_on_ground(Vec2::new(-100.0, 0.0), slope_normal);
+        assert!(downhill.x < 0.0 && downhill.y < 0.0);
+
+        // Flat ground and jumps are left alone
+        assert_eq!(
+            project_on_ground(Vec2::new(100.0, 0.0), Vec2::Y),
+            Vec2::new(100.0, 0.0)
+        );
+        assert_eq!(
+            project_on_ground(Vec2::new(100.0, 50.0), slope_normal),
+            Vec2::new(100.0, 50.0)
+        );
+    }
+
+    /// Ray cast against a wall filling everything right of `wall_x` and below `wall_top`
+    fn cast_against_ledge(
+        wall_x: f32,
+        wall_top: f32,
+    ) -> impl FnMut(Vec2, Dir2, f32) -> Option<f32> {
+        move |origin, direction, distance| {
+            if direction == Dir2::X && origin.y < wall_top {
+                let to_wall = wall_x - origin.x;
+                (to_wall >= 0.0 && to_wall <= distance).then_some(to_wall)
+            } else if direction == Dir2::NEG_Y && origin.x > wall_x {
+                let to_top = origin.y - wall_top;
+                (to_top >= 0.0 && to_top <= distance).then_some(to_top)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Ray cast against a step filling everything right of `step_x` and below `step_top`
+    fn cast_against_step(
+        step_x: f32,
+        step_top: f32,
+    ) -> impl FnMut(Vec2, Dir2, f32) -> Option<SlideHit> {
+        let mut cast = cast_against_ledge(step_x, step_top);
+        move |origin, direction, distance| {
+            let normal = if direction == Dir2::X {
+                Vec2::NEG_X
+            } else {
+                Vec2::Y
+            };
+            cast(origin, direction, distance).map(|distance| SlideHit { distance, normal })
+        }
+    }
+
+    #[test]
+    fn test_step_up_onto_low_step() {
+        let config = CollisionConfig {
+            max_slope_angle: DEFAULT_MAX_SLOPE_ANGLE,
+            max_step_height: 4.0,
+            ..default()
+        };
+        let origin = Vec2::new(9.5, 0.5);
+
+        let stepped = step_up(origin, 2.0, &config, cast_against_step(10.0, 3.0)).unwrap();
+        assert!((stepped - Vec2::new(11.5, 3.0 + SKIN_WIDTH)).length() < 1e-4);
+
+        // Too tall to step onto
+        assert_eq!(
+            step_up(origin, 2.0, &config, cast_against_step(10.0, 6.0)),
+            None
+        );
+
+        // Not walking into it yet
+        assert_eq!(
+            step_up(origin, 0.25, &config, cast_against_step(10.0, 3.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ledge_found_at_wall_top() {
+        let min = Vec2::new(-8.0, -16.0);
+        let max = Vec2::new(8.0, 16.0);
+
+        // Wall top just below the top of the collider
+        let ledge = find_ledge(min, max, 1.0, 2.0, cast_against_ledge(9.0, 14.0));
+        assert_eq!(ledge, Some(Vec2::new(9.0, 14.0)));
+
+        // Wall reaching over the collider, nothing to grab
+        assert_eq!(
+            find_ledge(min, max, 1.0, 2.0, cast_against_ledge(9.0, 40.0)),
+            None
+        );
+
+        // Wall top below hand height
+        assert_eq!(
+            find_ledge(min, max, 1.0, 2.0, cast_against_ledge(9.0, 0.0)),
+            None
+        );
+
+        // Wall out of reach
+        assert_eq!(
+            find_ledge(min, max, 1.0, 2.0, cast_against_ledge(20.0, 14.0)),
+            None
+        );
+    }
+}
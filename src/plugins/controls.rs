@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::{GamepadControlDirection, GamepadStick, InputMap};
+use serde::{Deserialize, Serialize};
+
+use crate::bundles::player::Player;
+
+use super::{player::PlayerAction, settings::Settings};
+
+/// How far a stick has to be tilted before it counts, as a fraction of a full tilt
+pub const STICK_DEADZONE: f32 = 0.2;
+
+/// Keys and gamepad buttons for each player action
+///
+/// The sticks aren't rebindable, the left one always moves and the right one always aims.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub keys: HashMap<PlayerAction, Vec<KeyCode>>,
+    pub buttons: HashMap<PlayerAction, Vec<GamepadButton>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: HashMap::from([
+                (PlayerAction::Jump, vec![KeyCode::Space]),
+                (PlayerAction::Left, vec![KeyCode::ArrowLeft, KeyCode::KeyA]),
+                (
+                    PlayerAction::Right,
+                    vec![KeyCode::ArrowRight, KeyCode::KeyD],
+                ),
+                (PlayerAction::Up, vec![KeyCode::ArrowUp, KeyCode::KeyW]),
+                (PlayerAction::Down, vec![KeyCode::ArrowDown, KeyCode::KeyS]),
+                (PlayerAction::Shoot, vec![KeyCode::KeyJ]),
+                (PlayerAction::AimLock, vec![KeyCode::KeyK]),
+                (PlayerAction::Dash, vec![KeyCode::KeyL]),
+                (PlayerAction::Crouch, vec![KeyCode::KeyC]),
+                (PlayerAction::AimUp, vec![KeyCode::KeyI]),
+                (PlayerAction::AimDown, vec![KeyCode::KeyM]),
+                (PlayerAction::Melee, vec![KeyCode::KeyH]),
+                (PlayerAction::Interact, vec![KeyCode::KeyE]),
+            ]),
+            buttons: HashMap::from([
+                (PlayerAction::Left, vec![GamepadButton::DPadLeft]),
+                (PlayerAction::Right, vec![GamepadButton::DPadRight]),
+                (PlayerAction::Up, vec![GamepadButton::DPadUp]),
+                (PlayerAction::Down, vec![GamepadButton::DPadDown]),
+                (PlayerAction::Jump, vec![GamepadButton::South]),
+                (PlayerAction::Shoot, vec![GamepadButton::West]),
+                (PlayerAction::AimLock, vec![GamepadButton::LeftTrigger]),
+                (PlayerAction::Dash, vec![GamepadButton::RightTrigger]),
+                (PlayerAction::Crouch, vec![GamepadButton::East]),
+                (PlayerAction::AimUp, vec![GamepadButton::RightTrigger2]),
+                (PlayerAction::AimDown, vec![GamepadButton::LeftTrigger2]),
+                (PlayerAction::Melee, vec![GamepadButton::North]),
+                (PlayerAction::Interact, vec![GamepadButton::RightThumb]),
+            ]),
+        }
+    }
+}
+
+/// Key names without the `Key`/`Digit` prefixes, "A" rather than "KeyA"
+fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+impl KeyBindings {
+    /// Actions listed on the controls screen, in order
+    pub const REBINDABLE: [PlayerAction; 13] = [
+        PlayerAction::Left,
+        PlayerAction::Right,
+        PlayerAction::Up,
+        PlayerAction::Down,
+        PlayerAction::Jump,
+        PlayerAction::Shoot,
+        PlayerAction::Melee,
+        PlayerAction::Interact,
+        PlayerAction::Dash,
+        PlayerAction::Crouch,
+        PlayerAction::AimLock,
+        PlayerAction::AimUp,
+        PlayerAction::AimDown,
+    ];
+
+    pub fn input_map(&self) -> InputMap<PlayerAction> {
+        let mut input_map = InputMap::default();
+        for (action, keys) in &self.keys {
+            for key in keys {
+                input_map.insert(*action, *key);
+            }
+        }
+        for (action, buttons) in &self.buttons {
+            for button in buttons {
+                input_map.insert(*action, *button);
+            }
+        }
+        input_map
+            .with(
+                PlayerAction::Left,
+                GamepadControlDirection::LEFT_LEFT.threshold(STICK_DEADZONE),
+            )
+            .with(
+                PlayerAction::Right,
+                GamepadControlDirection::LEFT_RIGHT.threshold(STICK_DEADZONE),
+            )
+            .with(
+                PlayerAction::Up,
+                GamepadControlDirection::LEFT_UP.threshold(STICK_DEADZONE),
+            )
+            .with(
+                PlayerAction::Down,
+                GamepadControlDirection::LEFT_DOWN.threshold(STICK_DEADZONE),
+            )
+            .with_dual_axis(PlayerAction::Move, GamepadStick::LEFT)
+            .with_dual_axis(PlayerAction::Aim, GamepadStick::RIGHT)
+    }
+
+    /// Make `key` the only key for `action`, taking it away from any other action
+    pub fn rebind_key(&mut self, action: PlayerAction, key: KeyCode) {
+        for keys in self.keys.values_mut() {
+            keys.retain(|bound| *bound != key);
+        }
+        self.keys.insert(action, vec![key]);
+    }
+
+    /// Like `rebind_key`, for the gamepad
+    pub fn rebind_button(&mut self, action: PlayerAction, button: GamepadButton) {
+        for buttons in self.buttons.values_mut() {
+            buttons.retain(|bound| *bound != button);
+        }
+        self.buttons.insert(action, vec![button]);
+    }
+
+    /// What the controls screen shows for an action, like "Jump: Space"
+    pub fn label(&self, action: PlayerAction) -> String {
+        let keys = self.keys.get(&action).into_iter().flatten();
+        let buttons = self.buttons.get(&action).into_iter().flatten();
+        let inputs: Vec<_> = keys
+            .map(|key| key_name(*key))
+            .chain(buttons.map(|button| format!("{button:?}")))
+            .collect();
+        if inputs.is_empty() {
+            format!("{action:?}: -")
+        } else {
+            format!("{action:?}: {}", inputs.join(", "))
+        }
+    }
+}
+
+/// Stick tilt with the deadzone cut out, stretched so it still goes from 0.0 right past the
+/// deadzone to 1.0 at a full tilt
+pub fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    if value.abs() <= deadzone {
+        0.0
+    } else {
+        value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+    }
+}
+
+/// Whichever of the keyboard or a gamepad was used last, for showing the right button
+/// names to the player
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub enum ActiveInputDevice {
+    #[default]
+    Keyboard,
+    Gamepad,
+}
+
+impl ActiveInputDevice {
+    /// Name of the button that confirms menus
+    pub fn confirm_prompt(self) -> &'static str {
+        match self {
+            Self::Keyboard => "Enter",
+            Self::Gamepad => "A",
+        }
+    }
+}
+
+fn detect_input_device(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepad_query: Query<&Gamepad>,
+    mut device: ResMut<ActiveInputDevice>,
+) {
+    let gamepad_used = gamepad_query.iter().any(|gamepad| {
+        gamepad.get_just_pressed().next().is_some()
+            || gamepad.left_stick().length() > STICK_DEADZONE
+            || gamepad.right_stick().length() > STICK_DEADZONE
+    });
+    if gamepad_used {
+        device.set_if_neq(ActiveInputDevice::Gamepad);
+    } else if keyboard.get_just_pressed().next().is_some() {
+        device.set_if_neq(ActiveInputDevice::Keyboard);
+    }
+}
+
+/// Action waiting for the next key or button press to be bound to it
+#[derive(Resource, Default, Debug)]
+pub struct Rebinding(pub Option<PlayerAction>);
+
+pub fn is_rebinding(rebinding: Res<Rebinding>) -> bool {
+    rebinding.0.is_some()
+}
+
+/// Bind the first key or gamepad button pressed, Escape cancels
+pub fn listen_for_rebind(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepad_query: Query<&Gamepad>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+) {
+    let Some(action) = rebinding.0 else {
+        return;
+    };
+
+    if keyboard.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+    } else if let Some(key) = keyboard.get_just_pressed().next() {
+        settings.bindings.rebind_key(action, *key);
+        rebinding.0 = None;
+    } else if let Some(button) = gamepad_query
+        .iter()
+        .find_map(|gamepad| gamepad.get_just_pressed().next())
+    {
+        settings.bindings.rebind_button(action, *button);
+        rebinding.0 = None;
+    }
+}
+
+/// Swap the new bindings into the live player's input map
+fn apply_key_bindings(
+    bindings: Res<KeyBindings>,
+    mut query: Query<&mut InputMap<PlayerAction>, With<Player>>,
+) {
+    for mut input_map in query.iter_mut() {
+        *input_map = bindings.input_map();
+    }
+}
+
+/// Rebindable keyboard and gamepad controls, saved along with the other settings
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeyBindings>()
+            .init_resource::<Rebinding>()
+            .init_resource::<ActiveInputDevice>()
+            .add_systems(
+                Update,
+                (
+                    detect_input_device,
+                    listen_for_rebind.run_if(is_rebinding),
+                    apply_key_bindings.run_if(resource_changed::<KeyBindings>),
+                ),
+            )
+            .register_type::<KeyBindings>()
+            .register_type::<ActiveInputDevice>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebinding_takes_the_key_from_other_actions() {
+        let mut bindings = KeyBindings::default();
+
+        bindings.rebind_key(PlayerAction::Jump, KeyCode::KeyA);
+        assert_eq!(bindings.label(PlayerAction::Jump), "Jump: A");
+        assert_eq!(
+            bindings.label(PlayerAction::Left),
+            "Left: ArrowLeft, DPadLeft"
+        );
+
+        bindings.rebind_button(PlayerAction::Jump, GamepadButton::West);
+        assert_eq!(bindings.label(PlayerAction::Jump), "Jump: A, West");
+        assert_eq!(bindings.label(PlayerAction::Shoot), "Shoot: J");
+    }
+
+    #[test]
+    fn test_deadzone_is_cut_out_of_stick_tilt() {
+        assert_eq!(apply_deadzone(0.1, 0.25), 0.0);
+        assert_eq!(apply_deadzone(-0.25, 0.25), 0.0);
+        assert_eq!(apply_deadzone(0.625, 0.25), 0.5);
+        assert_eq!(apply_deadzone(-1.0, 0.25), -1.0);
+    }
+}
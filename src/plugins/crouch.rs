@@ -0,0 +1,235 @@
+use avian2d::prelude::{Collider, Sensor};
+use avian2d::spatial_query::SpatialQuery;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{bundles::player::Player, schedule::MovementSet};
+
+use super::{
+    character::CharacterController,
+    collision::{CollisionConfig, IsGrounded, Velocity, shape_cast},
+    player::PlayerAction,
+};
+
+/// Size and offset of a collider relative to its entity
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct Hitbox {
+    pub size: Vec2,
+    pub offset: Vec2,
+}
+
+impl Hitbox {
+    fn top(&self) -> f32 {
+        self.offset.y + self.size.y / 2.0
+    }
+}
+
+/// Ability to crouch under low ceilings, and to slide when crouching at speed
+#[derive(Component, Reflect)]
+pub struct Crouch {
+    pub standing: Hitbox,
+    pub crouching: Hitbox,
+    /// Walk speed while crouched, relative to the normal walk speed
+    pub walk_speed_multiplier: f32,
+    /// Crouching while moving at least this fast (pixels per second) starts a slide
+    pub slide_min_speed: f32,
+    /// Pixels per second squared
+    pub slide_deceleration: f32,
+}
+
+impl Crouch {
+    pub fn walk_speed(&self, walk_speed: f32) -> f32 {
+        walk_speed * self.walk_speed_multiplier
+    }
+}
+
+/// Present while the entity is crouched, using the shorter hitbox
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Crouching {
+    /// Keeping the momentum from before crouching, walk input is ignored
+    pub sliding: bool,
+}
+
+/// Crouch state for the next frame
+///
+/// Crouching starts on the ground only, and a crouched entity can't stand up while
+/// something blocks the space the standing hitbox needs. Slides end once they have
+/// slowed down to crouch walking speed.
+pub fn next_crouch_state(
+    crouching: Option<Crouching>,
+    crouch_pressed: bool,
+    grounded: bool,
+    speed_x: f32,
+    crouch_walk_speed: f32,
+    slide_min_speed: f32,
+    blocked_above: bool,
+) -> Option<Crouching> {
+    match crouching {
+        None if crouch_pressed && grounded => Some(Crouching {
+            sliding: speed_x.abs() >= slide_min_speed,
+        }),
+        None => None,
+        Some(_) if !crouch_pressed && !blocked_above => None,
+        Some(current) => Some(Crouching {
+            sliding: current.sliding && speed_x.abs() > crouch_walk_speed,
+        }),
+    }
+}
+
+/// Speed after sliding for `delta_secs`, never reversing direction
+fn slide_velocity_x(velocity_x: f32, deceleration: f32, delta_secs: f32) -> f32 {
+    velocity_x.signum() * (velocity_x.abs() - deceleration * delta_secs).max(0.0)
+}
+
+pub fn update_crouch(
+    mut commands: Commands,
+    spatial_query: SpatialQuery,
+    mut player_query: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &Crouch,
+            Option<&Crouching>,
+            &IsGrounded,
+            &CharacterController,
+            &CollisionConfig,
+            &mut Velocity,
+            &Transform,
+            &Children,
+        ),
+        (With<Player>, Without<Collider>),
+    >,
+    // Sensors like attack hitboxes keep their own shape
+    mut collider_query: Query<(&mut Collider, &mut Transform), Without<Sensor>>,
+    time: Res<Time>,
+) {
+    for (
+        entity,
+        action_state,
+        crouch,
+        crouching,
+        is_grounded,
+        controller,
+        config,
+        mut velocity,
+        transform,
+        children,
+    ) in player_query.iter_mut()
+    {
+        // Room to stand up in, checked by moving the crouched hitbox up to the standing height
+        let blocked_above = crouching.is_some() && {
+            let crouched = Collider::rectangle(crouch.crouching.size.x, crouch.crouching.size.y);
+            shape_cast(
+                &spatial_query,
+                transform.translation.xy() + crouch.crouching.offset,
+                Vec2::Y,
+                crouch.standing.top() - crouch.crouching.top(),
+                &crouched,
+                &config.collision_filter,
+            )
+            .is_some()
+        };
+
+        let next = next_crouch_state(
+            crouching.copied(),
+            action_state.pressed(&PlayerAction::Crouch),
+            is_grounded.grounded,
+            velocity.0.x,
+            crouch.walk_speed(controller.walk_speed),
+            crouch.slide_min_speed,
+            blocked_above,
+        );
+
+        if next.is_some() != crouching.is_some() {
+            let hitbox = if next.is_some() {
+                crouch.crouching
+            } else {
+                crouch.standing
+            };
+            for child in children.iter() {
+                if let Ok((mut collider, mut collider_transform)) = collider_query.get_mut(child) {
+                    *collider = Collider::rectangle(hitbox.size.x, hitbox.size.y);
+                    collider_transform.translation = hitbox.offset.extend(0.0);
+                }
+            }
+        }
+
+        match next {
+            Some(next) => {
+                if next.sliding {
+                    velocity.0.x = slide_velocity_x(
+                        velocity.0.x,
+                        crouch.slide_deceleration,
+                        time.delta_secs(),
+                    );
+                }
+                if crouching != Some(&next) {
+                    commands.entity(entity).insert(next);
+                }
+            }
+            None => {
+                if crouching.is_some() {
+                    commands.entity(entity).remove::<Crouching>();
+                }
+            }
+        }
+    }
+}
+
+pub struct CrouchPlugin;
+
+impl Plugin for CrouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            update_crouch
+                .after(MovementSet::Gravity)
+                .before(MovementSet::CollisionChecks),
+        )
+        .register_type::<Crouch>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crouch_state_transitions() {
+        let crouch_walk_speed = 80.0;
+        let slide_min_speed = 120.0;
+        let next = |crouching, pressed, grounded, speed_x, blocked_above| {
+            next_crouch_state(
+                crouching,
+                pressed,
+                grounded,
+                speed_x,
+                crouch_walk_speed,
+                slide_min_speed,
+                blocked_above,
+            )
+        };
+        let crouched = Some(Crouching { sliding: false });
+        let sliding = Some(Crouching { sliding: true });
+
+        // Only from the ground, sliding when already running
+        assert_eq!(next(None, true, false, 0.0, false), None);
+        assert_eq!(next(None, true, true, 0.0, false), crouched);
+        assert_eq!(next(None, true, true, -160.0, false), sliding);
+
+        // Slide keeps going until slowed down to crouch walking
+        assert_eq!(next(sliding, true, true, 100.0, false), sliding);
+        assert_eq!(next(sliding, true, true, 80.0, false), crouched);
+
+        // Can't stand up under a low ceiling
+        assert_eq!(next(crouched, false, true, 0.0, true), crouched);
+        assert_eq!(next(crouched, false, true, 0.0, false), None);
+    }
+
+    #[test]
+    fn test_slide_slows_down_without_reversing() {
+        assert_eq!(slide_velocity_x(200.0, 400.0, 0.25), 100.0);
+        assert_eq!(slide_velocity_x(-200.0, 400.0, 0.25), -100.0);
+        assert_eq!(slide_velocity_x(50.0, 400.0, 0.25), 0.0);
+    }
+}
@@ -0,0 +1,272 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{bundles::player::Player, components::GameEntity, schedule::MovementSet};
+
+use super::{
+    abilities::PlayerAbilities,
+    collision::{IgnoreSpeedCap, IsTouchingWallLeft, IsTouchingWallRight, Velocity},
+    gravity::EntityGravity,
+    player::PlayerAction,
+};
+
+/// Ability to dash a fixed distance in the facing direction
+#[derive(Component, Reflect)]
+pub struct Dash {
+    /// Distance covered by a full dash in pixels
+    pub distance: f32,
+    pub duration: Duration,
+    /// Time from the end of a dash until the next one can start
+    pub cooldown: Duration,
+}
+
+impl Dash {
+    /// Horizontal speed during the dash, in pixels per second
+    pub fn speed(&self) -> f32 {
+        self.distance / self.duration.as_secs_f32()
+    }
+}
+
+/// Present while a dash is in progress
+#[derive(Component)]
+pub struct Dashing {
+    /// -1.0 for left, 1.0 for right
+    pub direction: f32,
+    pub timer: Timer,
+    ghost_timer: Timer,
+}
+
+#[derive(Component, Default)]
+pub struct DashCooldownTimer(pub Timer);
+
+/// Fading afterimage left behind while dashing
+#[derive(Component)]
+struct DashGhost(Timer);
+
+const GHOST_INTERVAL: Duration = Duration::from_millis(30);
+const GHOST_LIFETIME: Duration = Duration::from_millis(200);
+const GHOST_ALPHA: f32 = 0.5;
+
+fn start_dash(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &Dash,
+            &mut DashCooldownTimer,
+            &Sprite,
+        ),
+        (With<Player>, Without<Dashing>),
+    >,
+    abilities: Res<PlayerAbilities>,
+    time: Res<Time>,
+) {
+    for (entity, action_state, dash, mut cooldown_timer, sprite) in query.iter_mut() {
+        cooldown_timer.0.tick(time.delta());
+        if !abilities.dash
+            || !action_state.just_pressed(&PlayerAction::Dash)
+            || !cooldown_timer.0.finished()
+        {
+            continue;
+        }
+
+        commands.entity(entity).insert((
+            Dashing {
+                direction: if sprite.flip_x { -1.0 } else { 1.0 },
+                timer: Timer::new(dash.duration, TimerMode::Once),
+                ghost_timer: Timer::new(GHOST_INTERVAL, TimerMode::Repeating),
+            },
+            IgnoreSpeedCap,
+        ));
+    }
+}
+
+/// Force the dash velocity with gravity off, until the dash runs out or hits a wall
+pub fn update_dash(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &Dash,
+        &mut Dashing,
+        &mut DashCooldownTimer,
+        &mut Velocity,
+        &mut EntityGravity,
+        &IsTouchingWallLeft,
+        &IsTouchingWallRight,
+    )>,
+    time: Res<Time>,
+) {
+    for (
+        entity,
+        dash,
+        mut dashing,
+        mut cooldown_timer,
+        mut velocity,
+        mut entity_gravity,
+        is_touching_wall_left,
+        is_touching_wall_right,
+    ) in query.iter_mut()
+    {
+        dashing.timer.tick(time.delta());
+
+        let blocked = if dashing.direction < 0.0 {
+            is_touching_wall_left.0
+        } else {
+            is_touching_wall_right.0
+        };
+
+        if dashing.timer.finished() || blocked {
+            entity_gravity.enabled = true;
+            if blocked {
+                velocity.0.x = 0.0;
+            }
+            cooldown_timer.0 = Timer::new(dash.cooldown, TimerMode::Once);
+            commands
+                .entity(entity)
+                .remove::<(Dashing, IgnoreSpeedCap)>();
+            continue;
+        }
+
+        entity_gravity.enabled = false;
+        velocity.0 = Vec2::new(dashing.direction * dash.speed(), 0.0);
+    }
+}
+
+fn spawn_dash_ghosts(
+    mut commands: Commands,
+    mut query: Query<(&mut Dashing, &Sprite, &Transform)>,
+    time: Res<Time>,
+) {
+    for (mut dashing, sprite, transform) in query.iter_mut() {
+        dashing.ghost_timer.tick(time.delta());
+        if !dashing.ghost_timer.just_finished() {
+            continue;
+        }
+
+        commands.spawn((
+            DashGhost(Timer::new(GHOST_LIFETIME, TimerMode::Once)),
+            Sprite {
+                image: sprite.image.clone(),
+                texture_atlas: sprite.texture_atlas.clone(),
+                flip_x: sprite.flip_x,
+                color: sprite.color.with_alpha(GHOST_ALPHA),
+                ..default()
+            },
+            transform.with_translation(transform.translation - Vec3::Z * 0.1),
+            GameEntity,
+        ));
+    }
+}
+
+fn fade_dash_ghosts(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut DashGhost, &mut Sprite)>,
+    time: Res<Time>,
+) {
+    for (entity, mut ghost, mut sprite) in query.iter_mut() {
+        ghost.0.tick(time.delta());
+        if ghost.0.finished() {
+            commands.entity(entity).despawn();
+        } else {
+            let alpha = GHOST_ALPHA * ghost.0.fraction_remaining();
+            sprite.color.set_alpha(alpha);
+        }
+    }
+}
+
+pub struct DashPlugin;
+
+impl Plugin for DashPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (start_dash, update_dash)
+                .chain()
+                .after(MovementSet::Gravity)
+                .before(MovementSet::CollisionChecks),
+        )
+        .add_systems(Update, (spawn_dash_ghosts, fade_dash_ghosts))
+        .register_type::<Dash>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn spawn_dashing(world: &mut World) -> Entity {
+        world
+            .spawn((
+                Dash {
+                    distance: 80.0,
+                    duration: Duration::from_millis(200),
+                    cooldown: Duration::from_millis(500),
+                },
+                Dashing {
+                    direction: -1.0,
+                    timer: Timer::new(Duration::from_millis(200), TimerMode::Once),
+                    ghost_timer: Timer::new(GHOST_INTERVAL, TimerMode::Repeating),
+                },
+                DashCooldownTimer::default(),
+                IgnoreSpeedCap,
+                Velocity(Vec2::new(0.0, -100.0)),
+                EntityGravity {
+                    gravity: 480.0,
+                    max_fall_speed: 240.0,
+                    enabled: true,
+                },
+                IsTouchingWallLeft(false),
+                IsTouchingWallRight(false),
+            ))
+            .id()
+    }
+
+    fn advance(world: &mut World, millis: u64) {
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(millis));
+    }
+
+    #[test]
+    fn test_dash_forces_velocity_without_gravity() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        let entity = spawn_dashing(&mut world);
+
+        advance(&mut world, 50);
+        world.run_system_once(update_dash).unwrap();
+
+        assert_eq!(
+            world.get::<Velocity>(entity).unwrap().0,
+            Vec2::new(-400.0, 0.0)
+        );
+        assert!(!world.get::<EntityGravity>(entity).unwrap().enabled);
+
+        // Dash ends when its time is up
+        advance(&mut world, 200);
+        world.run_system_once(update_dash).unwrap();
+
+        assert!(world.get::<Dashing>(entity).is_none());
+        assert!(world.get::<IgnoreSpeedCap>(entity).is_none());
+        assert!(world.get::<EntityGravity>(entity).unwrap().enabled);
+    }
+
+    #[test]
+    fn test_wall_stops_dash_early() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        let entity = spawn_dashing(&mut world);
+
+        world.get_mut::<IsTouchingWallLeft>(entity).unwrap().0 = true;
+        advance(&mut world, 16);
+        world.run_system_once(update_dash).unwrap();
+
+        assert!(world.get::<Dashing>(entity).is_none());
+        assert_eq!(world.get::<Velocity>(entity).unwrap().0.x, 0.0);
+    }
+}
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+use crate::bundles::player::Player;
+
+use super::{
+    character::CharacterController,
+    collision::{CollisionDiagnostics, CollisionDiagnosticsConfig, GroundedStopwatch, Velocity},
+};
+
+/// Surfaces the entity is touching, e.g. "ground, right wall", or "nothing"
+fn touching_label(diagnostics: &CollisionDiagnostics) -> String {
+    let touching: Vec<&str> = [
+        (diagnostics.grounded, "ground"),
+        (diagnostics.touching_wall_left, "left wall"),
+        (diagnostics.touching_wall_right, "right wall"),
+        (diagnostics.touching_ceiling, "ceiling"),
+    ]
+    .into_iter()
+    .filter_map(|(touching, name)| touching.then_some(name))
+    .collect();
+
+    if touching.is_empty() {
+        "nothing".to_string()
+    } else {
+        touching.join(", ")
+    }
+}
+
+/// Window with the player's collision state, shown while collision diagnostics are on
+fn collision_overlay_ui(
+    mut contexts: EguiContexts,
+    config: Res<CollisionDiagnosticsConfig>,
+    query: Query<
+        (
+            &CollisionDiagnostics,
+            &Velocity,
+            Option<&GroundedStopwatch>,
+            Option<&CharacterController>,
+        ),
+        With<Player>,
+    >,
+) -> Result {
+    if !config.enabled {
+        return Ok(());
+    }
+    let Ok((diagnostics, velocity, grounded_stopwatch, controller)) = query.single() else {
+        return Ok(());
+    };
+
+    egui::Window::new("Collision")
+        .default_width(260.0)
+        .show(contexts.ctx_mut()?, |ui| {
+            ui.monospace(format!("touching: {}", touching_label(diagnostics)));
+            ui.monospace(format!(
+                "velocity: {:.1}, {:.1}",
+                velocity.0.x, velocity.0.y
+            ));
+            if let (Some(stopwatch), Some(controller)) = (grounded_stopwatch, controller) {
+                let left = controller.coyote_time.saturating_sub(stopwatch.0.elapsed());
+                ui.monospace(format!(
+                    "coyote: {:.2} / {:.2} s",
+                    left.as_secs_f32(),
+                    controller.coyote_time.as_secs_f32()
+                ));
+            }
+            ui.monospace(format!(
+                "moved: {:.2}, {:.2} of {:.2}, {:.2}",
+                diagnostics.actual.x,
+                diagnostics.actual.y,
+                diagnostics.intended.x,
+                diagnostics.intended.y
+            ));
+            ui.monospace(format!(
+                "blocked: {}{}",
+                if diagnostics.blocked_x { "x " } else { "" },
+                if diagnostics.blocked_y { "y" } else { "" }
+            ));
+
+            ui.separator();
+            if diagnostics.hit_points.is_empty() {
+                ui.monospace("no hits");
+            }
+            for (point, distance) in diagnostics
+                .hit_points
+                .iter()
+                .zip(&diagnostics.hit_distances)
+            {
+                ui.monospace(format!(
+                    "hit at {:.1}, {:.1} after {:.2}",
+                    point.x, point.y, distance
+                ));
+            }
+        });
+
+    Ok(())
+}
+
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(EguiPrimaryContextPass, collision_overlay_ui);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_touching_label_lists_surfaces() {
+        let mut diagnostics = CollisionDiagnostics::default();
+        assert_eq!(touching_label(&diagnostics), "nothing");
+
+        diagnostics.grounded = true;
+        diagnostics.touching_wall_right = true;
+        assert_eq!(touching_label(&diagnostics), "ground, right wall");
+    }
+}
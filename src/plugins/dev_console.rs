@@ -0,0 +1,399 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::bundles::player::Player;
+
+use super::{
+    collision::Velocity,
+    health::Health,
+    player::{GodMode, PlayerAction},
+    projectile::Weapon,
+    time_control::{MAX_TIME_SCALE, MIN_TIME_SCALE, TimeControl},
+};
+
+const DEV_CONSOLE_TOGGLE_KEY: KeyCode = KeyCode::Backquote;
+
+/// Runs a console command with the arguments after its name, returning the line to print
+pub type ConsoleCommandFn = fn(&mut World, &[&str]) -> Result<String, String>;
+
+#[derive(Clone, Copy)]
+pub struct ConsoleCommand {
+    /// Arguments the command takes, shown by `help`, e.g. "<x> <y>"
+    pub usage: &'static str,
+    pub run: ConsoleCommandFn,
+}
+
+/// Commands the console knows, by name
+///
+/// Plugins add their own with `App::add_console_command`.
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(BTreeMap<String, ConsoleCommand>);
+
+impl ConsoleCommands {
+    pub fn insert(&mut self, name: &str, usage: &'static str, run: ConsoleCommandFn) {
+        self.0
+            .insert(name.to_lowercase(), ConsoleCommand { usage, run });
+    }
+
+    /// Split a console line into its command's name, the command and its arguments
+    pub fn parse<'a>(
+        &self,
+        line: &'a str,
+    ) -> Result<(String, ConsoleCommand, Vec<&'a str>), String> {
+        let mut tokens = line.split_whitespace();
+        let Some(name) = tokens.next() else {
+            return Err("empty command".to_string());
+        };
+        let name = name.to_lowercase();
+        let Some(command) = self.0.get(&name) else {
+            return Err(format!("unknown command '{}', try 'help'", name));
+        };
+        Ok((name, *command, tokens.collect()))
+    }
+}
+
+pub trait ConsoleCommandsAppExt {
+    fn add_console_command(
+        &mut self,
+        name: &str,
+        usage: &'static str,
+        run: ConsoleCommandFn,
+    ) -> &mut Self;
+}
+
+impl ConsoleCommandsAppExt for App {
+    fn add_console_command(
+        &mut self,
+        name: &str,
+        usage: &'static str,
+        run: ConsoleCommandFn,
+    ) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<ConsoleCommands>()
+            .insert(name, usage, run);
+        self
+    }
+}
+
+/// Fail on arguments past the first `count`
+pub fn expect_args(args: &[&str], count: usize) -> Result<(), String> {
+    match args.get(count) {
+        Some(extra) => Err(format!("unexpected argument '{}'", extra)),
+        None => Ok(()),
+    }
+}
+
+/// Parse argument `index`, described as `name` in errors
+pub fn parse_arg<T: FromStr>(args: &[&str], index: usize, name: &str) -> Result<T, String> {
+    let value = args.get(index).ok_or_else(|| format!("missing {}", name))?;
+    value
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid {}", value, name))
+}
+
+/// The player, for commands acting on it
+pub fn console_player(world: &mut World) -> Result<Entity, String> {
+    world
+        .query_filtered::<Entity, With<Player>>()
+        .single(world)
+        .map_err(|_| "no player to apply the command to".to_string())
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct DevCommandEvent(pub String);
+
+#[derive(Resource, Default)]
+pub struct DevConsole {
+    pub open: bool,
+    pub input: String,
+    pub log: Vec<String>,
+}
+
+impl DevConsole {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+    }
+}
+
+fn help(world: &mut World, args: &[&str]) -> Result<String, String> {
+    expect_args(args, 0)?;
+    let commands = world.resource::<ConsoleCommands>();
+    Ok(commands
+        .0
+        .iter()
+        .map(|(name, command)| format!("{} {}", name, command.usage).trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn teleport(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let x: f32 = parse_arg(args, 0, "x coordinate")?;
+    let y: f32 = parse_arg(args, 1, "y coordinate")?;
+    expect_args(args, 2)?;
+
+    let player = console_player(world)?;
+    let mut entity = world.entity_mut(player);
+    if let Some(mut transform) = entity.get_mut::<Transform>() {
+        transform.translation.x = x;
+        transform.translation.y = y;
+    }
+    if let Some(mut velocity) = entity.get_mut::<Velocity>() {
+        velocity.0 = Vec2::ZERO;
+    }
+    Ok(format!("teleported to {}, {}", x, y))
+}
+
+fn toggle_god_mode(world: &mut World, args: &[&str]) -> Result<String, String> {
+    expect_args(args, 0)?;
+    let player = console_player(world)?;
+    let mut entity = world.entity_mut(player);
+    if entity.contains::<GodMode>() {
+        entity.remove::<GodMode>();
+        Ok("godmode off".to_string())
+    } else {
+        entity.insert(GodMode);
+        Ok("godmode on".to_string())
+    }
+}
+
+fn give(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let item: String = parse_arg(args, 0, "item, e.g. 'give health'")?;
+    expect_args(args, 1)?;
+
+    let player = console_player(world)?;
+    let mut entity = world.entity_mut(player);
+    match item.to_lowercase().as_str() {
+        "health" => {
+            let mut health = entity
+                .get_mut::<Health>()
+                .ok_or("the player has no health")?;
+            health.current = health.max;
+            Ok(format!("health restored to {}", health.max))
+        }
+        "pistol" => {
+            entity.insert(Weapon::pistol());
+            Ok("gave pistol".to_string())
+        }
+        "shotgun" => {
+            entity.insert(Weapon::shotgun());
+            Ok("gave shotgun".to_string())
+        }
+        "grenade" => {
+            entity.insert(Weapon::grenade());
+            Ok("gave grenade".to_string())
+        }
+        other => Err(format!("unknown item '{}'", other)),
+    }
+}
+
+fn set_time_scale(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let scale: f32 = parse_arg(args, 0, "time scale")?;
+    expect_args(args, 1)?;
+    if !(MIN_TIME_SCALE..=MAX_TIME_SCALE).contains(&scale) {
+        return Err(format!(
+            "time scale has to be between {} and {}",
+            MIN_TIME_SCALE, MAX_TIME_SCALE
+        ));
+    }
+    world.resource_mut::<TimeControl>().set_scale(scale);
+    Ok(format!("time scale {}", scale))
+}
+
+fn toggle_dev_console(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut console: ResMut<DevConsole>,
+    mut action_states: Query<&mut ActionState<PlayerAction>, With<Player>>,
+) {
+    if !keys.just_pressed(DEV_CONSOLE_TOGGLE_KEY) {
+        return;
+    }
+
+    console.open = !console.open;
+
+    // Don't let typing in the console move the player around
+    for mut action_state in action_states.iter_mut() {
+        if console.open {
+            action_state.disable_all();
+        } else {
+            action_state.enable_all();
+        }
+    }
+}
+
+fn dev_console_ui(
+    mut contexts: EguiContexts,
+    mut console: ResMut<DevConsole>,
+    mut event_writer: EventWriter<DevCommandEvent>,
+    keys: Res<ButtonInput<KeyCode>>,
+) -> Result {
+    if !console.open {
+        return Ok(());
+    }
+
+    let ctx = contexts.ctx_mut()?;
+    // The key press opening the console isn't meant to be typed into it
+    if keys.just_pressed(DEV_CONSOLE_TOGGLE_KEY) {
+        ctx.input_mut(|input| {
+            input
+                .events
+                .retain(|event| !matches!(event, egui::Event::Text(_)))
+        });
+    }
+
+    let mut submitted = None;
+
+    egui::Window::new("Console")
+        .default_width(400.0)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in &console.log {
+                        ui.monospace(line);
+                    }
+                });
+
+            let response = ui.text_edit_singleline(&mut console.input);
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                submitted = Some(std::mem::take(&mut console.input));
+            }
+            response.request_focus();
+        });
+
+    if let Some(line) = submitted {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        console.print(format!("> {}", line));
+        event_writer.write(DevCommandEvent(line));
+    }
+
+    Ok(())
+}
+
+/// Run the submitted console lines, printing what they return
+fn run_dev_commands(world: &mut World) {
+    let lines: Vec<String> = world
+        .resource_mut::<Events<DevCommandEvent>>()
+        .drain()
+        .map(|DevCommandEvent(line)| line)
+        .collect();
+
+    for line in lines {
+        let output = match world.resource::<ConsoleCommands>().parse(&line) {
+            Ok((name, command, args)) => {
+                (command.run)(world, &args).map_err(|error| format!("{}: {}", name, error))
+            }
+            Err(error) => Err(error),
+        };
+        let mut console = world.resource_mut::<DevConsole>();
+        match output {
+            Ok(output) => {
+                for output_line in output.lines() {
+                    console.print(output_line);
+                }
+            }
+            Err(error) => console.print(format!("error: {}", error)),
+        }
+    }
+}
+
+pub struct DevConsolePlugin;
+
+impl Plugin for DevConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DevConsole>()
+            .init_resource::<ConsoleCommands>()
+            .add_event::<DevCommandEvent>()
+            .add_console_command("help", "", help)
+            .add_console_command("tp", "<x> <y>", teleport)
+            .add_console_command("godmode", "", toggle_god_mode)
+            .add_console_command("give", "<health|pistol|shotgun|grenade>", give)
+            .add_console_command("timescale", "<scale>", set_time_scale)
+            .add_systems(Update, (toggle_dev_console, run_dev_commands))
+            .add_systems(EguiPrimaryContextPass, dev_console_ui);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_world() -> (World, Entity) {
+        let mut world = World::new();
+        let mut commands = ConsoleCommands::default();
+        commands.insert("tp", "<x> <y>", teleport);
+        commands.insert("godmode", "", toggle_god_mode);
+        commands.insert("give", "<health|pistol|shotgun|grenade>", give);
+        world.insert_resource(commands);
+        let player = world
+            .spawn((
+                Player,
+                Transform::default(),
+                Velocity(Vec2::new(10.0, 10.0)),
+                Health { current: 1, max: 3 },
+            ))
+            .id();
+        (world, player)
+    }
+
+    fn run(world: &mut World, line: &str) -> Result<String, String> {
+        let (_, command, args) = world.resource::<ConsoleCommands>().parse(line)?;
+        (command.run)(world, &args)
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let (world, _) = test_world();
+        let commands = world.resource::<ConsoleCommands>();
+
+        let (name, _, args) = commands.parse("  GODMODE ").unwrap();
+        assert_eq!(name, "godmode");
+        assert!(args.is_empty());
+
+        let (name, _, args) = commands.parse("tp 12.5 -40").unwrap();
+        assert_eq!(name, "tp");
+        assert_eq!(args, vec!["12.5", "-40"]);
+
+        assert!(commands.parse("").is_err());
+        assert!(commands.parse("fly").is_err());
+    }
+
+    #[test]
+    fn test_run_commands() {
+        let (mut world, player) = test_world();
+
+        assert!(run(&mut world, "tp 12.5 -40").is_ok());
+        assert_eq!(
+            world.get::<Transform>(player).unwrap().translation.xy(),
+            Vec2::new(12.5, -40.0)
+        );
+        assert_eq!(world.get::<Velocity>(player).unwrap().0, Vec2::ZERO);
+
+        assert!(run(&mut world, "give health").is_ok());
+        assert_eq!(world.get::<Health>(player).unwrap().current, 3);
+
+        assert!(run(&mut world, "give grenade").is_ok());
+        let weapon = world.get::<Weapon>(player).unwrap();
+        assert!(weapon.arcing_shot.is_some());
+
+        assert!(run(&mut world, "godmode").is_ok());
+        assert!(world.get::<GodMode>(player).is_some());
+    }
+
+    #[test]
+    fn test_command_errors() {
+        let (mut world, player) = test_world();
+
+        assert!(run(&mut world, "tp 10").is_err());
+        assert!(run(&mut world, "tp ten 10").is_err());
+        assert!(run(&mut world, "give").is_err());
+        assert!(run(&mut world, "give dragon").is_err());
+        assert!(run(&mut world, "godmode please").is_err());
+        assert!(world.get::<GodMode>(player).is_none());
+    }
+}
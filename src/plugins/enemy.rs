@@ -0,0 +1,897 @@
+use std::{collections::HashSet, time::Duration};
+
+use avian2d::prelude::*;
+use bevy::{prelude::*, time::Stopwatch};
+use ldtk_rust::EntityInstance;
+
+use crate::{
+    bundles::player::Player,
+    components::{GameEntity, LevelEntity, LevelId},
+    constants::{
+        self, GameLayer, TILE_SIZE, TilesPerSecond, TilesPerSecondSquared, multiply_by_tile_size,
+    },
+    entity_fields::BatFields,
+    schedule::MovementSet,
+};
+
+use super::{
+    ai::{AiBehaviour, AiState, AiStateTime, AiTarget, ai_animation_configs, update_ai_state},
+    animation_library::AnimationLibrary,
+    boss::{Boss, BossDefinitions, BossKind, BossManifest},
+    character::CharacterController,
+    collision::{
+        CollisionBundle, CollisionConfig, DEFAULT_MAX_SLOPE_ANGLE, GroundedStopwatch, IsGrounded,
+        IsTouchingWallLeft, IsTouchingWallRight, Velocity, child_collider_aabb, shape_cast,
+    },
+    dev_console::{ConsoleCommandsAppExt, console_player, expect_args, parse_arg},
+    gravity::EntityGravity,
+    health::{ContactDamage, DamageEvent, Health, apply_damage},
+    level::{CurrentLevel, LdtkEntityAppExt, LdtkEntityContext, entity_world_rect},
+    projectile::{Damage, Projectile, ProjectileOwner, ProjectileVelocity},
+    slice_collider::{HITBOX_SLICE, HURTBOX_SLICE, SliceCollider, update_slice_colliders},
+    spawner::SpawnedEnemies,
+};
+
+/// The different enemy prefabs that can be spawned
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum EnemyKind {
+    #[default]
+    Walker,
+    Bat,
+    /// Placed inside a boss arena rather than spawned on its own
+    Boss(BossKind),
+}
+
+impl EnemyKind {
+    /// Look up a prefab by the name used in the level editor
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "walker" => Some(Self::Walker),
+            "bat" => Some(Self::Bat),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Enemy {
+    pub kind: EnemyKind,
+}
+
+/// Sensor following the attack hitbox slice of the enemy's current frame, hurting the player
+/// like touching the enemy does on the frames that have one
+#[derive(Component)]
+pub struct EnemyHitbox;
+
+/// Walks back and forth at its `CharacterController` walk speed, turning around at walls and
+/// ledges
+#[derive(Component, Reflect)]
+#[require(CharacterController)]
+pub struct Patrol {
+    /// 1.0 when walking right, -1.0 when walking left
+    pub direction: f32,
+}
+
+/// Tuning for flying enemies, set per instance in the level editor
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct FlightConfig {
+    /// Height of the wave from the middle to the top, in pixels
+    pub amplitude: f32,
+    /// Full waves per second
+    pub frequency: f32,
+    /// Distance in pixels at which the player is noticed and chased
+    pub aggro_range: f32,
+}
+
+impl Default for FlightConfig {
+    fn default() -> Self {
+        Self {
+            amplitude: multiply_by_tile_size(1),
+            frequency: 0.5,
+            aggro_range: multiply_by_tile_size(8),
+        }
+    }
+}
+
+/// Bobs up and down while flying, ignoring gravity
+#[derive(Component, Reflect)]
+pub struct SineWave {
+    pub amplitude: f32,
+    pub frequency: f32,
+    /// Seconds into the wave
+    pub elapsed: f32,
+}
+
+impl SineWave {
+    /// Vertical speed that keeps the entity on the wave, i.e. the derivative of its height
+    fn velocity_y(&self) -> f32 {
+        let angular_frequency = std::f32::consts::TAU * self.frequency;
+        self.amplitude * angular_frequency * (angular_frequency * self.elapsed).cos()
+    }
+}
+
+/// Chases the player once they come within range and in sight
+#[derive(Component, Reflect)]
+pub struct Aggro {
+    /// Pixels
+    pub range: f32,
+    /// Pixels per second
+    pub chase_speed: f32,
+}
+
+const WALKER_SPEED: TilesPerSecond = TilesPerSecond(3.0);
+const BAT_SPEED: TilesPerSecond = TilesPerSecond(2.0);
+const BAT_CHASE_SPEED: TilesPerSecond = TilesPerSecond(5.0);
+const HURT_DURATION: Duration = Duration::from_millis(300);
+/// Speed an enemy gets pushed back with when shot
+const HIT_KNOCKBACK: TilesPerSecond = TilesPerSecond(6.0);
+/// How quickly the knockback wears off while hurt
+const HURT_DECELERATION: TilesPerSecondSquared = TilesPerSecondSquared(30.0);
+/// Time the death animation gets to play before the enemy is removed
+const DEATH_DURATION: Duration = Duration::from_millis(500);
+
+/// The spawner an enemy came from
+#[derive(Component)]
+#[relationship(relationship_target = SpawnedEnemies)]
+pub struct SpawnedBy(pub Entity);
+
+#[derive(Event, Clone)]
+pub struct EnemySpawnEvent {
+    pub kind: EnemyKind,
+    pub transform: Transform,
+    pub spawned_by: Option<Entity>,
+    /// Level the enemy unloads along with, enemies from a spawner go along with it instead
+    pub level_id: Option<LevelId>,
+    /// Used by flying enemies, which fall back to the default tuning without it
+    pub flight: Option<FlightConfig>,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct EnemyDiedEvent {
+    pub enemy: Entity,
+    pub kind: EnemyKind,
+    pub spawned_by: Option<Entity>,
+}
+
+pub fn spawn_enemy(
+    mut commands: Commands,
+    mut event_reader: EventReader<EnemySpawnEvent>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    animation_library: Res<AnimationLibrary>,
+    boss_definitions: Res<BossDefinitions>,
+    boss_manifests: Res<Assets<BossManifest>>,
+) {
+    for event in event_reader.read() {
+        let mut enemy = commands.spawn((
+            Enemy { kind: event.kind },
+            event.transform,
+            RigidBody::Kinematic,
+            LockedAxes::ROTATION_LOCKED,
+            CollisionBundle {
+                grounded_stopwatch: GroundedStopwatch(Stopwatch::new()),
+                config: CollisionConfig {
+                    ground_check_distance: 1.0,
+                    wall_check_distance: 1.0,
+                    ceiling_check_distance: 1.0,
+                    max_slope_angle: DEFAULT_MAX_SLOPE_ANGLE,
+                    max_step_height: 0.0,
+                    corner_correction: 0.0,
+                    collision_filter: SpatialQueryFilter::from_mask(
+                        GameLayer::LevelGeometry.to_bits(),
+                    ),
+                },
+                ..Default::default()
+            },
+            CollisionLayers::new(
+                GameLayer::Enemy,
+                [
+                    GameLayer::LevelGeometry,
+                    GameLayer::Default,
+                    GameLayer::PlayerProjectile,
+                ],
+            ),
+            ContactDamage {
+                amount: 1,
+                knockback: multiply_by_tile_size(15),
+            },
+            (
+                AiState::Patrol,
+                AiBehaviour {
+                    patrols: true,
+                    attack_range: None,
+                    hurt_duration: HURT_DURATION,
+                },
+                AiTarget::default(),
+                AiStateTime::default(),
+            ),
+            GameEntity,
+            LevelEntity,
+        ));
+
+        let (anim_name, move_tag, color, size) = match event.kind {
+            EnemyKind::Walker => {
+                enemy.insert((
+                    EntityGravity {
+                        gravity: multiply_by_tile_size(30),
+                        max_fall_speed: multiply_by_tile_size(15),
+                        enabled: true,
+                    },
+                    Health::new(2),
+                    CharacterController::new(WALKER_SPEED.to_pixels(), 0.0),
+                    Patrol { direction: -1.0 },
+                ));
+                (
+                    AnimationLibrary::ENEMY_WALKER,
+                    "walk",
+                    Color::srgb(0.8, 0.2, 0.2),
+                    Vec2::splat(multiply_by_tile_size(2)),
+                )
+            }
+            EnemyKind::Bat => {
+                let flight = event.flight.unwrap_or_default();
+                enemy.insert((
+                    Health::new(1),
+                    CharacterController::new(BAT_SPEED.to_pixels(), 0.0),
+                    Patrol { direction: -1.0 },
+                    SineWave {
+                        amplitude: flight.amplitude,
+                        frequency: flight.frequency,
+                        elapsed: 0.0,
+                    },
+                    Aggro {
+                        range: flight.aggro_range,
+                        chase_speed: BAT_CHASE_SPEED.to_pixels(),
+                    },
+                ));
+                (
+                    AnimationLibrary::ENEMY_BAT,
+                    "fly",
+                    Color::srgb(0.5, 0.2, 0.6),
+                    Vec2::splat(multiply_by_tile_size(1)),
+                )
+            }
+            EnemyKind::Boss(kind) => {
+                let Some(definition) = boss_definitions.get(kind, &boss_manifests) else {
+                    warn!("no definition for boss {:?}", kind);
+                    enemy.despawn();
+                    continue;
+                };
+                if let Err(error) = definition.validate() {
+                    warn!("invalid definition for boss {:?}: {}", kind, error);
+                    enemy.despawn();
+                    continue;
+                }
+                enemy.insert((
+                    EntityGravity {
+                        gravity: multiply_by_tile_size(30),
+                        max_fall_speed: multiply_by_tile_size(15),
+                        enabled: true,
+                    },
+                    Health::new(definition.health),
+                    ContactDamage {
+                        amount: definition.contact_damage,
+                        knockback: multiply_by_tile_size(20),
+                    },
+                    // Shrugs hits off, its attack pattern doesn't stop for them
+                    AiBehaviour {
+                        patrols: false,
+                        attack_range: None,
+                        hurt_duration: Duration::ZERO,
+                    },
+                    Boss::new(kind, definition.phases.clone()),
+                ));
+                (
+                    kind.animation(),
+                    "walk",
+                    definition.color(),
+                    definition.size(),
+                )
+            }
+        };
+
+        // The body doubles as the hurtbox, following its slice where the sprite sheet has one
+        enemy.with_child((
+            SliceCollider::new(anim_name, HURTBOX_SLICE).with_fallback(size, Vec2::ZERO),
+            Collider::rectangle(size.x, size.y),
+            Transform::default(),
+        ));
+        enemy.with_child((
+            EnemyHitbox,
+            SliceCollider::new(anim_name, HITBOX_SLICE),
+            Sensor,
+            Collider::rectangle(1.0, 1.0),
+            CollisionLayers::new(GameLayer::EnemyProjectile, [GameLayer::Player]),
+            Transform::default(),
+        ));
+
+        // Plain box until the sprite sheet has loaded
+        if let Some(anim_data) = animation_library.get(anim_name) {
+            enemy.insert(AnimationLibrary::create_animation_bundle(
+                anim_data,
+                &anim_data.image_path,
+                ai_animation_configs(move_tag),
+                AiState::Patrol,
+                &asset_server,
+                &mut texture_atlas_layouts,
+            ));
+        } else {
+            enemy.insert(Sprite {
+                color,
+                custom_size: Some(size),
+                ..default()
+            });
+        }
+
+        if let Some(spawner) = event.spawned_by {
+            enemy.insert(SpawnedBy(spawner));
+        }
+        if let Some(level_id) = &event.level_id {
+            enemy.insert(level_id.clone());
+        }
+    }
+}
+
+/// Direction to patrol in next, turning back when about to walk into a wall or off a ledge
+fn patrol_direction(
+    direction: f32,
+    wall_left: bool,
+    wall_right: bool,
+    grounded: bool,
+    ground_ahead: bool,
+) -> f32 {
+    let blocked = (direction < 0.0 && wall_left) || (direction > 0.0 && wall_right);
+    let at_ledge = grounded && !ground_ahead;
+    if blocked || at_ledge {
+        -direction
+    } else {
+        direction
+    }
+}
+
+pub fn patrol(
+    spatial_query: SpatialQuery,
+    mut query: Query<(
+        &mut Patrol,
+        &CharacterController,
+        &mut Velocity,
+        &IsGrounded,
+        &IsTouchingWallLeft,
+        &IsTouchingWallRight,
+        &CollisionConfig,
+        &Transform,
+        &Children,
+        Option<&mut Sprite>,
+        &AiState,
+    )>,
+    collider_query: Query<(&Collider, &Transform)>,
+    aabb_query: Query<&ColliderAabb>,
+) {
+    for (
+        mut patrol,
+        controller,
+        mut velocity,
+        is_grounded,
+        wall_left,
+        wall_right,
+        config,
+        transform,
+        children,
+        sprite,
+        state,
+    ) in query.iter_mut()
+    {
+        if *state != AiState::Patrol {
+            continue;
+        }
+
+        let Some((collider, collider_transform)) = children
+            .iter()
+            .find_map(|child| collider_query.get(child).ok())
+        else {
+            continue;
+        };
+        let Some(aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        // Same cast as the ground check, from one collider width further along
+        let width = aabb.size().x;
+        let ahead = transform.translation.xy()
+            + collider_transform.translation.xy()
+            + Vec2::X * patrol.direction * width;
+        let ground_ahead = shape_cast(
+            &spatial_query,
+            ahead,
+            Vec2::NEG_Y,
+            config.ground_check_distance,
+            collider,
+            &config.collision_filter,
+        )
+        .is_some_and(|hit| config.is_walkable(hit.normal1));
+
+        patrol.direction = patrol_direction(
+            patrol.direction,
+            wall_left.0,
+            wall_right.0,
+            is_grounded.grounded,
+            ground_ahead,
+        );
+        velocity.0.x = patrol.direction * controller.walk_speed;
+
+        if let Some(mut sprite) = sprite {
+            sprite.flip_x = patrol.direction < 0.0;
+        }
+    }
+}
+
+fn fly_sine_wave(mut query: Query<(&mut SineWave, &mut Velocity, &AiState)>, time: Res<Time>) {
+    for (mut sine_wave, mut velocity, state) in query.iter_mut() {
+        if !matches!(state, AiState::Idle | AiState::Patrol) {
+            continue;
+        }
+        sine_wave.elapsed += time.delta_secs();
+        velocity.0.y = sine_wave.velocity_y();
+    }
+}
+
+/// Target the nearest player in range with no level geometry in between
+fn sense_player(
+    spatial_query: SpatialQuery,
+    mut enemy_query: Query<(&Aggro, &Transform, &CollisionConfig, &mut AiTarget)>,
+    player_query: Query<&Transform, With<Player>>,
+) {
+    for (aggro, transform, config, mut target) in enemy_query.iter_mut() {
+        let position = transform.translation.xy();
+        target.0 = player_query
+            .iter()
+            .map(|player_transform| player_transform.translation.xy())
+            .filter(|player| {
+                let Ok((direction, distance)) = Dir2::new_and_length(player - position) else {
+                    return true;
+                };
+                distance <= aggro.range
+                    && spatial_query
+                        .cast_ray(
+                            position,
+                            direction,
+                            distance,
+                            true,
+                            &config.collision_filter,
+                        )
+                        .is_none()
+            })
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            });
+    }
+}
+
+fn chase_target(
+    mut enemy_query: Query<(
+        &Aggro,
+        &AiState,
+        &AiTarget,
+        &Transform,
+        &mut Velocity,
+        Option<&mut Sprite>,
+    )>,
+) {
+    for (aggro, state, target, transform, mut velocity, sprite) in enemy_query.iter_mut() {
+        let (AiState::Chase, Some(target)) = (state, target.0) else {
+            continue;
+        };
+
+        velocity.0 = (target - transform.translation.xy()).normalize_or_zero() * aggro.chase_speed;
+        if let Some(mut sprite) = sprite {
+            sprite.flip_x = velocity.0.x < 0.0;
+        }
+    }
+}
+
+/// Speed left after slowing down by `deceleration` for `delta_secs`, never reversing
+fn decelerate(velocity: Vec2, deceleration: f32, delta_secs: f32) -> Vec2 {
+    let speed = (velocity.length() - deceleration * delta_secs).max(0.0);
+    velocity.normalize_or_zero() * speed
+}
+
+/// Stop moving while attacking or dying, and slide to a stop from the knockback while
+/// hurt, leaving only falling
+fn hold_still(
+    mut query: Query<(&AiState, &mut Velocity, Has<EntityGravity>), With<Enemy>>,
+    time: Res<Time>,
+) {
+    for (state, mut velocity, has_gravity) in query.iter_mut() {
+        let target = match state {
+            AiState::Attack | AiState::Dead => Vec2::ZERO,
+            AiState::Hurt => {
+                decelerate(velocity.0, HURT_DECELERATION.to_pixels(), time.delta_secs())
+            }
+            _ => continue,
+        };
+        velocity.0.x = target.x;
+        if !has_gravity {
+            velocity.0.y = target.y;
+        }
+    }
+}
+
+/// Projectiles are used up on the first enemy they hit, knocking it back the way they flew
+fn projectile_hits_enemy(
+    mut commands: Commands,
+    projectile_query: Query<
+        (
+            Entity,
+            &ColliderAabb,
+            &Damage,
+            &ProjectileVelocity,
+            &CollisionLayers,
+            Option<&ProjectileOwner>,
+        ),
+        With<Projectile>,
+    >,
+    enemy_query: Query<(Entity, &Health, &Children), With<Enemy>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut event_writer: EventWriter<DamageEvent>,
+) {
+    let mut used_projectiles = HashSet::new();
+
+    for (entity, health, children) in enemy_query.iter() {
+        if health.is_dead() {
+            continue;
+        }
+        let Some(enemy_aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        let hit = projectile_query
+            .iter()
+            .find(|(projectile, aabb, _, _, layers, owner)| {
+                layers.filters.has_all(GameLayer::Enemy)
+                    && owner.is_none_or(|owner| owner.0 != entity)
+                    && !used_projectiles.contains(projectile)
+                    && aabb.intersects(&enemy_aabb)
+            });
+
+        if let Some((projectile, _, damage, velocity, _, _)) = hit {
+            used_projectiles.insert(projectile);
+            commands.entity(projectile).despawn();
+            event_writer.write(DamageEvent {
+                target: entity,
+                amount: damage.0,
+                knockback: velocity.0.normalize_or_zero() * HIT_KNOCKBACK.to_pixels(),
+            });
+        }
+    }
+}
+
+/// Attacks hurt the player on the frames their hitbox slice is keyed on
+fn enemy_hitbox_hits_player(
+    hitbox_query: Query<(&SliceCollider, &ColliderAabb, &ChildOf), With<EnemyHitbox>>,
+    enemy_query: Query<(&Health, &ContactDamage, &Transform), With<Enemy>>,
+    player_query: Query<(Entity, &Transform, &Children), With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut event_writer: EventWriter<DamageEvent>,
+) {
+    for (slice_collider, hitbox_aabb, child_of) in hitbox_query.iter() {
+        if !slice_collider.active {
+            continue;
+        }
+        let Ok((health, contact_damage, enemy_transform)) = enemy_query.get(child_of.parent())
+        else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+
+        for (player, player_transform, children) in player_query.iter() {
+            let touching = child_collider_aabb(children, &aabb_query)
+                .is_some_and(|aabb| aabb.intersects(hitbox_aabb));
+            if !touching {
+                continue;
+            }
+
+            let away = if player_transform.translation.x < enemy_transform.translation.x {
+                -1.0
+            } else {
+                1.0
+            };
+            event_writer.write(DamageEvent {
+                target: player,
+                amount: contact_damage.amount,
+                knockback: Vec2::new(away, 0.5).normalize() * contact_damage.knockback,
+            });
+        }
+    }
+}
+
+/// Remove enemies once their death animation has played
+fn despawn_dead_enemies(
+    mut commands: Commands,
+    query: Query<(Entity, &Enemy, &AiState, &AiStateTime, Option<&SpawnedBy>)>,
+    mut event_writer: EventWriter<EnemyDiedEvent>,
+) {
+    for (entity, enemy, state, time_in_state, spawned_by) in query.iter() {
+        if *state != AiState::Dead || time_in_state.0.elapsed() < DEATH_DURATION {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        event_writer.write(EnemyDiedEvent {
+            enemy: entity,
+            kind: enemy.kind,
+            spawned_by: spawned_by.map(|spawned_by| spawned_by.0),
+        });
+    }
+}
+
+fn spawn_enemy_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name: String = parse_arg(args, 0, "enemy, e.g. 'spawn walker'")?;
+    expect_args(args, 1)?;
+    let kind = EnemyKind::from_name(&name.to_lowercase())
+        .ok_or_else(|| format!("unknown enemy '{}'", name))?;
+
+    let player = console_player(world)?;
+    let player_position = world
+        .get::<Transform>(player)
+        .ok_or("the player has no position")?
+        .translation;
+    let level_id = LevelId(world.resource::<CurrentLevel>().0.clone());
+    world.send_event(EnemySpawnEvent {
+        kind,
+        transform: Transform::from_translation(
+            player_position + Vec3::X * multiply_by_tile_size(4),
+        ),
+        spawned_by: None,
+        level_id: Some(level_id),
+        flight: None,
+    });
+    Ok(format!("spawned {}", name))
+}
+
+/// Deal every enemy its remaining health as damage, so they die the usual way
+fn kill_all_enemies_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    expect_args(args, 0)?;
+    let damage: Vec<DamageEvent> = world
+        .query_filtered::<(Entity, &Health), With<Enemy>>()
+        .iter(world)
+        .filter(|(_, health)| !health.is_dead())
+        .map(|(target, health)| DamageEvent {
+            target,
+            amount: health.current,
+            knockback: Vec2::ZERO,
+        })
+        .collect();
+    let count = damage.len();
+    world.send_event_batch(damage);
+    Ok(format!("killed {} enemies", count))
+}
+
+/// Flight tuning from a bat entity, with the amplitude and aggro range given in tiles
+fn flight_config_from_entity(entity: &EntityInstance) -> Option<FlightConfig> {
+    let fields = BatFields::from_entity(entity)?;
+
+    Some(FlightConfig {
+        amplitude: fields.amplitude * TILE_SIZE,
+        frequency: fields.frequency,
+        aggro_range: fields.aggro_range * TILE_SIZE,
+    })
+}
+
+fn spawn_ldtk_walker(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    let (center, _) = entity_world_rect(entity);
+    commands.send_event(EnemySpawnEvent {
+        kind: EnemyKind::Walker,
+        transform: Transform::from_translation(center.extend(1.0)),
+        spawned_by: None,
+        level_id: Some(context.level_id.clone()),
+        flight: None,
+    });
+}
+
+fn spawn_ldtk_bat(entity: &EntityInstance, context: &LdtkEntityContext, commands: &mut Commands) {
+    let Some(flight) = flight_config_from_entity(entity) else {
+        warn!("invalid bat: {:?}", entity);
+        return;
+    };
+    let (center, _) = entity_world_rect(entity);
+    commands.send_event(EnemySpawnEvent {
+        kind: EnemyKind::Bat,
+        transform: Transform::from_translation(center.extend(1.0)),
+        spawned_by: None,
+        level_id: Some(context.level_id.clone()),
+        flight: Some(flight),
+    });
+}
+
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::WALKER, spawn_ldtk_walker)
+            .register_ldtk_entity(constants::entities::BAT, spawn_ldtk_bat)
+            .add_event::<EnemySpawnEvent>()
+            .add_event::<EnemyDiedEvent>()
+            .add_console_command("spawn", "<walker|bat>", spawn_enemy_command)
+            .add_console_command("kill_all_enemies", "", kill_all_enemies_command)
+            .add_systems(
+                Update,
+                (
+                    spawn_enemy,
+                    projectile_hits_enemy.before(apply_damage),
+                    enemy_hitbox_hits_player
+                        .after(update_slice_colliders)
+                        .before(apply_damage),
+                    sense_player.before(update_ai_state),
+                    despawn_dead_enemies.after(update_ai_state),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (patrol, fly_sine_wave, chase_target, hold_still).in_set(MovementSet::Input),
+            )
+            .register_type::<EnemyKind>()
+            .register_type::<Patrol>()
+            .register_type::<SineWave>()
+            .register_type::<Aggro>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_patrol_turns_at_walls_and_ledges() {
+        // Open floor ahead
+        assert_eq!(patrol_direction(1.0, false, false, true, true), 1.0);
+        // Wall in the way, a wall behind doesn't matter
+        assert_eq!(patrol_direction(1.0, false, true, true, true), -1.0);
+        assert_eq!(patrol_direction(1.0, true, false, true, true), 1.0);
+        assert_eq!(patrol_direction(-1.0, true, false, true, true), 1.0);
+        // Ledge ahead
+        assert_eq!(patrol_direction(-1.0, false, false, true, false), 1.0);
+        // Falling, nothing to turn back to
+        assert_eq!(patrol_direction(-1.0, false, false, false, false), -1.0);
+    }
+
+    #[test]
+    fn test_sine_wave_returns_to_start() {
+        let mut sine_wave = SineWave {
+            amplitude: 16.0,
+            frequency: 0.5,
+            elapsed: 0.0,
+        };
+        let step = 1.0 / 600.0;
+        let mut height = 0.0_f32;
+        let mut peak = 0.0_f32;
+        // One full wave
+        for _ in 0..1200 {
+            height += sine_wave.velocity_y() * step;
+            peak = peak.max(height);
+            sine_wave.elapsed += step;
+        }
+
+        assert!((peak - 16.0).abs() < 0.1, "peak {peak}");
+        assert!(height.abs() < 0.1, "height {height}");
+    }
+
+    #[test]
+    fn test_projectile_damages_and_knocks_back_enemy() {
+        let mut world = World::new();
+        world.init_resource::<Events<DamageEvent>>();
+
+        let enemy = world
+            .spawn((
+                Enemy {
+                    kind: EnemyKind::Walker,
+                },
+                Health::new(2),
+            ))
+            .with_child(ColliderAabb::new(Vec2::ZERO, Vec2::splat(16.0)))
+            .id();
+        let projectile = |layers: CollisionLayers| {
+            (
+                Projectile,
+                ColliderAabb::new(Vec2::new(-10.0, 0.0), Vec2::splat(1.5)),
+                Damage(2),
+                ProjectileVelocity(Vec2::new(200.0, 0.0)),
+                layers,
+            )
+        };
+        // Not meant to hit enemies, flies through
+        world.spawn(projectile(CollisionLayers::new(
+            GameLayer::PlayerProjectile,
+            [GameLayer::LevelGeometry],
+        )));
+        let bullet = world
+            .spawn(projectile(CollisionLayers::new(
+                GameLayer::PlayerProjectile,
+                [GameLayer::Enemy, GameLayer::LevelGeometry],
+            )))
+            .id();
+
+        world.run_system_once(projectile_hits_enemy).unwrap();
+
+        assert!(world.get_entity(bullet).is_err());
+        let hits: Vec<_> = world
+            .resource_mut::<Events<DamageEvent>>()
+            .drain()
+            .map(|event| (event.target, event.amount, event.knockback))
+            .collect();
+        assert_eq!(
+            hits,
+            vec![(enemy, 2, Vec2::new(HIT_KNOCKBACK.to_pixels(), 0.0))]
+        );
+    }
+
+    #[test]
+    fn test_enemy_hitbox_hurts_player_on_active_frames() {
+        let mut world = World::new();
+        world.init_resource::<Events<DamageEvent>>();
+
+        let player = world
+            .spawn((Player, Transform::from_xyz(-20.0, 0.0, 0.0)))
+            .with_child(ColliderAabb::new(
+                Vec2::new(-20.0, 0.0),
+                Vec2::new(8.0, 16.0),
+            ))
+            .id();
+        let enemy = world
+            .spawn((
+                Enemy {
+                    kind: EnemyKind::Walker,
+                },
+                Health::new(2),
+                ContactDamage {
+                    amount: 1,
+                    knockback: 100.0,
+                },
+                Transform::default(),
+            ))
+            .id();
+        let hitbox = world
+            .spawn((
+                EnemyHitbox,
+                SliceCollider::new(AnimationLibrary::ENEMY_WALKER, HITBOX_SLICE),
+                ColliderAabb::new(Vec2::new(-12.0, 0.0), Vec2::new(8.0, 8.0)),
+                ChildOf(enemy),
+            ))
+            .id();
+        let hits = |world: &mut World| -> Vec<_> {
+            world.run_system_once(enemy_hitbox_hits_player).unwrap();
+            world
+                .resource_mut::<Events<DamageEvent>>()
+                .drain()
+                .map(|event| (event.target, event.amount, event.knockback.x < 0.0))
+                .collect()
+        };
+
+        // Wind-up frame without a hitbox
+        assert_eq!(hits(&mut world), vec![]);
+
+        world.get_mut::<SliceCollider>(hitbox).unwrap().active = true;
+        assert_eq!(hits(&mut world), vec![(player, 1, true)]);
+
+        // Dying enemies are harmless
+        world.get_mut::<Health>(enemy).unwrap().current = 0;
+        assert_eq!(hits(&mut world), vec![]);
+    }
+
+    #[test]
+    fn test_knockback_decelerates_without_reversing() {
+        assert_eq!(
+            decelerate(Vec2::new(-100.0, 0.0), 400.0, 0.1),
+            Vec2::new(-60.0, 0.0)
+        );
+        assert_eq!(decelerate(Vec2::new(30.0, 40.0), 400.0, 0.5), Vec2::ZERO);
+    }
+}
@@ -0,0 +1,170 @@
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+
+use crate::{
+    constants::{self, TILE_SIZE},
+    entity_fields::ForceZoneFields,
+    schedule::MovementSet,
+};
+
+use super::{
+    collision::{GroundEntity, IsGrounded, Velocity, child_collider_aabb},
+    gravity::EntityGravity,
+    level::{LdtkEntityAppExt, LdtkEntityContext, spawn_trigger_area},
+    surface::{SurfaceFriction, ground_friction},
+};
+
+/// Area pushing whatever falls into it, like a wind tunnel blowing the player upwards
+///
+/// Unlike a `Conveyor`, which moves whatever stands on it at its speed, a force zone
+/// accelerates everything inside it, in the air or not.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct ForceZone {
+    /// Pixels per second squared
+    pub acceleration: Vec2,
+    /// Speed along the acceleration the zone stops pushing at, in pixels per second
+    pub max_speed: f32,
+}
+
+/// How much of a sideways push full grip ground takes away from whatever stands on it
+const GROUND_GRIP: f32 = 0.5;
+
+/// Velocity after a timestep in a force zone
+///
+/// The push stops once moving `max_speed` along it. Standing on the ground, `friction` of it,
+/// holds against a sideways push: half of it on normal ground, barely any on ice.
+pub fn force_zone_velocity(
+    velocity: Vec2,
+    zone: &ForceZone,
+    friction: Option<f32>,
+    delta_secs: f32,
+) -> Vec2 {
+    let Ok(direction) = Dir2::new(zone.acceleration) else {
+        return velocity;
+    };
+    if velocity.dot(*direction) >= zone.max_speed {
+        return velocity;
+    }
+
+    let mut acceleration = zone.acceleration;
+    if let Some(friction) = friction {
+        acceleration.x *= (1.0 - friction * GROUND_GRIP).max(0.0);
+    }
+    let pushed = velocity + acceleration * delta_secs;
+
+    // Reaching the max speed rather than going past it
+    let overshoot = pushed.dot(*direction) - zone.max_speed;
+    if overshoot > 0.0 {
+        pushed - *direction * overshoot
+    } else {
+        pushed
+    }
+}
+
+fn apply_force_zones(
+    mut query: Query<
+        (
+            &mut Velocity,
+            &Children,
+            Option<&IsGrounded>,
+            Option<&GroundEntity>,
+        ),
+        With<EntityGravity>,
+    >,
+    zone_query: Query<(&ForceZone, &ColliderAabb)>,
+    aabb_query: Query<&ColliderAabb>,
+    friction_query: Query<&SurfaceFriction>,
+    time: Res<Time>,
+) {
+    for (mut velocity, children, is_grounded, ground_entity) in query.iter_mut() {
+        let Some(aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+        let friction = is_grounded
+            .is_some_and(|is_grounded| is_grounded.grounded)
+            .then(|| ground_friction(ground_entity, &friction_query));
+
+        for (zone, zone_aabb) in zone_query.iter() {
+            if zone_aabb.intersects(&aabb) {
+                velocity.0 = force_zone_velocity(velocity.0, zone, friction, time.delta_secs());
+            }
+        }
+    }
+}
+
+fn force_zone_from_entity(entity: &EntityInstance) -> Option<ForceZone> {
+    let fields = ForceZoneFields::from_entity(entity)?;
+
+    Some(ForceZone {
+        acceleration: Vec2::new(fields.acceleration_x, fields.acceleration_y) * TILE_SIZE,
+        max_speed: fields.max_speed * TILE_SIZE,
+    })
+}
+
+fn spawn_ldtk_force_zone(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    if let Some(force_zone) = force_zone_from_entity(entity) {
+        spawn_trigger_area(commands, context.level_id, entity, force_zone);
+    } else {
+        warn!("invalid force zone: {:?}", entity);
+    }
+}
+
+pub struct ForceZonePlugin;
+
+impl Plugin for ForceZonePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::FORCE_ZONE, spawn_ldtk_force_zone)
+            .add_systems(
+                FixedUpdate,
+                apply_force_zones
+                    .after(MovementSet::Gravity)
+                    .before(MovementSet::CollisionChecks),
+            )
+            .register_type::<ForceZone>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_force_zone_pushes_up_to_max_speed() {
+        let updraft = ForceZone {
+            acceleration: Vec2::new(0.0, 600.0),
+            max_speed: 100.0,
+        };
+        assert_eq!(
+            force_zone_velocity(Vec2::new(20.0, -50.0), &updraft, None, 0.1),
+            Vec2::new(20.0, 10.0)
+        );
+        // Doesn't go past the max speed, nor slow down something already faster
+        assert_eq!(
+            force_zone_velocity(Vec2::new(0.0, 80.0), &updraft, None, 0.1),
+            Vec2::new(0.0, 100.0)
+        );
+        assert_eq!(
+            force_zone_velocity(Vec2::new(0.0, 300.0), &updraft, None, 0.1),
+            Vec2::new(0.0, 300.0)
+        );
+    }
+
+    #[test]
+    fn test_ground_holds_against_sideways_force() {
+        let wind = ForceZone {
+            acceleration: Vec2::new(100.0, 0.0),
+            max_speed: 1000.0,
+        };
+        let airborne = force_zone_velocity(Vec2::ZERO, &wind, None, 1.0);
+        let grounded = force_zone_velocity(Vec2::ZERO, &wind, Some(1.0), 1.0);
+        let on_ice = force_zone_velocity(Vec2::ZERO, &wind, Some(SurfaceFriction::ICE.0), 1.0);
+        assert_eq!(airborne.x, 100.0);
+        assert_eq!(grounded.x, 50.0);
+        assert!(on_ice.x > grounded.x && on_ice.x < airborne.x);
+    }
+}
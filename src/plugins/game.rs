@@ -8,7 +8,11 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Game), setup)
-            .add_systems(OnExit(GameState::Game), cleanup_game);
+            .add_systems(OnExit(GameState::Loading), cleanup_game)
+            .add_systems(OnExit(GameState::Menu), cleanup_game)
+            .add_systems(OnExit(GameState::Game), cleanup_game)
+            .add_systems(OnExit(GameState::GameOver), cleanup_game)
+            .add_systems(OnExit(GameState::LevelComplete), cleanup_game);
     }
 }
 
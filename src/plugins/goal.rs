@@ -0,0 +1,312 @@
+use std::{collections::HashSet, time::Duration};
+
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{bundles::player::Player, constants, entity_fields::ExitDoorFields, states::GameState};
+
+use super::{
+    collision::{Velocity, child_collider_aabb},
+    dev_console::DevConsole,
+    level::{
+        LdtkEntityAppExt, LdtkEntityContext, LevelStartedEvent, PendingLevelLoad,
+        spawn_trigger_area,
+    },
+    player::PlayerAction,
+    projectile::Projectile,
+    transition::{TransitionEvent, TransitionKind},
+};
+
+/// Level exit, completes the level when the player overlaps it
+#[derive(Component, Debug)]
+pub struct Goal {
+    pub level_identifier: String,
+    /// Set once the goal has fired, so it only completes the level once
+    pub reached: bool,
+}
+
+impl Goal {
+    pub fn new(level_identifier: &str) -> Self {
+        Self {
+            level_identifier: level_identifier.to_string(),
+            reached: false,
+        }
+    }
+}
+
+/// Door leading straight into another level, without completing the current one
+#[derive(Component, Debug)]
+pub struct ExitDoor {
+    pub target_level: String,
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct LevelCompleteEvent {
+    pub level_identifier: String,
+}
+
+/// Progress through the game's levels
+#[derive(Resource, Default, Debug)]
+pub struct LevelProgress {
+    pub completed_levels: HashSet<String>,
+}
+
+/// Time between touching the goal and the summary screen, letting the player land
+const LEVEL_EXIT_DELAY: Duration = Duration::from_secs(1);
+const EXIT_DOOR_TRANSITION: Duration = Duration::from_millis(500);
+
+/// Counts down to leaving the completed level
+#[derive(Resource)]
+struct LevelExitTimer(Timer);
+
+fn check_goal_reached(
+    mut goal_query: Query<(&mut Goal, &ColliderAabb)>,
+    player_query: Query<&Children, With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut event_writer: EventWriter<LevelCompleteEvent>,
+) {
+    for children in player_query.iter() {
+        let Some(player_aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        for (mut goal, goal_aabb) in goal_query.iter_mut() {
+            if !goal.reached && goal_aabb.intersects(&player_aabb) {
+                goal.reached = true;
+                event_writer.write(LevelCompleteEvent {
+                    level_identifier: goal.level_identifier.clone(),
+                });
+            }
+        }
+    }
+}
+
+fn check_exit_doors(
+    door_query: Query<(&ExitDoor, &ColliderAabb)>,
+    player_query: Query<&Children, With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut pending_load: ResMut<PendingLevelLoad>,
+    mut event_writer: EventWriter<TransitionEvent>,
+) {
+    // Still standing in the door while the screen is being covered
+    if pending_load.0.is_some() {
+        return;
+    }
+
+    for children in player_query.iter() {
+        let Some(player_aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        // The level gets loaded behind the transition, once the screen is covered
+        if let Some((door, _)) = door_query
+            .iter()
+            .find(|(_, door_aabb)| door_aabb.intersects(&player_aabb))
+        {
+            pending_load.0 = Some(door.target_level.clone());
+            event_writer.write(TransitionEvent::cover_and_reveal(
+                TransitionKind::CircleWipe,
+                EXIT_DOOR_TRANSITION,
+            ));
+        }
+    }
+}
+
+/// Record the completion and settle the game world: stray projectiles are removed, and the
+/// player loses control and is left to land if they were mid-jump.
+fn handle_level_complete(
+    mut commands: Commands,
+    mut event_reader: EventReader<LevelCompleteEvent>,
+    mut progress: ResMut<LevelProgress>,
+    mut player_query: Query<(&mut Velocity, &mut ActionState<PlayerAction>), With<Player>>,
+    projectile_query: Query<Entity, With<Projectile>>,
+) {
+    for event in event_reader.read() {
+        info!("Level '{}' complete", event.level_identifier);
+        commands.insert_resource(LevelExitTimer(Timer::new(
+            LEVEL_EXIT_DELAY,
+            TimerMode::Once,
+        )));
+        progress
+            .completed_levels
+            .insert(event.level_identifier.clone());
+
+        for projectile in projectile_query.iter() {
+            commands.entity(projectile).despawn();
+        }
+
+        for (mut velocity, mut action_state) in player_query.iter_mut() {
+            action_state.disable_all();
+            velocity.0.x = 0.0;
+            velocity.0.y = velocity.0.y.min(0.0);
+        }
+    }
+}
+
+/// Hand control back that completing the previous level took away, unless the dev console
+/// has it
+fn restore_player_control(
+    mut event_reader: EventReader<LevelStartedEvent>,
+    mut action_states: Query<&mut ActionState<PlayerAction>, With<Player>>,
+    console: Option<Res<DevConsole>>,
+) {
+    if event_reader.read().last().is_none() || console.is_some_and(|console| console.open) {
+        return;
+    }
+    for mut action_state in action_states.iter_mut() {
+        action_state.enable_all();
+    }
+}
+
+fn exit_completed_level(
+    mut commands: Commands,
+    exit_timer: Option<ResMut<LevelExitTimer>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
+) {
+    if let Some(mut exit_timer) = exit_timer
+        && exit_timer.0.tick(time.delta()).finished()
+    {
+        commands.remove_resource::<LevelExitTimer>();
+        next_state.set(GameState::LevelComplete);
+    }
+}
+
+fn spawn_ldtk_goal(entity: &EntityInstance, context: &LdtkEntityContext, commands: &mut Commands) {
+    spawn_trigger_area(
+        commands,
+        context.level_id,
+        entity,
+        Goal::new(&context.level_id.0),
+    );
+}
+
+fn spawn_ldtk_exit_door(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    let target_level = ExitDoorFields::from_entity(entity)
+        .map(|fields| fields.level)
+        .filter(|level| !level.is_empty());
+    if let Some(target_level) = target_level {
+        spawn_trigger_area(
+            commands,
+            context.level_id,
+            entity,
+            ExitDoor { target_level },
+        );
+    } else {
+        warn!("exit door without a target level: {:?}", entity);
+    }
+}
+
+pub struct GoalPlugin;
+
+impl Plugin for GoalPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::GOAL, spawn_ldtk_goal)
+            .register_ldtk_entity(constants::entities::EXIT_DOOR, spawn_ldtk_exit_door)
+            .init_resource::<LevelProgress>()
+            .add_event::<LevelCompleteEvent>()
+            .add_systems(
+                Update,
+                (
+                    restore_player_control,
+                    check_goal_reached,
+                    handle_level_complete,
+                    exit_completed_level,
+                    check_exit_doors,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_goal_fires_once() {
+        let mut world = World::new();
+        world.init_resource::<Events<LevelCompleteEvent>>();
+
+        world.spawn((
+            Goal::new("level_0"),
+            ColliderAabb::new(Vec2::new(100.0, 0.0), Vec2::new(16.0, 24.0)),
+        ));
+        world.spawn(Player).with_child(ColliderAabb::new(
+            Vec2::new(110.0, 0.0),
+            Vec2::new(8.0, 12.0),
+        ));
+
+        // Staying inside the goal for multiple frames
+        for _ in 0..3 {
+            world.run_system_once(check_goal_reached).unwrap();
+        }
+
+        let events = world.resource::<Events<LevelCompleteEvent>>();
+        assert_eq!(events.len(), 1);
+        let event = events.iter_current_update_events().next().unwrap();
+        assert_eq!(event.level_identifier, "level_0");
+    }
+
+    #[test]
+    fn test_control_comes_back_on_the_next_level() {
+        let mut world = World::new();
+        world.init_resource::<Events<LevelCompleteEvent>>();
+        world.init_resource::<Events<LevelStartedEvent>>();
+        world.init_resource::<LevelProgress>();
+
+        let player = world
+            .spawn((
+                Player,
+                Velocity(Vec2::new(50.0, 100.0)),
+                ActionState::<PlayerAction>::default(),
+            ))
+            .id();
+        let disabled = |world: &World| {
+            world
+                .get::<ActionState<PlayerAction>>(player)
+                .unwrap()
+                .disabled()
+        };
+
+        world.send_event(LevelCompleteEvent {
+            level_identifier: "level_0".to_string(),
+        });
+        world.run_system_once(handle_level_complete).unwrap();
+        assert!(disabled(&world));
+
+        world.send_event(LevelStartedEvent {
+            level_identifier: "level_1".to_string(),
+        });
+        world.run_system_once(restore_player_control).unwrap();
+        assert!(!disabled(&world));
+    }
+
+    #[test]
+    fn test_goal_ignores_distant_player() {
+        let mut world = World::new();
+        world.init_resource::<Events<LevelCompleteEvent>>();
+
+        world.spawn((
+            Goal::new("level_0"),
+            ColliderAabb::new(Vec2::new(100.0, 0.0), Vec2::new(16.0, 24.0)),
+        ));
+        world.spawn(Player).with_child(ColliderAabb::new(
+            Vec2::new(-100.0, 0.0),
+            Vec2::new(8.0, 12.0),
+        ));
+
+        world.run_system_once(check_goal_reached).unwrap();
+
+        assert!(world.resource::<Events<LevelCompleteEvent>>().is_empty());
+    }
+}
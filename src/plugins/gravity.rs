@@ -1,6 +1,11 @@
 use bevy::prelude::*;
 
-use super::collision::{IsGrounded, Velocity};
+use crate::schedule::MovementSet;
+
+use super::{
+    collision::{IsGrounded, Noclip, Velocity},
+    surface::{InWater, WATER_GRAVITY_SCALE, WATER_MAX_FALL_SPEED},
+};
 
 #[derive(Default, Component)]
 pub struct EntityGravity {
@@ -9,18 +14,41 @@ pub struct EntityGravity {
     pub enabled: bool,
 }
 
+impl EntityGravity {
+    /// Gravity and max fall speed, both lower in water
+    pub fn strength(&self, in_water: bool) -> (f32, f32) {
+        if in_water {
+            (
+                self.gravity * WATER_GRAVITY_SCALE,
+                self.max_fall_speed.min(WATER_MAX_FALL_SPEED.to_pixels()),
+            )
+        } else {
+            (self.gravity, self.max_fall_speed)
+        }
+    }
+}
+
 pub fn apply_gravity(
     time: Res<Time>,
-    mut query: Query<(&EntityGravity, &mut Velocity, Option<&IsGrounded>)>,
+    mut query: Query<
+        (
+            &EntityGravity,
+            &mut Velocity,
+            Option<&IsGrounded>,
+            Has<InWater>,
+        ),
+        Without<Noclip>,
+    >,
 ) {
-    for (gravity, mut velocity, is_grounded) in query.iter_mut() {
-        if gravity.enabled && velocity.0.y > -gravity.max_fall_speed {
+    for (gravity, mut velocity, is_grounded, in_water) in query.iter_mut() {
+        let (strength, max_fall_speed) = gravity.strength(in_water);
+        if gravity.enabled && velocity.0.y > -max_fall_speed {
             if let Some(is_grounded) = is_grounded {
-                if !is_grounded.0 {
-                    velocity.0.y -= gravity.gravity * time.delta_secs()
+                if !is_grounded.grounded {
+                    velocity.0.y -= strength * time.delta_secs()
                 }
             } else {
-                velocity.0.y -= gravity.gravity * time.delta_secs()
+                velocity.0.y -= strength * time.delta_secs()
             }
         }
     }
@@ -32,6 +60,6 @@ pub struct GravityPlugin;
 
 impl Plugin for GravityPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, apply_gravity);
+        app.add_systems(FixedUpdate, apply_gravity.in_set(MovementSet::Gravity));
     }
 }
@@ -0,0 +1,173 @@
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+
+use crate::{bundles::player::Player, states::GameState};
+
+use super::{
+    collision::{GroundEntity, IsGrounded, MovementDelta, Velocity, child_collider_aabb},
+    health::{Health, apply_damage, contact_damage},
+};
+
+/// Area that sends the player back to where they last stood safely on touch, on top of
+/// the `ContactDamage` it does, like spikes at the bottom of a pit
+#[derive(Component, Clone, Debug, Default)]
+pub struct Hazard;
+
+/// Where the player last stood on solid ground, away from any hazard
+#[derive(Component, Debug, Default)]
+pub struct SafeGround(pub Option<Vec2>);
+
+fn touching_hazard(
+    children: &Children,
+    aabb_query: &Query<&ColliderAabb>,
+    hazard_query: &Query<&ColliderAabb, With<Hazard>>,
+) -> bool {
+    child_collider_aabb(children, aabb_query).is_some_and(|player_aabb| {
+        hazard_query
+            .iter()
+            .any(|hazard_aabb| hazard_aabb.intersects(&player_aabb))
+    })
+}
+
+/// Remember the player's position while standing on ground that stays put
+///
+/// Moving platforms don't count, they may be long gone by the time the player needs to
+/// be put back.
+fn track_safe_ground(
+    mut player_query: Query<
+        (
+            &mut SafeGround,
+            &Transform,
+            &IsGrounded,
+            &GroundEntity,
+            &Children,
+        ),
+        With<Player>,
+    >,
+    moving_query: Query<(), With<MovementDelta>>,
+    aabb_query: Query<&ColliderAabb>,
+    hazard_query: Query<&ColliderAabb, With<Hazard>>,
+) {
+    for (mut safe_ground, transform, is_grounded, ground_entity, children) in
+        player_query.iter_mut()
+    {
+        let on_still_ground = is_grounded.grounded
+            && ground_entity
+                .0
+                .is_some_and(|ground| !moving_query.contains(ground));
+        if on_still_ground && !touching_hazard(children, &aabb_query, &hazard_query) {
+            safe_ground.0 = Some(transform.translation.xy());
+        }
+    }
+}
+
+/// Put a player touching a hazard back on safe ground, unless the hit killed them
+fn respawn_from_hazards(
+    mut player_query: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &SafeGround,
+            &Children,
+            &Health,
+        ),
+        With<Player>,
+    >,
+    aabb_query: Query<&ColliderAabb>,
+    hazard_query: Query<&ColliderAabb, With<Hazard>>,
+) {
+    for (mut transform, mut velocity, safe_ground, children, health) in player_query.iter_mut() {
+        if health.is_dead() {
+            continue;
+        }
+        let Some(safe_position) = safe_ground.0 else {
+            continue;
+        };
+        if touching_hazard(children, &aabb_query, &hazard_query) {
+            transform.translation = safe_position.extend(transform.translation.z);
+            velocity.0 = Vec2::ZERO;
+        }
+    }
+}
+
+pub struct HazardPlugin;
+
+impl Plugin for HazardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                track_safe_ground,
+                respawn_from_hazards
+                    .after(contact_damage)
+                    .after(apply_damage),
+            )
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_hazard_returns_player_to_safe_ground() {
+        let mut world = World::new();
+        let ground = world.spawn_empty().id();
+        let spikes = world
+            .spawn((
+                Hazard,
+                ColliderAabb::new(Vec2::new(100.0, 0.0), Vec2::new(16.0, 8.0)),
+            ))
+            .id();
+        let collider = world
+            .spawn(ColliderAabb::new(Vec2::ZERO, Vec2::new(8.0, 16.0)))
+            .id();
+        let player = world
+            .spawn((
+                Player,
+                Transform::default(),
+                Velocity(Vec2::new(50.0, 0.0)),
+                SafeGround::default(),
+                IsGrounded {
+                    grounded: true,
+                    normal: Vec2::Y,
+                },
+                GroundEntity(Some(ground)),
+                Health::new(3),
+            ))
+            .add_child(collider)
+            .id();
+
+        world.run_system_once(track_safe_ground).unwrap();
+        assert_eq!(world.get::<SafeGround>(player).unwrap().0, Some(Vec2::ZERO));
+
+        // Walked onto the spikes
+        let on_spikes = Vec2::new(100.0, 0.0);
+        world.get_mut::<Transform>(player).unwrap().translation = on_spikes.extend(0.0);
+        *world.get_mut::<ColliderAabb>(collider).unwrap() =
+            ColliderAabb::new(on_spikes, Vec2::new(8.0, 16.0));
+        world.run_system_once(track_safe_ground).unwrap();
+        assert_eq!(world.get::<SafeGround>(player).unwrap().0, Some(Vec2::ZERO));
+
+        world.run_system_once(respawn_from_hazards).unwrap();
+        assert_eq!(
+            world.get::<Transform>(player).unwrap().translation,
+            Vec3::ZERO
+        );
+        assert_eq!(world.get::<Velocity>(player).unwrap().0, Vec2::ZERO);
+
+        // Somewhere the spikes can't reach, nothing happens
+        world.entity_mut(spikes).despawn();
+        world.get_mut::<Transform>(player).unwrap().translation = on_spikes.extend(0.0);
+        world.run_system_once(respawn_from_hazards).unwrap();
+        assert_eq!(
+            world.get::<Transform>(player).unwrap().translation,
+            on_spikes.extend(0.0)
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use std::{collections::HashSet, time::Duration};
+
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+
+use crate::{bundles::player::Player, states::GameState};
+
+use super::{
+    audio::{PlaySfxAtEvent, PlaySfxEvent, SfxId},
+    collision::{Velocity, child_collider_aabb},
+    player::GodMode,
+    sprite_effect::{Blink, FlashWhite},
+};
+
+#[derive(Component, Debug, Reflect)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct DamageEvent {
+    pub target: Entity,
+    pub amount: u32,
+    /// Velocity the target is sent flying with, replacing its current one
+    pub knockback: Vec2,
+}
+
+/// Fired once when the player's health runs out
+#[derive(Event, Clone, Debug)]
+pub struct PlayerDiedEvent {
+    pub player: Entity,
+}
+
+/// Time an entity can't be damaged again after taking a hit
+#[derive(Component, Reflect)]
+pub struct InvincibilityFrames(pub Duration);
+
+/// Present while the entity is recovering from a hit and can't be damaged
+#[derive(Component)]
+pub struct Invincible {
+    timer: Timer,
+}
+
+impl Invincible {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            timer: Timer::new(duration, TimerMode::Once),
+        }
+    }
+}
+
+/// Damages the player on touch, e.g. an enemy or spikes
+#[derive(Component, Clone, Reflect)]
+pub struct ContactDamage {
+    pub amount: u32,
+    /// Knockback speed, pointing away from the damage source and slightly up
+    pub knockback: f32,
+}
+
+const BLINK_INTERVAL: Duration = Duration::from_millis(100);
+const HIT_FLASH_DURATION: Duration = Duration::from_millis(100);
+
+pub fn apply_damage(
+    mut commands: Commands,
+    mut event_reader: EventReader<DamageEvent>,
+    mut query: Query<(
+        &mut Health,
+        Option<&mut Velocity>,
+        Option<&Transform>,
+        Option<&InvincibilityFrames>,
+        Has<Invincible>,
+        Has<FlashWhite>,
+        Has<GodMode>,
+        Has<Player>,
+    )>,
+    mut player_died_writer: EventWriter<PlayerDiedEvent>,
+    mut sfx_writer: EventWriter<PlaySfxEvent>,
+    mut positional_sfx_writer: EventWriter<PlaySfxAtEvent>,
+) {
+    // `Invincible` only shows up once the commands are applied, so later hits in the same
+    // batch have to be told apart here
+    let mut made_invincible = HashSet::new();
+    for event in event_reader.read() {
+        let Ok((
+            mut health,
+            velocity,
+            transform,
+            invincibility_frames,
+            is_invincible,
+            is_flashing,
+            god_mode,
+            is_player,
+        )) = query.get_mut(event.target)
+        else {
+            continue;
+        };
+        if is_invincible || made_invincible.contains(&event.target) || god_mode || health.is_dead()
+        {
+            continue;
+        }
+
+        health.current = health.current.saturating_sub(event.amount);
+        // Enemies are heard from where they are, the player always up close
+        match transform.filter(|_| !is_player) {
+            Some(transform) => {
+                positional_sfx_writer.write(PlaySfxAtEvent {
+                    id: SfxId::Hit,
+                    position: transform.translation.xy(),
+                });
+            }
+            None => {
+                sfx_writer.write(PlaySfxEvent(SfxId::Hit));
+            }
+        }
+        if let Some(mut velocity) = velocity {
+            velocity.0 = event.knockback;
+        }
+
+        if let Some(invincibility_frames) = invincibility_frames {
+            made_invincible.insert(event.target);
+            commands.entity(event.target).insert((
+                Invincible::new(invincibility_frames.0),
+                Blink::new(BLINK_INTERVAL, invincibility_frames.0),
+            ));
+        } else if !is_flashing {
+            // Entities without i-frames have no blinking of their own to show the hit
+            commands
+                .entity(event.target)
+                .insert(FlashWhite::new(HIT_FLASH_DURATION));
+        }
+
+        if health.is_dead() && is_player {
+            player_died_writer.write(PlayerDiedEvent {
+                player: event.target,
+            });
+        }
+    }
+}
+
+fn update_invincibility(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invincible)>,
+    time: Res<Time>,
+) {
+    for (entity, mut invincible) in query.iter_mut() {
+        if invincible.timer.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invincible>();
+        }
+    }
+}
+
+pub fn contact_damage(
+    player_query: Query<(Entity, &Transform, &Children), With<Player>>,
+    damage_query: Query<
+        (
+            Entity,
+            &ContactDamage,
+            &Transform,
+            Option<&Children>,
+            Option<&Health>,
+        ),
+        Without<Player>,
+    >,
+    aabb_query: Query<&ColliderAabb>,
+    mut event_writer: EventWriter<DamageEvent>,
+) {
+    for (player, player_transform, player_children) in player_query.iter() {
+        let Some(player_aabb) = child_collider_aabb(player_children, &aabb_query) else {
+            continue;
+        };
+
+        for (entity, contact_damage, transform, children, health) in damage_query.iter() {
+            // Dying enemies are harmless
+            if health.is_some_and(Health::is_dead) {
+                continue;
+            }
+            // Enemies keep their collider on a child, hazards like spikes are one themselves
+            let touching = children
+                .and_then(|children| child_collider_aabb(children, &aabb_query))
+                .or_else(|| aabb_query.get(entity).ok().copied())
+                .is_some_and(|aabb| aabb.intersects(&player_aabb));
+            if !touching {
+                continue;
+            }
+
+            let away = if player_transform.translation.x < transform.translation.x {
+                -1.0
+            } else {
+                1.0
+            };
+            event_writer.write(DamageEvent {
+                target: player,
+                amount: contact_damage.amount,
+                knockback: Vec2::new(away, 0.5).normalize() * contact_damage.knockback,
+            });
+        }
+    }
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<PlayerDiedEvent>()
+            .add_systems(
+                Update,
+                (contact_damage, apply_damage, update_invincibility)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .register_type::<Health>()
+            .register_type::<InvincibilityFrames>()
+            .register_type::<ContactDamage>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::sprite_effect::{FLASH_COLOR, update_flash_white};
+
+    use super::*;
+
+    fn hit(world: &mut World, target: Entity) {
+        world.send_event(DamageEvent {
+            target,
+            amount: 1,
+            knockback: Vec2::new(-100.0, 50.0),
+        });
+        world.run_system_once(apply_damage).unwrap();
+        world.resource_mut::<Events<DamageEvent>>().clear();
+    }
+
+    fn setup() -> (World, Entity) {
+        let mut world = World::new();
+        world.init_resource::<Events<DamageEvent>>();
+        world.init_resource::<Events<PlayerDiedEvent>>();
+        world.init_resource::<Events<PlaySfxEvent>>();
+        world.init_resource::<Events<PlaySfxAtEvent>>();
+        let player = world
+            .spawn((
+                Player,
+                Health::new(2),
+                Velocity::default(),
+                InvincibilityFrames(Duration::from_secs(1)),
+            ))
+            .id();
+        (world, player)
+    }
+
+    #[test]
+    fn test_damage_knockback_and_iframes() {
+        let (mut world, player) = setup();
+
+        hit(&mut world, player);
+        assert_eq!(world.get::<Health>(player).unwrap().current, 1);
+        assert_eq!(
+            world.get::<Velocity>(player).unwrap().0,
+            Vec2::new(-100.0, 50.0)
+        );
+        assert!(world.get::<Invincible>(player).is_some());
+
+        // Ignored while invincible
+        hit(&mut world, player);
+        assert_eq!(world.get::<Health>(player).unwrap().current, 1);
+        assert!(world.resource::<Events<PlayerDiedEvent>>().is_empty());
+
+        world.entity_mut(player).remove::<Invincible>();
+        hit(&mut world, player);
+        assert!(world.get::<Health>(player).unwrap().is_dead());
+        assert_eq!(world.resource::<Events<PlayerDiedEvent>>().len(), 1);
+    }
+
+    #[test]
+    fn test_simultaneous_hits_only_count_once() {
+        let (mut world, player) = setup();
+
+        for _ in 0..2 {
+            world.send_event(DamageEvent {
+                target: player,
+                amount: 1,
+                knockback: Vec2::ZERO,
+            });
+        }
+        world.run_system_once(apply_damage).unwrap();
+        assert_eq!(world.get::<Health>(player).unwrap().current, 1);
+    }
+
+    #[test]
+    fn test_god_mode_ignores_damage() {
+        let (mut world, player) = setup();
+        world.entity_mut(player).insert(GodMode);
+
+        hit(&mut world, player);
+        assert_eq!(world.get::<Health>(player).unwrap().current, 2);
+        assert!(world.get::<Invincible>(player).is_none());
+    }
+
+    #[test]
+    fn test_hit_flash_without_iframes() {
+        let (mut world, player) = setup();
+        world.init_resource::<Time>();
+        let color = Color::srgb(0.8, 0.2, 0.2);
+        let enemy = world
+            .spawn((Health::new(3), Sprite { color, ..default() }))
+            .id();
+
+        hit(&mut world, enemy);
+        assert!(world.get::<FlashWhite>(enemy).is_some());
+        world.run_system_once(update_flash_white).unwrap();
+        assert_eq!(world.get::<Sprite>(enemy).unwrap().color, FLASH_COLOR);
+
+        world.resource_mut::<Time>().advance_by(HIT_FLASH_DURATION);
+        world.run_system_once(update_flash_white).unwrap();
+        assert_eq!(world.get::<Sprite>(enemy).unwrap().color, color);
+        assert!(world.get::<FlashWhite>(enemy).is_none());
+
+        // The player blinks through their i-frames instead
+        hit(&mut world, player);
+        assert!(world.get::<Blink>(player).is_some());
+        assert!(world.get::<FlashWhite>(player).is_none());
+    }
+}
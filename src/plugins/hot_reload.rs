@@ -0,0 +1,174 @@
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+use ldtk_rust::Level;
+
+use crate::{
+    bundles::player::Player,
+    components::LevelEntity,
+    constants::{self, TILE_SIZE, int_grid::LevelGeometry},
+    states::GameState,
+};
+
+use super::{
+    collision::{Velocity, child_collider_aabb},
+    level::{LdtkProject, LdtkProjectHandle, LevelBounds, LevelBuilder, level_rect},
+    level_streaming::LoadedLevels,
+};
+
+/// Where to move a player of `half_height` whose center ended up inside a solid tile, standing
+/// them on top of the solid column
+///
+/// `None` if the player is in open space or outside the grid.
+pub fn position_out_of_tiles(
+    int_grid_csv: &[i64],
+    width: usize,
+    top_left: Vec2,
+    position: Vec2,
+    half_height: f32,
+) -> Option<Vec2> {
+    let local = (position - top_left) / TILE_SIZE;
+    if local.x < 0.0 || local.y > 0.0 || width == 0 {
+        return None;
+    }
+    let x = local.x as usize;
+    let mut y = (-local.y) as usize;
+    let height = int_grid_csv.len() / width;
+    let is_solid = |x: usize, y: usize| {
+        int_grid_csv
+            .get(y * width + x)
+            .and_then(|&tile| LevelGeometry::from_value(tile))
+            == Some(LevelGeometry::Solid)
+    };
+    if x >= width || y >= height || !is_solid(x, y) {
+        return None;
+    }
+
+    while y > 0 && is_solid(x, y - 1) {
+        y -= 1;
+    }
+    let solid_top = top_left.y - y as f32 * TILE_SIZE;
+    Some(Vec2::new(position.x, solid_top + half_height))
+}
+
+fn geometry_position_out_of_tiles(
+    level_data: &Level,
+    position: Vec2,
+    half_height: f32,
+) -> Option<Vec2> {
+    let layer = level_data
+        .layer_instances
+        .as_ref()?
+        .iter()
+        .find(|layer| layer.identifier == constants::layers::LEVEL_GEOMETRY)?;
+    let rect = level_rect(level_data);
+    position_out_of_tiles(
+        &layer.int_grid_csv,
+        layer.c_wid as usize,
+        Vec2::new(rect.min.x, rect.max.y),
+        position,
+        half_height,
+    )
+}
+
+/// Rebuild the loaded levels when the LDtk project changes on disk
+///
+/// The player stays where they are, unless the new geometry buries them.
+fn reload_levels(
+    mut asset_events: EventReader<AssetEvent<LdtkProject>>,
+    project_handle: Res<LdtkProjectHandle>,
+    projects: Res<Assets<LdtkProject>>,
+    mut loaded_levels: ResMut<LoadedLevels>,
+    level_entity_query: Query<Entity, With<LevelEntity>>,
+    mut player_query: Query<(&mut Transform, &mut Velocity, &Children), With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut builder: LevelBuilder,
+) {
+    let modified = asset_events
+        .read()
+        .any(|event| matches!(event, AssetEvent::Modified { id } if *id == project_handle.0.id()));
+    let Some(project) = projects.get(&project_handle.0).filter(|_| modified) else {
+        return;
+    };
+
+    for entity in level_entity_query.iter() {
+        builder.commands.entity(entity).despawn();
+    }
+
+    let identifiers: Vec<String> = loaded_levels.0.keys().cloned().collect();
+    loaded_levels.0.clear();
+    for identifier in identifiers {
+        let Some(level_data) = project
+            .0
+            .levels
+            .iter()
+            .find(|level| level.identifier == identifier)
+        else {
+            warn!("level '{}' is gone after reloading", identifier);
+            continue;
+        };
+        info!("Reloading level '{}'", identifier);
+        builder.build(level_data);
+        let rect = level_rect(level_data);
+        loaded_levels.0.insert(identifier, rect);
+
+        for (mut transform, mut velocity, children) in player_query.iter_mut() {
+            let position = transform.translation.xy();
+            if !rect.contains(position) {
+                continue;
+            }
+            builder.commands.insert_resource(LevelBounds(rect));
+
+            let half_height = child_collider_aabb(children, &aabb_query)
+                .map_or(TILE_SIZE / 2.0, |aabb| aabb.size().y / 2.0);
+            if let Some(free) = geometry_position_out_of_tiles(level_data, position, half_height) {
+                transform.translation = free.extend(transform.translation.z);
+                velocity.0 = Vec2::ZERO;
+            }
+        }
+    }
+}
+
+/// Rebuilds levels live while editing them in LDtk, needs the `hot_reload` feature to watch
+/// the asset folder
+pub struct HotReloadPlugin;
+
+impl Plugin for HotReloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, reload_levels.run_if(in_state(GameState::Game)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buried_player_is_lifted_onto_the_ground() {
+        // 3 wide, 4 tall, with a 2 tile high pillar in the middle column
+        #[rustfmt::skip]
+        let grid = [
+            0, 0, 0,
+            0, 1, 0,
+            0, 1, 0,
+            1, 1, 1,
+        ];
+        let top_left = Vec2::new(0.0, 0.0);
+        let half_height = 12.0;
+
+        // In open space
+        assert_eq!(
+            position_out_of_tiles(&grid, 3, top_left, Vec2::new(8.0, -24.0), half_height),
+            None
+        );
+        // Inside the bottom of the pillar, ending up standing on its top
+        assert_eq!(
+            position_out_of_tiles(&grid, 3, top_left, Vec2::new(24.0, -56.0), half_height),
+            Some(Vec2::new(24.0, -TILE_SIZE + half_height))
+        );
+        // Outside the level
+        assert_eq!(
+            position_out_of_tiles(&grid, 3, top_left, Vec2::new(-8.0, -8.0), half_height),
+            None
+        );
+    }
+}
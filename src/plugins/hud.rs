@@ -0,0 +1,172 @@
+use bevy::prelude::*;
+
+use crate::{bundles::player::Player, components::GameEntity, states::GameState};
+
+use super::{health::Health, pickup::Inventory, projectile::Weapon};
+
+#[derive(Component)]
+struct HealthBarFill;
+
+#[derive(Component)]
+struct CollectiblesText;
+
+#[derive(Component)]
+struct WeaponText;
+
+const HEALTH_BAR_SIZE: Vec2 = Vec2::new(64.0, 6.0);
+const HEALTH_BAR_BACKGROUND: Color = Color::srgb(0.15, 0.1, 0.1);
+const HEALTH_BAR_COLOR: Color = Color::srgb(0.85, 0.2, 0.25);
+const HUD_FONT_SIZE: f32 = 12.0;
+
+/// Top left corner overlay, cleaned up with the rest of the game through `GameEntity`
+fn spawn_hud(mut commands: Commands) {
+    let text = || {
+        (
+            Text::default(),
+            TextFont {
+                font_size: HUD_FONT_SIZE,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        )
+    };
+
+    commands
+        .spawn((
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.0),
+                left: Val::Px(8.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            GameEntity,
+        ))
+        .with_children(|hud| {
+            hud.spawn((
+                Node {
+                    width: Val::Px(HEALTH_BAR_SIZE.x),
+                    height: Val::Px(HEALTH_BAR_SIZE.y),
+                    ..default()
+                },
+                BackgroundColor(HEALTH_BAR_BACKGROUND),
+            ))
+            .with_child((
+                HealthBarFill,
+                Node {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                BackgroundColor(HEALTH_BAR_COLOR),
+            ));
+            hud.spawn((CollectiblesText, text()));
+            hud.spawn((WeaponText, text()));
+        });
+}
+
+/// Widgets only change along with what they show, or when they've just been spawned
+fn update_health_bar(
+    player_query: Query<Ref<Health>, With<Player>>,
+    mut fill_query: Query<(&mut Node, Ref<HealthBarFill>)>,
+) {
+    let Ok(health) = player_query.single() else {
+        return;
+    };
+
+    let fraction = if health.max == 0 {
+        0.0
+    } else {
+        health.current as f32 / health.max as f32
+    };
+    for (mut node, fill) in fill_query.iter_mut() {
+        if health.is_changed() || fill.is_added() {
+            node.width = Val::Percent(fraction * 100.0);
+        }
+    }
+}
+
+fn update_collectibles_text(
+    inventory: Res<Inventory>,
+    mut query: Query<(&mut Text, Ref<CollectiblesText>)>,
+) {
+    for (mut text, marker) in query.iter_mut() {
+        if inventory.is_changed() || marker.is_added() {
+            text.0 = if inventory.keys > 0 {
+                format!("Coins: {} | Keys: {}", inventory.coins, inventory.keys)
+            } else {
+                format!("Coins: {}", inventory.coins)
+            };
+        }
+    }
+}
+
+/// The weapon's name, with the ammo left for weapons that use any
+fn weapon_label(weapon: &Weapon, ammo: u32) -> String {
+    if weapon.ammo_per_shot > 0 {
+        format!("{} | Ammo: {}", weapon.name, ammo)
+    } else {
+        weapon.name.clone()
+    }
+}
+
+fn update_weapon_text(
+    player_query: Query<Ref<Weapon>, With<Player>>,
+    inventory: Res<Inventory>,
+    mut query: Query<(&mut Text, Ref<WeaponText>)>,
+) {
+    let Ok(weapon) = player_query.single() else {
+        return;
+    };
+
+    for (mut text, marker) in query.iter_mut() {
+        if weapon.is_changed() || inventory.is_changed() || marker.is_added() {
+            text.0 = weapon_label(&weapon, inventory.ammo);
+        }
+    }
+}
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Game), spawn_hud)
+            .add_systems(
+                Update,
+                (
+                    update_health_bar,
+                    update_collectibles_text,
+                    update_weapon_text,
+                )
+                    .run_if(in_state(GameState::Game)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_health_bar_follows_player_health() {
+        let mut world = World::new();
+        let player = world.spawn((Player, Health { current: 1, max: 4 })).id();
+        let fill = world.spawn((HealthBarFill, Node::default())).id();
+
+        world.run_system_once(update_health_bar).unwrap();
+        assert_eq!(world.get::<Node>(fill).unwrap().width, Val::Percent(25.0));
+
+        world.get_mut::<Health>(player).unwrap().current = 4;
+        world.run_system_once(update_health_bar).unwrap();
+        assert_eq!(world.get::<Node>(fill).unwrap().width, Val::Percent(100.0));
+    }
+
+    #[test]
+    fn test_ammo_is_only_shown_for_weapons_using_it() {
+        assert_eq!(weapon_label(&Weapon::pistol(), 3), "Pistol");
+        assert_eq!(weapon_label(&Weapon::shotgun(), 3), "Shotgun | Ammo: 3");
+    }
+}
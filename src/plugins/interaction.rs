@@ -0,0 +1,629 @@
+use avian2d::prelude::{Collider, ColliderAabb, CollisionLayers, RigidBody};
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{
+    bundles::player::Player,
+    components::GameEntity,
+    constants::{self, GameLayer, multiply_by_tile_size},
+    entity_fields::{DoorFields, LeverFields, TriggerFields},
+    states::GameState,
+};
+
+use super::{
+    collision::child_collider_aabb,
+    level::{LdtkEntityAppExt, LdtkEntityContext, entity_world_rect, spawn_trigger_area},
+    pickup::Inventory,
+    player::PlayerAction,
+};
+
+/// Something the player can act on, like a lever, a door or an NPC
+#[derive(Component, Reflect)]
+pub struct Interactable {
+    /// Text shown to the player while the interactable is highlighted
+    pub prompt: String,
+}
+
+/// Marks the interactable closest to the player, remembering the tint it had before
+#[derive(Component)]
+pub struct Highlighted {
+    original_color: Color,
+}
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct InteractionConfig {
+    /// Max distance in pixels between the player and an interactable for it to be highlighted
+    pub range: f32,
+    pub highlight_color: Color,
+}
+
+impl Default for InteractionConfig {
+    fn default() -> Self {
+        Self {
+            range: multiply_by_tile_size(3),
+            highlight_color: Color::srgb(1.0, 0.9, 0.4),
+        }
+    }
+}
+
+#[derive(Component)]
+struct InteractionPrompt;
+
+const LEVER_OFF_COLOR: Color = Color::srgb(0.76, 0.55, 0.25);
+const LEVER_ON_COLOR: Color = Color::srgb(0.45, 0.8, 0.35);
+
+/// Instance id of the LDtk entity something was spawned from, for others to refer to it by
+#[derive(Component, Clone, Debug, PartialEq, Eq, Reflect)]
+pub struct LdtkIid(pub String);
+
+/// Switch signalling its targets whenever the player flips it
+#[derive(Component, Debug, Default, Reflect)]
+pub struct Lever {
+    pub on: bool,
+    /// `LdtkIid`s of the entities signalled
+    pub targets: Vec<String>,
+}
+
+/// Solid door, opened and closed by signals
+///
+/// Locked doors ignore signals until a key is spent on them, which opens them for good.
+/// Doors that no lever or trigger targets are opened for good by the player instead.
+#[derive(Component, Debug, Default, Reflect)]
+pub struct Door {
+    pub open: bool,
+    pub locked: bool,
+}
+
+/// Area signalling its targets on whenever the player walks into it
+#[derive(Component, Debug, Default, Reflect)]
+pub struct Trigger {
+    /// `LdtkIid`s of the entities signalled
+    pub targets: Vec<String>,
+    /// Whether the player was inside last frame, so staying in doesn't signal again
+    occupied: bool,
+}
+
+impl Trigger {
+    pub fn new(targets: Vec<String>) -> Self {
+        Self {
+            targets,
+            occupied: false,
+        }
+    }
+}
+
+/// Turns the entity with the `LdtkIid` `target` on or off, like a lever opening a door
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct SignalEvent {
+    pub target: String,
+    pub on: bool,
+}
+
+/// The interactable closest to `origin`, if any is within `range`
+pub fn nearest_in_range(
+    origin: Vec2,
+    candidates: impl IntoIterator<Item = (Entity, Vec2)>,
+    range: f32,
+) -> Option<Entity> {
+    candidates
+        .into_iter()
+        .map(|(entity, position)| (entity, origin.distance(position)))
+        .filter(|(_, distance)| *distance <= range)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity)
+}
+
+fn highlight_nearest_interactable(
+    mut commands: Commands,
+    player_query: Query<&Transform, With<Player>>,
+    mut interactable_query: Query<
+        (
+            Entity,
+            &Transform,
+            Option<&mut Sprite>,
+            Option<&Highlighted>,
+        ),
+        (With<Interactable>, Without<Player>),
+    >,
+    config: Res<InteractionConfig>,
+) {
+    let nearest = player_query.single().ok().and_then(|player_transform| {
+        nearest_in_range(
+            player_transform.translation.xy(),
+            interactable_query
+                .iter()
+                .map(|(entity, transform, _, _)| (entity, transform.translation.xy())),
+            config.range,
+        )
+    });
+
+    for (entity, _, sprite, highlighted) in interactable_query.iter_mut() {
+        match (Some(entity) == nearest, highlighted) {
+            (true, None) => {
+                let original_color = sprite.as_ref().map_or(Color::WHITE, |sprite| sprite.color);
+                if let Some(mut sprite) = sprite {
+                    sprite.color = config.highlight_color;
+                }
+                commands
+                    .entity(entity)
+                    .insert(Highlighted { original_color });
+            }
+            (false, Some(highlighted)) => {
+                if let Some(mut sprite) = sprite {
+                    sprite.color = highlighted.original_color;
+                }
+                commands.entity(entity).remove::<Highlighted>();
+            }
+            _ => {}
+        }
+    }
+}
+
+fn spawn_interaction_prompt(mut commands: Commands) {
+    commands.spawn((
+        InteractionPrompt,
+        Text2d::new(""),
+        TextFont {
+            font_size: 10.0,
+            ..default()
+        },
+        Transform::from_xyz(0.0, 0.0, 10.0),
+        Visibility::Hidden,
+        GameEntity,
+    ));
+}
+
+/// Show the highlighted interactable's prompt right above it
+fn update_interaction_prompt(
+    highlighted_query: Query<(&Interactable, &Transform), With<Highlighted>>,
+    mut prompt_query: Query<
+        (&mut Text2d, &mut Transform, &mut Visibility),
+        (With<InteractionPrompt>, Without<Highlighted>),
+    >,
+) {
+    let Ok((mut text, mut transform, mut visibility)) = prompt_query.single_mut() else {
+        return;
+    };
+
+    if let Some((interactable, interactable_transform)) = highlighted_query.iter().next() {
+        text.0.clone_from(&interactable.prompt);
+        transform.translation.x = interactable_transform.translation.x;
+        transform.translation.y = interactable_transform.translation.y + multiply_by_tile_size(2);
+        *visibility = Visibility::Visible;
+    } else {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Act on the highlighted interactable when the player presses Interact
+fn interact(
+    mut commands: Commands,
+    player_query: Query<&ActionState<PlayerAction>, With<Player>>,
+    mut lever_query: Query<&mut Lever, With<Highlighted>>,
+    mut door_query: Query<(Entity, &mut Door), With<Highlighted>>,
+    mut inventory: ResMut<Inventory>,
+    mut event_writer: EventWriter<SignalEvent>,
+) {
+    if !player_query
+        .iter()
+        .any(|action_state| action_state.just_pressed(&PlayerAction::Interact))
+    {
+        return;
+    }
+
+    for mut lever in lever_query.iter_mut() {
+        lever.on = !lever.on;
+        for target in &lever.targets {
+            event_writer.write(SignalEvent {
+                target: target.clone(),
+                on: lever.on,
+            });
+        }
+    }
+
+    for (entity, mut door) in door_query.iter_mut() {
+        if door.locked {
+            if inventory.keys == 0 {
+                continue;
+            }
+            inventory.keys -= 1;
+            door.locked = false;
+        }
+        door.open = true;
+        // Nothing left to do with it, and it stays open
+        commands
+            .entity(entity)
+            .remove::<(Interactable, Highlighted)>();
+    }
+}
+
+/// Let the player open closed doors that no lever or trigger is wired to, which would
+/// otherwise stay shut
+fn make_unwired_doors_interactable(
+    mut commands: Commands,
+    door_query: Query<(Entity, &LdtkIid, &Door), Added<Door>>,
+    lever_query: Query<&Lever>,
+    trigger_query: Query<&Trigger>,
+) {
+    for (entity, iid, door) in door_query.iter() {
+        let wired = lever_query
+            .iter()
+            .flat_map(|lever| &lever.targets)
+            .chain(trigger_query.iter().flat_map(|trigger| &trigger.targets))
+            .any(|target| *target == iid.0);
+        if !door.open && !door.locked && !wired {
+            commands.entity(entity).insert(Interactable {
+                prompt: "Open".to_string(),
+            });
+        }
+    }
+}
+
+/// Open or close the targeted doors, except locked ones, which only a key opens
+fn apply_signals(
+    mut event_reader: EventReader<SignalEvent>,
+    mut door_query: Query<(&LdtkIid, &mut Door)>,
+) {
+    for event in event_reader.read() {
+        for (iid, mut door) in door_query.iter_mut() {
+            if iid.0 == event.target && !door.locked {
+                door.open = event.on;
+            }
+        }
+    }
+}
+
+fn fire_triggers(
+    mut trigger_query: Query<(&mut Trigger, &ColliderAabb)>,
+    player_query: Query<&Children, With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut signal_writer: EventWriter<SignalEvent>,
+) {
+    let player_aabb = player_query
+        .iter()
+        .find_map(|children| child_collider_aabb(children, &aabb_query));
+
+    for (mut trigger, aabb) in trigger_query.iter_mut() {
+        let occupied = player_aabb.is_some_and(|player_aabb| player_aabb.intersects(aabb));
+        if occupied && !trigger.occupied {
+            for target in trigger.targets.iter() {
+                signal_writer.write(SignalEvent {
+                    target: target.clone(),
+                    on: true,
+                });
+            }
+        }
+        trigger.occupied = occupied;
+    }
+}
+
+/// Open doors let everything through and disappear
+fn update_doors(mut query: Query<(&Door, &mut CollisionLayers, &mut Visibility), Changed<Door>>) {
+    for (door, mut layers, mut visibility) in query.iter_mut() {
+        if door.open {
+            *layers = CollisionLayers::NONE;
+            *visibility = Visibility::Hidden;
+        } else {
+            *layers = CollisionLayers::new(
+                GameLayer::LevelGeometry,
+                [GameLayer::Player, GameLayer::Default],
+            );
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+/// Levers change color while on, the tint they go back to after a highlight included
+fn update_levers(
+    mut query: Query<(&Lever, &mut Sprite, Option<&mut Highlighted>), Changed<Lever>>,
+) {
+    for (lever, mut sprite, highlighted) in query.iter_mut() {
+        let color = if lever.on {
+            LEVER_ON_COLOR
+        } else {
+            LEVER_OFF_COLOR
+        };
+        match highlighted {
+            Some(mut highlighted) => highlighted.original_color = color,
+            None => sprite.color = color,
+        }
+    }
+}
+
+fn spawn_ldtk_lever(entity: &EntityInstance, context: &LdtkEntityContext, commands: &mut Commands) {
+    let targets = LeverFields::from_entity(entity)
+        .map(|fields| fields.targets)
+        .unwrap_or_default();
+    let (center, size) = entity_world_rect(entity);
+    commands.spawn((
+        Lever {
+            on: false,
+            targets: targets
+                .into_iter()
+                .map(|target| target.entity_iid)
+                .collect(),
+        },
+        Interactable {
+            prompt: "Pull".to_string(),
+        },
+        LdtkIid(entity.iid.clone()),
+        Sprite {
+            color: LEVER_OFF_COLOR,
+            custom_size: Some(size),
+            ..default()
+        },
+        Transform::from_translation(center.extend(0.5)),
+        context.level_id.clone(),
+    ));
+}
+
+fn spawn_ldtk_door(entity: &EntityInstance, context: &LdtkEntityContext, commands: &mut Commands) {
+    let (open, locked) = DoorFields::from_entity(entity)
+        .map_or((false, false), |fields| (fields.open, fields.locked));
+    let (center, size) = entity_world_rect(entity);
+    let mut door = commands.spawn((
+        Door { open, locked },
+        LdtkIid(entity.iid.clone()),
+        Sprite {
+            color: Color::srgb(0.42, 0.31, 0.23),
+            custom_size: Some(size),
+            ..default()
+        },
+        Transform::from_translation(center.extend(0.5)),
+        RigidBody::Static,
+        Collider::rectangle(size.x, size.y),
+        CollisionLayers::new(
+            GameLayer::LevelGeometry,
+            [GameLayer::Player, GameLayer::Default],
+        ),
+        context.level_id.clone(),
+    ));
+    if locked {
+        door.insert(Interactable {
+            prompt: "Unlock".to_string(),
+        });
+    }
+}
+
+fn spawn_ldtk_trigger(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    let targets = TriggerFields::from_entity(entity)
+        .map(|fields| fields.targets)
+        .unwrap_or_default();
+    spawn_trigger_area(
+        commands,
+        context.level_id,
+        entity,
+        Trigger::new(
+            targets
+                .into_iter()
+                .map(|target| target.entity_iid)
+                .collect(),
+        ),
+    );
+}
+
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::LEVER, spawn_ldtk_lever)
+            .register_ldtk_entity(constants::entities::DOOR, spawn_ldtk_door)
+            .register_ldtk_entity(constants::entities::TRIGGER, spawn_ldtk_trigger)
+            .init_resource::<InteractionConfig>()
+            .add_event::<SignalEvent>()
+            .add_systems(OnEnter(GameState::Game), spawn_interaction_prompt)
+            .add_systems(
+                Update,
+                (
+                    make_unwired_doors_interactable,
+                    highlight_nearest_interactable,
+                    update_interaction_prompt,
+                    interact,
+                    fire_triggers,
+                    apply_signals,
+                    update_doors,
+                    update_levers,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .register_type::<Interactable>()
+            .register_type::<InteractionConfig>()
+            .register_type::<Lever>()
+            .register_type::<Door>()
+            .register_type::<Trigger>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn spawn_interactable(world: &mut World, x: f32) -> Entity {
+        world
+            .spawn((
+                Interactable {
+                    prompt: "Pull".to_string(),
+                },
+                Sprite::default(),
+                Transform::from_xyz(x, 0.0, 0.0),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn test_nearest_interactable_in_range_is_highlighted() {
+        let mut world = World::new();
+        world.insert_resource(InteractionConfig {
+            range: 50.0,
+            ..default()
+        });
+        let player = world.spawn((Player, Transform::default())).id();
+        let near = spawn_interactable(&mut world, 20.0);
+        let farther = spawn_interactable(&mut world, -40.0);
+        let out_of_range = spawn_interactable(&mut world, 200.0);
+
+        world
+            .run_system_once(highlight_nearest_interactable)
+            .unwrap();
+
+        assert!(world.get::<Highlighted>(near).is_some());
+        assert!(world.get::<Highlighted>(farther).is_none());
+        assert!(world.get::<Highlighted>(out_of_range).is_none());
+        assert_eq!(
+            world.get::<Sprite>(near).unwrap().color,
+            InteractionConfig::default().highlight_color
+        );
+
+        // Moving away clears the highlight and restores the tint
+        world.get_mut::<Transform>(player).unwrap().translation.x = -1000.0;
+        world
+            .run_system_once(highlight_nearest_interactable)
+            .unwrap();
+
+        assert!(world.get::<Highlighted>(near).is_none());
+        assert_eq!(world.get::<Sprite>(near).unwrap().color, Color::WHITE);
+    }
+
+    #[test]
+    fn test_lever_opens_its_door_and_keys_unlock() {
+        let mut world = World::new();
+        world.init_resource::<Inventory>();
+        world.init_resource::<Events<SignalEvent>>();
+        let mut action_state = ActionState::<PlayerAction>::default();
+        action_state.press(&PlayerAction::Interact);
+        world.spawn((Player, action_state));
+
+        let closed_layers = CollisionLayers::new(
+            GameLayer::LevelGeometry,
+            [GameLayer::Player, GameLayer::Default],
+        );
+        let mut spawn_door = |iid: &str, locked: bool| {
+            world
+                .spawn((
+                    Door {
+                        open: false,
+                        locked,
+                    },
+                    LdtkIid(iid.to_string()),
+                    closed_layers,
+                    Visibility::default(),
+                ))
+                .id()
+        };
+        let door = spawn_door("door", false);
+        let other_door = spawn_door("other_door", false);
+        let locked_door = spawn_door("locked_door", true);
+        world.spawn((
+            Lever {
+                on: false,
+                targets: vec!["door".to_string(), "locked_door".to_string()],
+            },
+            Highlighted {
+                original_color: Color::WHITE,
+            },
+        ));
+
+        let run_systems = |world: &mut World| {
+            world.run_system_once(interact).unwrap();
+            world.run_system_once(apply_signals).unwrap();
+            world.run_system_once(update_doors).unwrap();
+            world.resource_mut::<Events<SignalEvent>>().clear();
+        };
+        run_systems(&mut world);
+
+        assert!(world.get::<Door>(door).unwrap().open);
+        assert_eq!(
+            *world.get::<CollisionLayers>(door).unwrap(),
+            CollisionLayers::NONE
+        );
+        assert!(!world.get::<Door>(other_door).unwrap().open);
+        // Locked doors don't listen to levers
+        assert!(!world.get::<Door>(locked_door).unwrap().open);
+
+        // With a key the locked door opens once it's the one highlighted
+        let lever = world
+            .query_filtered::<Entity, With<Lever>>()
+            .single(&world)
+            .unwrap();
+        world.entity_mut(lever).remove::<Highlighted>();
+        world.entity_mut(locked_door).insert(Highlighted {
+            original_color: Color::WHITE,
+        });
+        world.resource_mut::<Inventory>().keys = 1;
+        run_systems(&mut world);
+
+        assert!(world.get::<Door>(locked_door).unwrap().open);
+        assert_eq!(world.resource::<Inventory>().keys, 0);
+        assert_eq!(
+            *world.get::<CollisionLayers>(other_door).unwrap(),
+            closed_layers
+        );
+    }
+
+    #[test]
+    fn test_doors_nothing_opens_are_opened_by_hand() {
+        let mut world = World::new();
+        world.init_resource::<Inventory>();
+        let mut action_state = ActionState::<PlayerAction>::default();
+        action_state.press(&PlayerAction::Interact);
+        world.spawn((Player, action_state));
+        world.spawn(Trigger::new(vec!["wired_door".to_string()]));
+
+        let mut spawn_door = |iid: &str| {
+            world
+                .spawn((Door::default(), LdtkIid(iid.to_string())))
+                .id()
+        };
+        let wired_door = spawn_door("wired_door");
+        let unwired_door = spawn_door("unwired_door");
+        world
+            .run_system_once(make_unwired_doors_interactable)
+            .unwrap();
+
+        assert!(world.get::<Interactable>(wired_door).is_none());
+        assert!(world.get::<Interactable>(unwired_door).is_some());
+
+        world.entity_mut(unwired_door).insert(Highlighted {
+            original_color: Color::WHITE,
+        });
+        world.run_system_once(interact).unwrap();
+        assert!(world.get::<Door>(unwired_door).unwrap().open);
+        assert!(world.get::<Interactable>(unwired_door).is_none());
+    }
+
+    #[test]
+    fn test_lever_color_shows_whether_it_is_on() {
+        let mut world = World::new();
+        let lever = world
+            .spawn((
+                Lever::default(),
+                Sprite::from_color(LEVER_OFF_COLOR, Vec2::ONE),
+                Highlighted {
+                    original_color: LEVER_OFF_COLOR,
+                },
+            ))
+            .id();
+
+        // Highlighted while pulled, so the color shows once the highlight goes
+        world.get_mut::<Lever>(lever).unwrap().on = true;
+        world.run_system_once(update_levers).unwrap();
+        assert_eq!(
+            world.get::<Highlighted>(lever).unwrap().original_color,
+            LEVER_ON_COLOR
+        );
+
+        world.entity_mut(lever).remove::<Highlighted>();
+        world.get_mut::<Lever>(lever).unwrap().on = false;
+        world.run_system_once(update_levers).unwrap();
+        assert_eq!(world.get::<Sprite>(lever).unwrap().color, LEVER_OFF_COLOR);
+    }
+}
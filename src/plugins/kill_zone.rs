@@ -0,0 +1,122 @@
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+
+use crate::{
+    bundles::player::Player,
+    constants::{self, multiply_by_tile_size},
+    states::GameState,
+};
+
+use super::{
+    collision::child_collider_aabb,
+    health::{Health, PlayerDiedEvent},
+    level::{LdtkEntityAppExt, LdtkEntityContext, LevelBounds, spawn_trigger_area},
+};
+
+/// Area that kills the player outright on touch, like a bottomless pit or lava
+#[derive(Component, Debug, Default)]
+pub struct KillZone;
+
+/// How far below the bottom of the level the player can fall before dying
+const FALL_OUT_MARGIN: f32 = multiply_by_tile_size(4);
+
+/// Kill the player on touching a kill zone or falling out of the level
+///
+/// Unlike damage this ignores invincibility and god mode, there is no coming back
+/// from a bottomless pit.
+fn check_kill_zones(
+    mut player_query: Query<(Entity, &Transform, &Children, &mut Health), With<Player>>,
+    kill_zone_query: Query<&ColliderAabb, With<KillZone>>,
+    aabb_query: Query<&ColliderAabb>,
+    level_bounds: Option<Res<LevelBounds>>,
+    mut event_writer: EventWriter<PlayerDiedEvent>,
+) {
+    for (player, transform, children, mut health) in player_query.iter_mut() {
+        if health.is_dead() {
+            continue;
+        }
+
+        let fell_out = level_bounds.as_ref().is_some_and(|level_bounds| {
+            transform.translation.y < level_bounds.0.min.y - FALL_OUT_MARGIN
+        });
+        let in_kill_zone = child_collider_aabb(children, &aabb_query).is_some_and(|player_aabb| {
+            kill_zone_query
+                .iter()
+                .any(|kill_zone_aabb| kill_zone_aabb.intersects(&player_aabb))
+        });
+
+        if fell_out || in_kill_zone {
+            health.current = 0;
+            event_writer.write(PlayerDiedEvent { player });
+        }
+    }
+}
+
+fn spawn_ldtk_kill_zone(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    spawn_trigger_area(commands, context.level_id, entity, KillZone);
+}
+
+pub struct KillZonePlugin;
+
+impl Plugin for KillZonePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::KILL_ZONE, spawn_ldtk_kill_zone)
+            .add_systems(Update, check_kill_zones.run_if(in_state(GameState::Game)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn spawn_player(world: &mut World, position: Vec2) -> Entity {
+        world
+            .spawn((
+                Player,
+                Transform::from_translation(position.extend(1.0)),
+                Health::new(3),
+            ))
+            .with_child(ColliderAabb::new(position, Vec2::new(8.0, 16.0)))
+            .id()
+    }
+
+    fn deaths(world: &mut World) -> usize {
+        world.run_system_once(check_kill_zones).unwrap();
+        world
+            .resource_mut::<Events<PlayerDiedEvent>>()
+            .drain()
+            .count()
+    }
+
+    #[test]
+    fn test_kill_zone_and_falling_out_kill_once() {
+        let mut world = World::new();
+        world.init_resource::<Events<PlayerDiedEvent>>();
+        world.insert_resource(LevelBounds(Rect::new(0.0, -320.0, 640.0, 0.0)));
+        world.spawn((
+            KillZone,
+            ColliderAabb::new(Vec2::new(100.0, -300.0), Vec2::new(32.0, 8.0)),
+        ));
+
+        let safe = spawn_player(&mut world, Vec2::new(300.0, -100.0));
+        assert_eq!(deaths(&mut world), 0);
+        world.entity_mut(safe).despawn();
+
+        let in_lava = spawn_player(&mut world, Vec2::new(100.0, -290.0));
+        assert_eq!(deaths(&mut world), 1);
+        assert!(world.get::<Health>(in_lava).unwrap().is_dead());
+        // Still overlapping, but already dead
+        assert_eq!(deaths(&mut world), 0);
+        world.entity_mut(in_lava).despawn();
+
+        spawn_player(&mut world, Vec2::new(300.0, -500.0));
+        assert_eq!(deaths(&mut world), 1);
+    }
+}
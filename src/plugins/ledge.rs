@@ -0,0 +1,265 @@
+use std::time::Duration;
+
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{bundles::player::Player, schedule::MovementSet};
+
+use super::{
+    animation::NextAnimation,
+    climbing::Climbing,
+    collision::{IsGrounded, IsTouchingLedge, Velocity, child_collider_aabb},
+    gravity::EntityGravity,
+    player::{PlayerAction, PlayerAnimations},
+};
+
+/// Gap left between the player's collider and the level when snapping to or onto a ledge
+const LEDGE_SNAP_GAP: f32 = 0.1;
+
+/// Ability to hang from ledges and climb up onto them
+#[derive(Component, Reflect)]
+pub struct LedgeGrab {
+    /// Time it takes to pull up onto the ledge
+    pub climb_up_duration: Duration,
+}
+
+/// Present while the entity is holding on to a ledge
+#[derive(Component, Debug)]
+pub enum LedgeState {
+    /// Hanging from the ledge with the top corner of the wall at `corner`
+    Hanging { corner: Vec2, side: f32 },
+    /// Pulling up, the entity is moved on top of the ledge once the timer finishes
+    ClimbingUp {
+        corner: Vec2,
+        side: f32,
+        timer: Timer,
+    },
+}
+
+/// Translation that moves a collider spanning `min` to `max` to hang next to the ledge,
+/// its top level with the top of the wall
+pub fn hang_offset(min: Vec2, max: Vec2, corner: Vec2, side: f32) -> Vec2 {
+    let edge_x = if side < 0.0 { min.x } else { max.x };
+    Vec2::new(corner.x - side * LEDGE_SNAP_GAP - edge_x, corner.y - max.y)
+}
+
+/// Translation that moves a collider spanning `min` to `max` to stand on top of the ledge
+pub fn climb_up_offset(min: Vec2, max: Vec2, corner: Vec2, side: f32) -> Vec2 {
+    let width = max.x - min.x;
+    let target_min_x = if side < 0.0 {
+        corner.x - LEDGE_SNAP_GAP - width
+    } else {
+        corner.x + LEDGE_SNAP_GAP
+    };
+    Vec2::new(target_min_x - min.x, corner.y + LEDGE_SNAP_GAP - min.y)
+}
+
+/// Grab a ledge while falling past it, holding towards the wall
+pub fn grab_ledge(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &IsTouchingLedge,
+            &IsGrounded,
+            &Children,
+            &mut Velocity,
+            &mut Transform,
+        ),
+        (
+            With<Player>,
+            With<LedgeGrab>,
+            Without<LedgeState>,
+            Without<Climbing>,
+        ),
+    >,
+    aabb_query: Query<&ColliderAabb>,
+) {
+    for (
+        entity,
+        action_state,
+        is_touching_ledge,
+        is_grounded,
+        children,
+        mut velocity,
+        mut transform,
+    ) in query.iter_mut()
+    {
+        if is_grounded.grounded || velocity.0.y > 0.0 || action_state.pressed(&PlayerAction::Down) {
+            continue;
+        }
+
+        let ledge = if action_state.pressed(&PlayerAction::Left) {
+            is_touching_ledge.left.map(|corner| (corner, -1.0))
+        } else if action_state.pressed(&PlayerAction::Right) {
+            is_touching_ledge.right.map(|corner| (corner, 1.0))
+        } else {
+            None
+        };
+        let Some((corner, side)) = ledge else {
+            continue;
+        };
+        let Some(aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        transform.translation += hang_offset(aabb.min, aabb.max, corner, side).extend(0.0);
+        velocity.0 = Vec2::ZERO;
+        commands
+            .entity(entity)
+            .insert(LedgeState::Hanging { corner, side });
+    }
+}
+
+/// Hold still on the ledge, then climb up with Up/Jump or let go with Down
+pub fn update_ledge_state(
+    mut commands: Commands,
+    mut query: Query<(
+        Entity,
+        &ActionState<PlayerAction>,
+        &LedgeGrab,
+        &mut LedgeState,
+        &Children,
+        &mut Velocity,
+        &mut EntityGravity,
+        &mut Transform,
+        Option<&mut NextAnimation<PlayerAnimations>>,
+    )>,
+    aabb_query: Query<&ColliderAabb>,
+    time: Res<Time>,
+) {
+    for (
+        entity,
+        action_state,
+        ledge_grab,
+        mut ledge_state,
+        children,
+        mut velocity,
+        mut entity_gravity,
+        mut transform,
+        next_animation,
+    ) in query.iter_mut()
+    {
+        velocity.0 = Vec2::ZERO;
+        entity_gravity.enabled = false;
+
+        let animation = match &mut *ledge_state {
+            LedgeState::Hanging { corner, side } => {
+                if action_state.just_pressed(&PlayerAction::Up)
+                    || action_state.just_pressed(&PlayerAction::Jump)
+                {
+                    *ledge_state = LedgeState::ClimbingUp {
+                        corner: *corner,
+                        side: *side,
+                        timer: Timer::new(ledge_grab.climb_up_duration, TimerMode::Once),
+                    };
+                    PlayerAnimations::ClimbUp
+                } else if action_state.just_pressed(&PlayerAction::Down) {
+                    entity_gravity.enabled = true;
+                    commands.entity(entity).remove::<LedgeState>();
+                    continue;
+                } else {
+                    PlayerAnimations::Hang
+                }
+            }
+            LedgeState::ClimbingUp {
+                corner,
+                side,
+                timer,
+            } => {
+                timer.tick(time.delta());
+                if timer.finished() {
+                    if let Some(aabb) = child_collider_aabb(children, &aabb_query) {
+                        transform.translation +=
+                            climb_up_offset(aabb.min, aabb.max, *corner, *side).extend(0.0);
+                    }
+                    entity_gravity.enabled = true;
+                    commands.entity(entity).remove::<LedgeState>();
+                    continue;
+                }
+                PlayerAnimations::ClimbUp
+            }
+        };
+
+        if let Some(mut next_animation) = next_animation {
+            next_animation.key = Some(animation);
+        }
+    }
+}
+
+pub struct LedgePlugin;
+
+impl Plugin for LedgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (grab_ledge, update_ledge_state)
+                .chain()
+                .after(MovementSet::CollisionChecks)
+                .before(MovementSet::ApplyVelocity),
+        )
+        .register_type::<LedgeGrab>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_hang_and_climb_up_offsets() {
+        let min = Vec2::new(-8.0, -16.0);
+        let max = Vec2::new(8.0, 16.0);
+        let corner = Vec2::new(9.0, 10.0);
+
+        // Hanging: collider's top level with the ledge, right side against the wall
+        let hang = hang_offset(min, max, corner, 1.0);
+        assert_eq!(max.y + hang.y, corner.y);
+        assert!((max.x + hang.x - (corner.x - LEDGE_SNAP_GAP)).abs() < 1e-4);
+
+        // Climbed up: standing on top of the wall, clear of its edge
+        let up = climb_up_offset(min, max, corner, 1.0);
+        assert!((min.y + up.y - (corner.y + LEDGE_SNAP_GAP)).abs() < 1e-4);
+        assert!(min.x + up.x > corner.x);
+
+        let up_left = climb_up_offset(min, max, corner, -1.0);
+        assert!(max.x + up_left.x < corner.x);
+    }
+
+    #[test]
+    fn test_hanging_player_drops_on_down() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        let mut action_state = ActionState::<PlayerAction>::default();
+        action_state.press(&PlayerAction::Down);
+        let player = world
+            .spawn((
+                action_state,
+                LedgeGrab {
+                    climb_up_duration: Duration::from_millis(200),
+                },
+                LedgeState::Hanging {
+                    corner: Vec2::ZERO,
+                    side: 1.0,
+                },
+                Velocity(Vec2::new(0.0, -50.0)),
+                EntityGravity {
+                    gravity: 480.0,
+                    max_fall_speed: 240.0,
+                    enabled: false,
+                },
+                Transform::default(),
+            ))
+            .with_child(ColliderAabb::new(Vec2::ZERO, Vec2::new(8.0, 16.0)))
+            .id();
+
+        world.run_system_once(update_ledge_state).unwrap();
+
+        assert!(world.get::<LedgeState>(player).is_none());
+        assert!(world.get::<EntityGravity>(player).unwrap().enabled);
+    }
+}
@@ -1,129 +1,985 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use avian2d::prelude::{Collider, CollisionLayers, RigidBody};
-use bevy::prelude::*;
+use avian2d::prelude::{Collider, CollisionLayers, RigidBody, Rotation, Sensor};
+use bevy::{ecs::system::SystemParam, prelude::*};
+use ldtk_rust::{EntityInstance, LayerInstance, Level};
+use serde::Deserialize;
 
 use crate::{
-    bundles::level::{LevelBundle, StaticLevelData, TileCoords},
-    constants::{self, GameLayer, TILE_SIZE},
+    bundles::{
+        level::{LevelBundle, StaticLevelData, SurfaceEdges, TileCoords},
+        player::Player,
+        spawn_point::SetSpawn,
+    },
+    components::{LevelEntity, LevelId},
+    constants::{self, GameLayer, TILE_SIZE, int_grid::LevelGeometry, multiply_by_tile_size},
     states::GameState,
     tile_merger::TileMerger,
 };
 
-use super::player::PlayerSpawnEvent;
+use super::{
+    animated_tile::{animated_tile_def, animated_tile_run_bundle, find_animated_tile_runs},
+    breakable::{BREAKABLE_COLOR, Breakable},
+    collision::{OneWayPlatform, Velocity},
+    dev_console::{ConsoleCommandsAppExt, expect_args, parse_arg},
+    goal::check_exit_doors,
+    hazard::Hazard,
+    health::ContactDamage,
+    level_streaming::LoadedLevels,
+    parallax::{LEVEL_BACKGROUND_SCROLL, LEVEL_BACKGROUND_Z, ParallaxLayer},
+    player::PlayerSpawnEvent,
+    surface::{CONVEYOR_SPEED, Conveyor, SurfaceFriction, Water},
+    transition::{TransitionCoveredEvent, TransitionInterruptedEvent},
+};
+
+/// Fired whenever a level has been (re)built and play in it begins
+#[derive(Event, Clone, Debug)]
+pub struct LevelStartedEvent {
+    pub level_identifier: String,
+}
+
+/// Level that gets loaded when entering the game
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub struct CurrentLevel(pub String);
+
+impl Default for CurrentLevel {
+    fn default() -> Self {
+        Self(constants::levels::LEVEL_0.to_string())
+    }
+}
+
+/// Replace the loaded level with the one with this identifier
+#[derive(Event, Clone, Debug)]
+pub struct LoadLevelEvent(pub String);
+
+/// Level to load once a transition has covered the screen
+#[derive(Resource, Default, Debug)]
+pub struct PendingLevelLoad(pub Option<String>);
+
+/// Where the player starts in the next level loaded, instead of its player start, like a
+/// checkpoint from a saved game
+#[derive(Resource, Default, Debug)]
+pub struct SpawnOverride(pub Option<Vec2>);
+
+/// Level after the given one in the project, wrapping around to the first after the last
+pub fn next_level(level_identifier: &str) -> &'static str {
+    let levels = constants::levels::ALL;
+    let index = levels
+        .iter()
+        .position(|level| *level == level_identifier)
+        .map_or(0, |index| (index + 1) % levels.len());
+    levels[index]
+}
+
+/// The LDtk project all levels are built from, loaded as an asset
+#[derive(Asset, TypePath, Deserialize)]
+#[serde(transparent)]
+pub struct LdtkProject(pub ldtk_rust::Project);
+
+#[derive(Resource)]
+pub struct LdtkProjectHandle(pub Handle<LdtkProject>);
+
+fn load_ldtk_project(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(LdtkProjectHandle(asset_server.load("ldtk/project.ldtk")));
+}
+
+const SPIKE_DAMAGE: u32 = 1;
+const SPIKE_KNOCKBACK: f32 = multiply_by_tile_size(12);
+
+/// Depth between tile layers, small enough to keep every layer behind the level's entities
+const TILE_LAYER_Z_STEP: f32 = 0.01;
+
+/// Asset path of a file, like a tileset image, that LDtk refers to relative to the project file
+pub fn ldtk_asset_path(rel_path: &str) -> String {
+    let mut components: Vec<&str> = vec!["ldtk"];
+    for component in rel_path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// World space area covered by a level
+pub fn level_rect(level_data: &Level) -> Rect {
+    let top_left = Vec2::new(level_data.world_x as f32, (level_data.world_y * -1) as f32);
+    Rect::from_corners(
+        top_left,
+        top_left + Vec2::new(level_data.px_wid as f32, -level_data.px_hei as f32),
+    )
+}
+
+/// World space area covered by the current level
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct LevelBounds(pub Rect);
+
+/// How the merged level geometry is turned into physics colliders
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Resource)]
+pub enum LevelColliderMode {
+    /// One entity per merged rectangle, each with its own `SurfaceEdges`.
+    /// Needed when individual pieces of geometry must be addressable, e.g. breakables.
+    #[default]
+    PerEntity,
+    /// A single compound collider for the whole level, far fewer entities for the broadphase
+    Compound,
+}
+
+/// Where an LDtk entity is being spawned
+pub struct LdtkEntityContext<'a> {
+    /// Tag for whatever gets spawned, so it unloads along with its level
+    pub level_id: &'a LevelId,
+    /// World position of the level's top left corner
+    pub level_origin: Vec2,
+    /// Size of the entity layer's grid cells in pixels
+    pub grid_size: f32,
+}
+
+/// Spawns whatever an LDtk entity stands for
+pub type LdtkEntitySpawnFn = fn(&EntityInstance, &LdtkEntityContext, &mut Commands);
+
+/// How each kind of LDtk entity gets spawned, by identifier, filled in by the plugins
+/// owning them
+#[derive(Resource, Default)]
+pub struct LdtkEntityRegistry(HashMap<String, LdtkEntitySpawnFn>);
+
+impl LdtkEntityRegistry {
+    pub fn register(&mut self, identifier: &str, spawn: LdtkEntitySpawnFn) {
+        if self.0.insert(identifier.to_string(), spawn).is_some() {
+            warn!("LDtk entity {:?} registered twice", identifier);
+        }
+    }
+
+    pub fn get(&self, identifier: &str) -> Option<LdtkEntitySpawnFn> {
+        self.0.get(identifier).copied()
+    }
+}
+
+pub trait LdtkEntityAppExt {
+    fn register_ldtk_entity(&mut self, identifier: &str, spawn: LdtkEntitySpawnFn) -> &mut Self;
+}
+
+impl LdtkEntityAppExt for App {
+    fn register_ldtk_entity(&mut self, identifier: &str, spawn: LdtkEntitySpawnFn) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<LdtkEntityRegistry>()
+            .register(identifier, spawn);
+        self
+    }
+}
 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
         println!("Building level");
-        app.add_systems(OnEnter(GameState::Game), setup_level);
+        app.init_resource::<LevelColliderMode>()
+            .init_resource::<LdtkEntityRegistry>()
+            .init_resource::<CurrentLevel>()
+            .init_resource::<PendingLevelLoad>()
+            .init_resource::<SpawnOverride>()
+            .add_event::<LevelStartedEvent>()
+            .add_event::<LoadLevelEvent>()
+            .add_console_command("loadlevel", "<level identifier>", load_level_command)
+            .add_systems(Startup, load_ldtk_project)
+            .add_systems(OnEnter(GameState::Game), load_current_level)
+            .add_systems(OnExit(GameState::Game), clear_pending_level_load)
+            .add_systems(
+                Update,
+                (
+                    cancel_pending_level_load,
+                    (load_pending_level, load_level)
+                        .chain()
+                        .after(check_exit_doors)
+                        .run_if(in_state(GameState::Game)),
+                ),
+            )
+            .register_type::<LevelColliderMode>();
     }
 }
 
-pub fn setup_level(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
+/// Center (in Bevy world coordinates) and size of an LDtk entity, taking its pivot into account
+pub fn entity_world_rect(entity: &EntityInstance) -> (Vec2, Vec2) {
+    let size = Vec2::new(entity.width as f32, entity.height as f32);
+    let pivot = Vec2::new(entity.pivot[0] as f32, entity.pivot[1] as f32);
+    let world_position = Vec2::new(
+        entity.world_x.unwrap() as f32,
+        entity.world_y.unwrap() as f32,
+    );
+    // LDtk has y growing downwards
+    let center = world_position + (Vec2::splat(0.5) - pivot) * size;
+    (Vec2::new(center.x, -center.y), size)
+}
+
+/// Build a single collider out of the merged rectangles, relative to the level's top left corner
+pub fn compound_level_collider(collider_data: &[(f32, f32, f32, f32, SurfaceEdges)]) -> Collider {
+    Collider::compound(
+        collider_data
+            .iter()
+            .map(|&(center_x, center_y, width, height, _)| {
+                (
+                    Vec2::new(center_x, center_y * -1.0), // Flip Y coordinate for Bevy
+                    Rotation::default(),
+                    Collider::rectangle(width, height),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Coordinates of the level geometry tiles of the given kind
+pub fn int_grid_tiles(
+    int_grid_csv: &[i64],
+    width: usize,
+    kind: LevelGeometry,
+) -> HashSet<TileCoords> {
+    int_grid_csv
+        .iter()
+        .enumerate()
+        .filter(|&(_, &tile)| LevelGeometry::from_value(tile) == Some(kind))
+        .map(|(index, _)| TileCoords {
+            x: (index % width) as i64,
+            y: (index / width) as i64,
+        })
+        .collect()
+}
+
+/// Triangle collider for a single tile, 45 degree slope, relative to the tile's center
+pub fn slope_tile_collider(int_grid_value: i64) -> Option<Collider> {
+    let half = TILE_SIZE / 2.0;
+    let bottom_left = Vec2::new(-half, -half);
+    let bottom_right = Vec2::new(half, -half);
+    match LevelGeometry::from_value(int_grid_value)? {
+        LevelGeometry::SlopeUpRight => Some(Collider::triangle(
+            bottom_left,
+            bottom_right,
+            Vec2::new(half, half),
+        )),
+        LevelGeometry::SlopeUpLeft => Some(Collider::triangle(
+            bottom_left,
+            bottom_right,
+            Vec2::new(-half, half),
+        )),
+        _ => None,
+    }
+}
+
+/// Spawn a sensor area matching the LDtk entity's bounds
+pub fn spawn_trigger_area(
+    commands: &mut Commands,
+    level_id: &LevelId,
+    entity: &EntityInstance,
+    bundle: impl Bundle,
+) {
+    let (center, size) = entity_world_rect(entity);
+    commands.spawn((
+        bundle,
+        Transform::from_translation(center.extend(0.0)),
+        RigidBody::Static,
+        Collider::rectangle(size.x, size.y),
+        Sensor,
+        CollisionLayers::new(GameLayer::Trigger, [GameLayer::Player]),
+        level_id.clone(),
+    ));
+}
+
+fn load_current_level(
+    current_level: Res<CurrentLevel>,
+    mut event_writer: EventWriter<LoadLevelEvent>,
+) {
+    event_writer.write(LoadLevelEvent(current_level.0.clone()));
+}
+
+fn load_pending_level(
+    mut covered_events: EventReader<TransitionCoveredEvent>,
+    mut pending_load: ResMut<PendingLevelLoad>,
+    mut event_writer: EventWriter<LoadLevelEvent>,
+) {
+    if covered_events.read().count() == 0 {
+        return;
+    }
+    if let Some(level_identifier) = pending_load.0.take() {
+        event_writer.write(LoadLevelEvent(level_identifier));
+    }
+}
+
+/// A level load that lost its transition, or the game it was for, is forgotten, so it doesn't
+/// go off on a later transition or hold up the exit doors
+fn cancel_pending_level_load(
+    mut interrupted_events: EventReader<TransitionInterruptedEvent>,
+    mut pending_load: ResMut<PendingLevelLoad>,
+) {
+    if interrupted_events.read().count() > 0 {
+        pending_load.0 = None;
+    }
+}
+
+fn clear_pending_level_load(mut pending_load: ResMut<PendingLevelLoad>) {
+    pending_load.0 = None;
+}
+
+fn load_level_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let identifier: String = parse_arg(args, 0, "level identifier")?;
+    expect_args(args, 1)?;
+
+    let project_handle = world.resource::<LdtkProjectHandle>();
+    let project = world
+        .resource::<Assets<LdtkProject>>()
+        .get(&project_handle.0)
+        .ok_or("the LDtk project hasn't loaded yet")?;
+    if !project
+        .0
+        .levels
+        .iter()
+        .any(|level| level.identifier == identifier)
+    {
+        return Err(format!("unknown level '{}'", identifier));
+    }
+
+    world.send_event(LoadLevelEvent(identifier.clone()));
+    Ok(format!("loading level {}", identifier))
+}
+
+/// Despawn the loaded levels and build the requested one in their place
+///
+/// A player that is already around is moved to the new level's start, keeping its state,
+/// otherwise one gets spawned there.
+pub fn load_level(
+    mut load_events: EventReader<LoadLevelEvent>,
+    project_handle: Res<LdtkProjectHandle>,
+    projects: Res<Assets<LdtkProject>>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut loaded_levels: ResMut<LoadedLevels>,
+    mut spawn_override: ResMut<SpawnOverride>,
+    level_entity_query: Query<Entity, With<LevelEntity>>,
+    mut player_query: Query<(&mut Transform, &mut Velocity), With<Player>>,
+    mut builder: LevelBuilder,
     mut event_writer: EventWriter<PlayerSpawnEvent>,
+    mut set_spawn_writer: EventWriter<SetSpawn>,
+    mut level_started_writer: EventWriter<LevelStartedEvent>,
 ) {
-    let project = ldtk_rust::Project::new("assets/ldtk/project.ldtk");
-    let level_data = project
+    let Some(LoadLevelEvent(level_identifier)) = load_events.read().last() else {
+        return;
+    };
+    let Some(project) = projects.get(&project_handle.0) else {
+        warn!("loading level {:?} before the project", level_identifier);
+        return;
+    };
+    let Some(level_data) = project
+        .0
         .levels
         .iter()
-        .find(|level| level.identifier == constants::levels::LEVEL_0)
-        .unwrap();
-
-    if let Some(layers) = &level_data.layer_instances {
-        for layer in layers {
-            let identifier = layer.identifier.clone();
-            match identifier.as_str() {
-                constants::layers::LEVEL_GEOMETRY => {
-                    let width = layer.c_wid as usize;
-
-                    // Collect all solid tile positions
-                    let mut tile_positions = HashSet::new();
-                    for (index, &tile) in layer.int_grid_csv.iter().enumerate() {
-                        if tile == 1 {
-                            let x = (index % width) as i64;
-                            let y = (index / width) as i64;
-                            tile_positions.insert(TileCoords { x, y });
-                        }
-                    }
+        .find(|level| &level.identifier == level_identifier)
+    else {
+        warn!("unknown level: {:?}", level_identifier);
+        return;
+    };
 
-                    println!("Found {} individual tiles", tile_positions.len());
+    // Spawned enemies go along with their spawners, so may be gone already
+    for entity in level_entity_query.iter() {
+        builder.commands.entity(entity).try_despawn();
+    }
+    loaded_levels.0.clear();
+    current_level.0.clone_from(level_identifier);
 
-                    // Use tile merger to create optimized colliders
-                    let tile_merger = TileMerger::new(TILE_SIZE);
-                    let collider_data = tile_merger.create_collider_data(&tile_positions);
+    let bounds = level_rect(level_data);
+    builder.commands.insert_resource(LevelBounds(bounds));
+    loaded_levels.0.insert(level_identifier.clone(), bounds);
 
-                    println!("Merged into {} physics colliders", collider_data.len());
+    if let Some(mut player_start) = builder.build(level_data) {
+        if let Some(position) = spawn_override.0.take() {
+            player_start.translation = position.extend(player_start.translation.z);
+        }
+        set_spawn_writer.write(SetSpawn(player_start));
+        if let Ok((mut transform, mut velocity)) = player_query.single_mut() {
+            *transform = player_start;
+            velocity.0 = Vec2::ZERO;
+        } else {
+            event_writer.write(PlayerSpawnEvent(player_start));
+        }
+    } else {
+        warn!("level {:?} has no player start", level_identifier);
+    }
+
+    level_started_writer.write(LevelStartedEvent {
+        level_identifier: level_data.identifier.clone(),
+    });
+}
+
+/// Everything needed to spawn the contents of a level
+#[derive(SystemParam)]
+pub struct LevelBuilder<'w, 's> {
+    pub commands: Commands<'w, 's>,
+    asset_server: Res<'w, AssetServer>,
+    texture_atlas_layouts: ResMut<'w, Assets<TextureAtlasLayout>>,
+    entity_registry: Res<'w, LdtkEntityRegistry>,
+    collider_mode: Res<'w, LevelColliderMode>,
+}
+
+impl LevelBuilder<'_, '_> {
+    /// Spawn the level's geometry and entities, each tagged with the level's `LevelId`,
+    /// returning where the player starts in it
+    pub fn build(&mut self, level_data: &Level) -> Option<Transform> {
+        let level_id = LevelId(level_data.identifier.clone());
+        let mut player_start = None;
+
+        let level_entity = self
+            .commands
+            .spawn((
+                LevelBundle {
+                    level_data: StaticLevelData {
+                        level_identifier: level_data.identifier.clone(),
+                    },
+                },
+                level_id.clone(),
+                Transform::from_xyz(
+                    level_data.world_x as f32,
+                    (level_data.world_y * -1) as f32,
+                    0.0,
+                ),
+                Visibility::default(),
+            ))
+            .id();
+
+        if let Some(background_path) = &level_data.bg_rel_path {
+            self.commands.spawn((
+                ParallaxLayer {
+                    scroll: LEVEL_BACKGROUND_SCROLL,
+                    repeat_width: None,
+                },
+                Sprite::from_image(self.asset_server.load(ldtk_asset_path(background_path))),
+                Transform::from_translation(
+                    level_rect(level_data).center().extend(LEVEL_BACKGROUND_Z),
+                ),
+                level_id.clone(),
+            ));
+        }
+
+        if let Some(layers) = &level_data.layer_instances {
+            for (layer_index, layer) in layers.iter().enumerate() {
+                // LDtk lists the top-most layer first
+                self.spawn_tile_layer(
+                    level_entity,
+                    layer,
+                    -(layer_index as f32) * TILE_LAYER_Z_STEP,
+                );
+
+                let identifier = layer.identifier.clone();
+                match identifier.as_str() {
+                    constants::layers::LEVEL_GEOMETRY => {
+                        let width = layer.c_wid as usize;
+
+                        // Collect all solid tile positions
+                        let tile_positions =
+                            int_grid_tiles(&layer.int_grid_csv, width, LevelGeometry::Solid);
+
+                        println!("Found {} individual tiles", tile_positions.len());
+
+                        // Use tile merger to create optimized colliders
+                        let tile_merger = TileMerger::new(TILE_SIZE);
+                        let collider_data = tile_merger.create_collider_data(&tile_positions);
+
+                        println!("Merged into {} physics colliders", collider_data.len());
+
+                        let collision_layers = CollisionLayers::new(
+                            GameLayer::LevelGeometry,
+                            [GameLayer::Player, GameLayer::Default],
+                        );
+
+                        match *self.collider_mode {
+                            LevelColliderMode::PerEntity => {
+                                // Spawn merged colliders as children of the level, in one batch
+                                let collider_bundles: Vec<_> = collider_data
+                                    .into_iter()
+                                    .map(|(center_x, center_y, width, height, edges)| {
+                                        (
+                                            RigidBody::Static,
+                                            Collider::rectangle(width, height),
+                                            Transform::from_xyz(
+                                                center_x,
+                                                center_y * -1.0, // Flip Y coordinate for Bevy
+                                                0.0,
+                                            ),
+                                            collision_layers,
+                                            edges,
+                                            ChildOf(level_entity),
+                                        )
+                                    })
+                                    .collect();
+                                self.commands.spawn_batch(collider_bundles);
+                            }
+                            LevelColliderMode::Compound => {
+                                self.commands.spawn((
+                                    RigidBody::Static,
+                                    compound_level_collider(&collider_data),
+                                    Transform::default(),
+                                    collision_layers,
+                                    ChildOf(level_entity),
+                                ));
+                            }
+                        }
 
-                    let level_entity = commands
-                        .spawn((
-                            LevelBundle {
-                                level_data: StaticLevelData {
-                                    level_identifier: "test".to_string(),
+                        // Slopes can't be merged into rectangles, each tile gets its own triangle
+                        let slope_bundles: Vec<_> = layer
+                            .int_grid_csv
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(index, &tile)| {
+                                let collider = slope_tile_collider(tile)?;
+                                let x = (index % width) as f32;
+                                let y = (index / width) as f32;
+                                Some((
+                                    RigidBody::Static,
+                                    collider,
+                                    Transform::from_xyz(
+                                        (x + 0.5) * TILE_SIZE,
+                                        -(y + 0.5) * TILE_SIZE, // Flip Y coordinate for Bevy
+                                        0.0,
+                                    ),
+                                    collision_layers,
+                                    ChildOf(level_entity),
+                                ))
+                            })
+                            .collect();
+                        self.commands.spawn_batch(slope_bundles);
+
+                        // Breakable blocks aren't merged, each one breaks on its own
+                        let breakable_bundles: Vec<_> =
+                            int_grid_tiles(&layer.int_grid_csv, width, LevelGeometry::Breakable)
+                                .into_iter()
+                                .map(|tile| {
+                                    (
+                                        Breakable::default(),
+                                        Sprite::from_color(BREAKABLE_COLOR, Vec2::splat(TILE_SIZE)),
+                                        RigidBody::Static,
+                                        Collider::rectangle(TILE_SIZE, TILE_SIZE),
+                                        Transform::from_xyz(
+                                            (tile.x as f32 + 0.5) * TILE_SIZE,
+                                            -(tile.y as f32 + 0.5) * TILE_SIZE, // Flip Y coordinate for Bevy
+                                            0.0,
+                                        ),
+                                        collision_layers,
+                                        ChildOf(level_entity),
+                                    )
+                                })
+                                .collect();
+                        self.commands.spawn_batch(breakable_bundles);
+
+                        // Special ground gets colliders of its own even in compound mode, so
+                        // whatever stands on it can tell what it is
+                        let tiles = |value| int_grid_tiles(&layer.int_grid_csv, width, value);
+                        self.spawn_surface_colliders(
+                            level_entity,
+                            &tiles(LevelGeometry::Ice),
+                            (SurfaceFriction::ICE, collision_layers),
+                        );
+                        self.spawn_surface_colliders(
+                            level_entity,
+                            &tiles(LevelGeometry::ConveyorLeft),
+                            (
+                                Conveyor {
+                                    speed: -CONVEYOR_SPEED.to_pixels(),
                                 },
-                            },
-                            Transform::from_xyz(
-                                level_data.world_x as f32,
-                                (level_data.world_y * -1) as f32,
-                                0.0,
+                                collision_layers,
                             ),
-                            Sprite {
-                                image: asset_server.load(format!(
-                                    "ldtk/project/simplified/{}/_composite.png",
-                                    level_data.identifier
-                                )),
-                                anchor: bevy::sprite::Anchor::TopLeft,
-                                ..default()
-                            },
-                        ))
-                        .id();
-
-                    // Spawn merged colliders as children of the level
-                    for (center_x, center_y, width, height) in collider_data {
-                        let collider_entity = commands
-                            .spawn((
-                                RigidBody::Static,
-                                Collider::rectangle(width, height),
-                                Transform::from_xyz(
-                                    center_x,
-                                    center_y * -1.0, // Flip Y coordinate for Bevy
-                                    0.0,
-                                ),
+                        );
+                        self.spawn_surface_colliders(
+                            level_entity,
+                            &tiles(LevelGeometry::ConveyorRight),
+                            (
+                                Conveyor {
+                                    speed: CONVEYOR_SPEED.to_pixels(),
+                                },
+                                collision_layers,
+                            ),
+                        );
+                        self.spawn_surface_colliders(
+                            level_entity,
+                            &tiles(LevelGeometry::OneWayPlatform),
+                            (
+                                OneWayPlatform,
                                 CollisionLayers::new(
-                                    GameLayer::LevelGeometry,
+                                    GameLayer::OneWayPlatform,
                                     [GameLayer::Player, GameLayer::Default],
                                 ),
-                            ))
-                            .id();
+                            ),
+                        );
+
+                        // Hazards and water are areas to overlap rather than stand on
+                        let level_origin =
+                            Vec2::new(level_data.world_x as f32, (level_data.world_y * -1) as f32);
+                        self.spawn_tile_areas(
+                            level_origin,
+                            &level_id,
+                            &tiles(LevelGeometry::Spikes),
+                            (
+                                ContactDamage {
+                                    amount: SPIKE_DAMAGE,
+                                    knockback: SPIKE_KNOCKBACK,
+                                },
+                                Hazard,
+                            ),
+                        );
+                        self.spawn_tile_areas(
+                            level_origin,
+                            &level_id,
+                            &tiles(LevelGeometry::Water),
+                            Water,
+                        );
 
-                        commands.entity(level_entity).add_child(collider_entity);
+                        // Animated tiles are purely visual, one entity per horizontal run
+                        let animated_tile_bundles: Vec<_> =
+                            find_animated_tile_runs(&layer.int_grid_csv, width)
+                                .iter()
+                                .filter_map(|run| {
+                                    let def = animated_tile_def(run.value)?;
+                                    Some((
+                                        animated_tile_run_bundle(
+                                            run,
+                                            def,
+                                            &self.asset_server,
+                                            &mut self.texture_atlas_layouts,
+                                        ),
+                                        ChildOf(level_entity),
+                                    ))
+                                })
+                                .collect();
+                        self.commands.spawn_batch(animated_tile_bundles);
                     }
-                }
-                constants::layers::ENTITIES => {
-                    for entity in layer.entity_instances.iter() {
-                        match entity.identifier.as_str() {
-                            constants::entities::PLAYER_START => {
-                                println!("Spawning player, data: {:?}", entity);
-                                event_writer.write(PlayerSpawnEvent(Transform::from_xyz(
+                    constants::layers::ENTITIES => {
+                        let context = LdtkEntityContext {
+                            level_id: &level_id,
+                            level_origin: Vec2::new(
+                                level_data.world_x as f32,
+                                (level_data.world_y * -1) as f32,
+                            ),
+                            grid_size: layer.grid_size as f32,
+                        };
+                        for entity in layer.entity_instances.iter() {
+                            // Where the player starts is handed back rather than spawned
+                            if entity.identifier == constants::entities::PLAYER_START {
+                                player_start = Some(Transform::from_xyz(
                                     entity.world_x.unwrap() as f32,
                                     (entity.world_y.unwrap() * -1) as f32,
                                     1.0,
-                                )));
+                                ));
+                                continue;
                             }
-                            _ => {
-                                warn!("unhandled entity id: {:?}", entity.identifier)
+                            match self.entity_registry.get(&entity.identifier) {
+                                Some(spawn) => spawn(entity, &context, &mut self.commands),
+                                None => warn!("unhandled entity id: {:?}", entity.identifier),
                             }
                         }
                     }
-                }
-                constants::layers::LEVEL_GEOMETRY_TILES => {}
-                _ => {
-                    warn!("unhandled layer id: {:?}", layer.identifier)
+                    // Purely visual, spawned above along with any other layer's tiles
+                    constants::layers::LEVEL_GEOMETRY_TILES => {}
+                    _ => {
+                        warn!("unhandled layer id: {:?}", layer.identifier)
+                    }
                 }
             }
         }
+
+        player_start
+    }
+
+    /// Spawn merged colliders for the tiles as children of the level, each with a copy of
+    /// `bundle`
+    fn spawn_surface_colliders(
+        &mut self,
+        level_entity: Entity,
+        tiles: &HashSet<TileCoords>,
+        bundle: impl Bundle + Clone,
+    ) {
+        let collider_bundles: Vec<_> = TileMerger::new(TILE_SIZE)
+            .create_collider_data(tiles)
+            .into_iter()
+            .map(|(center_x, center_y, width, height, edges)| {
+                (
+                    bundle.clone(),
+                    RigidBody::Static,
+                    Collider::rectangle(width, height),
+                    Transform::from_xyz(center_x, center_y * -1.0, 0.0),
+                    edges,
+                    ChildOf(level_entity),
+                )
+            })
+            .collect();
+        self.commands.spawn_batch(collider_bundles);
+    }
+
+    /// Spawn merged sensor areas over the tiles, each with a copy of `bundle`
+    ///
+    /// The areas live in world space like the level's other triggers, with `level_origin`
+    /// being the level's top left corner.
+    fn spawn_tile_areas(
+        &mut self,
+        level_origin: Vec2,
+        level_id: &LevelId,
+        tiles: &HashSet<TileCoords>,
+        bundle: impl Bundle + Clone,
+    ) {
+        let area_bundles: Vec<_> = TileMerger::new(TILE_SIZE)
+            .create_collider_data(tiles)
+            .into_iter()
+            .map(|(center_x, center_y, width, height, _)| {
+                (
+                    bundle.clone(),
+                    Transform::from_xyz(level_origin.x + center_x, level_origin.y - center_y, 0.0),
+                    RigidBody::Static,
+                    Collider::rectangle(width, height),
+                    Sensor,
+                    CollisionLayers::new(GameLayer::Trigger, [GameLayer::Player]),
+                    level_id.clone(),
+                )
+            })
+            .collect();
+        self.commands.spawn_batch(area_bundles);
+    }
+
+    /// Spawn a sprite for each of the layer's tiles, manual and auto-layer alike, as children
+    /// of the level
+    fn spawn_tile_layer(&mut self, level_entity: Entity, layer: &LayerInstance, z: f32) {
+        let Some(tileset_rel_path) = &layer.tileset_rel_path else {
+            return;
+        };
+        let image = self.asset_server.load(ldtk_asset_path(tileset_rel_path));
+        let grid_size = layer.grid_size as f32;
+        let offset = Vec2::new(
+            layer.px_total_offset_x as f32,
+            layer.px_total_offset_y as f32,
+        );
+
+        let tile_bundles: Vec<_> = layer
+            .grid_tiles
+            .iter()
+            .chain(layer.auto_layer_tiles.iter())
+            .map(|tile| {
+                let source = Vec2::new(tile.src[0] as f32, tile.src[1] as f32);
+                // LDtk has y growing downwards
+                let position = Vec2::new(tile.px[0] as f32, tile.px[1] as f32)
+                    + offset
+                    + Vec2::splat(grid_size / 2.0);
+                (
+                    Sprite {
+                        image: image.clone(),
+                        rect: Some(Rect::from_corners(source, source + Vec2::splat(grid_size))),
+                        flip_x: tile.f & 1 != 0,
+                        flip_y: tile.f & 2 != 0,
+                        color: Color::WHITE.with_alpha((tile.a * layer.opacity) as f32),
+                        ..default()
+                    },
+                    Transform::from_xyz(position.x, -position.y, z),
+                    ChildOf(level_entity),
+                )
+            })
+            .collect();
+        self.commands.spawn_batch(tile_bundles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use avian2d::prelude::Position;
+    use bevy::ecs::world::CommandQueue;
+
+    use super::*;
+
+    #[test]
+    fn test_tileset_paths_resolve_against_the_project() {
+        assert_eq!(
+            ldtk_asset_path("../sprites/tile-template.png"),
+            "sprites/tile-template.png"
+        );
+        assert_eq!(ldtk_asset_path("tiles/cave.png"), "ldtk/tiles/cave.png");
+    }
+
+    #[test]
+    fn test_next_level_wraps_around() {
+        let levels = constants::levels::ALL;
+        for pair in levels.windows(2) {
+            assert_eq!(next_level(pair[0]), pair[1]);
+        }
+        assert_eq!(next_level(levels[levels.len() - 1]), levels[0]);
+        assert_eq!(next_level("not_a_level"), levels[0]);
+    }
+
+    #[test]
+    fn test_compound_collider_covers_same_area() {
+        // An L-shaped floor with a gap next to it
+        let mut tiles = HashSet::new();
+        for x in 0..6 {
+            tiles.insert(TileCoords { x, y: 4 });
+        }
+        for y in 0..4 {
+            tiles.insert(TileCoords { x: 0, y });
+        }
+        tiles.insert(TileCoords { x: 8, y: 4 });
+
+        let collider_data = TileMerger::new(TILE_SIZE).create_collider_data(&tiles);
+        let compound = compound_level_collider(&collider_data);
+        let individual: Vec<_> = collider_data
+            .iter()
+            .map(|&(center_x, center_y, width, height, _)| {
+                (
+                    Vec2::new(center_x, -center_y),
+                    Collider::rectangle(width, height),
+                )
+            })
+            .collect();
+
+        // Sample the center of every tile in and around the shapes
+        for x in -1..10 {
+            for y in -1..6 {
+                let point = Vec2::new((x as f32 + 0.5) * TILE_SIZE, -(y as f32 + 0.5) * TILE_SIZE);
+                let in_compound =
+                    compound.contains_point(Position::default(), Rotation::default(), point);
+                let in_individual = individual.iter().any(|(center, collider)| {
+                    collider.contains_point(*center, Rotation::default(), point)
+                });
+
+                assert_eq!(in_compound, in_individual, "mismatch at tile {}, {}", x, y);
+                assert_eq!(
+                    in_compound,
+                    tiles.contains(&TileCoords {
+                        x: x as i64,
+                        y: y as i64
+                    })
+                );
+            }
+        }
+    }
+
+    /// Times spawning a large level's colliders one by one with `add_child`, as it used to be
+    /// done, against the batched and compound spawning used now. Run it with
+    /// `cargo test --release bench_level_colliders -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_level_colliders() {
+        // 256 by 256 tiles, with holes all over so the merger leaves plenty of colliders
+        let tiles: HashSet<_> = (0..256)
+            .flat_map(|x| (0..256).map(move |y| TileCoords { x, y }))
+            .filter(|tile| (tile.x * 7 + tile.y * 13) % 5 != 0)
+            .collect();
+        let merge_start = Instant::now();
+        let collider_data = TileMerger::new(TILE_SIZE).create_collider_data(&tiles);
+        println!(
+            "merged {} tiles into {} colliders in {:?}",
+            tiles.len(),
+            collider_data.len(),
+            merge_start.elapsed()
+        );
+
+        let bundle =
+            |&(center_x, center_y, width, height, _): &(f32, f32, f32, f32, SurfaceEdges)| {
+                (
+                    RigidBody::Static,
+                    Collider::rectangle(width, height),
+                    Transform::from_xyz(center_x, -center_y, 0.0),
+                )
+            };
+        let time = |name: &str, spawn: &dyn Fn(&mut Commands, Entity)| {
+            let mut world = World::new();
+            let level = world.spawn_empty().id();
+            let start = Instant::now();
+            let mut queue = CommandQueue::default();
+            {
+                let mut commands = Commands::new(&mut queue, &world);
+                spawn(&mut commands, level);
+            }
+            queue.apply(&mut world);
+            println!(
+                "{}: {:?} for {} entities",
+                name,
+                start.elapsed(),
+                world.entities().len()
+            );
+        };
+
+        time("spawn and add_child each", &|commands, level| {
+            for data in &collider_data {
+                let child = commands.spawn(bundle(data)).id();
+                commands.entity(level).add_child(child);
+            }
+        });
+        time("spawn_batch", &|commands, level| {
+            let bundles: Vec<_> = collider_data
+                .iter()
+                .map(|data| (bundle(data), ChildOf(level)))
+                .collect();
+            commands.spawn_batch(bundles);
+        });
+        time("compound", &|commands, level| {
+            commands.spawn((
+                RigidBody::Static,
+                compound_level_collider(&collider_data),
+                Transform::default(),
+                ChildOf(level),
+            ));
+        });
+    }
+
+    #[test]
+    fn test_int_grid_tiles_by_kind() {
+        #[rustfmt::skip]
+        let grid = [
+            0, 5, 5,
+            1, 1, 5,
+        ];
+        assert_eq!(
+            int_grid_tiles(&grid, 3, LevelGeometry::Spikes),
+            HashSet::from([
+                TileCoords { x: 1, y: 0 },
+                TileCoords { x: 2, y: 0 },
+                TileCoords { x: 2, y: 1 },
+            ])
+        );
+        assert!(int_grid_tiles(&grid, 3, LevelGeometry::Ice).is_empty());
+    }
+
+    #[test]
+    fn test_slope_tile_colliders() {
+        let half = TILE_SIZE / 2.0;
+        let inside = |collider: &Collider, point: Vec2| {
+            collider.contains_point(Position::default(), Rotation::default(), point)
+        };
+
+        let up_right = slope_tile_collider(LevelGeometry::SlopeUpRight.value()).unwrap();
+        assert!(inside(&up_right, Vec2::new(half - 1.0, half - 2.0)));
+        assert!(!inside(&up_right, Vec2::new(-half + 1.0, half - 2.0)));
+
+        let up_left = slope_tile_collider(LevelGeometry::SlopeUpLeft.value()).unwrap();
+        assert!(inside(&up_left, Vec2::new(-half + 1.0, half - 2.0)));
+        assert!(!inside(&up_left, Vec2::new(half - 1.0, half - 2.0)));
+
+        assert!(slope_tile_collider(1).is_none());
+    }
+
+    #[test]
+    fn test_entity_registry_looks_up_by_identifier() {
+        fn spawn_walker(_: &EntityInstance, _: &LdtkEntityContext, _: &mut Commands) {}
+        fn spawn_bat(_: &EntityInstance, _: &LdtkEntityContext, _: &mut Commands) {}
+
+        let mut app = App::new();
+        app.register_ldtk_entity("Walker", spawn_walker)
+            .register_ldtk_entity("Bat", spawn_walker)
+            // Registering again replaces the earlier spawner
+            .register_ldtk_entity("Bat", spawn_bat);
+
+        let registry = app.world().resource::<LdtkEntityRegistry>();
+        let is = |identifier, spawn: LdtkEntitySpawnFn| {
+            registry
+                .get(identifier)
+                .is_some_and(|found| std::ptr::fn_addr_eq(found, spawn))
+        };
+        assert!(is("Walker", spawn_walker));
+        assert!(is("Bat", spawn_bat));
+        assert!(registry.get("Unknown").is_none());
     }
 }
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::{
+    bundles::player::Player,
+    components::{LevelEntity, LevelId},
+    constants::multiply_by_tile_size,
+    states::GameState,
+};
+
+use super::level::{
+    CurrentLevel, LdtkProject, LdtkProjectHandle, LevelBounds, LevelBuilder, level_rect, load_level,
+};
+
+/// Levels currently spawned, with the world space area each covers
+#[derive(Resource, Default, Debug)]
+pub struct LoadedLevels(pub HashMap<String, Rect>);
+
+/// How close the player has to get to a neighbouring level for it to load
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct LevelStreamingConfig {
+    /// Distance in pixels to a neighbour's bounds at which it gets loaded
+    pub load_distance: f32,
+    /// Distance in pixels at which a loaded level gets unloaded again, kept larger than
+    /// `load_distance` so levels don't flicker in and out at the boundary
+    pub unload_distance: f32,
+}
+
+impl Default for LevelStreamingConfig {
+    fn default() -> Self {
+        Self {
+            load_distance: multiply_by_tile_size(8),
+            unload_distance: multiply_by_tile_size(16),
+        }
+    }
+}
+
+/// Distance from `point` to the closest point of `rect`, zero inside it
+pub fn distance_to_rect(rect: Rect, point: Vec2) -> f32 {
+    point.distance(point.clamp(rect.min, rect.max))
+}
+
+/// Neighbours to load and loaded levels to unload for a player at `position`
+///
+/// The level the player is in is never unloaded, even if it's far from its edges.
+fn streaming_changes(
+    loaded: &HashMap<String, Rect>,
+    neighbours: &[(String, Rect)],
+    position: Vec2,
+    config: &LevelStreamingConfig,
+) -> (Vec<String>, Vec<String>) {
+    let to_load = neighbours
+        .iter()
+        .filter(|(identifier, rect)| {
+            !loaded.contains_key(identifier)
+                && distance_to_rect(*rect, position) <= config.load_distance
+        })
+        .map(|(identifier, _)| identifier.clone())
+        .collect();
+    let to_unload = loaded
+        .iter()
+        .filter(|(_, rect)| {
+            !rect.contains(position) && distance_to_rect(**rect, position) > config.unload_distance
+        })
+        .map(|(identifier, _)| identifier.clone())
+        .collect();
+    (to_load, to_unload)
+}
+
+/// Whether an entity goes along with the `unloaded` levels
+///
+/// Entities without a `LevelId`, like projectiles, go once they're outside every level still
+/// `loaded`, so none get left behind.
+fn unloads_with(
+    level_id: Option<&LevelId>,
+    position: Vec2,
+    unloaded: &[String],
+    loaded: &HashMap<String, Rect>,
+) -> bool {
+    match level_id {
+        Some(level_id) => unloaded.contains(&level_id.0),
+        None => !loaded.values().any(|rect| rect.contains(position)),
+    }
+}
+
+/// Load the neighbours of loaded levels as the player approaches them, and unload the ones
+/// left behind
+fn stream_levels(
+    player_query: Query<&Transform, With<Player>>,
+    level_entity_query: Query<(Entity, Option<&LevelId>, &Transform), With<LevelEntity>>,
+    project_handle: Res<LdtkProjectHandle>,
+    projects: Res<Assets<LdtkProject>>,
+    config: Res<LevelStreamingConfig>,
+    mut loaded_levels: ResMut<LoadedLevels>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut builder: LevelBuilder,
+) {
+    let (Ok(player_transform), Some(project)) =
+        (player_query.single(), projects.get(&project_handle.0))
+    else {
+        return;
+    };
+    let position = player_transform.translation.xy();
+
+    let neighbours: Vec<(String, Rect)> = project
+        .0
+        .levels
+        .iter()
+        .filter(|level| loaded_levels.0.contains_key(&level.identifier))
+        .flat_map(|level| level.neighbours.iter())
+        .filter_map(|neighbour| {
+            project
+                .0
+                .levels
+                .iter()
+                .find(|level| level.iid == neighbour.level_iid)
+        })
+        .map(|level| (level.identifier.clone(), level_rect(level)))
+        .collect();
+
+    let (to_load, to_unload) = streaming_changes(&loaded_levels.0, &neighbours, position, &config);
+
+    let unloaded: Vec<String> = to_unload
+        .into_iter()
+        .filter(|identifier| loaded_levels.0.remove(identifier).is_some())
+        .collect();
+    if !unloaded.is_empty() {
+        info!("Unloading levels {:?}", unloaded);
+        for (entity, level_id, transform) in level_entity_query.iter() {
+            if unloads_with(
+                level_id,
+                transform.translation.xy(),
+                &unloaded,
+                &loaded_levels.0,
+            ) {
+                // Spawned enemies go along with their spawners, so may be gone already
+                builder.commands.entity(entity).try_despawn();
+            }
+        }
+    }
+
+    for identifier in to_load {
+        let Some(level_data) = project
+            .0
+            .levels
+            .iter()
+            .find(|level| level.identifier == identifier)
+        else {
+            continue;
+        };
+        info!("Loading neighbouring level '{}'", identifier);
+        builder.build(level_data);
+        loaded_levels.0.insert(identifier, level_rect(level_data));
+    }
+
+    // Walking into another level makes it the current one
+    if let Some((identifier, rect)) = loaded_levels
+        .0
+        .iter()
+        .find(|(_, rect)| rect.contains(position))
+        && *identifier != current_level.0
+    {
+        current_level.0.clone_from(identifier);
+        builder.commands.insert_resource(LevelBounds(*rect));
+    }
+}
+
+pub struct LevelStreamingPlugin;
+
+impl Plugin for LevelStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadedLevels>()
+            .init_resource::<LevelStreamingConfig>()
+            .add_systems(
+                Update,
+                stream_levels
+                    .after(load_level)
+                    .run_if(in_state(GameState::Game)),
+            )
+            .register_type::<LevelStreamingConfig>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streaming_loads_close_neighbours_and_unloads_far_levels() {
+        let config = LevelStreamingConfig {
+            load_distance: 32.0,
+            unload_distance: 64.0,
+        };
+        let start = Rect::new(0.0, -256.0, 256.0, 0.0);
+        let east = Rect::new(256.0, -256.0, 512.0, 0.0);
+        let neighbours = vec![("east".to_string(), east)];
+
+        let loaded = HashMap::from([("start".to_string(), start)]);
+        assert_eq!(
+            streaming_changes(&loaded, &neighbours, Vec2::new(128.0, -128.0), &config),
+            (vec![], vec![])
+        );
+        assert_eq!(
+            streaming_changes(&loaded, &neighbours, Vec2::new(240.0, -128.0), &config),
+            (vec!["east".to_string()], vec![])
+        );
+
+        // Just across the boundary both stay loaded, deep into the east level the start is
+        // left behind
+        let loaded = HashMap::from([("start".to_string(), start), ("east".to_string(), east)]);
+        assert_eq!(
+            streaming_changes(&loaded, &neighbours, Vec2::new(288.0, -128.0), &config),
+            (vec![], vec![])
+        );
+        assert_eq!(
+            streaming_changes(&loaded, &neighbours, Vec2::new(400.0, -128.0), &config),
+            (vec![], vec!["start".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_unloading_leaves_nothing_behind() {
+        let loaded = HashMap::from([("east".to_string(), Rect::new(256.0, -256.0, 512.0, 0.0))]);
+        let unloaded = ["start".to_string()];
+        let start = LevelId("start".to_string());
+        let east = LevelId("east".to_string());
+
+        assert!(unloads_with(
+            Some(&start),
+            Vec2::new(300.0, -128.0),
+            &unloaded,
+            &loaded
+        ));
+        assert!(!unloads_with(
+            Some(&east),
+            Vec2::new(128.0, -128.0),
+            &unloaded,
+            &loaded
+        ));
+        // Untagged, like a projectile, stays while inside a level that's still loaded
+        assert!(!unloads_with(
+            None,
+            Vec2::new(300.0, -128.0),
+            &unloaded,
+            &loaded
+        ));
+        assert!(unloads_with(
+            None,
+            Vec2::new(128.0, -128.0),
+            &unloaded,
+            &loaded
+        ));
+        assert!(unloads_with(
+            None,
+            Vec2::new(-600.0, 0.0),
+            &unloaded,
+            &loaded
+        ));
+    }
+
+    #[test]
+    fn test_distance_to_rect() {
+        let rect = Rect::new(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(distance_to_rect(rect, Vec2::new(5.0, 5.0)), 0.0);
+        assert_eq!(distance_to_rect(rect, Vec2::new(13.0, 5.0)), 3.0);
+        assert_eq!(distance_to_rect(rect, Vec2::new(13.0, 14.0)), 5.0);
+    }
+}
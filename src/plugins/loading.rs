@@ -0,0 +1,195 @@
+use bevy::{asset::LoadState, prelude::*};
+
+use crate::{aseprite_deserialize::Aseprite, components::GameEntity, states::GameState};
+
+use super::{
+    animation_library::{AnimationDataHandles, AnimationManifest},
+    boss::BossDefinitions,
+    level::{LdtkProject, LdtkProjectHandle, ldtk_asset_path},
+};
+
+/// Assets the game waits on before it starts
+#[derive(Resource, Default)]
+struct LoadingAssets {
+    handles: Vec<UntypedHandle>,
+    /// The tileset images are only known once the project itself has loaded
+    tilesets_queued: bool,
+    /// Likewise for the sprite sheets listed in the animation manifest
+    animations_queued: bool,
+    /// The LDtk project failed to load, which leaves no levels to play
+    failed: bool,
+}
+
+#[derive(Component)]
+struct LoadingText;
+
+/// Whether an asset is done loading, one way or another
+///
+/// Missing assets don't block the game, whatever uses them falls back to placeholders. The
+/// LDtk project is the exception, see `project_error_message`.
+fn is_settled(load_state: &LoadState) -> bool {
+    matches!(load_state, LoadState::Loaded | LoadState::Failed(_))
+}
+
+/// What to show when the LDtk project couldn't be loaded, which the game can't go on without
+fn project_error_message(load_state: &LoadState) -> Option<String> {
+    match load_state {
+        LoadState::Failed(error) => Some(format!("Failed to load the levels:\n{}", error)),
+        _ => None,
+    }
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            ..default()
+        },
+        GameEntity,
+        children![(
+            LoadingText,
+            Text::new("Loading..."),
+            TextFont {
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(Color::WHITE),
+        )],
+    ));
+}
+
+fn track_loading(
+    mut loading: ResMut<LoadingAssets>,
+    asset_server: Res<AssetServer>,
+    project_handle: Option<Res<LdtkProjectHandle>>,
+    animation_handles: Option<Res<AnimationDataHandles>>,
+    boss_definitions: Option<Res<BossDefinitions>>,
+    projects: Res<Assets<LdtkProject>>,
+    manifests: Res<Assets<AnimationManifest>>,
+    mut text_query: Query<&mut Text, With<LoadingText>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let (Some(project_handle), Some(animation_handles), Some(boss_definitions)) =
+        (project_handle, animation_handles, boss_definitions)
+    else {
+        return;
+    };
+
+    if loading.failed {
+        return;
+    }
+    if let Some(message) = project_error_message(&asset_server.load_state(project_handle.0.id())) {
+        error!("{}", message);
+        for mut text in text_query.iter_mut() {
+            text.0.clone_from(&message);
+        }
+        loading.failed = true;
+        return;
+    }
+
+    if loading.handles.is_empty() {
+        loading.handles.extend([
+            project_handle.0.clone().untyped(),
+            animation_handles.manifest.clone().untyped(),
+            boss_definitions.0.clone().untyped(),
+        ]);
+    }
+
+    if !loading.tilesets_queued
+        && let Some(project) = projects.get(&project_handle.0)
+    {
+        let images: Vec<_> = project
+            .0
+            .defs
+            .tilesets
+            .iter()
+            .filter_map(|tileset| tileset.rel_path.as_deref())
+            .map(|rel_path| {
+                asset_server
+                    .load::<Image>(ldtk_asset_path(rel_path))
+                    .untyped()
+            })
+            .collect();
+        loading.handles.extend(images);
+        loading.tilesets_queued = true;
+    }
+
+    // A manifest that failed to load has nothing more to wait for
+    if !loading.animations_queued
+        && is_settled(&asset_server.load_state(animation_handles.manifest.id()))
+    {
+        let data: Vec<_> = manifests
+            .get(&animation_handles.manifest)
+            .into_iter()
+            .flat_map(|manifest| manifest.animations.values())
+            .map(|source| asset_server.load::<Aseprite>(&source.data).untyped())
+            .collect();
+        loading.handles.extend(data);
+        loading.animations_queued = true;
+    }
+
+    let settled = loading
+        .handles
+        .iter()
+        .filter(|handle| is_settled(&asset_server.load_state(handle.id())))
+        .count();
+    for mut text in text_query.iter_mut() {
+        text.0 = format!("Loading... {}/{}", settled, loading.handles.len());
+    }
+
+    if loading.tilesets_queued && loading.animations_queued && settled == loading.handles.len() {
+        info!("Loaded {} assets", settled);
+        next_state.set(GameState::Menu);
+    }
+}
+
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadingAssets>()
+            .add_systems(OnEnter(GameState::Loading), spawn_loading_screen)
+            .add_systems(Update, track_loading.run_if(in_state(GameState::Loading)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bevy::asset::{AssetLoadError, AssetPath};
+
+    use super::*;
+
+    #[test]
+    fn test_failed_project_is_reported() {
+        assert_eq!(project_error_message(&LoadState::Loading), None);
+        assert_eq!(project_error_message(&LoadState::Loaded), None);
+
+        let message = project_error_message(&LoadState::Failed(Arc::new(
+            AssetLoadError::AssetLoaderPanic {
+                path: AssetPath::from("ldtk/project.ldtk"),
+                loader_name: "json",
+            },
+        )))
+        .unwrap();
+        assert!(message.contains("ldtk/project.ldtk"), "{}", message);
+    }
+
+    #[test]
+    fn test_failed_assets_dont_block_loading() {
+        assert!(!is_settled(&LoadState::NotLoaded));
+        assert!(!is_settled(&LoadState::Loading));
+        assert!(is_settled(&LoadState::Loaded));
+        // Like a tileset image deleted from under the project
+        assert!(is_settled(&LoadState::Failed(Arc::new(
+            AssetLoadError::AssetLoaderPanic {
+                path: AssetPath::from("ldtk/tiles/cave.png"),
+                loader_name: "image",
+            }
+        ))));
+    }
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashSet, time::Duration};
+
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{bundles::player::Player, constants::GameLayer, states::GameState};
+
+use super::{
+    animation::NextAnimation,
+    animation_library::AnimationLibrary,
+    breakable::{Breakable, BreakableHitEvent, damage_breakables},
+    collision::child_collider_aabb,
+    enemy::Enemy,
+    health::{DamageEvent, Health, apply_damage},
+    player::{PlayerAction, PlayerAnimations},
+    slice_collider::{HITBOX_SLICE, SliceCollider, update_slice_colliders},
+};
+
+/// Hitbox in front of the player for as long as the sprite sheet has no attack hitbox slice
+const FALLBACK_HITBOX_SIZE: Vec2 = Vec2::new(20.0, 24.0);
+const FALLBACK_HITBOX_OFFSET: Vec2 = Vec2::new(22.0, 0.0);
+
+/// Close range attack, hitting whatever overlaps the attack hitbox slice
+#[derive(Component, Reflect)]
+pub struct MeleeAttack {
+    pub damage: u32,
+    /// Knockback speed, pointing away from the attacker and slightly up
+    pub knockback: f32,
+    pub duration: Duration,
+}
+
+/// Present while the entity is attacking
+#[derive(Component)]
+pub struct Attacking {
+    timer: Timer,
+    hitbox: Entity,
+}
+
+/// Sensor following the attack hitbox slice of the attacker's current frame
+#[derive(Component)]
+pub struct MeleeHitbox {
+    damage: u32,
+    knockback: f32,
+    /// Every target is hit once per attack
+    hit: HashSet<Entity>,
+}
+
+fn start_melee(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &ActionState<PlayerAction>,
+            &MeleeAttack,
+            &mut NextAnimation<PlayerAnimations>,
+        ),
+        (With<Player>, Without<Attacking>),
+    >,
+) {
+    for (entity, action_state, melee_attack, mut next_animation) in query.iter_mut() {
+        if !action_state.just_pressed(&PlayerAction::Melee) {
+            continue;
+        }
+
+        let hitbox = commands
+            .spawn((
+                MeleeHitbox {
+                    damage: melee_attack.damage,
+                    knockback: melee_attack.knockback,
+                    hit: HashSet::new(),
+                },
+                SliceCollider::new(AnimationLibrary::PLAYER, HITBOX_SLICE)
+                    .with_fallback(FALLBACK_HITBOX_SIZE, FALLBACK_HITBOX_OFFSET),
+                Sensor,
+                Collider::rectangle(1.0, 1.0),
+                CollisionLayers::new(GameLayer::PlayerProjectile, [GameLayer::Enemy]),
+                Transform::default(),
+                ChildOf(entity),
+            ))
+            .id();
+        commands.entity(entity).insert(Attacking {
+            timer: Timer::new(melee_attack.duration, TimerMode::Once),
+            hitbox,
+        });
+        next_animation.key = Some(PlayerAnimations::Attack);
+    }
+}
+
+/// End the attack once it's over, the hitbox follows the slice on its own meanwhile
+fn update_melee(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Attacking), With<Player>>,
+    time: Res<Time>,
+) {
+    for (entity, mut attacking) in query.iter_mut() {
+        if attacking.timer.tick(time.delta()).finished() {
+            commands.entity(attacking.hitbox).despawn();
+            commands.entity(entity).remove::<Attacking>();
+        }
+    }
+}
+
+fn melee_hits_enemy(
+    mut hitbox_query: Query<(&mut MeleeHitbox, &SliceCollider, &ColliderAabb, &ChildOf)>,
+    enemy_query: Query<(Entity, &Health, &Transform, &Children), With<Enemy>>,
+    attacker_query: Query<&Transform>,
+    aabb_query: Query<&ColliderAabb>,
+    mut event_writer: EventWriter<DamageEvent>,
+) {
+    for (mut hitbox, slice_collider, hitbox_aabb, child_of) in hitbox_query.iter_mut() {
+        // Wind-up and recovery frames have no hitbox
+        if !slice_collider.active {
+            continue;
+        }
+        let Ok(attacker_transform) = attacker_query.get(child_of.parent()) else {
+            continue;
+        };
+
+        for (enemy, health, transform, children) in enemy_query.iter() {
+            if health.is_dead() || hitbox.hit.contains(&enemy) {
+                continue;
+            }
+            let touching = child_collider_aabb(children, &aabb_query)
+                .is_some_and(|aabb| aabb.intersects(hitbox_aabb));
+            if !touching {
+                continue;
+            }
+
+            hitbox.hit.insert(enemy);
+            let away = if transform.translation.x < attacker_transform.translation.x {
+                -1.0
+            } else {
+                1.0
+            };
+            event_writer.write(DamageEvent {
+                target: enemy,
+                amount: hitbox.damage,
+                knockback: Vec2::new(away, 0.5).normalize() * hitbox.knockback,
+            });
+        }
+    }
+}
+
+fn melee_hits_breakables(
+    mut hitbox_query: Query<(&mut MeleeHitbox, &SliceCollider, &ColliderAabb)>,
+    breakable_query: Query<(Entity, &ColliderAabb), With<Breakable>>,
+    mut event_writer: EventWriter<BreakableHitEvent>,
+) {
+    for (mut hitbox, slice_collider, hitbox_aabb) in hitbox_query.iter_mut() {
+        if !slice_collider.active {
+            continue;
+        }
+
+        for (block, block_aabb) in breakable_query.iter() {
+            if hitbox.hit.contains(&block) || !block_aabb.intersects(hitbox_aabb) {
+                continue;
+            }
+            hitbox.hit.insert(block);
+            event_writer.write(BreakableHitEvent {
+                target: block,
+                damage: hitbox.damage,
+            });
+        }
+    }
+}
+
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                start_melee,
+                update_melee,
+                melee_hits_enemy,
+                melee_hits_breakables,
+            )
+                .chain()
+                .after(update_slice_colliders)
+                .before(apply_damage)
+                .before(damage_breakables)
+                .run_if(in_state(GameState::Game)),
+        )
+        .register_type::<MeleeAttack>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::super::enemy::EnemyKind;
+    use super::*;
+
+    #[test]
+    fn test_melee_hits_each_enemy_once_on_active_frames() {
+        let mut world = World::new();
+        world.init_resource::<Events<DamageEvent>>();
+
+        let player = world.spawn(Transform::default()).id();
+        let hitbox = world
+            .spawn((
+                MeleeHitbox {
+                    damage: 1,
+                    knockback: 100.0,
+                    hit: HashSet::new(),
+                },
+                SliceCollider::new(AnimationLibrary::PLAYER, HITBOX_SLICE),
+                ColliderAabb::new(Vec2::new(20.0, 0.0), Vec2::new(12.0, 8.0)),
+                ChildOf(player),
+            ))
+            .id();
+        let enemy = world
+            .spawn((
+                Enemy {
+                    kind: EnemyKind::Walker,
+                },
+                Health::new(2),
+                Transform::from_xyz(32.0, 0.0, 0.0),
+            ))
+            .with_child(ColliderAabb::new(Vec2::new(32.0, 0.0), Vec2::splat(8.0)))
+            .id();
+
+        let hits = |world: &mut World| {
+            world.run_system_once(melee_hits_enemy).unwrap();
+            world
+                .resource_mut::<Events<DamageEvent>>()
+                .drain()
+                .map(|event| (event.target, event.knockback.x > 0.0))
+                .collect::<Vec<_>>()
+        };
+
+        // Wind-up frame without a hitbox
+        assert_eq!(hits(&mut world), vec![]);
+
+        world.get_mut::<SliceCollider>(hitbox).unwrap().active = true;
+        assert_eq!(hits(&mut world), vec![(enemy, true)]);
+        // Still overlapping on the next frame, but already hit
+        assert_eq!(hits(&mut world), vec![]);
+    }
+}
@@ -1,14 +1,104 @@
 //pub mod _clause_collision;
+pub mod abilities;
+pub mod ai;
+pub mod aim;
+pub mod animated_tile;
 pub mod animation;
 pub mod animation_library;
+pub mod audio;
+pub mod boss;
+pub mod breakable;
 pub mod camera;
+pub mod character;
+pub mod charge_shot;
+pub mod climbing;
 pub mod collision;
+pub mod controls;
+pub mod crouch;
+pub mod dash;
+pub mod debug_overlay;
+pub mod dev_console;
+pub mod enemy;
+pub mod force_zone;
 pub mod game;
+pub mod goal;
 pub mod gravity;
+pub mod hazard;
+pub mod health;
+pub mod hot_reload;
+pub mod hud;
+pub mod interaction;
+pub mod kill_zone;
+pub mod ledge;
 pub mod level;
+pub mod level_streaming;
+pub mod loading;
+pub mod melee;
+pub mod moving_platform;
+pub mod noclip;
+pub mod parallax;
+pub mod pickup;
 pub mod player;
 pub mod projectile;
+pub mod replay;
+pub mod save;
+pub mod screens;
+pub mod settings;
+pub mod slice_collider;
+pub mod spawner;
+pub mod speedrun;
+pub mod sprite_effect;
+pub mod surface;
+pub mod time_control;
+pub mod touch_controls;
+pub mod trajectory;
+pub mod transition;
 
+pub use abilities::AbilitiesPlugin;
+pub use ai::AiPlugin;
+pub use aim::AimPlugin;
+pub use animated_tile::AnimatedTilePlugin;
 pub use animation_library::AnimationLibraryPlugin;
+pub use audio::AudioPlugin;
+pub use boss::BossPlugin;
+pub use breakable::BreakablePlugin;
 pub use camera::CameraPlugin;
+pub use character::CharacterPlugin;
+pub use charge_shot::ChargeShotPlugin;
+pub use climbing::ClimbingPlugin;
+pub use controls::ControlsPlugin;
+pub use crouch::CrouchPlugin;
+pub use dash::DashPlugin;
+pub use debug_overlay::DebugOverlayPlugin;
+pub use dev_console::DevConsolePlugin;
+pub use enemy::EnemyPlugin;
+pub use force_zone::ForceZonePlugin;
 pub use game::GamePlugin;
+pub use goal::GoalPlugin;
+pub use hazard::HazardPlugin;
+pub use health::HealthPlugin;
+pub use hot_reload::HotReloadPlugin;
+pub use hud::HudPlugin;
+pub use interaction::InteractionPlugin;
+pub use kill_zone::KillZonePlugin;
+pub use ledge::LedgePlugin;
+pub use level_streaming::LevelStreamingPlugin;
+pub use loading::LoadingPlugin;
+pub use melee::MeleePlugin;
+pub use moving_platform::MovingPlatformPlugin;
+pub use noclip::NoclipPlugin;
+pub use parallax::ParallaxPlugin;
+pub use pickup::PickupPlugin;
+pub use replay::InputRecorderPlugin;
+pub use save::SavePlugin;
+pub use screens::ScreensPlugin;
+pub use settings::SettingsPlugin;
+pub use slice_collider::SliceColliderPlugin;
+pub use spawner::SpawnerPlugin;
+pub use speedrun::SpeedrunPlugin;
+pub use sprite_effect::SpriteEffectPlugin;
+pub use surface::SurfacePlugin;
+pub use time_control::TimeControlPlugin;
+pub use touch_controls::TouchControlsPlugin;
+pub use trajectory::TrajectoryPlugin;
+pub use transition::TransitionPlugin;
@@ -0,0 +1,240 @@
+use avian2d::prelude::{Collider, CollisionLayers, RigidBody, TransformInterpolation};
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+
+use crate::{
+    constants::{self, GameLayer, TILE_SIZE},
+    entity_fields::MovingPlatformFields,
+    schedule::MovementSet,
+};
+
+use super::{
+    collision::MovementDelta,
+    level::{LdtkEntityAppExt, LdtkEntityContext, entity_world_rect},
+};
+
+/// What a platform does after reaching the last waypoint of its path
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum PlatformPathMode {
+    /// Head straight back to the first waypoint and go around again
+    #[default]
+    Loop,
+    /// Retrace the path backwards, then forwards again
+    PingPong,
+}
+
+impl PlatformPathMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "loop" => Some(Self::Loop),
+            "ping_pong" => Some(Self::PingPong),
+            _ => None,
+        }
+    }
+}
+
+/// Piece of level geometry travelling along a fixed path
+///
+/// Entities standing on it are carried along through its `MovementDelta`.
+#[derive(Component, Reflect)]
+#[require(MovementDelta, TransformInterpolation)]
+pub struct MovingPlatform {
+    /// World positions the platform's center passes through, in order
+    pub waypoints: Vec<Vec2>,
+    /// Pixels per second
+    pub speed: f32,
+    pub mode: PlatformPathMode,
+    /// Index of the waypoint currently headed for
+    next: usize,
+    /// Only changes in `PlatformPathMode::PingPong`
+    forward: bool,
+}
+
+impl MovingPlatform {
+    /// Platform starting at the first waypoint and heading for the second
+    pub fn new(waypoints: Vec<Vec2>, speed: f32, mode: PlatformPathMode) -> Self {
+        Self {
+            next: if waypoints.len() > 1 { 1 } else { 0 },
+            waypoints,
+            speed,
+            mode,
+            forward: true,
+        }
+    }
+
+    fn advance_waypoint(&mut self) {
+        let last = self.waypoints.len() - 1;
+        match self.mode {
+            PlatformPathMode::Loop => self.next = (self.next + 1) % self.waypoints.len(),
+            PlatformPathMode::PingPong => {
+                if self.forward && self.next == last {
+                    self.forward = false;
+                } else if !self.forward && self.next == 0 {
+                    self.forward = true;
+                }
+                self.next = if self.forward {
+                    self.next + 1
+                } else {
+                    self.next - 1
+                };
+            }
+        }
+    }
+
+    /// Move `distance` along the path from `position`, turning at as many waypoints as needed
+    pub fn advance(&mut self, position: Vec2, mut distance: f32) -> Vec2 {
+        if self.waypoints.len() < 2 {
+            return position;
+        }
+
+        let mut position = position;
+        // Bounded so a path of identical waypoints can't loop forever
+        for _ in 0..self.waypoints.len() * 2 {
+            let to_target = self.waypoints[self.next] - position;
+            let to_target_length = to_target.length();
+            if to_target_length > distance {
+                return position + to_target / to_target_length * distance;
+            }
+
+            position = self.waypoints[self.next];
+            distance -= to_target_length;
+            self.advance_waypoint();
+        }
+
+        position
+    }
+}
+
+pub fn move_platforms(
+    mut query: Query<(&mut MovingPlatform, &mut Transform, &mut MovementDelta)>,
+    time: Res<Time>,
+) {
+    for (mut platform, mut transform, mut movement_delta) in query.iter_mut() {
+        let before = transform.translation.xy();
+        let distance = platform.speed * time.delta_secs();
+        let after = platform.advance(before, distance);
+
+        transform.translation.x = after.x;
+        transform.translation.y = after.y;
+        movement_delta.0 = after - before;
+    }
+}
+
+/// Moving platform starting at the entity's position and following its `path` points
+///
+/// The path points are grid cells relative to the level at `level_origin`, the platform's
+/// center passes through the middle of each.
+fn moving_platform_from_entity(
+    entity: &EntityInstance,
+    level_origin: Vec2,
+    grid_size: f32,
+) -> Option<MovingPlatform> {
+    let fields = MovingPlatformFields::from_entity(entity)?;
+    let (center, _) = entity_world_rect(entity);
+    let mut waypoints = vec![center];
+    for point in fields.path {
+        waypoints.push(Vec2::new(
+            level_origin.x + (point.cx as f32 + 0.5) * grid_size,
+            level_origin.y - (point.cy as f32 + 0.5) * grid_size, // Flip Y coordinate for Bevy
+        ));
+    }
+
+    let Some(mode) = PlatformPathMode::from_name(&fields.mode) else {
+        warn!("unknown platform path mode: {:?}", fields.mode);
+        return None;
+    };
+
+    Some(MovingPlatform::new(
+        waypoints,
+        fields.speed * TILE_SIZE,
+        mode,
+    ))
+}
+
+fn spawn_ldtk_moving_platform(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    let Some(platform) =
+        moving_platform_from_entity(entity, context.level_origin, context.grid_size)
+    else {
+        warn!("invalid moving platform: {:?}", entity);
+        return;
+    };
+    let (center, size) = entity_world_rect(entity);
+    commands.spawn((
+        platform,
+        Sprite {
+            color: Color::srgb(0.56, 0.42, 0.31),
+            custom_size: Some(size),
+            ..default()
+        },
+        Transform::from_translation(center.extend(0.5)),
+        RigidBody::Kinematic,
+        Collider::rectangle(size.x, size.y),
+        CollisionLayers::new(
+            GameLayer::LevelGeometry,
+            [GameLayer::Player, GameLayer::Default],
+        ),
+        context.level_id.clone(),
+    ));
+}
+
+pub struct MovingPlatformPlugin;
+
+impl Plugin for MovingPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(
+            constants::entities::MOVING_PLATFORM,
+            spawn_ldtk_moving_platform,
+        )
+        .add_systems(FixedUpdate, move_platforms.in_set(MovementSet::Input))
+        .register_type::<MovingPlatform>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square_path() -> Vec<Vec2> {
+        vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+        ]
+    }
+
+    #[test]
+    fn test_loop_returns_to_start() {
+        let mut platform = MovingPlatform::new(square_path(), 1.0, PlatformPathMode::Loop);
+
+        let position = platform.advance(Vec2::ZERO, 15.0);
+        assert_eq!(position, Vec2::new(10.0, 5.0));
+
+        // Straight from the last waypoint back to the first
+        let closing_length = Vec2::new(10.0, 10.0).length();
+        let position = platform.advance(position, 5.0 + closing_length);
+        assert!(position.abs_diff_eq(Vec2::ZERO, 1e-4));
+
+        let position = platform.advance(position, 4.0);
+        assert!(position.abs_diff_eq(Vec2::new(4.0, 0.0), 1e-4));
+    }
+
+    #[test]
+    fn test_ping_pong_retraces_path() {
+        let mut platform = MovingPlatform::new(square_path(), 1.0, PlatformPathMode::PingPong);
+
+        // Overshoots the end and comes back down the last segment
+        let position = platform.advance(Vec2::ZERO, 24.0);
+        assert_eq!(position, Vec2::new(10.0, 6.0));
+
+        let position = platform.advance(position, 16.0);
+        assert_eq!(position, Vec2::ZERO);
+
+        // And turns around again at the start
+        let position = platform.advance(position, 3.0);
+        assert_eq!(position, Vec2::new(3.0, 0.0));
+    }
+}
@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{bundles::player::Player, constants::multiply_by_tile_size, schedule::MovementSet};
+
+use super::{
+    climbing::Climbing,
+    collision::{Noclip, Velocity},
+    dev_console::{ConsoleCommandsAppExt, console_player, expect_args},
+    gravity::EntityGravity,
+    player::PlayerAction,
+};
+
+const NOCLIP_TOGGLE_KEY: KeyCode = KeyCode::F2;
+
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct NoclipConfig {
+    /// Flying speed in pixels per second
+    pub speed: f32,
+}
+
+impl Default for NoclipConfig {
+    fn default() -> Self {
+        Self {
+            speed: multiply_by_tile_size(40),
+        }
+    }
+}
+
+fn toggle_noclip(
+    mut commands: Commands,
+    keys: Res<ButtonInput<KeyCode>>,
+    query: Query<(Entity, Has<Noclip>), With<Player>>,
+) {
+    if !keys.just_pressed(NOCLIP_TOGGLE_KEY) {
+        return;
+    }
+
+    for (entity, has_noclip) in query.iter() {
+        if has_noclip {
+            commands.entity(entity).remove::<Noclip>();
+        } else {
+            commands.entity(entity).insert(Noclip);
+        }
+    }
+}
+
+fn noclip_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    expect_args(args, 0)?;
+    let player = console_player(world)?;
+    let mut entity = world.entity_mut(player);
+    if entity.contains::<Noclip>() {
+        entity.remove::<Noclip>();
+        Ok("noclip off".to_string())
+    } else {
+        entity.insert(Noclip);
+        Ok("noclip on".to_string())
+    }
+}
+
+/// Move noclipping players directly from input, ignoring level geometry
+pub fn fly_noclip(
+    mut query: Query<(&ActionState<PlayerAction>, &mut Transform), (With<Player>, With<Noclip>)>,
+    config: Res<NoclipConfig>,
+    time: Res<Time>,
+) {
+    for (action_state, mut transform) in query.iter_mut() {
+        let mut direction = Vec2::ZERO;
+        if action_state.pressed(&PlayerAction::Left) {
+            direction.x -= 1.0;
+        }
+        if action_state.pressed(&PlayerAction::Right) {
+            direction.x += 1.0;
+        }
+        if action_state.pressed(&PlayerAction::Up) {
+            direction.y += 1.0;
+        }
+        if action_state.pressed(&PlayerAction::Down) {
+            direction.y -= 1.0;
+        }
+
+        let movement = direction.normalize_or_zero() * config.speed * time.delta_secs();
+        transform.translation += movement.extend(0.0);
+    }
+}
+
+/// Physics state from before noclip started, put back once it ends
+#[derive(Component)]
+struct NoclipRestore {
+    gravity_enabled: bool,
+}
+
+/// Start and end noclip with a clean slate so no momentum carries over
+///
+/// Climbing is let go of, since flying away would leave the entity attached to nothing.
+fn reset_on_noclip_added(
+    trigger: Trigger<OnAdd, Noclip>,
+    mut commands: Commands,
+    mut query: Query<(&mut Velocity, Option<&EntityGravity>, Has<Climbing>)>,
+) {
+    let entity = trigger.target();
+    let Ok((mut velocity, entity_gravity, is_climbing)) = query.get_mut(entity) else {
+        return;
+    };
+
+    velocity.0 = Vec2::ZERO;
+    if let Some(entity_gravity) = entity_gravity {
+        commands.entity(entity).insert(NoclipRestore {
+            // Climbing turns gravity off only until letting go
+            gravity_enabled: entity_gravity.enabled || is_climbing,
+        });
+    }
+    if is_climbing {
+        commands.entity(entity).remove::<Climbing>();
+    }
+}
+
+fn restore_on_noclip_removed(
+    trigger: Trigger<OnRemove, Noclip>,
+    mut commands: Commands,
+    mut query: Query<(
+        &mut Velocity,
+        Option<&mut EntityGravity>,
+        Option<&NoclipRestore>,
+    )>,
+) {
+    let entity = trigger.target();
+    if let Ok((mut velocity, entity_gravity, restore)) = query.get_mut(entity) {
+        velocity.0 = Vec2::ZERO;
+        if let Some(mut entity_gravity) = entity_gravity {
+            entity_gravity.enabled = restore.is_none_or(|restore| restore.gravity_enabled);
+        }
+        if restore.is_some() {
+            commands.entity(entity).remove::<NoclipRestore>();
+        }
+    }
+}
+
+pub struct NoclipPlugin;
+
+impl Plugin for NoclipPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NoclipConfig>()
+            .add_console_command("noclip", "", noclip_command)
+            .add_systems(Update, toggle_noclip)
+            .add_systems(FixedUpdate, fly_noclip.in_set(MovementSet::ApplyVelocity))
+            .add_observer(reset_on_noclip_added)
+            .add_observer(restore_on_noclip_removed)
+            .register_type::<NoclipConfig>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noclip_restores_physics_state() {
+        let mut world = World::new();
+        world.add_observer(reset_on_noclip_added);
+        world.add_observer(restore_on_noclip_removed);
+
+        let gravity = |enabled| EntityGravity {
+            gravity: 480.0,
+            max_fall_speed: 240.0,
+            enabled,
+        };
+        // Gravity switched off on purpose, e.g. right after a jump
+        let floating = world
+            .spawn((Velocity(Vec2::new(30.0, 40.0)), gravity(false)))
+            .id();
+        let climbing = world
+            .spawn((Velocity(Vec2::ZERO), gravity(false), Climbing))
+            .id();
+
+        for entity in [floating, climbing] {
+            world.entity_mut(entity).insert(Noclip);
+        }
+        assert_eq!(world.get::<Velocity>(floating).unwrap().0, Vec2::ZERO);
+        assert!(world.get::<Climbing>(climbing).is_none());
+
+        world.get_mut::<Velocity>(floating).unwrap().0 = Vec2::new(100.0, 0.0);
+        for entity in [floating, climbing] {
+            world.entity_mut(entity).remove::<Noclip>();
+        }
+
+        assert_eq!(world.get::<Velocity>(floating).unwrap().0, Vec2::ZERO);
+        assert!(!world.get::<EntityGravity>(floating).unwrap().enabled);
+        assert!(world.get::<EntityGravity>(climbing).unwrap().enabled);
+        assert!(world.get::<NoclipRestore>(floating).is_none());
+    }
+}
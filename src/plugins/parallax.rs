@@ -0,0 +1,174 @@
+use bevy::{prelude::*, sprite::SpriteImageMode};
+
+use crate::{bundles::camera::MainCamera, components::GameEntity, states::GameState};
+
+use super::camera::{CameraDelta, update_camera};
+
+/// Background layer scrolling slower than the level, to look further away
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct ParallaxLayer {
+    /// Fraction of the camera's movement the layer scrolls by on screen, per axis.
+    /// 1.0 moves along with the level, 0.0 stays put on the screen.
+    pub scroll: Vec2,
+    /// Width after which the layer's image repeats, for layers tiling infinitely to the sides
+    pub repeat_width: Option<f32>,
+}
+
+/// Sprite layer spawned behind every level
+#[derive(Clone, Debug, Reflect)]
+pub struct ParallaxLayerDef {
+    pub image_path: String,
+    /// Size of the image in pixels
+    pub size: Vec2,
+    pub scroll: Vec2,
+    /// Further back layers go lower, all of them below the level's tiles
+    pub z: f32,
+    pub repeat: bool,
+}
+
+/// Background layers shown in every level, on top of any background image the LDtk level has
+#[derive(Resource, Default, Reflect)]
+#[reflect(Resource)]
+pub struct ParallaxConfig {
+    pub layers: Vec<ParallaxLayerDef>,
+}
+
+/// How far an LDtk level's background image scrolls compared to the level
+pub const LEVEL_BACKGROUND_SCROLL: Vec2 = Vec2::splat(0.5);
+/// Depth of the LDtk level backgrounds, behind every configured layer
+pub const LEVEL_BACKGROUND_Z: f32 = -20.0;
+
+/// Horizontal position of a repeating layer moved by whole repeats to be as close as
+/// possible to `center`
+fn wrap_around(x: f32, center: f32, repeat_width: f32) -> f32 {
+    center + (x - center + repeat_width / 2.0).rem_euclid(repeat_width) - repeat_width / 2.0
+}
+
+fn spawn_parallax_layers(
+    mut commands: Commands,
+    config: Res<ParallaxConfig>,
+    asset_server: Res<AssetServer>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+) {
+    let camera_position = camera_query
+        .single()
+        .map_or(Vec2::ZERO, |transform| transform.translation.xy());
+
+    for def in &config.layers {
+        // Repeating layers are three repeats wide, so the screen is always covered while
+        // the middle one is kept under the camera
+        let (custom_size, image_mode) = if def.repeat {
+            (
+                Vec2::new(def.size.x * 3.0, def.size.y),
+                SpriteImageMode::Tiled {
+                    tile_x: true,
+                    tile_y: false,
+                    stretch_value: 1.0,
+                },
+            )
+        } else {
+            (def.size, SpriteImageMode::Auto)
+        };
+
+        commands.spawn((
+            ParallaxLayer {
+                scroll: def.scroll,
+                repeat_width: def.repeat.then_some(def.size.x),
+            },
+            Sprite {
+                image: asset_server.load(&def.image_path),
+                custom_size: Some(custom_size),
+                image_mode,
+                ..default()
+            },
+            Transform::from_translation(camera_position.extend(def.z)),
+            GameEntity,
+        ));
+    }
+}
+
+/// Move the layers by the part of the camera's movement they don't scroll by
+pub fn scroll_parallax_layers(
+    camera_delta: Res<CameraDelta>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+    mut layer_query: Query<(&ParallaxLayer, &mut Transform), Without<MainCamera>>,
+) {
+    let Ok(camera_transform) = camera_query.single() else {
+        return;
+    };
+
+    for (layer, mut transform) in layer_query.iter_mut() {
+        let follow = camera_delta.0 * (Vec2::ONE - layer.scroll);
+        transform.translation += follow.extend(0.0);
+
+        if let Some(repeat_width) = layer.repeat_width {
+            transform.translation.x = wrap_around(
+                transform.translation.x,
+                camera_transform.translation.x,
+                repeat_width,
+            );
+        }
+    }
+}
+
+pub struct ParallaxPlugin;
+
+impl Plugin for ParallaxPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParallaxConfig>()
+            .add_systems(OnEnter(GameState::Game), spawn_parallax_layers)
+            .add_systems(
+                Update,
+                scroll_parallax_layers
+                    .after(update_camera)
+                    .run_if(in_state(GameState::Game)),
+            )
+            .register_type::<ParallaxLayer>()
+            .register_type::<ParallaxConfig>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_layers_scroll_by_their_fraction_and_wrap() {
+        let mut world = World::new();
+        world.insert_resource(CameraDelta(Vec2::new(100.0, 10.0)));
+        world.spawn((MainCamera, Transform::from_xyz(100.0, 10.0, 0.0)));
+        let far = world
+            .spawn((
+                ParallaxLayer {
+                    scroll: Vec2::new(0.25, 0.0),
+                    repeat_width: None,
+                },
+                Transform::default(),
+            ))
+            .id();
+        let repeating = world
+            .spawn((
+                ParallaxLayer {
+                    scroll: Vec2::ONE,
+                    repeat_width: Some(64.0),
+                },
+                Transform::default(),
+            ))
+            .id();
+
+        world.run_system_once(scroll_parallax_layers).unwrap();
+
+        // A quarter of the camera's movement shows as scrolling, the rest is followed
+        assert_eq!(
+            world.get::<Transform>(far).unwrap().translation,
+            Vec3::new(75.0, 10.0, 0.0)
+        );
+        // Doesn't move with the camera, but jumps ahead by a repeat to stay under it
+        assert_eq!(
+            world.get::<Transform>(repeating).unwrap().translation,
+            Vec3::new(128.0, 0.0, 0.0)
+        );
+    }
+}
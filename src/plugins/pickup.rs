@@ -0,0 +1,295 @@
+use std::time::Duration;
+
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+
+use crate::{bundles::player::Player, constants, entity_fields::PickupFields, states::GameState};
+
+use super::{
+    collision::child_collider_aabb,
+    health::Health,
+    level::{LdtkEntityAppExt, LdtkEntityContext, LevelStartedEvent, spawn_trigger_area},
+    sprite_effect::FadeOut,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum PickupKind {
+    Coin,
+    HealthPack,
+    Ammo,
+    /// Opens a single locked door
+    Key,
+}
+
+impl PickupKind {
+    /// Kind matching the `kind` field of LDtk pickup entities
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "coin" => Some(Self::Coin),
+            "health_pack" => Some(Self::HealthPack),
+            "ammo" => Some(Self::Ammo),
+            "key" => Some(Self::Key),
+            _ => None,
+        }
+    }
+
+    /// Placeholder color until pickups get sprites of their own
+    fn color(self) -> Color {
+        match self {
+            Self::Coin => Color::srgb(0.95, 0.8, 0.3),
+            Self::HealthPack => Color::srgb(0.9, 0.3, 0.35),
+            Self::Ammo => Color::srgb(0.5, 0.7, 0.9),
+            Self::Key => Color::srgb(0.8, 0.8, 0.85),
+        }
+    }
+}
+
+/// Collectible, picked up by the player on touch
+#[derive(Component, Debug, Reflect)]
+#[require(Sprite)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    /// Coins, ammo, keys or health points gained
+    pub amount: u32,
+}
+
+impl Pickup {
+    pub fn new(kind: PickupKind, amount: u32) -> Self {
+        Self { kind, amount }
+    }
+}
+
+/// Fired when the player collects a pickup, for hooking up sounds and effects
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct PickupCollectedEvent {
+    pub player: Entity,
+    pub kind: PickupKind,
+    pub amount: u32,
+    pub position: Vec2,
+}
+
+/// What the player has collected during the current run of a level
+#[derive(Resource, Default, Debug, Reflect)]
+pub struct Inventory {
+    pub coins: u32,
+    /// Coins from the levels completed before this one, which restarts don't take away
+    pub banked_coins: u32,
+    pub ammo: u32,
+    /// Keys not yet spent on locked doors
+    pub keys: u32,
+}
+
+impl Inventory {
+    /// Take `amount` ammo, or none at all when there isn't that much left
+    pub fn spend_ammo(&mut self, amount: u32) -> bool {
+        match self.ammo.checked_sub(amount) {
+            Some(left) => {
+                self.ammo = left;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Pickup floating up while it fades out after being collected
+#[derive(Component)]
+struct CollectEffect;
+
+const PICKUP_SIZE: f32 = 8.0;
+const COLLECT_EFFECT_DURATION: Duration = Duration::from_millis(300);
+/// How far the effect rises over its duration
+const COLLECT_EFFECT_RISE: f32 = 12.0;
+
+/// Entering a level (again) starts the inventory from zero, so restarts don't carry it over
+fn reset_inventory(
+    mut event_reader: EventReader<LevelStartedEvent>,
+    mut inventory: ResMut<Inventory>,
+) {
+    if event_reader.read().last().is_some() {
+        *inventory = Inventory {
+            banked_coins: inventory.banked_coins,
+            ..default()
+        };
+    }
+}
+
+/// The coins of a completed level are the player's to keep
+fn bank_coins(mut inventory: ResMut<Inventory>) {
+    inventory.banked_coins += inventory.coins;
+}
+
+fn setup_pickup_sprites(mut query: Query<(&Pickup, &mut Sprite), Added<Pickup>>) {
+    for (pickup, mut sprite) in query.iter_mut() {
+        sprite.color = pickup.kind.color();
+        sprite.custom_size = Some(Vec2::splat(PICKUP_SIZE));
+    }
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    pickup_query: Query<(Entity, &Pickup, &ColliderAabb, &Transform, &Sprite)>,
+    player_query: Query<(Entity, &Children), With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut event_writer: EventWriter<PickupCollectedEvent>,
+) {
+    for (player, children) in player_query.iter() {
+        let Some(player_aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        for (entity, pickup, pickup_aabb, transform, sprite) in pickup_query.iter() {
+            if !pickup_aabb.intersects(&player_aabb) {
+                continue;
+            }
+
+            event_writer.write(PickupCollectedEvent {
+                player,
+                kind: pickup.kind,
+                amount: pickup.amount,
+                position: transform.translation.xy(),
+            });
+            commands.entity(entity).despawn();
+            commands.spawn((
+                CollectEffect,
+                FadeOut::new(COLLECT_EFFECT_DURATION).then_despawn(),
+                sprite.clone(),
+                *transform,
+            ));
+        }
+    }
+}
+
+fn apply_pickups(
+    mut event_reader: EventReader<PickupCollectedEvent>,
+    mut inventory: ResMut<Inventory>,
+    mut health_query: Query<&mut Health>,
+) {
+    for event in event_reader.read() {
+        match event.kind {
+            PickupKind::Coin => inventory.coins += event.amount,
+            PickupKind::Ammo => inventory.ammo += event.amount,
+            PickupKind::Key => inventory.keys += event.amount,
+            PickupKind::HealthPack => {
+                if let Ok(mut health) = health_query.get_mut(event.player)
+                    && !health.is_dead()
+                {
+                    health.current = (health.current + event.amount).min(health.max);
+                }
+            }
+        }
+    }
+}
+
+fn update_collect_effects(mut query: Query<&mut Transform, With<CollectEffect>>, time: Res<Time>) {
+    for mut transform in query.iter_mut() {
+        let rise = COLLECT_EFFECT_RISE * time.delta_secs() / COLLECT_EFFECT_DURATION.as_secs_f32();
+        transform.translation.y += rise;
+    }
+}
+
+fn pickup_from_entity(entity: &EntityInstance) -> Option<Pickup> {
+    let fields = PickupFields::from_entity(entity)?;
+    let Some(kind) = PickupKind::from_name(&fields.kind) else {
+        warn!("unknown pickup kind: {:?}", fields.kind);
+        return None;
+    };
+
+    Some(Pickup::new(kind, fields.amount as u32))
+}
+
+fn spawn_ldtk_pickup(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    if let Some(pickup) = pickup_from_entity(entity) {
+        spawn_trigger_area(commands, context.level_id, entity, pickup);
+    } else {
+        warn!("invalid pickup: {:?}", entity);
+    }
+}
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::PICKUP, spawn_ldtk_pickup)
+            .add_event::<PickupCollectedEvent>()
+            .init_resource::<Inventory>()
+            .add_systems(
+                Update,
+                (
+                    reset_inventory,
+                    setup_pickup_sprites,
+                    collect_pickups,
+                    apply_pickups,
+                    update_collect_effects,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnEnter(GameState::LevelComplete), bank_coins)
+            .register_type::<Pickup>()
+            .register_type::<Inventory>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn spawn_pickup(world: &mut World, kind: PickupKind, amount: u32, position: Vec2) -> Entity {
+        world
+            .spawn((
+                Pickup::new(kind, amount),
+                ColliderAabb::new(position, Vec2::splat(4.0)),
+                Transform::from_translation(position.extend(0.0)),
+            ))
+            .id()
+    }
+
+    #[test]
+    fn test_collecting_pickups() {
+        let mut world = World::new();
+        world.init_resource::<Events<PickupCollectedEvent>>();
+        world.init_resource::<Inventory>();
+
+        let player = world
+            .spawn((Player, Health { current: 1, max: 3 }))
+            .with_child(ColliderAabb::new(Vec2::ZERO, Vec2::new(8.0, 16.0)))
+            .id();
+        let coin = spawn_pickup(&mut world, PickupKind::Coin, 1, Vec2::new(4.0, 0.0));
+        spawn_pickup(&mut world, PickupKind::Ammo, 10, Vec2::new(0.0, 8.0));
+        spawn_pickup(&mut world, PickupKind::HealthPack, 5, Vec2::new(-4.0, 0.0));
+        let far_away = spawn_pickup(&mut world, PickupKind::Coin, 1, Vec2::new(100.0, 0.0));
+
+        world.run_system_once(collect_pickups).unwrap();
+        world.run_system_once(apply_pickups).unwrap();
+
+        let inventory = world.resource::<Inventory>();
+        assert_eq!((inventory.coins, inventory.ammo), (1, 10));
+        // Healing stops at full health
+        assert_eq!(world.get::<Health>(player).unwrap().current, 3);
+        assert!(world.get_entity(coin).is_err());
+        assert!(world.get_entity(far_away).is_ok());
+    }
+
+    #[test]
+    fn test_spending_ammo() {
+        let mut inventory = Inventory {
+            ammo: 2,
+            ..default()
+        };
+        assert!(inventory.spend_ammo(1));
+        assert!(inventory.spend_ammo(1));
+        // Out of ammo
+        assert!(!inventory.spend_ammo(1));
+        assert_eq!(inventory.ammo, 0);
+        // Weapons spending none fire anyway
+        assert!(inventory.spend_ammo(0));
+    }
+}
@@ -5,14 +5,17 @@ use bevy::{prelude::*, time::Stopwatch};
 use avian2d::prelude::*;
 
 use bevy_inspector_egui::InspectorOptions;
-use leafwing_input_manager::{
-    Actionlike,
-    prelude::{ActionState, InputMap},
-};
+use serde::{Deserialize, Serialize};
+
+use leafwing_input_manager::{Actionlike, prelude::ActionState};
 
 use crate::{
     bundles::player::Player,
-    constants::{GameLayer, PLAYER_HEIGHT, PLAYER_WIDTH, multiply_by_tile_size},
+    constants::{
+        GameLayer, PLAYER_HEIGHT, PLAYER_WIDTH, TilesPerSecond, TilesPerSecondSquared,
+        multiply_by_tile_size,
+    },
+    schedule::MovementSet,
 };
 
 /// Represents a rectangular bounds with position and dimensions
@@ -73,105 +76,225 @@ fn calculate_sprite_offset(
     Vec2::new(final_x, offset_y)
 }
 
+/// How much the player's collider is shrunk on every side compared to its hitbox
+///
+/// The hitbox is exactly as wide as a gap the player should fit through, so without
+/// a little slack the casts would hit both sides of the gap at once.
+pub const COLLIDER_INSET: f32 = 0.5;
+
+/// Size of the collider for a hitbox of `size`, shrunk by `inset` on every side
+pub fn inset_hitbox(size: Vec2, inset: f32) -> Vec2 {
+    (size - Vec2::splat(2.0 * inset)).max(Vec2::ONE)
+}
+
 /// Get sprite dimensions for the player
 /// This should match the actual sprite dimensions in the asset
 const PLAYER_SPRITE_WIDTH: f32 = 64.0;
 const PLAYER_SPRITE_HEIGHT: f32 = 64.0;
 
 use super::{
-    animation::{AnimationKey, AnimationPlugin, CurrentAnimation, NextAnimation},
-    animation_library::{AnimationConfig, AnimationLibrary},
-    collision::{CollisionBundle, CollisionConfig, GroundedStopwatch, IsGrounded, Velocity},
-    gravity::EntityGravity,
-    projectile::{ProjectileSpawnEvent, ProjectileVelocity},
+    abilities::PlayerAbilities,
+    aim::{AimDirection, AimMode},
+    animation::{
+        AnimationKey, AnimationPlaybackState, AnimationPlugin, CurrentAnimation, NextAnimation,
+    },
+    animation_library::{AnimationConfig, AnimationData, AnimationLibrary},
+    audio::{PlaySfxEvent, SfxId},
+    character::{
+        CharacterController, CharacterState, SpeedJump, WallJump, WallJumpMode,
+        tick_character_states,
+    },
+    charge_shot::{ChargeMultipliers, ChargeShot},
+    climbing::{Climber, Climbing},
+    collision::{
+        CollisionBundle, CollisionConfig, DEFAULT_MAX_SLOPE_ANGLE, GroundEntity, GroundedStopwatch,
+        IsGrounded, IsTouchingLedge, IsTouchingWallLeft, IsTouchingWallRight, MaxHorizontalSpeed,
+        Noclip, Velocity, WallLeftStopwatch, WallRightStopwatch,
+    },
+    controls::{KeyBindings, STICK_DEADZONE, apply_deadzone},
+    crouch::{Crouch, Crouching, Hitbox},
+    dash::{Dash, DashCooldownTimer},
+    gravity::{EntityGravity, apply_gravity},
+    hazard::SafeGround,
+    health::{Health, InvincibilityFrames},
+    ledge::{LedgeGrab, LedgeState},
+    melee::{Attacking, MeleeAttack},
+    pickup::Inventory,
+    projectile::{
+        Faction, ProjectileSpawnEvent, ProjectileVelocity, Weapon, WeaponCooldownTimer,
+        spread_directions,
+    },
+    surface::{ConveyorPush, InWater, SurfaceFriction, ground_friction, swim_stroke},
 };
 
 #[derive(Event)]
 pub struct PlayerSpawnEvent(pub Transform);
 
 #[derive(Event)]
-pub struct PlayerShootEvent;
+pub struct PlayerShootEvent {
+    /// How long Shoot was held before releasing, from 0.0 for a tap to 1.0 for a full charge
+    pub charge: f32,
+}
 
-#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect)]
+#[derive(Actionlike, PartialEq, Eq, Hash, Clone, Copy, Debug, Reflect, Serialize, Deserialize)]
 pub enum PlayerAction {
     Left,
     Right,
+    Up,
+    Down,
     Jump,
     Shoot,
+    /// Left stick, for walking slower when it's only partly tilted
+    #[actionlike(DualAxis)]
+    Move,
+    #[actionlike(DualAxis)]
+    Aim,
+    AimLock,
+    Dash,
+    Crouch,
+    /// Held to shoot upwards, diagonally while running
+    AimUp,
+    /// Held to shoot downwards, diagonally while running
+    AimDown,
+    Melee,
+    /// Acting on whatever is highlighted nearby, like pulling a lever
+    Interact,
 }
 
 #[derive(Component, Default, Reflect, Resource, InspectorOptions)]
 pub struct BarrelPosition(pub Vec2);
 
+/// Debug marker that makes the player ignore damage
 #[derive(Component, Default)]
-pub struct AfterJumpGravityImmunityTimer(pub Timer);
-
-#[derive(Component, Default)]
-pub struct JumpForce(pub f32);
-
-#[derive(Component, Default)]
-pub struct WalkSpeed(pub f32);
-
-#[derive(Component, Default)]
-pub struct WalkAcceleration(pub f32);
-
-#[derive(Component, Default)]
-pub struct GroundDeceleration(pub f32);
-
-#[derive(Component, Default)]
-pub struct CoyoteTime(pub Duration);
-
-#[derive(Component, Default)]
-pub struct JumpCooldownTimer(pub Timer);
+pub struct GodMode;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-enum PlayerAnimations {
+pub enum PlayerAnimations {
     Idle,
     Run,
     Jump,
+    Climb,
+    Hang,
+    ClimbUp,
+    Attack,
 }
 impl AnimationKey for PlayerAnimations {}
 
+// Player movement tuning
+const WALK_SPEED: TilesPerSecond = TilesPerSecond(10.0);
+/// Cap for walking combined with everything else pushing the player sideways
+const MAX_HORIZONTAL_SPEED: TilesPerSecond = TilesPerSecond(20.0);
+/// Reaches walk speed from standstill in 0.4 seconds
+const WALK_ACCELERATION: TilesPerSecondSquared = TilesPerSecondSquared(25.0);
+/// Stops from walk speed in 0.2 seconds
+const GROUND_DECELERATION: TilesPerSecondSquared = TilesPerSecondSquared(50.0);
+/// Upwards speed right after jumping
+const JUMP_VELOCITY: TilesPerSecond = TilesPerSecond(15.0);
+const GRAVITY: TilesPerSecondSquared = TilesPerSecondSquared(30.0);
+const MAX_FALL_SPEED: TilesPerSecond = TilesPerSecond(15.0);
+const CLIMB_SPEED: TilesPerSecond = TilesPerSecond(6.0);
+/// Pixels the player walks up onto without jumping, about a quarter tile
+const MAX_STEP_HEIGHT: f32 = 4.0;
+/// Pixels the player can be nudged sideways to slip past a ceiling corner while jumping
+const CORNER_CORRECTION: f32 = 4.0;
+/// Slowest and fastest the run animation plays, relative to running at walk speed
+const RUN_ANIMATION_MIN_SPEED: f32 = 0.5;
+const RUN_ANIMATION_MAX_SPEED: f32 = 2.0;
+
+/// How the player walks and jumps
+pub fn player_controller() -> CharacterController {
+    let walk_speed = WALK_SPEED.to_pixels();
+    let jump_force = JUMP_VELOCITY.to_pixels();
+
+    CharacterController::new(walk_speed, jump_force)
+        .with_acceleration(
+            WALK_ACCELERATION.to_pixels(),
+            GROUND_DECELERATION.to_pixels(),
+        )
+        .with_coyote_time(Duration::from_millis(500))
+        .with_jump_cooldown(Duration::from_millis(500))
+        .with_gravity_immunity(Duration::from_millis(300))
+        .with_jump_buffer(Duration::from_millis(120))
+        .with_jump_cut(0.5)
+        .with_sticky_feet()
+        .with_wall_jump(
+            WallJump {
+                horizontal_force: walk_speed,
+                vertical_force: jump_force,
+                mode: WallJumpMode::default(),
+            },
+            Duration::from_millis(150),
+        )
+}
+
+pub fn player_gravity() -> EntityGravity {
+    EntityGravity {
+        gravity: GRAVITY.to_pixels(),
+        max_fall_speed: MAX_FALL_SPEED.to_pixels(),
+        enabled: true,
+    }
+}
+
+pub fn player_collision_config() -> CollisionConfig {
+    CollisionConfig {
+        ground_check_distance: 1.0,
+        wall_check_distance: 1.0,
+        ceiling_check_distance: 1.0,
+        max_slope_angle: DEFAULT_MAX_SLOPE_ANGLE,
+        max_step_height: MAX_STEP_HEIGHT,
+        corner_correction: CORNER_CORRECTION,
+        collision_filter: SpatialQueryFilter::from_mask(GameLayer::LevelGeometry.to_bits()),
+    }
+}
+
 pub fn spawn_player(
     mut event_reader: EventReader<PlayerSpawnEvent>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
     animation_library: Res<AnimationLibrary>,
+    key_bindings: Res<KeyBindings>,
 ) {
-    let walk_speed = multiply_by_tile_size(10);
-    let walk_acceleration = walk_speed * 2.5;
-    let walk_deceleration = walk_acceleration * 2.0;
+    let character_controller = player_controller();
+    let walk_speed = character_controller.walk_speed;
 
-    let jump_force = multiply_by_tile_size(15);
-    let gravity = multiply_by_tile_size(30);
-    let max_fall_speed = multiply_by_tile_size(15);
-    let gravity_immunity_duration = Duration::from_millis(300);
-
-    let Some(player_anim_data) = &animation_library.player else {
+    let Some(player_anim_data) = animation_library.get(AnimationLibrary::PLAYER) else {
         return;
     };
 
     if let Some(event) = event_reader.read().last() {
-        let input_map = InputMap::new([
-            (PlayerAction::Jump, KeyCode::Space),
-            (PlayerAction::Left, KeyCode::ArrowLeft),
-            (PlayerAction::Left, KeyCode::KeyA),
-            (PlayerAction::Right, KeyCode::ArrowRight),
-            (PlayerAction::Right, KeyCode::KeyD),
-            (PlayerAction::Shoot, KeyCode::KeyJ),
-        ]);
+        let input_map = key_bindings.input_map();
 
         // Configure player animations
         let animation_configs = HashMap::from([
             (PlayerAnimations::Idle, AnimationConfig::looping("idle")),
             (PlayerAnimations::Run, AnimationConfig::looping("run")),
             (PlayerAnimations::Jump, AnimationConfig::once("jump")),
+            (
+                PlayerAnimations::Climb,
+                AnimationConfig::looping("climb").with_fallback("idle"),
+            ),
+            (
+                PlayerAnimations::Hang,
+                AnimationConfig::looping("hang").with_fallback("idle"),
+            ),
+            (
+                PlayerAnimations::ClimbUp,
+                AnimationConfig::once("climb_up")
+                    .with_fallback("jump")
+                    .uninterruptible(),
+            ),
+            (
+                PlayerAnimations::Attack,
+                AnimationConfig::once_then("attack", PlayerAnimations::Idle)
+                    .with_fallback("idle")
+                    .uninterruptible(),
+            ),
         ]);
 
         let animations = AnimationLibrary::create_animation_bundle(
             player_anim_data,
-            "sprites/player.png",
+            &player_anim_data.image_path,
             animation_configs,
             PlayerAnimations::Idle,
             &asset_server,
@@ -179,12 +302,8 @@ pub fn spawn_player(
         );
 
         // Get hitbox dimensions and offset from the slice data
-        let (hitbox_width, hitbox_height, hitbox_offset) = player_anim_data
-            .slices
-            .iter()
-            .find(|s| s.name == "hitbox")
-            .and_then(|s| s.keys.first())
-            .map(|key| {
+        let slice_hitbox = |name: &str| {
+            player_anim_data.first_slice_key(name).map(|key| {
                 let bounds = BoundsRect::from_aseprite_rect(&key.bounds);
                 let offset = calculate_sprite_offset(
                     &bounds,
@@ -194,7 +313,24 @@ pub fn spawn_player(
                 );
                 (bounds.width, bounds.height, offset)
             })
-            .unwrap_or((PLAYER_WIDTH, PLAYER_HEIGHT, Vec2::ZERO));
+        };
+        let (hitbox_width, hitbox_height, hitbox_offset) =
+            slice_hitbox("hitbox").unwrap_or((PLAYER_WIDTH, PLAYER_HEIGHT, Vec2::ZERO));
+        let standing_hitbox = Hitbox {
+            size: inset_hitbox(Vec2::new(hitbox_width, hitbox_height), COLLIDER_INSET),
+            offset: hitbox_offset,
+        };
+        // Without a slice of its own the crouching hitbox is the lower half of the standing one
+        let crouching_hitbox = slice_hitbox("hitbox_crouch").map_or_else(
+            || Hitbox {
+                size: standing_hitbox.size * Vec2::new(1.0, 0.5),
+                offset: hitbox_offset - Vec2::new(0.0, standing_hitbox.size.y / 4.0),
+            },
+            |(width, height, offset)| Hitbox {
+                size: inset_hitbox(Vec2::new(width, height), COLLIDER_INSET),
+                offset,
+            },
+        );
 
         commands
             .spawn((
@@ -206,56 +342,89 @@ pub fn spawn_player(
             ))
             .with_children(|children| {
                 children.spawn((
-                    Collider::rectangle(hitbox_width, hitbox_height),
+                    Collider::rectangle(standing_hitbox.size.x, standing_hitbox.size.y),
                     Transform::from_xyz(hitbox_offset.x, hitbox_offset.y, 0.0),
                 ));
             })
             .insert(CollisionBundle {
                 grounded_stopwatch: GroundedStopwatch(Stopwatch::new()),
-                config: CollisionConfig {
-                    ground_check_distance: 1.0,
-                    wall_check_distance: 1.0,
-                    ceiling_check_distance: 1.0,
-                    collision_filter: SpatialQueryFilter::from_mask(
-                        GameLayer::LevelGeometry.to_bits(),
-                    ),
-                },
+                config: player_collision_config(),
                 ..Default::default()
             })
             .insert(CollisionLayers::new(
                 GameLayer::Player,
-                [GameLayer::LevelGeometry, GameLayer::Default],
+                [
+                    GameLayer::LevelGeometry,
+                    GameLayer::Default,
+                    GameLayer::EnemyProjectile,
+                ],
             ))
             .insert((
-                EntityGravity {
-                    gravity,
-                    max_fall_speed,
-                    enabled: true,
-                },
-                CoyoteTime(Duration::from_millis(500)),
-                AfterJumpGravityImmunityTimer(Timer::new(
-                    gravity_immunity_duration,
-                    TimerMode::Once,
-                )),
-                JumpCooldownTimer(Timer::new(Duration::from_millis(500), TimerMode::Once)),
-                JumpForce(jump_force),
-                WalkSpeed(walk_speed),
-                WalkAcceleration(walk_acceleration),
-                GroundDeceleration(walk_deceleration),
+                player_gravity(),
+                character_controller,
+                MaxHorizontalSpeed(MAX_HORIZONTAL_SPEED.to_pixels()),
                 input_map,
                 BarrelPosition::default(),
+                AimDirection::default(),
+                AimMode::default(),
+            ))
+            .insert((
+                ConveyorPush::default(),
+                Dash {
+                    distance: multiply_by_tile_size(5),
+                    duration: Duration::from_millis(150),
+                    cooldown: Duration::from_millis(500),
+                },
+                DashCooldownTimer::default(),
+                Climber {
+                    speed: CLIMB_SPEED.to_pixels(),
+                },
+                LedgeGrab {
+                    climb_up_duration: Duration::from_millis(250),
+                },
+                IsTouchingLedge::default(),
+                SafeGround::default(),
+                Crouch {
+                    standing: standing_hitbox,
+                    crouching: crouching_hitbox,
+                    walk_speed_multiplier: 0.4,
+                    slide_min_speed: walk_speed * 0.75,
+                    slide_deceleration: GROUND_DECELERATION.to_pixels() / 4.0,
+                },
+            ))
+            .insert((
+                AnimationPlaybackState::default(),
+                Health::new(3),
+                InvincibilityFrames(Duration::from_secs(1)),
+                Weapon::pistol(),
+                WeaponCooldownTimer::default(),
+                MeleeAttack {
+                    damage: 1,
+                    knockback: multiply_by_tile_size(8),
+                    duration: Duration::from_millis(300),
+                },
+                ChargeShot {
+                    full_charge: Duration::from_secs(1),
+                    max_damage_multiplier: 3.0,
+                    max_size_multiplier: 3.0,
+                    max_speed_multiplier: 1.5,
+                },
             ));
     }
 }
 
 pub fn toggle_gravity(
-    action_state: Single<&ActionState<PlayerAction>, With<Player>>,
-    mut query: Query<(&mut EntityGravity, &mut AfterJumpGravityImmunityTimer)>,
-    time: Res<Time>,
+    mut query: Query<
+        (
+            &ActionState<PlayerAction>,
+            &mut EntityGravity,
+            &CharacterState,
+        ),
+        With<Player>,
+    >,
 ) {
-    for (mut entity_gravity, mut gravity_immunity_timer) in query.iter_mut() {
-        gravity_immunity_timer.0.tick(time.delta());
-        if gravity_immunity_timer.0.finished() || !action_state.pressed(&PlayerAction::Jump) {
+    for (action_state, mut entity_gravity, character_state) in query.iter_mut() {
+        if !character_state.is_gravity_immune() || !action_state.pressed(&PlayerAction::Jump) {
             entity_gravity.enabled = true;
         } else {
             entity_gravity.enabled = false;
@@ -263,97 +432,306 @@ pub fn toggle_gravity(
     }
 }
 
-fn apply_controls(
+/// Horizontal movement intent from input: -1.0 for left, 1.0 for right and 0.0 for none,
+/// or anything in between from a partly tilted stick.
+/// While aim-locked the directional input only aims, so the player doesn't move.
+fn movement_input(action_state: &ActionState<PlayerAction>, aim_mode: AimMode) -> f32 {
+    if aim_mode == AimMode::AimLock {
+        return 0.0;
+    }
+
+    let stick = apply_deadzone(
+        action_state.axis_pair(&PlayerAction::Move).x,
+        STICK_DEADZONE,
+    );
+    if stick != 0.0 {
+        stick
+    } else if action_state.pressed(&PlayerAction::Left) {
+        -1.0
+    } else if action_state.pressed(&PlayerAction::Right) {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Velocity right after jumping off a wall
+///
+/// `away_from_wall` is -1.0 when jumping off a wall on the right and 1.0 for a wall on the left.
+pub fn wall_jump_velocity(velocity: Vec2, away_from_wall: f32, wall_jump: &WallJump) -> Vec2 {
+    let vertical = match wall_jump.mode {
+        WallJumpMode::ResetVertical => wall_jump.vertical_force,
+        WallJumpMode::AddVertical => velocity.y + wall_jump.vertical_force,
+    };
+    Vec2::new(away_from_wall * wall_jump.horizontal_force, vertical)
+}
+
+/// Which way a wall jump would push the player, if one is possible
+///
+/// Touching a wall always allows it, and so does having left a wall less than
+/// `wall_coyote` ago. Returns 1.0 for jumping off a wall on the left and -1.0 for the right.
+pub fn wall_jump_direction(
+    touching_left: bool,
+    touching_right: bool,
+    since_left: Duration,
+    since_right: Duration,
+    wall_coyote: Duration,
+) -> Option<f32> {
+    if touching_left {
+        Some(1.0)
+    } else if touching_right {
+        Some(-1.0)
+    } else if since_left < wall_coyote && since_left <= since_right {
+        Some(1.0)
+    } else if since_right < wall_coyote {
+        Some(-1.0)
+    } else {
+        None
+    }
+}
+
+/// Horizontal velocity after a frame without movement input
+///
+/// Decelerates towards zero without overshooting, or stops immediately with `stop`
+/// (sticky feet). Pushes applied after the controls, like conveyors, still move the player.
+fn idle_velocity_x(velocity_x: f32, deceleration: f32, delta_secs: f32, stop: bool) -> f32 {
+    if stop {
+        return 0.0;
+    }
+
+    let step = deceleration * delta_secs;
+    if velocity_x > 0.0 {
+        (velocity_x - step).max(0.0)
+    } else {
+        (velocity_x + step).min(0.0)
+    }
+}
+
+/// Horizontal velocity after a frame of moving above a lowered speed cap, like crouching
+/// while already running
+///
+/// Slows down towards the cap by `deceleration` without dropping below it.
+fn slow_to_cap(velocity_x: f32, cap: f32, deceleration: f32, delta_secs: f32) -> f32 {
+    if velocity_x.abs() <= cap {
+        return velocity_x;
+    }
+    let slowed = velocity_x.abs() - deceleration * delta_secs;
+    slowed.max(cap).copysign(velocity_x)
+}
+
+/// Upwards velocity given by a jump, including the run speed bonus
+fn jump_velocity(jump_force: f32, horizontal_speed: f32, speed_jump: &SpeedJump) -> f32 {
+    let bonus = (horizontal_speed.abs() * speed_jump.factor).min(speed_jump.max_bonus);
+    jump_force + bonus
+}
+
+/// Vertical velocity after letting go of Jump, only cutting the jump short while rising
+fn cut_jump(vertical_velocity: f32, jump_cut: f32) -> f32 {
+    if vertical_velocity > 0.0 {
+        vertical_velocity * jump_cut
+    } else {
+        vertical_velocity
+    }
+}
+
+fn update_jump_buffer(
+    mut query: Query<(&ActionState<PlayerAction>, &mut CharacterState), With<Player>>,
+) {
+    for (action_state, mut character_state) in query.iter_mut() {
+        if action_state.just_pressed(&PlayerAction::Jump) {
+            character_state.buffer_jump();
+        }
+    }
+}
+
+pub fn apply_controls(
     action_state: Single<&ActionState<PlayerAction>, With<Player>>,
-    mut event_writer: EventWriter<PlayerShootEvent>,
     mut query: Query<
         (
             &mut Velocity,
             &IsGrounded,
-            &mut AfterJumpGravityImmunityTimer,
             &GroundedStopwatch,
-            &CoyoteTime,
-            &JumpForce,
-            &WalkSpeed,
-            &WalkAcceleration,
-            &GroundDeceleration,
-            &mut JumpCooldownTimer,
+            &CharacterController,
+            &mut CharacterState,
+            (
+                Option<&Crouch>,
+                Option<&Crouching>,
+                Has<Attacking>,
+                Has<InWater>,
+            ),
+            Option<&GroundEntity>,
             &mut Sprite,
             &mut NextAnimation<PlayerAnimations>,
+            &AimMode,
+            (
+                &IsTouchingWallLeft,
+                &IsTouchingWallRight,
+                &mut WallLeftStopwatch,
+                &mut WallRightStopwatch,
+            ),
+        ),
+        (
+            With<Player>,
+            Without<Noclip>,
+            Without<Climbing>,
+            Without<LedgeState>,
         ),
-        With<Player>,
     >,
+    friction_query: Query<&SurfaceFriction>,
+    abilities: Res<PlayerAbilities>,
     time: Res<Time>,
+    mut sfx_writer: EventWriter<PlaySfxEvent>,
 ) {
     for (
         mut velocity,
         is_grounded,
-        mut after_jump_gravity_immunity_timer,
         grounded_stopwatch,
-        coyote_time,
-        jump_force,
-        walk_speed,
-        walk_acceleration,
-        ground_deceleration,
-        mut jump_cooldown_timer,
+        controller,
+        mut character_state,
+        (crouch, crouching, attacking, in_water),
+        ground_entity,
         mut sprite,
         mut next_animation,
+        aim_mode,
+        (
+            is_touching_wall_left,
+            is_touching_wall_right,
+            mut wall_left_stopwatch,
+            mut wall_right_stopwatch,
+        ),
     ) in query.iter_mut()
     {
         let mut direction = Vec2::ZERO;
 
-        jump_cooldown_timer.0.tick(time.delta());
-
         let mut is_running = false;
         let mut just_jumped = false;
 
-        if action_state.pressed(&PlayerAction::Left) {
-            if velocity.0.x > -walk_speed.0 {
-                direction.x = -walk_acceleration.0 * time.delta_secs();
+        let move_input = movement_input(&action_state, *aim_mode);
+        // Slippery ground makes both speeding up and slowing down take longer
+        let friction = ground_friction(ground_entity, &friction_query);
+        let acceleration = controller.walk_acceleration * friction;
+        let max_walk_speed = match (crouch, crouching) {
+            (Some(crouch), Some(_)) => crouch.walk_speed(controller.walk_speed),
+            _ => controller.walk_speed,
+        };
+        // A partly tilted stick walks slower
+        let max_walk_speed = max_walk_speed * move_input.abs();
+        // Crouching or easing off the stick on the ground brings the player down to the lower
+        // speed, other speed above the cap is momentum that's left alone
+        let slow_down_to_cap =
+            (crouching.is_some() || move_input.abs() < 1.0) && is_grounded.grounded;
+        let slowed_x = slow_to_cap(
+            velocity.0.x,
+            max_walk_speed,
+            controller.ground_deceleration * friction,
+            time.delta_secs(),
+        );
+
+        if crouching.is_some_and(|crouching| crouching.sliding) {
+            // The crouch plugin slows slides down on its own
+        } else if move_input < 0.0 {
+            if velocity.0.x > -max_walk_speed {
+                direction.x = -acceleration * time.delta_secs();
+            } else if slow_down_to_cap {
+                direction.x = slowed_x - velocity.0.x;
             }
             sprite.flip_x = true;
             is_running = true;
-        } else if action_state.pressed(&PlayerAction::Right) {
-            if velocity.0.x < walk_speed.0 {
-                direction.x = walk_acceleration.0 * time.delta_secs();
+        } else if move_input > 0.0 {
+            if velocity.0.x < max_walk_speed {
+                direction.x = acceleration * time.delta_secs();
+            } else if slow_down_to_cap {
+                direction.x = slowed_x - velocity.0.x;
             }
             sprite.flip_x = false;
             is_running = true;
         } else {
-            // Moving left but not holding left
-            if velocity.0.x < 0.0 {
-                direction.x = (ground_deceleration.0 * time.delta_secs())
-                    .clamp(velocity.0.x, ground_deceleration.0);
-            // Moving right but not holding right
-            } else if velocity.0.x > 0.0 {
-                direction.x = (-ground_deceleration.0 * time.delta_secs())
-                    .clamp(-ground_deceleration.0, velocity.0.x);
-            } else {
-                direction.x = 0.0;
+            direction.x = idle_velocity_x(
+                velocity.0.x,
+                controller.ground_deceleration * friction,
+                time.delta_secs(),
+                is_grounded.grounded && friction >= 1.0 && controller.sticky_feet,
+            ) - velocity.0.x;
+
+            // Aim-locked players can still turn around in place
+            if *aim_mode == AimMode::AimLock {
+                if action_state.pressed(&PlayerAction::Left) {
+                    sprite.flip_x = true;
+                } else if action_state.pressed(&PlayerAction::Right) {
+                    sprite.flip_x = false;
+                }
             }
         }
 
-        if action_state.pressed(&PlayerAction::Jump) {
-            if is_grounded.0
-                || grounded_stopwatch.0.elapsed() < coyote_time.0
-                    && jump_cooldown_timer.0.finished()
+        // The ground check still sees the ground the step after jumping off it, which would
+        // jump a second time on top of the first while Jump is held
+        let on_ground = is_grounded.grounded && !character_state.jumped_recently(time.delta());
+        if on_ground {
+            character_state.land();
+        }
+
+        if in_water {
+            // Jump swims upwards instead, a stroke per press
+            if action_state.just_pressed(&PlayerAction::Jump) {
+                velocity.0.y = swim_stroke(velocity.0.y);
+            }
+        } else if action_state.pressed(&PlayerAction::Jump) || character_state.is_jump_buffered() {
+            if on_ground
+                || grounded_stopwatch.0.elapsed() < controller.coyote_time
+                    && character_state.jump_cooldown_finished(controller)
             {
-                direction.y += jump_force.0;
-                after_jump_gravity_immunity_timer.0.reset();
-                jump_cooldown_timer.0.reset();
+                direction.y +=
+                    jump_velocity(controller.jump_force, velocity.0.x, &controller.speed_jump);
+                character_state.jump(controller);
+                just_jumped = true;
+            } else if let Some(wall_jump) = &controller.wall_jump
+                && abilities.wall_jump
+                && action_state.just_pressed(&PlayerAction::Jump)
+                && let Some(away_from_wall) = wall_jump_direction(
+                    is_touching_wall_left.0,
+                    is_touching_wall_right.0,
+                    wall_left_stopwatch.0.elapsed(),
+                    wall_right_stopwatch.0.elapsed(),
+                    controller.wall_coyote,
+                )
+            {
+                velocity.0 = wall_jump_velocity(velocity.0, away_from_wall, wall_jump);
+                sprite.flip_x = away_from_wall < 0.0;
+                character_state.wall_jump(controller);
+                just_jumped = true;
+
+                // Use up the wall coyote window so the same wall can't be jumped off twice
+                wall_left_stopwatch.0.set_elapsed(controller.wall_coyote);
+                wall_right_stopwatch.0.set_elapsed(controller.wall_coyote);
+            } else if abilities.double_jump
+                && action_state.just_pressed(&PlayerAction::Jump)
+                && character_state.can_air_jump()
+            {
+                // Always the same height, however fast the player was falling
+                velocity.0.y = controller.jump_force;
+                character_state.air_jump(controller);
                 just_jumped = true;
-            } else {
             }
         }
 
-        if action_state.just_pressed(&PlayerAction::Shoot) {
-            println!("Player shot!");
-            event_writer.write(PlayerShootEvent {});
+        if action_state.just_released(&PlayerAction::Jump)
+            && !in_water
+            && let Some(jump_cut) = controller.jump_cut
+        {
+            velocity.0.y = cut_jump(velocity.0.y, jump_cut);
         }
 
         velocity.0 += direction;
+        if just_jumped {
+            sfx_writer.write(PlaySfxEvent(SfxId::Jump));
+        }
 
-        match (is_grounded.0, just_jumped, is_running) {
+        // The attack animation plays to the end
+        if attacking {
+            continue;
+        }
+
+        match (is_grounded.grounded, just_jumped, is_running) {
             (false, _, _) | (true, true, _) => {
                 next_animation.key = Some(PlayerAnimations::Jump);
             }
@@ -367,9 +745,34 @@ fn apply_controls(
     }
 }
 
+fn run_animation_speed(velocity_x: f32, walk_speed: f32) -> f32 {
+    (velocity_x.abs() / walk_speed).clamp(RUN_ANIMATION_MIN_SPEED, RUN_ANIMATION_MAX_SPEED)
+}
+
+/// Match the run animation to how fast the player is actually moving
+fn scale_run_animation(
+    mut query: Query<
+        (
+            &CurrentAnimation<PlayerAnimations>,
+            &Velocity,
+            &CharacterController,
+            &mut AnimationPlaybackState,
+        ),
+        With<Player>,
+    >,
+) {
+    for (current_animation, velocity, controller, mut playback) in query.iter_mut() {
+        playback.speed = if current_animation.key == PlayerAnimations::Run {
+            run_animation_speed(velocity.0.x, controller.walk_speed)
+        } else {
+            1.0
+        };
+    }
+}
+
 fn debug_player_colors(mut query: Query<(&mut Sprite, &IsGrounded)>) {
     for (mut sprite, is_grounded) in query.iter_mut() {
-        if is_grounded.0 {
+        if is_grounded.grounded {
             sprite.color = Color::srgb(0.3, 0.7, 0.3);
         } else {
             sprite.color = Color::srgb(0.7, 0.3, 0.3);
@@ -377,54 +780,139 @@ fn debug_player_colors(mut query: Query<(&mut Sprite, &IsGrounded)>) {
     }
 }
 
+/// Name of the Aseprite slice marking the gun barrel when aiming in `aim`
+///
+/// Aim within 22.5 degrees of a direction uses that direction's slice.
+fn barrel_slice_name(aim: Vec2) -> &'static str {
+    let degrees_from_horizontal = aim.y.atan2(aim.x.abs()).to_degrees();
+    match degrees_from_horizontal {
+        angle if angle > 67.5 => "gun_barrel_up",
+        angle if angle > 22.5 => "gun_barrel_diagonal_up",
+        angle if angle < -67.5 => "gun_barrel_down",
+        angle if angle < -22.5 => "gun_barrel_diagonal_down",
+        _ => "gun_barrel",
+    }
+}
+
+/// Offset from the player's center of the pivot of slice `name` on the sprite's current frame,
+/// or of the slice's center if it has no pivot
+pub fn player_slice_point(
+    player_anim_data: &AnimationData,
+    sprite: &Sprite,
+    name: &str,
+) -> Option<Vec2> {
+    let atlas = sprite.texture_atlas.as_ref()?;
+    let key = player_anim_data.slice_key_at(name, atlas.index)?;
+    let mut bounds = BoundsRect::from_aseprite_rect(&key.bounds);
+    if let Some(pivot) = key.pivot {
+        bounds = BoundsRect {
+            x: bounds.x + pivot.x as f32,
+            y: bounds.y + pivot.y as f32,
+            width: 0.0,
+            height: 0.0,
+        };
+    }
+    Some(calculate_sprite_offset(
+        &bounds,
+        PLAYER_SPRITE_WIDTH,
+        PLAYER_SPRITE_HEIGHT,
+        sprite.flip_x,
+    ))
+}
+
 fn update_animated_components(
-    mut query: Query<(&Sprite, &mut BarrelPosition)>,
+    mut query: Query<(&Sprite, &AimDirection, &mut BarrelPosition)>,
     animation_library: Res<AnimationLibrary>,
 ) {
-    let Some(player_anim_data) = &animation_library.player else {
+    let Some(player_anim_data) = animation_library.get(AnimationLibrary::PLAYER) else {
         return;
     };
 
-    for (sprite, mut barrel_position) in query.iter_mut() {
-        if let Some(barrel_positions_for_frames) = player_anim_data.slice_map.get("gun_barrel")
-            && let Some(ref atlas) = sprite.texture_atlas
+    for (sprite, aim_direction, mut barrel_position) in query.iter_mut() {
+        // Sprites without a slice for the aim direction shoot from the regular barrel
+        let barrel_slice_name = if player_anim_data
+            .slice_map
+            .contains_key(barrel_slice_name(aim_direction.0))
         {
-            if let Some(frame) = barrel_positions_for_frames
-                .keys
-                .iter()
-                .find(|&frame| frame.frame == atlas.index)
-            {
-                let bounds = BoundsRect::from_aseprite_rect(&frame.bounds);
-                barrel_position.0 = calculate_sprite_offset(
-                    &bounds,
-                    PLAYER_SPRITE_WIDTH,
-                    PLAYER_SPRITE_HEIGHT,
-                    sprite.flip_x,
-                );
-            }
+            barrel_slice_name(aim_direction.0)
+        } else {
+            "gun_barrel"
+        };
+        // The pivot marks the muzzle, when the slice has one
+        if let Some(offset) = player_slice_point(player_anim_data, sprite, barrel_slice_name) {
+            barrel_position.0 = offset;
         }
     }
 }
 
-fn shoot(
-    mut query: Query<(&BarrelPosition, &Transform, &Sprite, &WalkSpeed), With<Player>>,
+pub fn shoot(
+    mut query: Query<
+        (
+            Entity,
+            &BarrelPosition,
+            &Transform,
+            &AimDirection,
+            &Weapon,
+            &mut WeaponCooldownTimer,
+            Option<&ChargeShot>,
+        ),
+        With<Player>,
+    >,
     mut event_reader: EventReader<PlayerShootEvent>,
     mut event_writer: EventWriter<ProjectileSpawnEvent>,
+    mut sfx_writer: EventWriter<PlaySfxEvent>,
+    mut inventory: ResMut<Inventory>,
     asset_server: Res<AssetServer>,
+    time: Res<Time>,
 ) {
-    if let Some(_) = event_reader.read().last() {
-        if let Some((barrel_position, player_transform, sprite, walk_speed)) =
-            query.iter_mut().last()
-        {
-            println!("Player shoot event triggered!");
-            let bullet_dir = if sprite.flip_x { -1.0 } else { 1.0 };
-            let bullet_speed = (walk_speed.0 + 70.0) * bullet_dir;
+    let charge = event_reader.read().last().map(|event| event.charge);
+
+    for (
+        entity,
+        barrel_position,
+        player_transform,
+        aim_direction,
+        weapon,
+        mut cooldown_timer,
+        charge_shot,
+    ) in query.iter_mut()
+    {
+        cooldown_timer.0.tick(time.delta());
+        let Some(charge) = charge else {
+            continue;
+        };
+        if !cooldown_timer.0.finished() {
+            continue;
+        }
+        // Weapons out of ammo don't fire
+        if weapon.ammo_per_shot > 0 && !inventory.spend_ammo(weapon.ammo_per_shot) {
+            continue;
+        }
+        cooldown_timer.0 = Timer::new(weapon.cooldown, TimerMode::Once);
+        sfx_writer.write(PlaySfxEvent(SfxId::Shoot));
+
+        let (bullet_speed, gravity) = match &weapon.arcing_shot {
+            Some(arcing_shot) => (arcing_shot.speed, arcing_shot.gravity),
+            None => (weapon.muzzle_velocity, 0.0),
+        };
+        let multipliers = charge_shot.map_or(ChargeMultipliers::default(), |charge_shot| {
+            charge_shot.multipliers(charge)
+        });
 
-            let world_position = player_transform.translation.xy() + barrel_position.0;
+        let world_position = player_transform.translation.xy() + barrel_position.0;
+        for direction in
+            spread_directions(aim_direction.0, weapon.spread, weapon.projectiles_per_shot)
+        {
             event_writer.write(ProjectileSpawnEvent {
                 transform: Transform::from_translation(world_position.extend(0.0)),
-                velocity: ProjectileVelocity(Vec2::new(bullet_speed, 0.0)),
-                sprite: asset_server.load("sprites/bullet.png"),
+                velocity: ProjectileVelocity(direction * bullet_speed * multipliers.speed),
+                sprite: asset_server.load(&weapon.projectile_sprite),
+                animation: None,
+                gravity,
+                damage: (weapon.damage as f32 * multipliers.damage).round() as u32,
+                size: weapon.projectile_size * multipliers.size,
+                faction: Faction::Player,
+                owner: Some(entity),
             });
         }
     }
@@ -434,14 +922,14 @@ pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<PlayerSpawnEvent>()
+        app.add_plugins(PlayerMovementPlugin)
+            .add_event::<PlayerSpawnEvent>()
             .add_event::<PlayerShootEvent>()
             .add_systems(
                 Update,
                 (
                     spawn_player,
-                    apply_controls,
-                    toggle_gravity,
+                    scale_run_animation.in_set(MovementSet::Animation),
                     //debug_player_colors,
                     update_animated_components,
                     shoot,
@@ -450,3 +938,214 @@ impl Plugin for PlayerPlugin {
             .add_plugins(AnimationPlugin::<PlayerAnimations>::default());
     }
 }
+
+/// The player's controls and jumping, without the sprites, animations and shooting, so they
+/// also run headless
+pub struct PlayerMovementPlugin;
+
+impl Plugin for PlayerMovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                (update_jump_buffer, apply_controls)
+                    .chain()
+                    .in_set(MovementSet::Input)
+                    .after(tick_character_states),
+                toggle_gravity
+                    .in_set(MovementSet::Gravity)
+                    .before(apply_gravity),
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_barrel_slice_for_aim_direction() {
+        assert_eq!(barrel_slice_name(Vec2::X), "gun_barrel");
+        assert_eq!(barrel_slice_name(Vec2::NEG_X), "gun_barrel");
+        assert_eq!(barrel_slice_name(Vec2::Y), "gun_barrel_up");
+        assert_eq!(barrel_slice_name(Vec2::NEG_Y), "gun_barrel_down");
+        assert_eq!(
+            barrel_slice_name(Vec2::new(-1.0, 1.0).normalize()),
+            "gun_barrel_diagonal_up"
+        );
+        assert_eq!(
+            barrel_slice_name(Vec2::new(1.0, -1.0).normalize()),
+            "gun_barrel_diagonal_down"
+        );
+    }
+
+    #[test]
+    fn test_aim_lock_blocks_movement() {
+        let mut action_state = ActionState::<PlayerAction>::default();
+        action_state.press(&PlayerAction::Right);
+
+        assert_eq!(movement_input(&action_state, AimMode::RunAndGun), 1.0);
+        assert_eq!(movement_input(&action_state, AimMode::AimLock), 0.0);
+
+        // A partly tilted stick walks slower
+        action_state.set_axis_pair(&PlayerAction::Move, Vec2::new(-0.6, 0.0));
+        assert_eq!(
+            movement_input(&action_state, AimMode::RunAndGun),
+            apply_deadzone(-0.6, STICK_DEADZONE)
+        );
+    }
+
+    #[test]
+    fn test_tuning_matches_raw_values() {
+        assert_eq!(WALK_SPEED.to_pixels(), multiply_by_tile_size(10));
+        assert_eq!(
+            WALK_ACCELERATION.to_pixels(),
+            multiply_by_tile_size(10) * 2.5
+        );
+        assert_eq!(
+            GROUND_DECELERATION.to_pixels(),
+            multiply_by_tile_size(10) * 2.5 * 2.0
+        );
+        assert_eq!(JUMP_VELOCITY.to_pixels(), multiply_by_tile_size(15));
+        assert_eq!(GRAVITY.to_pixels(), multiply_by_tile_size(30));
+        assert_eq!(MAX_FALL_SPEED.to_pixels(), multiply_by_tile_size(15));
+    }
+
+    #[test]
+    fn test_jump_cut_only_while_rising() {
+        assert_eq!(cut_jump(200.0, 0.5), 100.0);
+        // Already falling, releasing Jump changes nothing
+        assert_eq!(cut_jump(-50.0, 0.5), -50.0);
+    }
+
+    #[test]
+    fn test_speed_jump_raises_apex() {
+        let jump_force = JUMP_VELOCITY.to_pixels();
+        let gravity = GRAVITY.to_pixels();
+        let run_speed = WALK_SPEED.to_pixels();
+        let apex = |vertical_velocity: f32| vertical_velocity.powi(2) / (2.0 * gravity);
+
+        let speed_jump = SpeedJump {
+            factor: 0.25,
+            max_bonus: jump_force,
+        };
+        let standstill = apex(jump_velocity(jump_force, 0.0, &speed_jump));
+        let running = apex(jump_velocity(jump_force, -run_speed, &speed_jump));
+        assert_eq!(standstill, apex(jump_force));
+        assert!(running > standstill);
+
+        // The bonus is capped
+        let capped = SpeedJump {
+            factor: 10.0,
+            max_bonus: 50.0,
+        };
+        assert_eq!(
+            jump_velocity(jump_force, run_speed, &capped),
+            jump_force + 50.0
+        );
+
+        // Factor 0 keeps the regular jump
+        assert_eq!(
+            jump_velocity(jump_force, run_speed, &SpeedJump::default()),
+            jump_force
+        );
+    }
+
+    #[test]
+    fn test_run_animation_follows_speed() {
+        let walk_speed = WALK_SPEED.to_pixels();
+        assert_eq!(run_animation_speed(-walk_speed, walk_speed), 1.0);
+        assert_eq!(run_animation_speed(walk_speed * 1.5, walk_speed), 1.5);
+        // Barely moving, e.g. turning around, doesn't slow it to a crawl
+        assert_eq!(
+            run_animation_speed(1.0, walk_speed),
+            RUN_ANIMATION_MIN_SPEED
+        );
+    }
+
+    #[test]
+    fn test_slowing_down_to_a_lowered_cap() {
+        let delta_secs = 0.1;
+        // Running at 200 px/s, crouched down to a 100 px/s cap
+        assert_eq!(slow_to_cap(200.0, 100.0, 500.0, delta_secs), 150.0);
+        assert_eq!(slow_to_cap(-200.0, 100.0, 500.0, delta_secs), -150.0);
+        // Never below the cap
+        assert_eq!(slow_to_cap(120.0, 100.0, 500.0, delta_secs), 100.0);
+        assert_eq!(slow_to_cap(80.0, 100.0, 500.0, delta_secs), 80.0);
+    }
+
+    #[test]
+    fn test_sticky_feet_prevents_idle_drift() {
+        let delta_secs = 1.0 / 60.0;
+        let deceleration = GROUND_DECELERATION.to_pixels();
+
+        // Leftover velocity from float error after landing
+        let mut velocity_x = 0.3;
+        let mut x = 0.0;
+        for _ in 0..600 {
+            velocity_x = idle_velocity_x(velocity_x, deceleration, delta_secs, true);
+            x += velocity_x * delta_secs;
+        }
+        assert_eq!(x, 0.0);
+        assert_eq!(velocity_x, 0.0);
+
+        // Without sticky feet the player decelerates to a stop instead
+        let mut velocity_x = 100.0;
+        for _ in 0..600 {
+            velocity_x = idle_velocity_x(velocity_x, deceleration, delta_secs, false);
+        }
+        assert_eq!(velocity_x, 0.0);
+    }
+
+    #[test]
+    fn test_wall_jump_within_wall_coyote() {
+        let wall_coyote = Duration::from_millis(150);
+        let long_ago = Duration::from_secs(10);
+
+        // Left a wall on the left 100ms ago
+        assert_eq!(
+            wall_jump_direction(
+                false,
+                false,
+                Duration::from_millis(100),
+                long_ago,
+                wall_coyote
+            ),
+            Some(1.0)
+        );
+        // Too late
+        assert_eq!(
+            wall_jump_direction(
+                false,
+                false,
+                Duration::from_millis(200),
+                long_ago,
+                wall_coyote
+            ),
+            None
+        );
+        // Touching a wall on the right always works
+        assert_eq!(
+            wall_jump_direction(false, true, long_ago, Duration::ZERO, Duration::ZERO),
+            Some(-1.0)
+        );
+    }
+
+    #[test]
+    fn test_wall_jump_modes_while_falling() {
+        let falling = Vec2::new(0.0, -200.0);
+        let mut wall_jump = WallJump {
+            horizontal_force: 100.0,
+            vertical_force: 300.0,
+            mode: WallJumpMode::ResetVertical,
+        };
+
+        let reset = wall_jump_velocity(falling, 1.0, &wall_jump);
+        assert_eq!(reset, Vec2::new(100.0, 300.0));
+
+        wall_jump.mode = WallJumpMode::AddVertical;
+        let added = wall_jump_velocity(falling, -1.0, &wall_jump);
+        assert_eq!(added, Vec2::new(-100.0, 100.0));
+    }
+}
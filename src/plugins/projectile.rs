@@ -1,46 +1,265 @@
-use std::ops::Deref;
+use std::{collections::HashMap, ops::Deref, time::Duration};
 
-use avian2d::prelude::{Collider, RigidBody};
+use avian2d::prelude::{
+    Collider, ColliderAabb, CollisionLayers, RigidBody, SpatialQuery, SpatialQueryFilter,
+    TransformInterpolation,
+};
 use bevy::{platform::time, prelude::*};
 
+use crate::{
+    bundles::player::Player,
+    components::{GameEntity, LevelEntity},
+    constants::GameLayer,
+};
+
+use super::{
+    animation::{AnimationKey, AnimationPlugin},
+    animation_library::{AnimationConfig, AnimationData, AnimationLibrary},
+    audio::{PlaySfxAtEvent, SfxId},
+    breakable::{Breakable, BreakableHitEvent},
+    collision::child_collider_aabb,
+    health::{DamageEvent, apply_damage},
+};
+
 #[derive(Component)]
-struct Projectile;
+pub struct Projectile;
 
 #[derive(Component, Clone)]
+#[require(TransformInterpolation)]
 pub struct ProjectileVelocity(pub Vec2);
 
+/// Which side a projectile was fired by, deciding what it can hit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum Faction {
+    #[default]
+    Player,
+    Enemy,
+}
+
+impl Faction {
+    /// Projectiles hit the other side and level geometry, never each other
+    pub fn projectile_layers(self) -> CollisionLayers {
+        match self {
+            Faction::Player => CollisionLayers::new(
+                GameLayer::PlayerProjectile,
+                [GameLayer::Enemy, GameLayer::LevelGeometry],
+            ),
+            Faction::Enemy => CollisionLayers::new(
+                GameLayer::EnemyProjectile,
+                [GameLayer::Player, GameLayer::LevelGeometry],
+            ),
+        }
+    }
+}
+
+/// The entity that fired the projectile, which it never hits
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ProjectileOwner(pub Entity);
+
+/// Health taken from whatever the projectile hits
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct Damage(pub u32);
+
+/// Downwards acceleration for arcing/lobbed projectiles
+#[derive(Component, Clone)]
+pub struct ProjectileGravity(pub f32);
+
+/// Lobbed projectiles (e.g. grenades) fired by a weapon instead of straight shots
+#[derive(Clone, Debug, Reflect)]
+pub struct ArcingShot {
+    /// Launch speed in pixels per second
+    pub speed: f32,
+    /// Gravity applied to the launched projectile
+    pub gravity: f32,
+}
+
+/// What the shooter fires, described as data so new weapons need no new code
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Weapon {
+    /// Shown in the HUD
+    pub name: String,
+    /// Minimum time between shots
+    pub cooldown: Duration,
+    /// Launch speed in pixels per second
+    pub muzzle_velocity: f32,
+    /// Angle in radians the projectiles of one shot fan out over
+    pub spread: f32,
+    pub projectiles_per_shot: u32,
+    /// Path to the projectile's image (e.g., "sprites/bullet.png")
+    pub projectile_sprite: String,
+    /// Collider size of each projectile
+    pub projectile_size: Vec2,
+    pub damage: u32,
+    /// Ammo spent on every shot, 0 for weapons that never run out
+    pub ammo_per_shot: u32,
+    /// Fired in an arc instead of straight at `muzzle_velocity`
+    pub arcing_shot: Option<ArcingShot>,
+}
+
+impl Weapon {
+    pub fn pistol() -> Self {
+        Self {
+            name: "Pistol".to_string(),
+            cooldown: Duration::from_millis(250),
+            muzzle_velocity: 230.0,
+            spread: 0.0,
+            projectiles_per_shot: 1,
+            projectile_sprite: "sprites/bullet.png".to_string(),
+            projectile_size: Vec2::splat(3.0),
+            damage: 1,
+            ammo_per_shot: 0,
+            arcing_shot: None,
+        }
+    }
+
+    pub fn shotgun() -> Self {
+        Self {
+            name: "Shotgun".to_string(),
+            cooldown: Duration::from_millis(800),
+            muzzle_velocity: 200.0,
+            spread: 30f32.to_radians(),
+            projectiles_per_shot: 5,
+            projectile_sprite: "sprites/bullet.png".to_string(),
+            projectile_size: Vec2::splat(3.0),
+            damage: 1,
+            ammo_per_shot: 1,
+            arcing_shot: None,
+        }
+    }
+
+    /// Lobbed over obstacles, with a preview of where it lands while aiming
+    pub fn grenade() -> Self {
+        Self {
+            name: "Grenade".to_string(),
+            cooldown: Duration::from_millis(700),
+            muzzle_velocity: 180.0,
+            spread: 0.0,
+            projectiles_per_shot: 1,
+            projectile_sprite: "sprites/bullet.png".to_string(),
+            projectile_size: Vec2::splat(5.0),
+            damage: 2,
+            ammo_per_shot: 1,
+            arcing_shot: Some(ArcingShot {
+                speed: 180.0,
+                gravity: 400.0,
+            }),
+        }
+    }
+}
+
+/// Time until the equipped weapon can fire again
+#[derive(Component, Default)]
+pub struct WeaponCooldownTimer(pub Timer);
+
+/// Directions of the projectiles in one shot, fanned out evenly over `spread` around `aim`
+pub fn spread_directions(aim: Vec2, spread: f32, count: u32) -> Vec<Vec2> {
+    if count <= 1 {
+        return vec![aim];
+    }
+
+    let step = spread / (count - 1) as f32;
+    (0..count)
+        .map(|index| Vec2::from_angle(-spread / 2.0 + step * index as f32).rotate(aim))
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProjectileAnimations {
+    Fly,
+}
+impl AnimationKey for ProjectileAnimations {}
+
+/// Looping animation for a projectile, played through the regular animation pipeline
+#[derive(Clone)]
+pub struct ProjectileAnimation {
+    pub data: AnimationData,
+    /// Path to the sprite sheet image (e.g., "sprites/bullet.png")
+    pub sprite_path: String,
+    /// The name of the Aseprite tag to loop
+    pub tag_name: &'static str,
+}
+
 #[derive(Event, Clone)]
 pub struct ProjectileSpawnEvent {
     pub transform: Transform,
     pub velocity: ProjectileVelocity,
+    /// Static sprite, used when `animation` is `None`
     pub sprite: Handle<Image>,
+    pub animation: Option<ProjectileAnimation>,
+    /// Gravity applied to the projectile, 0.0 for a straight shot
+    pub gravity: f32,
+    pub damage: u32,
+    /// Collider size, which the static sprite is drawn at too, so scaled shots look scaled
+    pub size: Vec2,
+    pub faction: Faction,
+    pub owner: Option<Entity>,
 }
 
 pub fn spawn_projectile(
     mut commands: Commands,
     mut spawn_events: EventReader<ProjectileSpawnEvent>,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
 ) {
     for event in spawn_events.read().into_iter() {
         println!("Projectile spawned at {:?}", event.transform.translation);
-        commands.spawn((
+        let mut projectile = commands.spawn((
             Projectile,
             event.transform,
             event.velocity.clone(),
-            Sprite {
-                image: event.sprite.clone_weak(),
-                ..default()
-            },
+            Damage(event.damage),
             RigidBody::Kinematic,
-            Collider::rectangle(3.0, 3.0),
+            Collider::rectangle(event.size.x, event.size.y),
+            event.faction.projectile_layers(),
+            GameEntity,
+            LevelEntity,
         ));
+
+        if let Some(owner) = event.owner {
+            projectile.insert(ProjectileOwner(owner));
+        }
+
+        if event.gravity != 0.0 {
+            projectile.insert(ProjectileGravity(event.gravity));
+        }
+
+        if let Some(animation) = &event.animation {
+            projectile.insert(AnimationLibrary::create_animation_bundle(
+                &animation.data,
+                &animation.sprite_path,
+                HashMap::from([(
+                    ProjectileAnimations::Fly,
+                    AnimationConfig::looping(animation.tag_name),
+                )]),
+                ProjectileAnimations::Fly,
+                &asset_server,
+                &mut texture_atlas_layouts,
+            ));
+        } else {
+            projectile.insert(Sprite {
+                image: event.sprite.clone_weak(),
+                custom_size: Some(event.size),
+                ..default()
+            });
+        }
     }
 }
 
 fn move_projectiles(
-    mut query: Query<(&mut Transform, &ProjectileVelocity), With<Projectile>>,
+    mut query: Query<
+        (
+            &mut Transform,
+            &mut ProjectileVelocity,
+            Option<&ProjectileGravity>,
+        ),
+        With<Projectile>,
+    >,
     time: Res<Time>,
 ) {
-    for (mut transform, velocity) in query.iter_mut() {
+    for (mut transform, mut velocity, gravity) in query.iter_mut() {
+        if let Some(gravity) = gravity {
+            velocity.0.y -= gravity.0 * time.delta_secs();
+        }
         let movement = velocity.0 * time.delta_secs();
         transform.translation += Vec3 {
             x: movement.x,
@@ -50,11 +269,256 @@ fn move_projectiles(
     }
 }
 
+/// Projectiles stop at the first wall they fly into, damaging it if it's breakable
+fn projectile_hits_level(
+    mut commands: Commands,
+    spatial_query: SpatialQuery,
+    query: Query<(Entity, &Transform, &CollisionLayers, &Damage), With<Projectile>>,
+    breakable_query: Query<(), With<Breakable>>,
+    mut sfx_writer: EventWriter<PlaySfxAtEvent>,
+    mut breakable_writer: EventWriter<BreakableHitEvent>,
+) {
+    for (entity, transform, layers, damage) in query.iter() {
+        let hits_level = layers.filters.has_all(GameLayer::LevelGeometry);
+        if !hits_level {
+            continue;
+        }
+
+        let filter = SpatialQueryFilter::from_mask(GameLayer::LevelGeometry);
+        let hits = spatial_query.point_intersections(transform.translation.xy(), &filter);
+        if !hits.is_empty() {
+            for &hit in hits.iter().filter(|&&hit| breakable_query.contains(hit)) {
+                breakable_writer.write(BreakableHitEvent {
+                    target: hit,
+                    damage: damage.0,
+                });
+            }
+            commands.entity(entity).despawn();
+            sfx_writer.write(PlaySfxAtEvent {
+                id: SfxId::Impact,
+                position: transform.translation.xy(),
+            });
+        }
+    }
+}
+
+const PLAYER_HIT_KNOCKBACK: f32 = 200.0;
+
+/// Projectiles that can hit the player damage them and are used up
+fn projectile_hits_player(
+    mut commands: Commands,
+    projectile_query: Query<
+        (
+            Entity,
+            &ColliderAabb,
+            &Damage,
+            &ProjectileVelocity,
+            &CollisionLayers,
+            Option<&ProjectileOwner>,
+        ),
+        With<Projectile>,
+    >,
+    player_query: Query<(Entity, &Children), With<Player>>,
+    aabb_query: Query<&ColliderAabb>,
+    mut event_writer: EventWriter<DamageEvent>,
+) {
+    for (player, children) in player_query.iter() {
+        let Some(player_aabb) = child_collider_aabb(children, &aabb_query) else {
+            continue;
+        };
+
+        for (projectile, aabb, damage, velocity, layers, owner) in projectile_query.iter() {
+            let can_hit = layers.filters.has_all(GameLayer::Player)
+                && owner.is_none_or(|owner| owner.0 != player);
+            if !can_hit || !aabb.intersects(&player_aabb) {
+                continue;
+            }
+
+            commands.entity(projectile).despawn();
+            event_writer.write(DamageEvent {
+                target: player,
+                amount: damage.0,
+                knockback: (velocity.0.normalize_or_zero() + Vec2::Y * 0.5).normalize_or_zero()
+                    * PLAYER_HIT_KNOCKBACK,
+            });
+        }
+    }
+}
+
 pub struct ProjectilePlugin;
 
 impl Plugin for ProjectilePlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<ProjectileSpawnEvent>()
-            .add_systems(Update, (spawn_projectile, move_projectiles));
+            .add_systems(
+                Update,
+                (
+                    spawn_projectile,
+                    projectile_hits_player.before(apply_damage),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (move_projectiles, projectile_hits_level).chain(),
+            )
+            .add_plugins(AnimationPlugin::<ProjectileAnimations>::default())
+            .register_type::<ArcingShot>()
+            .register_type::<Damage>()
+            .register_type::<Weapon>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::super::animation::{
+        Animation, AnimationFrame, AnimationFrameEvent, AnimationMap, AnimationTimer,
+        CurrentAnimation, NextAnimation, OnAnimationEndAction, PlaybackDirection,
+        update_animations,
+    };
+    use super::*;
+
+    fn advance_time(world: &mut World, millis: u64) {
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(millis));
+    }
+
+    #[test]
+    fn test_enemy_projectiles_hit_player_but_not_owner() {
+        let mut world = World::new();
+        world.init_resource::<Events<DamageEvent>>();
+
+        let player = world
+            .spawn(Player)
+            .with_child(ColliderAabb::new(Vec2::ZERO, Vec2::new(8.0, 16.0)))
+            .id();
+        let projectile = |faction: Faction, owner: Entity| {
+            (
+                Projectile,
+                ColliderAabb::new(Vec2::new(4.0, 0.0), Vec2::splat(1.5)),
+                Damage(1),
+                ProjectileVelocity(Vec2::new(-100.0, 0.0)),
+                faction.projectile_layers(),
+                ProjectileOwner(owner),
+            )
+        };
+        let turret = world.spawn_empty().id();
+        let own_shot = world.spawn(projectile(Faction::Player, player)).id();
+        let enemy_shot = world.spawn(projectile(Faction::Enemy, turret)).id();
+
+        world.run_system_once(projectile_hits_player).unwrap();
+
+        assert!(world.get_entity(own_shot).is_ok());
+        assert!(world.get_entity(enemy_shot).is_err());
+        let targets: Vec<_> = world
+            .resource_mut::<Events<DamageEvent>>()
+            .drain()
+            .map(|event| event.target)
+            .collect();
+        assert_eq!(targets, vec![player]);
+    }
+
+    #[test]
+    fn test_spread_fans_out_evenly() {
+        assert_eq!(spread_directions(Vec2::X, 1.0, 1), vec![Vec2::X]);
+
+        let directions = spread_directions(Vec2::X, 90f32.to_radians(), 3);
+        let expected = [
+            Vec2::new(1.0, -1.0).normalize(),
+            Vec2::X,
+            Vec2::new(1.0, 1.0).normalize(),
+        ];
+        assert_eq!(directions.len(), 3);
+        for (direction, expected) in directions.iter().zip(expected) {
+            assert!(direction.abs_diff_eq(expected, 1e-5), "{direction}");
+        }
+    }
+
+    #[test]
+    fn test_animated_projectile_advances_frames() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.init_resource::<Events<AnimationFrameEvent>>();
+
+        let projectile = world
+            .spawn((
+                Projectile,
+                Transform::default(),
+                ProjectileVelocity(Vec2::new(100.0, 0.0)),
+                CurrentAnimation::new(ProjectileAnimations::Fly),
+                NextAnimation::<ProjectileAnimations> { key: None },
+                AnimationTimer::default(),
+                AnimationMap {
+                    animations: HashMap::from([(
+                        ProjectileAnimations::Fly,
+                        Animation {
+                            first_index: 0,
+                            last_index: 3,
+                            on_end: OnAnimationEndAction::Loop,
+                            priority: 0,
+                            can_be_interrupted: true,
+                            events: Vec::new(),
+                            speed_multiplier: 1.0,
+                            direction: PlaybackDirection::Forward,
+                        },
+                    )]),
+                    frames: (0..4)
+                        .map(|index| AnimationFrame::new(index, Duration::from_millis(50)))
+                        .collect(),
+                },
+                Sprite {
+                    texture_atlas: Some(TextureAtlas::default()),
+                    ..default()
+                },
+            ))
+            .id();
+
+        let mut frames = Vec::new();
+        for _ in 0..3 {
+            advance_time(&mut world, 60);
+            world.run_system_once(move_projectiles).unwrap();
+            world
+                .run_system_once(update_animations::<ProjectileAnimations>)
+                .unwrap();
+            let sprite = world.get::<Sprite>(projectile).unwrap();
+            frames.push(sprite.texture_atlas.as_ref().unwrap().index);
+        }
+
+        assert_eq!(frames, vec![1, 2, 3]);
+        let transform = world.get::<Transform>(projectile).unwrap();
+        assert!(transform.translation.x > 0.0);
+    }
+
+    #[test]
+    fn test_projectile_sprite_is_drawn_at_its_size() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<TextureAtlasLayout>()
+            .add_event::<ProjectileSpawnEvent>()
+            .add_systems(Update, spawn_projectile);
+
+        // Like a fully charged shot at twice the usual size
+        app.world_mut().send_event(ProjectileSpawnEvent {
+            transform: Transform::default(),
+            velocity: ProjectileVelocity(Vec2::X),
+            sprite: Handle::default(),
+            animation: None,
+            gravity: 0.0,
+            damage: 1,
+            size: Vec2::splat(6.0),
+            faction: Faction::Player,
+            owner: None,
+        });
+        app.update();
+
+        let mut query = app
+            .world_mut()
+            .query_filtered::<&Sprite, With<Projectile>>();
+        let sprite = query.single(app.world()).unwrap();
+        assert_eq!(sprite.custom_size, Some(Vec2::splat(6.0)));
     }
 }
@@ -0,0 +1,579 @@
+use std::{fs, path::Path, time::Duration};
+
+use bevy::{asset::io::file::FileAssetReader, ecs::system::SystemParam, prelude::*};
+use leafwing_input_manager::{plugin::InputManagerSystem, prelude::ActionState};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bundles::{player::Player, spawn_point::Lives},
+    states::GameState,
+};
+
+use super::{
+    abilities::PlayerAbilities,
+    level::{CurrentLevel, LevelStartedEvent, LoadLevelEvent, SpawnOverride},
+    player::PlayerAction,
+    settings::config_path,
+};
+
+const REPLAY_FILE: &str = "replay.json";
+/// Replay shown on the title screen, in the assets directory
+const DEMO_PATH: &str = "replays/demo.json";
+/// How long the title screen sits untouched before the demo starts
+const ATTRACT_MODE_DELAY: Duration = Duration::from_secs(20);
+const RECORD_TOGGLE_KEY: KeyCode = KeyCode::F5;
+const PLAYBACK_KEY: KeyCode = KeyCode::F6;
+
+/// Player input for a single fixed timestep
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub pressed: Vec<PlayerAction>,
+    /// Left stick, missing from replays recorded before gamepad movement
+    #[serde(default)]
+    pub movement: [f32; 2],
+    pub aim: [f32; 2],
+}
+
+/// The same input held for several timesteps in a row, which is how replays are saved
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayRun {
+    pub frame: ReplayFrame,
+    pub repeat: u32,
+}
+
+/// Where a replay starts, restored before playing it back so the input plays out the same
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayStart {
+    pub level: String,
+    /// Player position, the level's player start when `None`
+    pub position: Option<[f32; 2]>,
+    pub abilities: PlayerAbilities,
+}
+
+/// How replays are saved
+#[derive(Debug, Serialize, Deserialize)]
+struct ReplayFile {
+    start: ReplayStart,
+    runs: Vec<ReplayRun>,
+}
+
+/// Merge consecutive identical frames into runs
+pub fn compress_frames(frames: &[ReplayFrame]) -> Vec<ReplayRun> {
+    let mut runs: Vec<ReplayRun> = Vec::new();
+    for frame in frames {
+        match runs.last_mut() {
+            Some(run) if run.frame == *frame => run.repeat += 1,
+            _ => runs.push(ReplayRun {
+                frame: frame.clone(),
+                repeat: 1,
+            }),
+        }
+    }
+    runs
+}
+
+pub fn expand_runs(runs: &[ReplayRun]) -> Vec<ReplayFrame> {
+    runs.iter()
+        .flat_map(|run| std::iter::repeat_n(&run.frame, run.repeat as usize).cloned())
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    /// Live input is appended to the buffer every fixed timestep
+    Recording,
+    /// Recorded input replaces the live input until the buffer runs out
+    Playing,
+}
+
+/// Recorded player inputs, one entry per fixed timestep, the same steps the movement runs in
+///
+/// Replays are only deterministic as long as everything else the game simulates is too,
+/// i.e. with seeded randomness.
+#[derive(Resource, Default, Debug)]
+pub struct ReplayBuffer {
+    pub mode: ReplayMode,
+    pub frames: Vec<ReplayFrame>,
+    /// Where the frames start from, unknown for replays saved before it was recorded
+    pub start: Option<ReplayStart>,
+    /// Next frame to play back
+    cursor: usize,
+    /// Holding off recording or playing back until the level restarted for it has loaded
+    waiting_for_level: bool,
+}
+
+impl ReplayBuffer {
+    pub fn start_recording(&mut self) {
+        self.frames.clear();
+        self.mode = ReplayMode::Recording;
+    }
+
+    pub fn start_playback(&mut self) {
+        self.cursor = 0;
+        self.mode = ReplayMode::Playing;
+    }
+
+    pub fn stop(&mut self) {
+        self.mode = ReplayMode::Off;
+    }
+
+    /// Load a saved replay, also from what older replays were saved as: runs without a start,
+    /// or one entry per frame
+    fn load(path: &Path) -> Option<Self> {
+        let json = fs::read_to_string(path).ok()?;
+        let (start, frames) = serde_json::from_str::<ReplayFile>(&json)
+            .map(|file| (Some(file.start), expand_runs(&file.runs)))
+            .or_else(|_| {
+                serde_json::from_str::<Vec<ReplayRun>>(&json).map(|runs| (None, expand_runs(&runs)))
+            })
+            .or_else(|_| serde_json::from_str(&json).map(|frames| (None, frames)))
+            .ok()?;
+        Some(Self {
+            start,
+            frames,
+            ..default()
+        })
+    }
+
+    fn save(&self) {
+        let path = config_path(REPLAY_FILE);
+        if let Some(dir) = path.parent()
+            && let Err(error) = fs::create_dir_all(dir)
+        {
+            warn!("Failed to create the replay directory: {}", error);
+            return;
+        }
+        let file = ReplayFile {
+            start: self.start.clone().unwrap_or_default(),
+            runs: compress_frames(&self.frames),
+        };
+        match serde_json::to_string(&file) {
+            Ok(json) => {
+                if let Err(error) = fs::write(&path, json) {
+                    warn!("Failed to save replay: {}", error);
+                }
+            }
+            Err(error) => warn!("Failed to serialize replay: {}", error),
+        }
+    }
+}
+
+/// The title screen demo, a replay played once the title screen has been left alone for a while
+#[derive(Resource)]
+pub struct AttractMode {
+    idle: Timer,
+    pub playing: bool,
+}
+
+impl Default for AttractMode {
+    fn default() -> Self {
+        Self {
+            idle: Timer::new(ATTRACT_MODE_DELAY, TimerMode::Once),
+            playing: false,
+        }
+    }
+}
+
+/// Whether the title screen demo is playing, which mustn't touch the player's progress
+pub fn is_playing_demo(attract_mode: Option<Res<AttractMode>>) -> bool {
+    attract_mode.is_some_and(|attract_mode| attract_mode.playing)
+}
+
+/// What a replay's start is made of, to record it or to put it back
+#[derive(SystemParam)]
+struct ReplayStartState<'w, 's> {
+    current_level: ResMut<'w, CurrentLevel>,
+    spawn_override: ResMut<'w, SpawnOverride>,
+    abilities: ResMut<'w, PlayerAbilities>,
+    player_query: Query<'w, 's, &'static Transform, With<Player>>,
+    load_writer: EventWriter<'w, LoadLevelEvent>,
+}
+
+impl ReplayStartState<'_, '_> {
+    fn capture(&self) -> ReplayStart {
+        ReplayStart {
+            level: self.current_level.0.clone(),
+            position: self
+                .player_query
+                .single()
+                .ok()
+                .map(|transform| transform.translation.xy().to_array()),
+            abilities: *self.abilities,
+        }
+    }
+
+    /// Set up the next level load to start the replay
+    fn restore(&mut self, start: &ReplayStart) {
+        self.current_level.0.clone_from(&start.level);
+        self.spawn_override.0 = start.position.map(Vec2::from_array);
+        *self.abilities = start.abilities;
+    }
+
+    /// Load the start's level over again, with the player put back where it started
+    fn restart(&mut self, start: &ReplayStart) {
+        self.restore(start);
+        self.load_writer.write(LoadLevelEvent(start.level.clone()));
+    }
+}
+
+fn record_frame(action_state: &ActionState<PlayerAction>) -> ReplayFrame {
+    ReplayFrame {
+        pressed: action_state.get_pressed(),
+        movement: action_state.axis_pair(&PlayerAction::Move).to_array(),
+        aim: action_state.axis_pair(&PlayerAction::Aim).to_array(),
+    }
+}
+
+fn apply_frame(frame: &ReplayFrame, action_state: &mut ActionState<PlayerAction>) {
+    for action in [
+        PlayerAction::Left,
+        PlayerAction::Right,
+        PlayerAction::Up,
+        PlayerAction::Down,
+        PlayerAction::Jump,
+        PlayerAction::Shoot,
+        PlayerAction::AimLock,
+        PlayerAction::Dash,
+        PlayerAction::Crouch,
+        PlayerAction::AimUp,
+        PlayerAction::AimDown,
+        PlayerAction::Melee,
+        PlayerAction::Interact,
+    ] {
+        if frame.pressed.contains(&action) {
+            action_state.press(&action);
+        } else {
+            action_state.release(&action);
+        }
+    }
+    action_state.set_axis_pair(&PlayerAction::Move, Vec2::from_array(frame.movement));
+    action_state.set_axis_pair(&PlayerAction::Aim, Vec2::from_array(frame.aim));
+}
+
+/// Recording restarts the level where the player stands, so that playing it back can put
+/// the level in the same state
+fn handle_replay_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut replay: ResMut<ReplayBuffer>,
+    mut start_state: ReplayStartState,
+) {
+    if keys.just_pressed(RECORD_TOGGLE_KEY) {
+        if replay.mode == ReplayMode::Recording {
+            replay.stop();
+            replay.save();
+            info!("Saved replay of {} frames", replay.frames.len());
+        } else {
+            let start = start_state.capture();
+            start_state.restart(&start);
+            replay.start = Some(start);
+            replay.waiting_for_level = true;
+            replay.start_recording();
+            info!("Recording replay");
+        }
+    }
+
+    if keys.just_pressed(PLAYBACK_KEY) {
+        let path = config_path(REPLAY_FILE);
+        match ReplayBuffer::load(&path) {
+            Some(loaded) => {
+                *replay = loaded;
+                if let Some(start) = replay.start.clone() {
+                    start_state.restart(&start);
+                    replay.waiting_for_level = true;
+                }
+                replay.start_playback();
+                info!("Playing back replay of {} frames", replay.frames.len());
+            }
+            None => warn!("No replay to play back at {}", path.display()),
+        }
+    }
+}
+
+fn start_replay_once_level_loads(
+    mut event_reader: EventReader<LevelStartedEvent>,
+    mut replay: ResMut<ReplayBuffer>,
+) {
+    if event_reader.read().count() > 0 {
+        replay.waiting_for_level = false;
+    }
+}
+
+fn any_just_pressed(
+    keyboard: &ButtonInput<KeyCode>,
+    mouse: &ButtonInput<MouseButton>,
+    gamepad_query: &Query<&Gamepad>,
+) -> bool {
+    keyboard.get_just_pressed().next().is_some()
+        || mouse.get_just_pressed().next().is_some()
+        || gamepad_query
+            .iter()
+            .any(|gamepad| gamepad.get_just_pressed().next().is_some())
+}
+
+/// Play the demo once the title screen has sat untouched for `ATTRACT_MODE_DELAY`
+///
+/// Without a demo in the assets the title screen just stays up.
+fn start_attract_mode(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepad_query: Query<&Gamepad>,
+    time: Res<Time>,
+    mut attract_mode: ResMut<AttractMode>,
+    mut replay: ResMut<ReplayBuffer>,
+    mut start_state: ReplayStartState,
+    mut lives: ResMut<Lives>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if any_just_pressed(&keyboard, &mouse, &gamepad_query) {
+        attract_mode.idle.reset();
+        return;
+    }
+    if !attract_mode.idle.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    let path = FileAssetReader::get_base_path()
+        .join("assets")
+        .join(DEMO_PATH);
+    let Some(demo) = ReplayBuffer::load(&path) else {
+        info!("No demo at {}, staying on the title screen", DEMO_PATH);
+        return;
+    };
+    *replay = demo;
+    // Entering the game loads the level
+    if let Some(start) = replay.start.clone() {
+        start_state.restore(&start);
+    }
+    replay.waiting_for_level = true;
+    replay.start_playback();
+    *lives = Lives::default();
+    attract_mode.playing = true;
+    next_state.set(GameState::Game);
+}
+
+/// Back to the title screen once the demo runs out, or the player in it dies or finishes the
+/// level, or anything is pressed
+fn stop_attract_mode(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    gamepad_query: Query<&Gamepad>,
+    state: Res<State<GameState>>,
+    mut attract_mode: ResMut<AttractMode>,
+    mut replay: ResMut<ReplayBuffer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let ended = replay.mode != ReplayMode::Playing
+        || matches!(state.get(), GameState::GameOver | GameState::LevelComplete);
+    if ended || any_just_pressed(&keyboard, &mouse, &gamepad_query) {
+        replay.stop();
+        attract_mode.playing = false;
+        attract_mode.idle.reset();
+        next_state.set(GameState::Menu);
+    }
+}
+
+/// Record the player's input, or overwrite it with the recorded one
+pub fn record_or_replay_input(
+    mut replay: ResMut<ReplayBuffer>,
+    mut query: Query<&mut ActionState<PlayerAction>, With<Player>>,
+) {
+    let Ok(mut action_state) = query.single_mut() else {
+        return;
+    };
+    // The level restarted for the replay loads in `Update`, after this frame's timesteps
+    if replay.waiting_for_level {
+        return;
+    }
+
+    match replay.mode {
+        ReplayMode::Off => {}
+        ReplayMode::Recording => {
+            let frame = record_frame(&action_state);
+            replay.frames.push(frame);
+        }
+        ReplayMode::Playing => {
+            let Some(frame) = replay.frames.get(replay.cursor) else {
+                info!("Replay finished");
+                replay.stop();
+                return;
+            };
+            apply_frame(frame, &mut action_state);
+            replay.cursor += 1;
+        }
+    }
+}
+
+/// Records the player's input to replay it, for checking on movement feel and for the title
+/// screen demo
+pub struct InputRecorderPlugin;
+
+impl Plugin for InputRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayBuffer>()
+            .init_resource::<AttractMode>()
+            .add_systems(
+                Update,
+                (
+                    handle_replay_keys.run_if(in_state(GameState::Game).and(not(is_playing_demo))),
+                    start_replay_once_level_loads,
+                    start_attract_mode.run_if(in_state(GameState::Menu)),
+                    stop_attract_mode.run_if(is_playing_demo),
+                ),
+            )
+            .add_systems(
+                FixedPreUpdate,
+                record_or_replay_input.in_set(InputManagerSystem::ManualControl),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::{
+        super::{
+            collision::Noclip,
+            noclip::{NoclipConfig, fly_noclip},
+        },
+        *,
+    };
+
+    fn run_frame(world: &mut World) {
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(16));
+        world.run_system_once(record_or_replay_input).unwrap();
+        world.run_system_once(fly_noclip).unwrap();
+    }
+
+    #[test]
+    fn test_replay_reproduces_final_position() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.insert_resource(NoclipConfig::default());
+        world.init_resource::<ReplayBuffer>();
+        let player = world
+            .spawn((
+                Player,
+                Noclip,
+                ActionState::<PlayerAction>::default(),
+                Transform::default(),
+            ))
+            .id();
+
+        let inputs: [&[PlayerAction]; 4] = [
+            &[PlayerAction::Right],
+            &[PlayerAction::Right, PlayerAction::Up],
+            &[],
+            &[PlayerAction::Down, PlayerAction::Left],
+        ];
+
+        world.resource_mut::<ReplayBuffer>().start_recording();
+        for pressed in inputs {
+            for _ in 0..10 {
+                let mut action_state = world.get_mut::<ActionState<PlayerAction>>(player).unwrap();
+                apply_frame(
+                    &ReplayFrame {
+                        pressed: pressed.to_vec(),
+                        movement: [0.0, 0.0],
+                        aim: [0.0, 0.0],
+                    },
+                    &mut action_state,
+                );
+                run_frame(&mut world);
+            }
+        }
+        let recorded_position = world.get::<Transform>(player).unwrap().translation;
+        assert_ne!(recorded_position, Vec3::ZERO);
+
+        // Play it back from the start with no live input
+        world.get_mut::<Transform>(player).unwrap().translation = Vec3::ZERO;
+        world
+            .get_mut::<ActionState<PlayerAction>>(player)
+            .unwrap()
+            .release_all();
+        world.resource_mut::<ReplayBuffer>().start_playback();
+        for _ in 0..40 {
+            run_frame(&mut world);
+        }
+
+        let replayed_position = world.get::<Transform>(player).unwrap().translation;
+        assert_eq!(replayed_position, recorded_position);
+    }
+
+    #[test]
+    fn test_runs_round_trip() {
+        let frame = |pressed: &[PlayerAction]| ReplayFrame {
+            pressed: pressed.to_vec(),
+            ..Default::default()
+        };
+        let frames = vec![
+            frame(&[]),
+            frame(&[PlayerAction::Right]),
+            frame(&[PlayerAction::Right]),
+            frame(&[PlayerAction::Right]),
+            frame(&[PlayerAction::Right, PlayerAction::Jump]),
+            frame(&[]),
+        ];
+
+        let runs = compress_frames(&frames);
+        assert_eq!(runs.len(), 4);
+        assert_eq!(runs[1].repeat, 3);
+        assert_eq!(expand_runs(&runs), frames);
+    }
+
+    #[test]
+    fn test_replays_are_saved_with_their_start() {
+        let start = ReplayStart {
+            level: "level_0".to_string(),
+            position: Some([40.0, 16.0]),
+            abilities: PlayerAbilities::default(),
+        };
+        let frames = vec![
+            ReplayFrame {
+                pressed: vec![PlayerAction::Right],
+                ..Default::default()
+            };
+            3
+        ];
+        let path = std::env::temp_dir().join("test_replays_are_saved_with_their_start.json");
+        let file = ReplayFile {
+            start: start.clone(),
+            runs: compress_frames(&frames),
+        };
+        fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+        let loaded = ReplayBuffer::load(&path).unwrap();
+        assert_eq!(loaded.start, Some(start));
+        assert_eq!(loaded.frames, frames);
+
+        // Replays saved as bare runs still play, from wherever the player is
+        fs::write(
+            &path,
+            serde_json::to_string(&compress_frames(&frames)).unwrap(),
+        )
+        .unwrap();
+        let loaded = ReplayBuffer::load(&path).unwrap();
+        assert_eq!(loaded.start, None);
+        assert_eq!(loaded.frames, frames);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_demo_asset_is_a_replay() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("assets")
+            .join(DEMO_PATH);
+        let demo = ReplayBuffer::load(&path).unwrap();
+        assert!(
+            demo.start
+                .is_some_and(|start| crate::constants::levels::ALL.contains(&start.level.as_str()))
+        );
+        assert!(!demo.frames.is_empty());
+    }
+}
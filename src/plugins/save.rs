@@ -0,0 +1,173 @@
+use std::fs;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{bundles::spawn_point::SetSpawn, states::GameState};
+
+use super::{
+    abilities::{AbilityUnlockedEvent, PlayerAbilities},
+    level::{CurrentLevel, next_level},
+    pickup::Inventory,
+    replay::is_playing_demo,
+    settings::config_path,
+};
+
+const SAVE_FILE: &str = "save.json";
+
+/// Progress through the game, enough to pick up where the player left off
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveData {
+    /// Level to continue in
+    pub level: String,
+    /// Where in `level` to respawn, its player start when `None`
+    pub checkpoint: Option<[f32; 2]>,
+    /// Coins collected over all the completed levels
+    pub coins: u32,
+    pub abilities: PlayerAbilities,
+}
+
+impl SaveData {
+    pub fn reach_checkpoint(&mut self, level: &str, position: Vec2) {
+        self.level = level.to_string();
+        self.checkpoint = Some(position.to_array());
+    }
+
+    /// Move on to the start of `next_level`, banking the coins collected in the finished one
+    pub fn complete_level(&mut self, next_level: &str, coins: u32) {
+        self.level = next_level.to_string();
+        self.checkpoint = None;
+        self.coins += coins;
+    }
+
+    fn load() -> Option<Self> {
+        fs::read_to_string(config_path(SAVE_FILE))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+    }
+
+    fn save(&self) {
+        let path = config_path(SAVE_FILE);
+        if let Some(dir) = path.parent()
+            && let Err(error) = fs::create_dir_all(dir)
+        {
+            warn!("Failed to create the save directory: {}", error);
+            return;
+        }
+        match serde_json::to_string_pretty(self) {
+            Ok(json) => {
+                if let Err(error) = fs::write(&path, json) {
+                    warn!("Failed to save the game: {}", error);
+                }
+            }
+            Err(error) => warn!("Failed to serialize the save: {}", error),
+        }
+    }
+}
+
+/// The saved game, `None` until there's been any progress to save
+#[derive(Resource, Default, Debug)]
+pub struct SavedGame(pub Option<SaveData>);
+
+/// Respawn points get saved, both checkpoints and the start of every level entered
+fn save_on_checkpoint(
+    mut event_reader: EventReader<SetSpawn>,
+    current_level: Res<CurrentLevel>,
+    abilities: Res<PlayerAbilities>,
+    mut saved_game: ResMut<SavedGame>,
+) {
+    let Some(SetSpawn(transform)) = event_reader.read().last() else {
+        return;
+    };
+    let data = saved_game.0.get_or_insert_default();
+    data.reach_checkpoint(&current_level.0, transform.translation.xy());
+    data.abilities = *abilities;
+    data.save();
+}
+
+/// Unlocked abilities are saved right away, there might not be a checkpoint for a while
+fn save_on_ability_unlock(
+    mut event_reader: EventReader<AbilityUnlockedEvent>,
+    abilities: Res<PlayerAbilities>,
+    mut saved_game: ResMut<SavedGame>,
+) {
+    if event_reader.read().count() == 0 {
+        return;
+    }
+    let data = saved_game.0.get_or_insert_default();
+    data.abilities = *abilities;
+    data.save();
+}
+
+fn save_on_level_complete(
+    current_level: Res<CurrentLevel>,
+    inventory: Res<Inventory>,
+    abilities: Res<PlayerAbilities>,
+    mut saved_game: ResMut<SavedGame>,
+) {
+    let data = saved_game.0.get_or_insert_default();
+    data.complete_level(next_level(&current_level.0), inventory.coins);
+    data.abilities = *abilities;
+    data.save();
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SavedGame(SaveData::load()))
+            .add_systems(
+                Update,
+                (save_on_checkpoint, save_on_ability_unlock)
+                    .run_if(in_state(GameState::Game).and(not(is_playing_demo))),
+            )
+            .add_systems(
+                OnEnter(GameState::LevelComplete),
+                save_on_level_complete.run_if(not(is_playing_demo)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_is_kept_through_levels() {
+        let mut data = SaveData::default();
+
+        data.reach_checkpoint("Level_0", Vec2::new(200.0, 50.0));
+        assert_eq!(data.checkpoint, Some([200.0, 50.0]));
+
+        data.complete_level("Level_1", 12);
+        data.complete_level("Level_2", 3);
+        assert_eq!(
+            data,
+            SaveData {
+                level: "Level_2".to_string(),
+                checkpoint: None,
+                coins: 15,
+                abilities: PlayerAbilities::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_unlocked_abilities_are_saved() {
+        let data = SaveData {
+            abilities: PlayerAbilities {
+                double_jump: true,
+                ..default()
+            },
+            ..default()
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(serde_json::from_str::<SaveData>(&json).unwrap(), data);
+
+        // Saves from before abilities existed load with just the starting ones
+        let old_save: SaveData =
+            serde_json::from_str(r#"{"level": "Level_0", "coins": 3}"#).unwrap();
+        assert_eq!(old_save.abilities, PlayerAbilities::default());
+    }
+}
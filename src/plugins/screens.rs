@@ -0,0 +1,571 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    bundles::spawn_point::Lives,
+    components::GameEntity,
+    states::{GameState, PauseState},
+};
+
+use super::{
+    abilities::PlayerAbilities,
+    audio::{PlaySfxEvent, SfxId},
+    controls::{ActiveInputDevice, KeyBindings, Rebinding, is_rebinding, listen_for_rebind},
+    level::{CurrentLevel, SpawnOverride, next_level},
+    pickup::Inventory,
+    player::PlayerAction,
+    save::{SaveData, SavedGame},
+    settings::{Settings, SettingsOption},
+    speedrun::{RunTimer, format_duration},
+    time_control::{PauseReason, PauseReasons},
+};
+
+/// What a screen's button does
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+enum ScreenButton {
+    /// Start the level over with a full set of lives
+    Retry,
+    NextLevel,
+    /// Start a new game from the first level
+    Play,
+    /// Pick the saved game back up at its level and checkpoint
+    Continue,
+    Resume,
+    Options,
+    /// Leave the options for the screen they were opened from
+    Back,
+    ChangeSetting {
+        option: SettingsOption,
+        steps: i32,
+    },
+    Controls,
+    /// Bind the next key or button pressed to the action
+    Rebind(PlayerAction),
+}
+
+impl ScreenButton {
+    /// Whether Enter or the gamepad's A button presses the button, for the one that moves on
+    /// from its screen
+    fn is_default(self) -> bool {
+        matches!(
+            self,
+            Self::Retry | Self::NextLevel | Self::Continue | Self::Resume
+        )
+    }
+}
+
+/// Root of a screen, for swapping one out for another
+#[derive(Component)]
+struct Screen;
+
+/// Text showing a setting's current value on the options screen
+#[derive(Component)]
+struct SettingLabel(SettingsOption);
+
+/// Text after a button's label naming the key or gamepad button that presses it, left
+/// empty on buttons that can only be clicked
+#[derive(Component)]
+struct ConfirmPrompt;
+
+/// Text showing an action's bindings on the controls screen
+#[derive(Component)]
+struct BindingLabel(PlayerAction);
+
+const BUTTON_COLOR: Color = Color::srgb(0.2, 0.2, 0.25);
+const BUTTON_HOVER_COLOR: Color = Color::srgb(0.3, 0.3, 0.4);
+const TITLE_FONT_SIZE: f32 = 32.0;
+const TEXT_FONT_SIZE: f32 = 16.0;
+
+/// Full screen, centered column that the screens put their content in
+fn spawn_screen(commands: &mut Commands, content: impl FnOnce(&mut ChildSpawnerCommands)) {
+    commands
+        .spawn((
+            Node {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+            Screen,
+            GameEntity,
+        ))
+        .with_children(content);
+}
+
+fn text(value: impl Into<String>, font_size: f32) -> impl Bundle {
+    (
+        Text::new(value),
+        TextFont {
+            font_size,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+    )
+}
+
+fn button(action: ScreenButton, label: &str) -> impl Bundle {
+    (
+        action,
+        Button,
+        Node {
+            padding: UiRect::axes(Val::Px(16.0), Val::Px(8.0)),
+            ..default()
+        },
+        BackgroundColor(BUTTON_COLOR),
+        children![
+            text(label, TEXT_FONT_SIZE),
+            (ConfirmPrompt, text("", TEXT_FONT_SIZE))
+        ],
+    )
+}
+
+fn spawn_game_over_screen(mut commands: Commands) {
+    spawn_screen(&mut commands, |screen| {
+        screen.spawn(text("Game Over", TITLE_FONT_SIZE));
+        screen.spawn(button(ScreenButton::Retry, "Retry"));
+    });
+}
+
+fn spawn_level_complete_screen(
+    mut commands: Commands,
+    run_timer: Res<RunTimer>,
+    inventory: Res<Inventory>,
+) {
+    let (level, time) = run_timer
+        .splits
+        .last()
+        .map(|(level, time)| (level.as_str(), format_duration(*time)))
+        .unwrap_or_default();
+
+    spawn_screen(&mut commands, |screen| {
+        screen.spawn(text("Level Complete", TITLE_FONT_SIZE));
+        screen.spawn(text(level, TEXT_FONT_SIZE));
+        screen.spawn(text(format!("Time: {time}"), TEXT_FONT_SIZE));
+        screen.spawn(text(format!("Coins: {}", inventory.coins), TEXT_FONT_SIZE));
+        screen.spawn(button(ScreenButton::NextLevel, "Next level"));
+    });
+}
+
+fn spawn_title_screen(mut commands: Commands, saved_game: Res<SavedGame>) {
+    spawn_title_screen_content(&mut commands, &saved_game);
+}
+
+fn spawn_title_screen_content(commands: &mut Commands, saved_game: &SavedGame) {
+    spawn_screen(commands, |screen| {
+        screen.spawn(text("Metroidvania", TITLE_FONT_SIZE));
+        if saved_game.0.is_some() {
+            screen.spawn(button(ScreenButton::Continue, "Continue"));
+        }
+        screen.spawn(button(ScreenButton::Play, "New game"));
+        screen.spawn(button(ScreenButton::Options, "Options"));
+    });
+}
+
+fn spawn_pause_screen_content(commands: &mut Commands) {
+    spawn_screen(commands, |screen| {
+        screen.spawn(text("Paused", TITLE_FONT_SIZE));
+        screen.spawn(button(ScreenButton::Resume, "Resume"));
+        screen.spawn(button(ScreenButton::Options, "Options"));
+    });
+}
+
+fn spawn_options_screen(commands: &mut Commands, settings: &Settings) {
+    spawn_screen(commands, |screen| {
+        screen.spawn(text("Options", TITLE_FONT_SIZE));
+        for option in SettingsOption::ALL {
+            let row = (
+                Node {
+                    column_gap: Val::Px(8.0),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                children![(
+                    SettingLabel(option),
+                    text(settings.label(option), TEXT_FONT_SIZE)
+                )],
+            );
+            let mut row = screen.spawn(row);
+            if option.is_volume() {
+                row.with_child(button(
+                    ScreenButton::ChangeSetting { option, steps: -1 },
+                    "-",
+                ));
+                row.with_child(button(
+                    ScreenButton::ChangeSetting { option, steps: 1 },
+                    "+",
+                ));
+            } else {
+                row.with_child(button(
+                    ScreenButton::ChangeSetting { option, steps: 1 },
+                    "Toggle",
+                ));
+            }
+        }
+        screen.spawn(button(ScreenButton::Controls, "Controls"));
+        screen.spawn(button(ScreenButton::Back, "Back"));
+    });
+}
+
+fn spawn_controls_screen(commands: &mut Commands, bindings: &KeyBindings) {
+    spawn_screen(commands, |screen| {
+        screen.spawn(text("Controls", TITLE_FONT_SIZE));
+        for action in KeyBindings::REBINDABLE {
+            screen.spawn((
+                Node {
+                    column_gap: Val::Px(8.0),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                children![
+                    (
+                        BindingLabel(action),
+                        text(bindings.label(action), TEXT_FONT_SIZE)
+                    ),
+                    button(ScreenButton::Rebind(action), "Rebind"),
+                ],
+            ));
+        }
+        // Back to the options the controls were opened from
+        screen.spawn(button(ScreenButton::Options, "Back"));
+    });
+}
+
+/// Stop the game's clock while paused, so everything driven by it stands still
+fn pause_game(
+    mut commands: Commands,
+    mut pause_reasons: ResMut<PauseReasons>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    pause_reasons.pause(PauseReason::Menu, &mut time);
+    spawn_pause_screen_content(&mut commands);
+}
+
+fn resume_game(
+    mut commands: Commands,
+    mut pause_reasons: ResMut<PauseReasons>,
+    mut time: ResMut<Time<Virtual>>,
+    screen_query: Query<Entity, With<Screen>>,
+) {
+    pause_reasons.resume(PauseReason::Menu, &mut time);
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn toggle_pause(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepad_query: Query<&Gamepad>,
+    pause_state: Res<State<PauseState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+) {
+    let start_pressed = gamepad_query
+        .iter()
+        .any(|gamepad| gamepad.just_pressed(GamepadButton::Start));
+    if keyboard.just_pressed(KeyCode::Escape) || start_pressed {
+        next_pause_state.set(match pause_state.get() {
+            PauseState::Running => PauseState::Paused,
+            PauseState::Paused => PauseState::Running,
+        });
+    }
+}
+
+fn update_setting_labels(settings: Res<Settings>, mut query: Query<(&SettingLabel, &mut Text)>) {
+    for (label, mut text) in query.iter_mut() {
+        text.0 = settings.label(label.0);
+    }
+}
+
+fn update_binding_labels(
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    mut query: Query<(&BindingLabel, &mut Text)>,
+) {
+    for (label, mut text) in query.iter_mut() {
+        text.0 = if rebinding.0 == Some(label.0) {
+            format!("{:?}: press a key or button", label.0)
+        } else {
+            settings.bindings.label(label.0)
+        };
+    }
+}
+
+/// Name the confirm button for whichever device was used last
+fn update_confirm_prompts(
+    device: Res<ActiveInputDevice>,
+    button_query: Query<&ScreenButton>,
+    mut prompt_query: Query<(&ChildOf, &mut Text), With<ConfirmPrompt>>,
+) {
+    for (child_of, mut text) in prompt_query.iter_mut() {
+        let prompt = match button_query.get(child_of.parent()) {
+            Ok(action) if action.is_default() => format!(" [{}]", device.confirm_prompt()),
+            _ => String::new(),
+        };
+        text.set_if_neq(Text(prompt));
+    }
+}
+
+/// Where the player is in the game, which the title and game over screens start over or
+/// pick back up
+#[derive(SystemParam)]
+struct RunProgress<'w> {
+    lives: ResMut<'w, Lives>,
+    current_level: ResMut<'w, CurrentLevel>,
+    spawn_override: ResMut<'w, SpawnOverride>,
+    saved_game: ResMut<'w, SavedGame>,
+    abilities: ResMut<'w, PlayerAbilities>,
+    inventory: ResMut<'w, Inventory>,
+}
+
+/// Buttons react to clicks, and Enter or the gamepad's A button presses the screen's
+/// default button
+fn handle_screen_buttons(
+    mut commands: Commands,
+    mut query: Query<(Ref<Interaction>, &ScreenButton, &mut BackgroundColor)>,
+    screen_query: Query<Entity, With<Screen>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepad_query: Query<&Gamepad>,
+    state: Res<State<GameState>>,
+    mut progress: RunProgress,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+    mut sfx_writer: EventWriter<PlaySfxEvent>,
+) {
+    for (interaction, action, mut background) in query.iter_mut() {
+        background.0 = match *interaction {
+            Interaction::Hovered | Interaction::Pressed => BUTTON_HOVER_COLOR,
+            Interaction::None => BUTTON_COLOR,
+        };
+
+        // Only once per click, so holding the button down doesn't keep repeating it
+        let clicked = *interaction == Interaction::Pressed && interaction.is_changed();
+        let entered = action.is_default()
+            && (keyboard.just_pressed(KeyCode::Enter)
+                || gamepad_query
+                    .iter()
+                    .any(|gamepad| gamepad.just_pressed(GamepadButton::South)));
+        if !clicked && !entered {
+            continue;
+        }
+        sfx_writer.write(PlaySfxEvent(SfxId::UiClick));
+
+        // Entering the game state loads the current level
+        match *action {
+            ScreenButton::Retry => {
+                *progress.lives = Lives::default();
+                next_state.set(GameState::Game);
+            }
+            ScreenButton::NextLevel => {
+                progress.current_level.0 = next_level(&progress.current_level.0).to_string();
+                next_state.set(GameState::Game);
+            }
+            ScreenButton::Play => {
+                *progress.current_level = CurrentLevel::default();
+                *progress.abilities = PlayerAbilities::default();
+                *progress.inventory = Inventory::default();
+                // The old run's progress gets overwritten by the first save of the new one
+                progress.saved_game.0 = Some(SaveData {
+                    level: progress.current_level.0.clone(),
+                    ..default()
+                });
+                *progress.lives = Lives::default();
+                next_state.set(GameState::Game);
+            }
+            ScreenButton::Continue => {
+                if let Some(data) = &progress.saved_game.0 {
+                    progress.current_level.0.clone_from(&data.level);
+                    progress.spawn_override.0 = data.checkpoint.map(Vec2::from_array);
+                    *progress.abilities = data.abilities;
+                    *progress.inventory = Inventory {
+                        banked_coins: data.coins,
+                        ..default()
+                    };
+                }
+                *progress.lives = Lives::default();
+                next_state.set(GameState::Game);
+            }
+            ScreenButton::Resume => next_pause_state.set(PauseState::Running),
+            ScreenButton::Options | ScreenButton::Back | ScreenButton::Controls => {
+                for entity in screen_query.iter() {
+                    commands.entity(entity).despawn();
+                }
+                rebinding.0 = None;
+                if *action == ScreenButton::Options {
+                    spawn_options_screen(&mut commands, &settings);
+                } else if *action == ScreenButton::Controls {
+                    spawn_controls_screen(&mut commands, &settings.bindings);
+                } else if *state.get() == GameState::Menu {
+                    spawn_title_screen_content(&mut commands, &progress.saved_game);
+                } else {
+                    spawn_pause_screen_content(&mut commands);
+                }
+            }
+            ScreenButton::ChangeSetting { option, steps } => settings.change(option, steps),
+            ScreenButton::Rebind(action) => rebinding.0 = Some(action),
+        }
+    }
+}
+
+pub struct ScreensPlugin;
+
+impl Plugin for ScreensPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PauseReasons>()
+            .add_systems(OnEnter(GameState::Menu), spawn_title_screen)
+            .add_systems(OnEnter(GameState::GameOver), spawn_game_over_screen)
+            .add_systems(
+                OnEnter(GameState::LevelComplete),
+                spawn_level_complete_screen,
+            )
+            .add_systems(OnEnter(PauseState::Paused), pause_game)
+            .add_systems(OnExit(PauseState::Paused), resume_game)
+            .add_systems(
+                Update,
+                (
+                    // Escape cancels rebinding rather than closing the pause menu
+                    toggle_pause
+                        .run_if(in_state(GameState::Game).and(not(is_rebinding)))
+                        .before(listen_for_rebind),
+                    handle_screen_buttons.run_if(
+                        in_state(GameState::Menu)
+                            .or(in_state(GameState::GameOver))
+                            .or(in_state(GameState::LevelComplete))
+                            .or(in_state(PauseState::Paused)),
+                    ),
+                    update_setting_labels.run_if(resource_changed::<Settings>),
+                    update_confirm_prompts,
+                    update_binding_labels
+                        .run_if(resource_changed::<Settings>.or(resource_changed::<Rebinding>)),
+                ),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_retry_restores_lives() {
+        let mut world = World::new();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.insert_resource(State::new(GameState::GameOver));
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<NextState<PauseState>>();
+        world.init_resource::<Settings>();
+        world.init_resource::<Rebinding>();
+        world.insert_resource(Lives(0));
+        world.init_resource::<CurrentLevel>();
+        world.init_resource::<SpawnOverride>();
+        world.init_resource::<SavedGame>();
+        world.init_resource::<PlayerAbilities>();
+        world.init_resource::<Inventory>();
+        world.init_resource::<Events<PlaySfxEvent>>();
+
+        let retry = world
+            .spawn((
+                ScreenButton::Retry,
+                Interaction::Hovered,
+                BackgroundColor(BUTTON_COLOR),
+            ))
+            .id();
+
+        world.run_system_once(handle_screen_buttons).unwrap();
+        assert_eq!(*world.resource::<Lives>(), Lives(0));
+        assert_eq!(
+            world.get::<BackgroundColor>(retry).unwrap().0,
+            BUTTON_HOVER_COLOR
+        );
+
+        *world.get_mut::<Interaction>(retry).unwrap() = Interaction::Pressed;
+        world.run_system_once(handle_screen_buttons).unwrap();
+        assert_eq!(*world.resource::<Lives>(), Lives::default());
+        assert!(matches!(
+            *world.resource::<NextState<GameState>>(),
+            NextState::Pending(GameState::Game)
+        ));
+        // Retrying stays on the same level
+        assert_eq!(*world.resource::<CurrentLevel>(), CurrentLevel::default());
+    }
+
+    #[test]
+    fn test_new_game_forgets_the_saved_progress() {
+        let mut world = World::new();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.insert_resource(State::new(GameState::Menu));
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<NextState<PauseState>>();
+        world.init_resource::<Settings>();
+        world.init_resource::<Rebinding>();
+        world.init_resource::<Lives>();
+        world.insert_resource(CurrentLevel("Level_2".to_string()));
+        world.init_resource::<SpawnOverride>();
+        let old_save = SaveData {
+            level: "Level_2".to_string(),
+            coins: 20,
+            abilities: PlayerAbilities {
+                double_jump: true,
+                ..default()
+            },
+            ..default()
+        };
+        world.insert_resource(SavedGame(Some(old_save.clone())));
+        world.insert_resource(old_save.abilities);
+        world.init_resource::<Events<PlaySfxEvent>>();
+        world.spawn((
+            ScreenButton::Play,
+            Interaction::Pressed,
+            BackgroundColor(BUTTON_COLOR),
+        ));
+
+        world.run_system_once(handle_screen_buttons).unwrap();
+        assert_eq!(
+            *world.resource::<PlayerAbilities>(),
+            PlayerAbilities::default()
+        );
+        assert_eq!(
+            world.resource::<SavedGame>().0,
+            Some(SaveData {
+                level: CurrentLevel::default().0,
+                ..default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_continue_restores_the_saved_coins() {
+        let mut world = World::new();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.insert_resource(State::new(GameState::Menu));
+        world.init_resource::<NextState<GameState>>();
+        world.init_resource::<NextState<PauseState>>();
+        world.init_resource::<Settings>();
+        world.init_resource::<Rebinding>();
+        world.init_resource::<Lives>();
+        world.init_resource::<CurrentLevel>();
+        world.init_resource::<SpawnOverride>();
+        world.insert_resource(SavedGame(Some(SaveData {
+            level: "Level_2".to_string(),
+            coins: 20,
+            ..default()
+        })));
+        world.init_resource::<PlayerAbilities>();
+        world.init_resource::<Inventory>();
+        world.init_resource::<Events<PlaySfxEvent>>();
+        world.spawn((
+            ScreenButton::Continue,
+            Interaction::Pressed,
+            BackgroundColor(BUTTON_COLOR),
+        ));
+
+        world.run_system_once(handle_screen_buttons).unwrap();
+        assert_eq!(world.resource::<CurrentLevel>().0, "Level_2");
+        assert_eq!(world.resource::<Inventory>().banked_coins, 20);
+    }
+}
@@ -0,0 +1,221 @@
+use std::{fs, path::PathBuf};
+
+use bevy::{
+    prelude::*,
+    window::{MonitorSelection, PresentMode, PrimaryWindow, WindowMode},
+};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use super::{audio::AudioSettings, camera::CameraShakeConfig, controls::KeyBindings};
+
+const CONFIG_DIR: &str = "bevy2dmetroidvania";
+const SETTINGS_FILE: &str = "settings.ron";
+/// How much a volume goes up or down per button press
+const VOLUME_STEP: f32 = 0.1;
+
+/// Player preferences, persisted between sessions
+///
+/// Changing the resource applies it to the window, audio, camera and controls, and saves it.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize, Reflect)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub screen_shake: bool,
+    pub bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let audio = AudioSettings::default();
+        Self {
+            master_volume: audio.master,
+            music_volume: audio.music,
+            sfx_volume: audio.sfx,
+            fullscreen: false,
+            vsync: true,
+            screen_shake: true,
+            bindings: KeyBindings::default(),
+        }
+    }
+}
+
+/// One editable line on the options screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingsOption {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    Vsync,
+    ScreenShake,
+}
+
+impl SettingsOption {
+    pub const ALL: [Self; 6] = [
+        Self::MasterVolume,
+        Self::MusicVolume,
+        Self::SfxVolume,
+        Self::Fullscreen,
+        Self::Vsync,
+        Self::ScreenShake,
+    ];
+
+    pub fn is_volume(self) -> bool {
+        matches!(
+            self,
+            Self::MasterVolume | Self::MusicVolume | Self::SfxVolume
+        )
+    }
+}
+
+/// Where the game keeps a file of its own, in the platform's config directory, or the
+/// working directory where there's none
+pub fn config_path(file_name: &str) -> PathBuf {
+    dirs::config_dir()
+        .map(|dir| dir.join(CONFIG_DIR))
+        .unwrap_or_default()
+        .join(file_name)
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value { "On" } else { "Off" }
+}
+
+impl Settings {
+    fn load() -> Self {
+        fs::read_to_string(config_path(SETTINGS_FILE))
+            .ok()
+            .and_then(|ron| ron::from_str(&ron).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let path = config_path(SETTINGS_FILE);
+        if let Some(dir) = path.parent()
+            && let Err(error) = fs::create_dir_all(dir)
+        {
+            warn!("Failed to create the settings directory: {}", error);
+            return;
+        }
+        match ron::ser::to_string_pretty(self, PrettyConfig::default()) {
+            Ok(ron) => {
+                if let Err(error) = fs::write(&path, ron) {
+                    warn!("Failed to save settings: {}", error);
+                }
+            }
+            Err(error) => warn!("Failed to serialize settings: {}", error),
+        }
+    }
+
+    /// Turn a volume up or down by `steps`, or flip a toggle whatever the steps
+    pub fn change(&mut self, option: SettingsOption, steps: i32) {
+        let step_volume =
+            |volume: &mut f32| *volume = (*volume + steps as f32 * VOLUME_STEP).clamp(0.0, 1.0);
+        match option {
+            SettingsOption::MasterVolume => step_volume(&mut self.master_volume),
+            SettingsOption::MusicVolume => step_volume(&mut self.music_volume),
+            SettingsOption::SfxVolume => step_volume(&mut self.sfx_volume),
+            SettingsOption::Fullscreen => self.fullscreen = !self.fullscreen,
+            SettingsOption::Vsync => self.vsync = !self.vsync,
+            SettingsOption::ScreenShake => self.screen_shake = !self.screen_shake,
+        }
+    }
+
+    /// What the options screen shows for a setting, like "Music: 50%"
+    pub fn label(&self, option: SettingsOption) -> String {
+        let percent = |volume: f32| format!("{}%", (volume * 100.0).round());
+        match option {
+            SettingsOption::MasterVolume => format!("Volume: {}", percent(self.master_volume)),
+            SettingsOption::MusicVolume => format!("Music: {}", percent(self.music_volume)),
+            SettingsOption::SfxVolume => format!("Sounds: {}", percent(self.sfx_volume)),
+            SettingsOption::Fullscreen => format!("Fullscreen: {}", on_off(self.fullscreen)),
+            SettingsOption::Vsync => format!("VSync: {}", on_off(self.vsync)),
+            SettingsOption::ScreenShake => format!("Screen shake: {}", on_off(self.screen_shake)),
+        }
+    }
+}
+
+/// Push the settings out to whatever they control, saving them unless they were just loaded
+fn apply_settings(
+    settings: Res<Settings>,
+    mut audio_settings: ResMut<AudioSettings>,
+    mut shake_config: ResMut<CameraShakeConfig>,
+    mut key_bindings: ResMut<KeyBindings>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    audio_settings.master = settings.master_volume;
+    audio_settings.music = settings.music_volume;
+    audio_settings.sfx = settings.sfx_volume;
+    shake_config.enabled = settings.screen_shake;
+    key_bindings.set_if_neq(settings.bindings.clone());
+
+    for mut window in window_query.iter_mut() {
+        window.mode = if settings.fullscreen {
+            WindowMode::BorderlessFullscreen(MonitorSelection::Current)
+        } else {
+            WindowMode::Windowed
+        };
+        window.present_mode = if settings.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        };
+    }
+
+    if !settings.is_added() {
+        settings.save();
+    }
+}
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Settings::load())
+            .add_systems(Update, apply_settings.run_if(resource_changed::<Settings>))
+            .register_type::<Settings>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changing_settings() {
+        let mut settings = Settings {
+            music_volume: 0.95,
+            ..default()
+        };
+
+        settings.change(SettingsOption::MusicVolume, 1);
+        assert_eq!(settings.music_volume, 1.0);
+        settings.change(SettingsOption::MusicVolume, -3);
+        assert_eq!(settings.label(SettingsOption::MusicVolume), "Music: 70%");
+
+        settings.change(SettingsOption::ScreenShake, 1);
+        assert!(!settings.screen_shake);
+        settings.change(SettingsOption::ScreenShake, -1);
+        assert!(settings.screen_shake);
+
+        // Files from older versions get defaults for whatever they're missing
+        let loaded: Settings = ron::from_str("(fullscreen: true)").unwrap();
+        assert_eq!(
+            loaded,
+            Settings {
+                fullscreen: true,
+                ..default()
+            }
+        );
+
+        // Bindings are keyed by action, which the file has to keep
+        let ron = ron::ser::to_string_pretty(&settings, PrettyConfig::default()).unwrap();
+        assert_eq!(ron::from_str::<Settings>(&ron).unwrap(), settings);
+    }
+}
@@ -0,0 +1,186 @@
+use avian2d::prelude::Collider;
+use bevy::prelude::*;
+
+use super::animation_library::AnimationLibrary;
+
+/// Aseprite slice marking where an attack hits, keyed only on its active frames
+pub const HITBOX_SLICE: &str = "attack_hitbox";
+/// Aseprite slice marking where the entity can be hit
+pub const HURTBOX_SLICE: &str = "hurtbox";
+
+/// Collider following a slice of its parent's sprite frame by frame, like an attack's hitbox
+/// or a hurtbox that ducks along with the sprite
+///
+/// Only frames with a key of their own for the slice count. On the others the collider keeps
+/// its last shape and is marked inactive.
+#[derive(Component, Debug)]
+pub struct SliceCollider {
+    /// Name the parent's animations are listed under in the `AnimationLibrary`
+    pub animation: &'static str,
+    pub slice: &'static str,
+    /// Size and offset, facing right, used on every frame while the sprite sheet has no such
+    /// slice at all
+    pub fallback: Option<(Vec2, Vec2)>,
+    /// Whether the parent's current frame has the slice
+    pub active: bool,
+}
+
+impl SliceCollider {
+    pub fn new(animation: &'static str, slice: &'static str) -> Self {
+        Self {
+            animation,
+            slice,
+            fallback: None,
+            active: false,
+        }
+    }
+
+    pub fn with_fallback(mut self, size: Vec2, offset: Vec2) -> Self {
+        self.fallback = Some((size, offset));
+        self
+    }
+}
+
+pub fn update_slice_colliders(
+    mut collider_query: Query<(&mut SliceCollider, &mut Collider, &mut Transform, &ChildOf)>,
+    sprite_query: Query<&Sprite>,
+    animation_library: Res<AnimationLibrary>,
+) {
+    for (mut slice_collider, mut collider, mut transform, child_of) in collider_query.iter_mut() {
+        let rect = sprite_query.get(child_of.parent()).ok().and_then(|sprite| {
+            let anim_data = animation_library.get(slice_collider.animation);
+            let has_slice = anim_data
+                .is_some_and(|anim_data| anim_data.slice_map.contains_key(slice_collider.slice));
+            if !has_slice {
+                let (size, offset) = slice_collider.fallback?;
+                let flip = if sprite.flip_x { -1.0 } else { 1.0 };
+                return Some((size, Vec2::new(offset.x * flip, offset.y)));
+            }
+            let atlas = sprite.texture_atlas.as_ref()?;
+            let key = anim_data?.slice_key_at(slice_collider.slice, atlas.index)?;
+            Some(anim_data?.slice_rect(key, sprite.flip_x))
+        });
+
+        slice_collider.active = rect.is_some();
+        if let Some((size, offset)) = rect {
+            *collider = Collider::rectangle(size.x, size.y);
+            transform.translation = offset.extend(transform.translation.z);
+        }
+    }
+}
+
+/// Hitboxes and hurtboxes taken from Aseprite slices
+pub struct SliceColliderPlugin;
+
+impl Plugin for SliceColliderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_slice_colliders);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::aseprite_deserialize::{Rect, Slice, SliceKey};
+
+    use super::super::animation_library::AnimationData;
+    use super::*;
+
+    #[test]
+    fn test_collider_follows_slice_on_keyed_frames() {
+        let key = |frame, x| SliceKey {
+            frame,
+            bounds: Rect {
+                x,
+                y: 8,
+                w: 16,
+                h: 8,
+            },
+            pivot: None,
+            center: None,
+        };
+        let mut library = AnimationLibrary::default();
+        library.insert(
+            "knight",
+            AnimationData {
+                frames: Vec::new(),
+                animations: HashMap::new(),
+                sheet_size: UVec2::new(128, 32),
+                frame_size: UVec2::splat(32),
+                slice_map: HashMap::from([(
+                    "hitbox".to_string(),
+                    Slice {
+                        name: "hitbox".to_string(),
+                        color: String::new(),
+                        keys: vec![key(1, 16), key(2, 24)],
+                    },
+                )]),
+                image_path: String::new(),
+            },
+        );
+
+        let mut world = World::new();
+        world.insert_resource(library);
+        let knight = world
+            .spawn(Sprite {
+                texture_atlas: Some(TextureAtlas {
+                    index: 0,
+                    ..default()
+                }),
+                ..default()
+            })
+            .id();
+        let hitbox = world
+            .spawn((
+                SliceCollider::new("knight", "hitbox"),
+                Collider::rectangle(1.0, 1.0),
+                Transform::default(),
+                ChildOf(knight),
+            ))
+            .id();
+        let set_frame = |world: &mut World, index: usize, flip_x: bool| {
+            let mut sprite = world.get_mut::<Sprite>(knight).unwrap();
+            sprite.texture_atlas.as_mut().unwrap().index = index;
+            sprite.flip_x = flip_x;
+            world.run_system_once(update_slice_colliders).unwrap();
+        };
+
+        // Wind-up frame without a key
+        set_frame(&mut world, 0, false);
+        assert!(!world.get::<SliceCollider>(hitbox).unwrap().active);
+
+        set_frame(&mut world, 2, false);
+        assert!(world.get::<SliceCollider>(hitbox).unwrap().active);
+        assert_eq!(
+            world.get::<Transform>(hitbox).unwrap().translation,
+            Vec3::new(16.0, 4.0, 0.0)
+        );
+
+        // Mirrored along with the sprite
+        set_frame(&mut world, 2, true);
+        assert_eq!(
+            world.get::<Transform>(hitbox).unwrap().translation,
+            Vec3::new(-16.0, 4.0, 0.0)
+        );
+
+        // Sheets without the slice use the fallback on every frame
+        let fallback = world
+            .spawn((
+                SliceCollider::new("knight", "sword")
+                    .with_fallback(Vec2::new(12.0, 8.0), Vec2::new(10.0, 2.0)),
+                Collider::rectangle(1.0, 1.0),
+                Transform::default(),
+                ChildOf(knight),
+            ))
+            .id();
+        set_frame(&mut world, 0, true);
+        assert!(world.get::<SliceCollider>(fallback).unwrap().active);
+        assert_eq!(
+            world.get::<Transform>(fallback).unwrap().translation,
+            Vec3::new(-10.0, 2.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,353 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+
+use crate::{
+    bundles::camera::MainCamera, constants, entity_fields::EnemySpawnerFields, states::GameState,
+};
+
+use super::{
+    enemy::{EnemyDiedEvent, EnemyKind, EnemySpawnEvent, spawn_enemy},
+    interaction::{LdtkIid, SignalEvent},
+    level::{LdtkEntityAppExt, LdtkEntityContext, entity_world_rect},
+};
+
+/// How a spawner paces its spawns
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum SpawnMode {
+    /// One every interval for as long as it's under its caps
+    #[default]
+    Interval,
+    /// Nothing until signalled, then `size` enemies one interval apart. Signals only start
+    /// another wave once the last one has been cleared.
+    Waves { size: u32 },
+    /// A single enemy, straight away
+    Once,
+}
+
+impl SpawnMode {
+    /// Look up a mode by the name used in the level editor
+    pub fn from_name(name: &str, wave_size: u32) -> Option<Self> {
+        match name {
+            "interval" => Some(Self::Interval),
+            "waves" => Some(Self::Waves { size: wave_size }),
+            "once" => Some(Self::Once),
+            _ => None,
+        }
+    }
+}
+
+/// Periodically spawns enemies, keeping at most `max_alive` of them around at once
+/// and stopping for good once `total` have been spawned
+#[derive(Component, Reflect)]
+pub struct Spawner {
+    pub prefab: EnemyKind,
+    pub mode: SpawnMode,
+    pub interval: Timer,
+    pub max_alive: u32,
+    pub total: u32,
+    /// Don't spawn while the spawner is outside the camera view
+    pub pause_off_screen: bool,
+    alive: u32,
+    spawned: u32,
+    /// Enemies still to come in the current wave
+    wave_left: u32,
+}
+
+impl Spawner {
+    pub fn new(prefab: EnemyKind, interval: Duration, max_alive: u32, total: u32) -> Self {
+        Self {
+            prefab,
+            mode: SpawnMode::Interval,
+            interval: Timer::new(interval, TimerMode::Repeating),
+            max_alive,
+            total,
+            pause_off_screen: true,
+            alive: 0,
+            spawned: 0,
+            wave_left: 0,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: SpawnMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Whether the spawner has spawned everything it ever will
+    pub fn is_exhausted(&self) -> bool {
+        self.spawned >= self.total || (self.mode == SpawnMode::Once && self.spawned > 0)
+    }
+
+    /// Whether it's time for the next enemy, the interval having been ticked already
+    fn is_due(&self) -> bool {
+        match self.mode {
+            SpawnMode::Interval => self.interval.just_finished(),
+            SpawnMode::Waves { .. } => self.wave_left > 0 && self.interval.just_finished(),
+            SpawnMode::Once => true,
+        }
+    }
+}
+
+/// Enemies a spawner has spawned, despawned along with it when its level unloads
+#[derive(Component, Debug)]
+#[relationship_target(relationship = SpawnedBy, linked_spawn)]
+pub struct SpawnedEnemies(Vec<Entity>);
+
+fn tick_spawners(
+    mut spawner_query: Query<(Entity, &mut Spawner, &Transform)>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut event_writer: EventWriter<EnemySpawnEvent>,
+    time: Res<Time>,
+) {
+    let camera = camera_query.single().ok();
+
+    for (entity, mut spawner, transform) in spawner_query.iter_mut() {
+        if spawner.is_exhausted() {
+            continue;
+        }
+
+        if spawner.pause_off_screen
+            && let Some((camera, camera_transform)) = camera
+        {
+            let on_screen = camera
+                .world_to_ndc(camera_transform, transform.translation)
+                .is_some_and(|ndc| ndc.x.abs() <= 1.0 && ndc.y.abs() <= 1.0);
+            if !on_screen {
+                continue;
+            }
+        }
+
+        spawner.interval.tick(time.delta());
+        if spawner.is_due() && spawner.alive < spawner.max_alive {
+            spawner.alive += 1;
+            spawner.spawned += 1;
+            spawner.wave_left = spawner.wave_left.saturating_sub(1);
+            event_writer.write(EnemySpawnEvent {
+                kind: spawner.prefab,
+                transform: *transform,
+                spawned_by: Some(entity),
+                level_id: None,
+                flight: None,
+            });
+        }
+    }
+}
+
+fn track_spawned_deaths(
+    mut event_reader: EventReader<EnemyDiedEvent>,
+    mut spawner_query: Query<&mut Spawner>,
+) {
+    for event in event_reader.read() {
+        if let Some(spawner) = event.spawned_by
+            && let Ok(mut spawner) = spawner_query.get_mut(spawner)
+        {
+            spawner.alive = spawner.alive.saturating_sub(1);
+        }
+    }
+}
+
+/// Start a wave on signalled wave spawners that have cleared their last one
+fn start_waves(
+    mut event_reader: EventReader<SignalEvent>,
+    mut spawner_query: Query<(&LdtkIid, &mut Spawner)>,
+) {
+    for event in event_reader.read().filter(|event| event.on) {
+        for (iid, mut spawner) in spawner_query.iter_mut() {
+            let SpawnMode::Waves { size } = spawner.mode else {
+                continue;
+            };
+            if iid.0 == event.target && spawner.wave_left == 0 && spawner.alive == 0 {
+                spawner.wave_left = size;
+                spawner.interval.reset();
+            }
+        }
+    }
+}
+
+fn spawner_from_entity(entity: &EntityInstance) -> Option<Spawner> {
+    let fields = EnemySpawnerFields::from_entity(entity)?;
+    let Some(prefab) = EnemyKind::from_name(&fields.prefab) else {
+        warn!("unknown enemy prefab: {:?}", fields.prefab);
+        return None;
+    };
+    let Some(mode) = SpawnMode::from_name(&fields.mode, fields.wave_size as u32) else {
+        warn!("unknown spawner mode: {:?}", fields.mode);
+        return None;
+    };
+    let Ok(interval) = Duration::try_from_secs_f32(fields.interval) else {
+        warn!("invalid spawner interval: {:?}", fields.interval);
+        return None;
+    };
+
+    let mut spawner = Spawner::new(
+        prefab,
+        interval,
+        fields.max_alive as u32,
+        fields.total as u32,
+    )
+    .with_mode(mode);
+    spawner.pause_off_screen = fields.pause_off_screen;
+    Some(spawner)
+}
+
+fn spawn_ldtk_spawner(
+    entity: &EntityInstance,
+    context: &LdtkEntityContext,
+    commands: &mut Commands,
+) {
+    let Some(spawner) = spawner_from_entity(entity) else {
+        warn!("invalid enemy spawner: {:?}", entity);
+        return;
+    };
+    let (center, _) = entity_world_rect(entity);
+    commands.spawn((
+        spawner,
+        LdtkIid(entity.iid.clone()),
+        Transform::from_translation(center.extend(1.0)),
+        context.level_id.clone(),
+    ));
+}
+
+pub struct SpawnerPlugin;
+
+impl Plugin for SpawnerPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::ENEMY_SPAWNER, spawn_ldtk_spawner)
+            .add_systems(
+                Update,
+                (track_spawned_deaths, start_waves, tick_spawners)
+                    .chain()
+                    .before(spawn_enemy)
+                    .run_if(in_state(GameState::Game)),
+            )
+            .register_type::<Spawner>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::super::enemy::SpawnedBy;
+    use super::*;
+
+    fn step(world: &mut World) -> usize {
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(1));
+        world.run_system_once(track_spawned_deaths).unwrap();
+        world.run_system_once(tick_spawners).unwrap();
+
+        // Clear the events so the next one-shot systems don't read them again
+        world.resource_mut::<Events<EnemyDiedEvent>>().clear();
+        let mut spawned = world.resource_mut::<Events<EnemySpawnEvent>>();
+        let count = spawned.len();
+        spawned.clear();
+        count
+    }
+
+    fn kill(world: &mut World, spawner: Entity) {
+        world.send_event(EnemyDiedEvent {
+            enemy: Entity::PLACEHOLDER,
+            kind: EnemyKind::Walker,
+            spawned_by: Some(spawner),
+        });
+    }
+
+    #[test]
+    fn test_spawner_respects_max_alive_and_total() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.init_resource::<Events<EnemySpawnEvent>>();
+        world.init_resource::<Events<EnemyDiedEvent>>();
+
+        let spawner = world
+            .spawn((
+                Spawner::new(EnemyKind::Walker, Duration::from_secs(1), 2, 3),
+                Transform::default(),
+            ))
+            .id();
+
+        // Capped at two alive
+        let spawned: usize = (0..5).map(|_| step(&mut world)).sum();
+        assert_eq!(spawned, 2);
+
+        // Killing one frees a slot
+        kill(&mut world, spawner);
+        let spawned: usize = (0..5).map(|_| step(&mut world)).sum();
+        assert_eq!(spawned, 1);
+
+        // Total reached, no more spawns even with free slots
+        kill(&mut world, spawner);
+        kill(&mut world, spawner);
+        let spawned: usize = (0..5).map(|_| step(&mut world)).sum();
+        assert_eq!(spawned, 0);
+
+        let spawner = world.get::<Spawner>(spawner).unwrap();
+        assert!(spawner.is_exhausted());
+        assert_eq!(spawner.alive, 0);
+    }
+
+    #[test]
+    fn test_wave_spawner_waits_for_signal_and_cleared_wave() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.init_resource::<Events<EnemySpawnEvent>>();
+        world.init_resource::<Events<EnemyDiedEvent>>();
+        world.init_resource::<Events<SignalEvent>>();
+
+        let spawner = world
+            .spawn((
+                Spawner::new(EnemyKind::Walker, Duration::from_secs(1), 5, 10)
+                    .with_mode(SpawnMode::Waves { size: 2 }),
+                LdtkIid("spawner".to_string()),
+                Transform::default(),
+            ))
+            .id();
+        let signal = |world: &mut World| {
+            world.send_event(SignalEvent {
+                target: "spawner".to_string(),
+                on: true,
+            });
+            world.run_system_once(start_waves).unwrap();
+            world.resource_mut::<Events<SignalEvent>>().clear();
+        };
+
+        // Dormant until signalled, then a wave's worth
+        let spawned: usize = (0..3).map(|_| step(&mut world)).sum();
+        assert_eq!(spawned, 0);
+        signal(&mut world);
+        let spawned: usize = (0..5).map(|_| step(&mut world)).sum();
+        assert_eq!(spawned, 2);
+
+        // The next wave waits for this one to be cleared
+        signal(&mut world);
+        kill(&mut world, spawner);
+        let spawned: usize = (0..3).map(|_| step(&mut world)).sum();
+        assert_eq!(spawned, 0);
+        kill(&mut world, spawner);
+        step(&mut world);
+        signal(&mut world);
+        let spawned: usize = (0..5).map(|_| step(&mut world)).sum();
+        assert_eq!(spawned, 2);
+    }
+
+    #[test]
+    fn test_spawned_enemies_despawn_with_spawner() {
+        let mut world = World::new();
+        let spawner = world
+            .spawn(Spawner::new(
+                EnemyKind::Walker,
+                Duration::from_secs(1),
+                2,
+                2,
+            ))
+            .id();
+        let enemy = world.spawn(SpawnedBy(spawner)).id();
+
+        world.despawn(spawner);
+        assert!(world.get_entity(enemy).is_err());
+    }
+}
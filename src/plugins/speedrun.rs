@@ -0,0 +1,282 @@
+use std::{collections::HashMap, fs, time::Duration};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{components::GameEntity, states::GameState};
+
+use super::{
+    goal::LevelCompleteEvent, level::LevelStartedEvent, replay::is_playing_demo,
+    settings::config_path,
+};
+
+const BEST_TIMES_FILE: &str = "best_times.json";
+
+/// Run timer, measured in virtual time so that pausing the game pauses the timer
+#[derive(Resource, Default, Debug)]
+pub struct RunTimer {
+    pub current_level: Option<String>,
+    /// Time spent in the current level
+    pub elapsed: Duration,
+    pub running: bool,
+    /// Finished levels of this run and the time each took
+    pub splits: Vec<(String, Duration)>,
+}
+
+impl RunTimer {
+    pub fn start_level(&mut self, level_identifier: &str) {
+        self.current_level = Some(level_identifier.to_string());
+        self.elapsed = Duration::ZERO;
+        self.running = true;
+    }
+
+    /// Stop the timer and record the split for the current level
+    pub fn finish_level(&mut self) -> Option<(String, Duration)> {
+        if !self.running {
+            return None;
+        }
+        self.running = false;
+        let split = (self.current_level.clone()?, self.elapsed);
+        self.splits.push(split.clone());
+        Some(split)
+    }
+
+    pub fn total(&self) -> Duration {
+        self.splits.iter().map(|(_, time)| *time).sum::<Duration>()
+            + if self.running {
+                self.elapsed
+            } else {
+                Duration::ZERO
+            }
+    }
+}
+
+/// Best time per level, persisted between sessions
+#[derive(Resource, Default, Debug, Serialize, Deserialize)]
+pub struct BestTimes {
+    pub levels: HashMap<String, Duration>,
+}
+
+impl BestTimes {
+    fn load() -> Self {
+        fs::read_to_string(config_path(BEST_TIMES_FILE))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let path = config_path(BEST_TIMES_FILE);
+        if let Some(dir) = path.parent()
+            && let Err(error) = fs::create_dir_all(dir)
+        {
+            warn!("Failed to create the best times directory: {}", error);
+            return;
+        }
+        match serde_json::to_string_pretty(self) {
+            Ok(json) => {
+                if let Err(error) = fs::write(&path, json) {
+                    warn!("Failed to save best times: {}", error);
+                }
+            }
+            Err(error) => warn!("Failed to serialize best times: {}", error),
+        }
+    }
+
+    /// Record a time for a level, returning whether it beat the previous best
+    pub fn record(&mut self, level_identifier: &str, time: Duration) -> bool {
+        match self.levels.get(level_identifier) {
+            Some(best) if *best <= time => false,
+            _ => {
+                self.levels.insert(level_identifier.to_string(), time);
+                true
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct RunTimerText;
+
+pub fn format_duration(duration: Duration) -> String {
+    let total_centis = duration.as_millis() / 10;
+    format!(
+        "{:02}:{:02}.{:02}",
+        total_centis / 6000,
+        (total_centis / 100) % 60,
+        total_centis % 100
+    )
+}
+
+fn load_best_times(mut commands: Commands) {
+    commands.insert_resource(BestTimes::load());
+}
+
+/// Entering a level (again) starts its split from zero, so restarts don't carry time over
+fn start_level_timer(
+    mut event_reader: EventReader<LevelStartedEvent>,
+    mut run_timer: ResMut<RunTimer>,
+) {
+    for event in event_reader.read() {
+        run_timer.start_level(&event.level_identifier);
+    }
+}
+
+pub fn tick_run_timer(mut run_timer: ResMut<RunTimer>, time: Res<Time>) {
+    if run_timer.running {
+        run_timer.elapsed += time.delta();
+    }
+}
+
+fn stop_level_timer(
+    mut event_reader: EventReader<LevelCompleteEvent>,
+    mut run_timer: ResMut<RunTimer>,
+    mut best_times: ResMut<BestTimes>,
+) {
+    for _ in event_reader.read() {
+        if let Some((level_identifier, time)) = run_timer.finish_level() {
+            info!("Split '{}': {}", level_identifier, format_duration(time));
+            if best_times.record(&level_identifier, time) {
+                best_times.save();
+            }
+        }
+    }
+}
+
+fn spawn_run_timer_text(mut commands: Commands) {
+    commands.spawn((
+        RunTimerText,
+        Text::new(""),
+        TextFont {
+            font_size: 16.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(8.0),
+            right: Val::Px(8.0),
+            ..default()
+        },
+        GameEntity,
+    ));
+}
+
+fn update_run_timer_text(
+    run_timer: Res<RunTimer>,
+    best_times: Res<BestTimes>,
+    mut query: Query<(&mut Text, &mut TextColor), With<RunTimerText>>,
+) {
+    let best = run_timer
+        .current_level
+        .as_ref()
+        .and_then(|level| best_times.levels.get(level));
+
+    for (mut text, mut color) in query.iter_mut() {
+        text.0 = format_duration(run_timer.elapsed);
+        color.0 = Color::WHITE;
+
+        // Show whether the current split is ahead or behind the best time
+        if let Some(best) = best {
+            if run_timer.elapsed <= *best {
+                text.0 += &format!(" (-{})", format_duration(*best - run_timer.elapsed));
+                color.0 = Color::srgb(0.4, 0.9, 0.4);
+            } else {
+                text.0 += &format!(" (+{})", format_duration(run_timer.elapsed - *best));
+                color.0 = Color::srgb(0.9, 0.4, 0.4);
+            }
+        }
+    }
+}
+
+pub struct SpeedrunPlugin;
+
+impl Plugin for SpeedrunPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunTimer>()
+            .add_systems(Startup, load_best_times)
+            .add_systems(OnEnter(GameState::Game), spawn_run_timer_text)
+            .add_systems(
+                Update,
+                (
+                    start_level_timer,
+                    tick_run_timer,
+                    // The title screen demo doesn't set best times
+                    stop_level_timer.run_if(not(is_playing_demo)),
+                    update_run_timer_text,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::system::RunSystemOnce,
+        platform::time::Instant,
+        time::{Real, Virtual, update_virtual_time},
+    };
+
+    use super::*;
+
+    fn step(world: &mut World, real: &mut Time<Real>, millis: u64) {
+        real.update_with_duration(Duration::from_millis(millis));
+        world.resource_scope(|world, mut virtual_time: Mut<Time<Virtual>>| {
+            update_virtual_time(&mut world.resource_mut::<Time>(), &mut virtual_time, real);
+        });
+        world.run_system_once(tick_run_timer).unwrap();
+    }
+
+    #[test]
+    fn test_timer_freezes_on_pause() {
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.insert_resource(Time::<Virtual>::default());
+        let mut real = Time::<Real>::new(Instant::now());
+        // The first real time update only records the starting instant
+        real.update_with_duration(Duration::ZERO);
+
+        let mut run_timer = RunTimer::default();
+        run_timer.start_level("level_0");
+        world.insert_resource(run_timer);
+
+        step(&mut world, &mut real, 100);
+        step(&mut world, &mut real, 100);
+        assert_eq!(
+            world.resource::<RunTimer>().elapsed,
+            Duration::from_millis(200)
+        );
+
+        world.resource_mut::<Time<Virtual>>().pause();
+        step(&mut world, &mut real, 100);
+        step(&mut world, &mut real, 100);
+        assert_eq!(
+            world.resource::<RunTimer>().elapsed,
+            Duration::from_millis(200)
+        );
+
+        world.resource_mut::<Time<Virtual>>().unpause();
+        step(&mut world, &mut real, 50);
+        assert_eq!(
+            world.resource::<RunTimer>().elapsed,
+            Duration::from_millis(250)
+        );
+    }
+
+    #[test]
+    fn test_best_times() {
+        let mut best_times = BestTimes::default();
+
+        assert!(best_times.record("level_0", Duration::from_secs(30)));
+        assert!(!best_times.record("level_0", Duration::from_secs(31)));
+        assert!(best_times.record("level_0", Duration::from_secs(29)));
+        assert_eq!(best_times.levels["level_0"], Duration::from_secs(29));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_millis(83_456)), "01:23.45");
+    }
+}
@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::states::GameState;
+
+/// Tints the sprite white for a moment, then puts its color back
+#[derive(Component)]
+pub struct FlashWhite {
+    timer: Timer,
+    /// Color to go back to, taken from the sprite when the flash starts
+    color: Option<Color>,
+}
+
+impl FlashWhite {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            timer: Timer::new(duration, TimerMode::Once),
+            color: None,
+        }
+    }
+}
+
+/// Turns the sprite see-through and back every `interval`, for `duration`
+#[derive(Component)]
+pub struct Blink {
+    interval: Timer,
+    duration: Timer,
+}
+
+impl Blink {
+    pub fn new(interval: Duration, duration: Duration) -> Self {
+        Self {
+            interval: Timer::new(interval, TimerMode::Repeating),
+            duration: Timer::new(duration, TimerMode::Once),
+        }
+    }
+}
+
+/// Fades the sprite out to fully transparent
+#[derive(Component)]
+pub struct FadeOut {
+    timer: Timer,
+    /// Despawn the entity once it's gone, instead of leaving it invisible
+    despawn: bool,
+}
+
+impl FadeOut {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            timer: Timer::new(duration, TimerMode::Once),
+            despawn: false,
+        }
+    }
+
+    pub fn then_despawn(self) -> Self {
+        Self {
+            despawn: true,
+            ..self
+        }
+    }
+}
+
+/// Way past full brightness, so even dark pixels of a texture come out white
+pub const FLASH_COLOR: Color = Color::srgb(8.0, 8.0, 8.0);
+/// Alpha of a blinking sprite while it's blinked out
+const BLINK_ALPHA: f32 = 0.3;
+
+pub fn update_flash_white(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut FlashWhite, Option<&mut Sprite>)>,
+    time: Res<Time>,
+) {
+    for (entity, mut flash, sprite) in query.iter_mut() {
+        let finished = flash.timer.tick(time.delta()).finished();
+        if let Some(mut sprite) = sprite {
+            let color = *flash.color.get_or_insert(sprite.color);
+            // Alpha is left to whatever else is going on, like blinking
+            let alpha = sprite.color.alpha();
+            sprite.color = if finished { color } else { FLASH_COLOR }.with_alpha(alpha);
+        }
+        if finished {
+            commands.entity(entity).remove::<FlashWhite>();
+        }
+    }
+}
+
+pub fn update_blink(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Blink, Option<&mut Sprite>)>,
+    time: Res<Time>,
+) {
+    for (entity, mut blink, sprite) in query.iter_mut() {
+        let finished = blink.duration.tick(time.delta()).finished();
+        blink.interval.tick(time.delta());
+        if let Some(mut sprite) = sprite {
+            if finished {
+                sprite.color.set_alpha(1.0);
+            } else if blink.interval.just_finished() {
+                let alpha = if sprite.color.alpha() < 1.0 {
+                    1.0
+                } else {
+                    BLINK_ALPHA
+                };
+                sprite.color.set_alpha(alpha);
+            }
+        }
+        if finished {
+            commands.entity(entity).remove::<Blink>();
+        }
+    }
+}
+
+pub fn update_fade_out(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut FadeOut, Option<&mut Sprite>)>,
+    time: Res<Time>,
+) {
+    for (entity, mut fade_out, sprite) in query.iter_mut() {
+        let finished = fade_out.timer.tick(time.delta()).finished();
+        if let Some(mut sprite) = sprite {
+            sprite.color.set_alpha(fade_out.timer.fraction_remaining());
+        }
+        if finished {
+            if fade_out.despawn {
+                commands.entity(entity).despawn();
+            } else {
+                commands.entity(entity).remove::<FadeOut>();
+            }
+        }
+    }
+}
+
+/// Short-lived color and alpha effects on sprites, like flashing after a hit
+pub struct SpriteEffectPlugin;
+
+impl Plugin for SpriteEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (update_flash_white, update_blink, update_fade_out)
+                .chain()
+                .run_if(in_state(GameState::Game)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn step(world: &mut World, elapsed: Duration) {
+        world.resource_mut::<Time>().advance_by(elapsed);
+        world.run_system_once(update_flash_white).unwrap();
+        world.run_system_once(update_blink).unwrap();
+        world.run_system_once(update_fade_out).unwrap();
+    }
+
+    #[test]
+    fn test_effects_restore_the_sprite_and_remove_themselves() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        let color = Color::srgb(0.8, 0.2, 0.2);
+        let entity = world
+            .spawn((
+                Sprite { color, ..default() },
+                FlashWhite::new(Duration::from_millis(100)),
+                Blink::new(Duration::from_millis(50), Duration::from_millis(200)),
+            ))
+            .id();
+        let fading = world
+            .spawn((
+                Sprite::default(),
+                FadeOut::new(Duration::from_millis(100)).then_despawn(),
+            ))
+            .id();
+
+        step(&mut world, Duration::from_millis(50));
+        let sprite = world.get::<Sprite>(entity).unwrap();
+        assert_eq!(sprite.color, FLASH_COLOR.with_alpha(BLINK_ALPHA));
+        assert_eq!(world.get::<Sprite>(fading).unwrap().color.alpha(), 0.5);
+
+        step(&mut world, Duration::from_millis(50));
+        assert_eq!(world.get::<Sprite>(entity).unwrap().color, color);
+        assert!(world.get::<FlashWhite>(entity).is_none());
+        assert!(world.get_entity(fading).is_err());
+
+        step(&mut world, Duration::from_millis(100));
+        assert_eq!(world.get::<Sprite>(entity).unwrap().color, color);
+        assert!(world.get::<Blink>(entity).is_none());
+    }
+}
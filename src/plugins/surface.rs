@@ -0,0 +1,297 @@
+use std::{f32::consts::PI, time::Duration};
+
+use avian2d::prelude::ColliderAabb;
+use bevy::prelude::*;
+use ldtk_rust::EntityInstance;
+
+use crate::{
+    constants::{self, TilesPerSecond, TilesPerSecondSquared},
+    schedule::MovementSet,
+    states::GameState,
+};
+
+use super::{
+    audio::{PlaySfxAtEvent, SfxId},
+    collision::{GroundEntity, IsGrounded, Velocity, child_collider_aabb},
+    gravity::EntityGravity,
+    level::{LdtkEntityAppExt, LdtkEntityContext, spawn_trigger_area},
+    player::apply_controls,
+    sprite_effect::FadeOut,
+};
+
+/// How well a piece of ground grips whoever walks on it, scaling their acceleration and
+/// deceleration. Ground without it has full grip.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct SurfaceFriction(pub f32);
+
+impl SurfaceFriction {
+    pub const ICE: Self = Self(0.1);
+}
+
+/// Ground that pushes whoever stands on it sideways
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct Conveyor {
+    /// Pixels per second, positive to the right
+    pub speed: f32,
+}
+
+/// Conveyor velocity currently included in the entity's `Velocity`
+///
+/// It's taken back out while the controls run, so walking and decelerating only ever deal
+/// with the entity's own speed.
+#[derive(Component, Default, Debug)]
+pub struct ConveyorPush(pub f32);
+
+/// Area of water slowing down whatever falls into it
+#[derive(Component, Clone, Default, Reflect)]
+pub struct Water;
+
+/// Present while the entity overlaps water
+#[derive(Component, Debug, Default)]
+pub struct InWater;
+
+/// Drop of water thrown up by a splash, falling until it fades out
+#[derive(Component)]
+struct SplashDrop {
+    velocity: Vec2,
+}
+
+pub const CONVEYOR_SPEED: TilesPerSecond = TilesPerSecond(4.0);
+/// Fraction of the horizontal velocity lost per second in water
+const WATER_DRAG: f32 = 4.0;
+pub const WATER_MAX_FALL_SPEED: TilesPerSecond = TilesPerSecond(4.0);
+/// How much of its usual gravity pulls on something in water
+pub const WATER_GRAVITY_SCALE: f32 = 0.3;
+/// Upwards speed of a single swim stroke
+const SWIM_STROKE_SPEED: TilesPerSecond = TilesPerSecond(8.0);
+const SPLASH_DROPS: usize = 6;
+const SPLASH_SPEED: TilesPerSecond = TilesPerSecond(6.0);
+const SPLASH_GRAVITY: TilesPerSecondSquared = TilesPerSecondSquared(30.0);
+const SPLASH_DURATION: Duration = Duration::from_millis(400);
+const SPLASH_COLOR: Color = Color::srgb(0.7, 0.85, 1.0);
+
+/// Friction of whatever the entity is standing on
+pub fn ground_friction(
+    ground_entity: Option<&GroundEntity>,
+    friction_query: &Query<&SurfaceFriction>,
+) -> f32 {
+    ground_entity
+        .and_then(|ground_entity| ground_entity.0)
+        .and_then(|ground| friction_query.get(ground).ok())
+        .map_or(1.0, |friction| friction.0)
+}
+
+/// Horizontal velocity with a conveyor's push taken out
+///
+/// Anything that already stopped the push, like running into a wall at the end of the belt,
+/// doesn't turn into a push the other way.
+fn without_push(velocity_x: f32, push: f32) -> f32 {
+    if push > 0.0 {
+        (velocity_x - push).max(velocity_x.min(0.0))
+    } else {
+        (velocity_x - push).min(velocity_x.max(0.0))
+    }
+}
+
+/// Velocity in water, with sideways movement damped and anything falling in fast slowed
+/// down to the water's max fall speed
+fn water_velocity(velocity: Vec2, delta_secs: f32) -> Vec2 {
+    Vec2::new(
+        velocity.x * (1.0 - WATER_DRAG * delta_secs).max(0.0),
+        velocity.y.max(-WATER_MAX_FALL_SPEED.to_pixels()),
+    )
+}
+
+/// Vertical velocity after a swim stroke, which never slows down something already
+/// rising faster
+pub fn swim_stroke(vertical_velocity: f32) -> f32 {
+    vertical_velocity.max(SWIM_STROKE_SPEED.to_pixels())
+}
+
+fn remove_conveyor_push(mut query: Query<(&mut Velocity, &mut ConveyorPush)>) {
+    for (mut velocity, mut push) in query.iter_mut() {
+        velocity.0.x = without_push(velocity.0.x, push.0);
+        push.0 = 0.0;
+    }
+}
+
+fn add_conveyor_push(
+    mut query: Query<(&mut Velocity, &mut ConveyorPush, &IsGrounded, &GroundEntity)>,
+    conveyor_query: Query<&Conveyor>,
+) {
+    for (mut velocity, mut push, is_grounded, ground_entity) in query.iter_mut() {
+        let Some(conveyor) = ground_entity
+            .0
+            .filter(|_| is_grounded.grounded)
+            .and_then(|ground| conveyor_query.get(ground).ok())
+        else {
+            continue;
+        };
+        velocity.0.x += conveyor.speed;
+        push.0 = conveyor.speed;
+    }
+}
+
+/// Mark whatever falls, like the player and walking enemies, as being in water or not
+fn update_in_water(
+    mut commands: Commands,
+    query: Query<(Entity, &Children, Has<InWater>), With<EntityGravity>>,
+    water_query: Query<&ColliderAabb, With<Water>>,
+    aabb_query: Query<&ColliderAabb>,
+) {
+    for (entity, children, was_in_water) in query.iter() {
+        let in_water = child_collider_aabb(children, &aabb_query).is_some_and(|aabb| {
+            water_query
+                .iter()
+                .any(|water_aabb| water_aabb.intersects(&aabb))
+        });
+        if in_water && !was_in_water {
+            commands.entity(entity).insert(InWater);
+        } else if !in_water && was_in_water {
+            commands.entity(entity).remove::<InWater>();
+        }
+    }
+}
+
+fn apply_water(mut query: Query<&mut Velocity, With<InWater>>, time: Res<Time>) {
+    for mut velocity in query.iter_mut() {
+        velocity.0 = water_velocity(velocity.0, time.delta_secs());
+    }
+}
+
+fn spawn_splash(commands: &mut Commands, position: Vec3) {
+    for drop in 0..SPLASH_DROPS {
+        // Fanned out upwards, from 30 to 150 degrees
+        let angle = PI / 6.0 + drop as f32 / (SPLASH_DROPS - 1) as f32 * (2.0 * PI / 3.0);
+        commands.spawn((
+            SplashDrop {
+                velocity: Vec2::from_angle(angle) * SPLASH_SPEED.to_pixels(),
+            },
+            Sprite::from_color(SPLASH_COLOR, Vec2::splat(2.0)),
+            Transform::from_translation(position),
+            FadeOut::new(SPLASH_DURATION).then_despawn(),
+        ));
+    }
+}
+
+/// Splash whenever something goes into or comes out of water
+fn splash(
+    mut commands: Commands,
+    entered_query: Query<&Transform, Added<InWater>>,
+    mut left: RemovedComponents<InWater>,
+    transform_query: Query<&Transform>,
+    mut sfx_writer: EventWriter<PlaySfxAtEvent>,
+) {
+    let left_positions = left
+        .read()
+        .filter_map(|entity| transform_query.get(entity).ok());
+    for transform in entered_query.iter().chain(left_positions) {
+        spawn_splash(&mut commands, transform.translation);
+        sfx_writer.write(PlaySfxAtEvent {
+            id: SfxId::Splash,
+            position: transform.translation.xy(),
+        });
+    }
+}
+
+fn move_splash_drops(mut query: Query<(&mut SplashDrop, &mut Transform)>, time: Res<Time>) {
+    for (mut drop, mut transform) in query.iter_mut() {
+        drop.velocity.y -= SPLASH_GRAVITY.to_pixels() * time.delta_secs();
+        transform.translation += (drop.velocity * time.delta_secs()).extend(0.0);
+    }
+}
+
+/// Special kinds of level geometry: ice, conveyors and water
+fn spawn_ldtk_water(entity: &EntityInstance, context: &LdtkEntityContext, commands: &mut Commands) {
+    spawn_trigger_area(commands, context.level_id, entity, Water);
+}
+
+pub struct SurfacePlugin;
+
+impl Plugin for SurfacePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_ldtk_entity(constants::entities::WATER, spawn_ldtk_water)
+            .add_systems(
+                FixedUpdate,
+                (
+                    (remove_conveyor_push, update_in_water)
+                        .in_set(MovementSet::Input)
+                        .before(apply_controls),
+                    (apply_water, add_conveyor_push)
+                        .chain()
+                        .after(MovementSet::Gravity)
+                        .before(MovementSet::CollisionChecks),
+                ),
+            )
+            .add_systems(
+                Update,
+                (splash, move_splash_drops).run_if(in_state(GameState::Game)),
+            )
+            .register_type::<SurfaceFriction>()
+            .register_type::<Conveyor>()
+            .register_type::<Water>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_conveyor_push_is_added_once() {
+        let mut world = World::new();
+        let belt = world.spawn(Conveyor { speed: 64.0 }).id();
+        let player = world
+            .spawn((
+                Velocity(Vec2::new(10.0, 0.0)),
+                ConveyorPush::default(),
+                IsGrounded {
+                    grounded: true,
+                    normal: Vec2::Y,
+                },
+                GroundEntity(Some(belt)),
+            ))
+            .id();
+
+        for _ in 0..3 {
+            world.run_system_once(remove_conveyor_push).unwrap();
+            world.run_system_once(add_conveyor_push).unwrap();
+            assert_eq!(world.get::<Velocity>(player).unwrap().0.x, 74.0);
+        }
+
+        // Stepping off keeps only the player's own speed
+        world.get_mut::<GroundEntity>(player).unwrap().0 = None;
+        world.run_system_once(remove_conveyor_push).unwrap();
+        world.run_system_once(add_conveyor_push).unwrap();
+        assert_eq!(world.get::<Velocity>(player).unwrap().0.x, 10.0);
+
+        // Pushed against a wall, which already stopped the push
+        assert_eq!(without_push(0.0, 64.0), 0.0);
+        assert_eq!(without_push(-20.0, -64.0), 0.0);
+    }
+
+    #[test]
+    fn test_water_slows_falls_and_swimming_rises() {
+        let max_fall_speed = WATER_MAX_FALL_SPEED.to_pixels();
+        let velocity = water_velocity(Vec2::new(100.0, -500.0), 0.1);
+        assert!(velocity.x < 100.0 && velocity.x > 0.0);
+        assert_eq!(velocity.y, -max_fall_speed);
+
+        let gravity = EntityGravity {
+            gravity: 480.0,
+            max_fall_speed: 240.0,
+            enabled: true,
+        };
+        assert_eq!(gravity.strength(false), (480.0, 240.0));
+        assert_eq!(
+            gravity.strength(true),
+            (480.0 * WATER_GRAVITY_SCALE, max_fall_speed)
+        );
+
+        // A stroke stops the sinking, but doesn't slow down a faster rise
+        assert_eq!(swim_stroke(-30.0), SWIM_STROKE_SPEED.to_pixels());
+        assert_eq!(swim_stroke(1000.0), 1000.0);
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+
+use bevy::{
+    app::{FixedMain, RunFixedMainLoop, RunFixedMainLoopSystem},
+    prelude::*,
+};
+
+const PAUSE_KEY: KeyCode = KeyCode::F7;
+const STEP_KEY: KeyCode = KeyCode::F8;
+const SLOWER_KEY: KeyCode = KeyCode::F9;
+const FASTER_KEY: KeyCode = KeyCode::F10;
+
+pub const MIN_TIME_SCALE: f32 = 0.1;
+pub const MAX_TIME_SCALE: f32 = 2.0;
+/// Time scales the slower and faster keys go through
+const TIME_SCALE_PRESETS: [f32; 6] = [0.1, 0.25, 0.5, 1.0, 1.5, 2.0];
+
+/// Something that has stopped the virtual clock
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+pub enum PauseReason {
+    /// The pause menu is open
+    Menu,
+    /// Paused with the time control debug keys
+    Debug,
+}
+
+/// Everything holding the virtual clock paused
+///
+/// The clock only runs again once every reason is gone, so closing the pause menu doesn't
+/// resume a simulation paused for debugging, or the other way around.
+#[derive(Resource, Default, Debug, Reflect)]
+#[reflect(Resource)]
+pub struct PauseReasons(HashSet<PauseReason>);
+
+impl PauseReasons {
+    pub fn contains(&self, reason: PauseReason) -> bool {
+        self.0.contains(&reason)
+    }
+
+    pub fn pause(&mut self, reason: PauseReason, time: &mut Time<Virtual>) {
+        self.0.insert(reason);
+        time.pause();
+    }
+
+    pub fn resume(&mut self, reason: PauseReason, time: &mut Time<Virtual>) {
+        self.0.remove(&reason);
+        if self.0.is_empty() {
+            time.unpause();
+        }
+    }
+}
+
+/// Debug controls for the simulation speed
+///
+/// While paused for debugging the game only advances one fixed timestep at a time with
+/// `step`, so jump arcs and collisions can be followed frame by frame.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TimeControl {
+    /// Speed of the virtual clock, between `MIN_TIME_SCALE` and `MAX_TIME_SCALE`
+    pub scale: f32,
+    /// Fixed timesteps still to run while paused
+    pending_steps: u32,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            pending_steps: 0,
+        }
+    }
+}
+
+impl TimeControl {
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+    }
+
+    /// Run a single fixed timestep, once paused for debugging
+    pub fn step(&mut self) {
+        self.pending_steps += 1;
+    }
+
+    pub fn slower(&mut self) {
+        if let Some(&scale) = TIME_SCALE_PRESETS
+            .iter()
+            .rev()
+            .find(|&&preset| preset < self.scale)
+        {
+            self.scale = scale;
+        }
+    }
+
+    pub fn faster(&mut self) {
+        if let Some(&scale) = TIME_SCALE_PRESETS
+            .iter()
+            .find(|&&preset| preset > self.scale)
+        {
+            self.scale = scale;
+        }
+    }
+}
+
+fn time_control_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut control: ResMut<TimeControl>,
+    mut pause_reasons: ResMut<PauseReasons>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if keys.just_pressed(PAUSE_KEY) {
+        if pause_reasons.contains(PauseReason::Debug) {
+            pause_reasons.resume(PauseReason::Debug, &mut time);
+            info!("simulation resumed");
+        } else {
+            pause_reasons.pause(PauseReason::Debug, &mut time);
+            info!("simulation paused");
+        }
+    }
+    if keys.just_pressed(STEP_KEY) {
+        control.step();
+    }
+    if keys.just_pressed(SLOWER_KEY) {
+        control.slower();
+        info!("time scale {}", control.scale);
+    }
+    if keys.just_pressed(FASTER_KEY) {
+        control.faster();
+        info!("time scale {}", control.scale);
+    }
+}
+
+fn apply_time_scale(control: Res<TimeControl>, mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(control.scale);
+}
+
+/// Run the fixed timesteps stepped through while paused for debugging
+///
+/// The virtual clock stays paused, so the fixed clock is advanced by hand, one timestep
+/// per step, the same way the fixed main loop would. Steps asked for while running are dropped.
+fn run_requested_steps(world: &mut World) {
+    let steps = std::mem::take(&mut world.resource_mut::<TimeControl>().pending_steps);
+    if steps == 0
+        || !world
+            .resource::<PauseReasons>()
+            .contains(PauseReason::Debug)
+    {
+        return;
+    }
+
+    for _ in 0..steps {
+        let timestep = world.resource::<Time<Fixed>>().timestep();
+        world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+        *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+        world.run_schedule(FixedMain);
+    }
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+pub struct TimeControlPlugin;
+
+impl Plugin for TimeControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeControl>()
+            .init_resource::<PauseReasons>()
+            .add_systems(
+                Update,
+                (
+                    time_control_keys,
+                    apply_time_scale.run_if(resource_changed::<TimeControl>),
+                )
+                    .chain(),
+            )
+            .add_systems(
+                RunFixedMainLoop,
+                run_requested_steps.in_set(RunFixedMainLoopSystem::AfterFixedMainLoop),
+            )
+            .register_type::<TimeControl>()
+            .register_type::<PauseReasons>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_scale_presets() {
+        let mut control = TimeControl::default();
+        control.slower();
+        assert_eq!(control.scale, 0.5);
+        control.set_scale(0.3);
+        control.faster();
+        assert_eq!(control.scale, 0.5);
+
+        // Stays within the limits
+        control.set_scale(5.0);
+        assert_eq!(control.scale, MAX_TIME_SCALE);
+        control.faster();
+        assert_eq!(control.scale, MAX_TIME_SCALE);
+        control.set_scale(0.0);
+        assert_eq!(control.scale, MIN_TIME_SCALE);
+        control.slower();
+        assert_eq!(control.scale, MIN_TIME_SCALE);
+    }
+
+    #[test]
+    fn test_clock_stays_paused_while_any_reason_is_left() {
+        let mut time = Time::<Virtual>::default();
+        let mut pause_reasons = PauseReasons::default();
+
+        pause_reasons.pause(PauseReason::Debug, &mut time);
+        pause_reasons.pause(PauseReason::Menu, &mut time);
+        // Closing the pause menu keeps the debug pause
+        pause_reasons.resume(PauseReason::Menu, &mut time);
+        assert!(time.is_paused());
+        pause_reasons.resume(PauseReason::Debug, &mut time);
+        assert!(!time.is_paused());
+    }
+
+    #[derive(Resource, Default)]
+    struct FixedSteps(u32);
+
+    #[test]
+    fn test_step_runs_one_fixed_timestep() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<Time<Fixed>>();
+        world.init_resource::<Time<Virtual>>();
+        world.init_resource::<FixedSteps>();
+        world.init_resource::<TimeControl>();
+        world.init_resource::<PauseReasons>();
+        let mut schedule = Schedule::new(FixedMain);
+        schedule.add_systems(|mut steps: ResMut<FixedSteps>| steps.0 += 1);
+        world.add_schedule(schedule);
+
+        // Stepping does nothing unless paused
+        world.resource_mut::<TimeControl>().step();
+        run_requested_steps(&mut world);
+        assert_eq!(world.resource::<FixedSteps>().0, 0);
+
+        world.resource_scope(|world, mut pause_reasons: Mut<PauseReasons>| {
+            pause_reasons.pause(
+                PauseReason::Debug,
+                &mut world.resource_mut::<Time<Virtual>>(),
+            );
+        });
+        world.resource_mut::<TimeControl>().step();
+        run_requested_steps(&mut world);
+        run_requested_steps(&mut world);
+        assert_eq!(world.resource::<FixedSteps>().0, 1);
+        let fixed = world.resource::<Time<Fixed>>();
+        assert_eq!(fixed.elapsed(), fixed.timestep());
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashSet;
+
+use bevy::{input::InputSystem, prelude::*, window::PrimaryWindow};
+use leafwing_input_manager::{
+    buttonlike::ButtonState, plugin::InputManagerSystem, prelude::ActionState,
+};
+
+use crate::{bundles::player::Player, components::GameEntity, states::GameState};
+
+use super::player::PlayerAction;
+
+/// Width and height of an on-screen button, in logical pixels
+const BUTTON_SIZE: f32 = 64.0;
+/// Space between the buttons and around the edge of the screen
+const BUTTON_MARGIN: f32 = 16.0;
+const BUTTON_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.2);
+const BUTTON_PRESSED_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.45);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScreenSide {
+    Left,
+    Right,
+}
+
+/// An on-screen button holding down a player action while touched
+#[derive(Component, Clone, Copy, Debug)]
+struct TouchButton {
+    action: PlayerAction,
+    label: &'static str,
+    side: ScreenSide,
+    /// Distance from the edge of the screen on `side` to the near edge of the button
+    offset: f32,
+}
+
+const TOUCH_BUTTONS: [TouchButton; 4] = [
+    TouchButton {
+        action: PlayerAction::Left,
+        label: "<",
+        side: ScreenSide::Left,
+        offset: BUTTON_MARGIN,
+    },
+    TouchButton {
+        action: PlayerAction::Right,
+        label: ">",
+        side: ScreenSide::Left,
+        offset: BUTTON_MARGIN * 2.0 + BUTTON_SIZE,
+    },
+    TouchButton {
+        action: PlayerAction::Shoot,
+        label: "Shoot",
+        side: ScreenSide::Right,
+        offset: BUTTON_MARGIN * 2.0 + BUTTON_SIZE,
+    },
+    TouchButton {
+        action: PlayerAction::Jump,
+        label: "Jump",
+        side: ScreenSide::Right,
+        offset: BUTTON_MARGIN,
+    },
+];
+
+impl TouchButton {
+    /// Where the button is on a screen of `screen_size`, with y going down like touch
+    /// positions do
+    fn rect(&self, screen_size: Vec2) -> Rect {
+        let x = match self.side {
+            ScreenSide::Left => self.offset,
+            ScreenSide::Right => screen_size.x - self.offset - BUTTON_SIZE,
+        };
+        let y = screen_size.y - BUTTON_MARGIN - BUTTON_SIZE;
+        Rect::new(x, y, x + BUTTON_SIZE, y + BUTTON_SIZE)
+    }
+}
+
+/// Actions held down by touches at `positions`
+fn touched_actions(
+    positions: impl IntoIterator<Item = Vec2>,
+    screen_size: Vec2,
+) -> HashSet<PlayerAction> {
+    positions
+        .into_iter()
+        .filter_map(|position| {
+            TOUCH_BUTTONS
+                .iter()
+                .find(|button| button.rect(screen_size).contains(position))
+                .map(|button| button.action)
+        })
+        .collect()
+}
+
+/// Whether the on-screen controls are shown, from the start with the `touch_controls`
+/// feature and otherwise once the screen is first touched
+#[derive(Resource, Debug)]
+pub struct TouchControlsEnabled(pub bool);
+
+impl Default for TouchControlsEnabled {
+    fn default() -> Self {
+        Self(cfg!(feature = "touch_controls"))
+    }
+}
+
+fn detect_touch(touches: Res<Touches>, mut enabled: ResMut<TouchControlsEnabled>) {
+    if !enabled.0 && touches.any_just_pressed() {
+        enabled.0 = true;
+    }
+}
+
+fn spawn_touch_controls(mut commands: Commands, enabled: Res<TouchControlsEnabled>) {
+    let visibility = if enabled.0 {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+
+    for button in TOUCH_BUTTONS {
+        let (left, right) = match button.side {
+            ScreenSide::Left => (Val::Px(button.offset), Val::Auto),
+            ScreenSide::Right => (Val::Auto, Val::Px(button.offset)),
+        };
+        commands.spawn((
+            button,
+            Node {
+                position_type: PositionType::Absolute,
+                left,
+                right,
+                bottom: Val::Px(BUTTON_MARGIN),
+                width: Val::Px(BUTTON_SIZE),
+                height: Val::Px(BUTTON_SIZE),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(BUTTON_COLOR),
+            visibility,
+            GameEntity,
+            children![(
+                Text::new(button.label),
+                TextFont {
+                    font_size: 16.0,
+                    ..default()
+                },
+                TextColor(Color::WHITE),
+            )],
+        ));
+    }
+}
+
+fn show_touch_controls(
+    enabled: Res<TouchControlsEnabled>,
+    mut query: Query<&mut Visibility, With<TouchButton>>,
+) {
+    for mut visibility in query.iter_mut() {
+        *visibility = if enabled.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
+
+/// Actions whose on-screen buttons are being touched
+#[derive(Resource, Default, Debug)]
+struct TouchedActions(HashSet<PlayerAction>);
+
+fn read_touches(
+    touches: Res<Touches>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    state: Res<State<GameState>>,
+    mut touched: ResMut<TouchedActions>,
+    mut button_query: Query<(&TouchButton, &mut BackgroundColor)>,
+) {
+    let Ok(window) = window_query.single() else {
+        return;
+    };
+
+    touched.0 = if *state.get() == GameState::Game {
+        touched_actions(touches.iter().map(|touch| touch.position()), window.size())
+    } else {
+        HashSet::new()
+    };
+
+    for (button, mut background) in button_query.iter_mut() {
+        background.0 = if touched.0.contains(&button.action) {
+            BUTTON_PRESSED_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+    }
+}
+
+/// Press the touched actions on top of whatever the input map read from the keyboard and
+/// gamepads, leaving the keys themselves alone
+///
+/// The input map releases actions it sees no input for, so one still held by a touch is put
+/// back to plain pressed rather than pressed anew, which would make it just pressed again.
+fn hold_touched_actions(
+    touched: Res<TouchedActions>,
+    mut query: Query<&mut ActionState<PlayerAction>, With<Player>>,
+    // Actions touched when this last ran
+    mut held: Local<HashSet<PlayerAction>>,
+) {
+    for mut action_state in query.iter_mut() {
+        for action in &touched.0 {
+            if held.contains(action) && action_state.just_released(action) {
+                if let Some(button_data) = action_state.button_data_mut(action) {
+                    button_data.state = ButtonState::Pressed;
+                }
+            } else {
+                action_state.press(action);
+            }
+        }
+    }
+    held.clone_from(&touched.0);
+}
+
+/// On-screen buttons for playing on touch screens
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControlsEnabled>()
+            .init_resource::<TouchedActions>()
+            .add_systems(OnEnter(GameState::Game), spawn_touch_controls)
+            .add_systems(
+                PreUpdate,
+                (
+                    read_touches.after(InputSystem),
+                    hold_touched_actions.in_set(InputManagerSystem::ManualControl),
+                )
+                    .chain(),
+            )
+            // The player's controls run on the fixed timestep, which has an action state of
+            // its own
+            .add_systems(
+                FixedPreUpdate,
+                hold_touched_actions.in_set(InputManagerSystem::ManualControl),
+            )
+            .add_systems(
+                Update,
+                (
+                    detect_touch,
+                    show_touch_controls.run_if(resource_changed::<TouchControlsEnabled>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_held_touch_is_only_just_pressed_once() {
+        let mut world = World::new();
+        world.insert_resource(TouchedActions(HashSet::from([PlayerAction::Jump])));
+        let player = world
+            .spawn((Player, ActionState::<PlayerAction>::default()))
+            .id();
+        let action_state = |world: &World| {
+            world
+                .get::<ActionState<PlayerAction>>(player)
+                .unwrap()
+                .clone()
+        };
+
+        let mut system = IntoSystem::into_system(hold_touched_actions);
+        system.initialize(&mut world);
+        system.run((), &mut world);
+        assert!(action_state(&world).just_pressed(&PlayerAction::Jump));
+
+        // The input map sees no key for it on the next frame
+        world
+            .get_mut::<ActionState<PlayerAction>>(player)
+            .unwrap()
+            .release(&PlayerAction::Jump);
+        system.run((), &mut world);
+        let held = action_state(&world);
+        assert!(held.pressed(&PlayerAction::Jump));
+        assert!(!held.just_pressed(&PlayerAction::Jump));
+
+        // Lifting the finger leaves the release to the input map
+        world.resource_mut::<TouchedActions>().0.clear();
+        world
+            .get_mut::<ActionState<PlayerAction>>(player)
+            .unwrap()
+            .release(&PlayerAction::Jump);
+        system.run((), &mut world);
+        assert!(action_state(&world).released(&PlayerAction::Jump));
+    }
+
+    #[test]
+    fn test_touches_press_the_buttons_under_them() {
+        let screen_size = Vec2::new(800.0, 600.0);
+        let bottom_row = screen_size.y - BUTTON_MARGIN - BUTTON_SIZE / 2.0;
+
+        let actions = touched_actions(
+            [
+                Vec2::new(BUTTON_MARGIN + 1.0, bottom_row),
+                Vec2::new(screen_size.x - BUTTON_MARGIN - 1.0, bottom_row),
+                // Nowhere near a button
+                Vec2::new(400.0, 300.0),
+            ],
+            screen_size,
+        );
+        assert_eq!(
+            actions,
+            HashSet::from([PlayerAction::Left, PlayerAction::Jump])
+        );
+
+        // Between the two left buttons
+        let gap = touched_actions(
+            [Vec2::new(BUTTON_MARGIN * 1.5 + BUTTON_SIZE, bottom_row)],
+            screen_size,
+        );
+        assert!(gap.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use avian2d::prelude::*;
+use bevy::prelude::*;
+use leafwing_input_manager::prelude::ActionState;
+
+use crate::{bundles::player::Player, constants::GameLayer};
+
+use super::{
+    aim::{AimAssist, AimDirection, AimMode},
+    player::{BarrelPosition, PlayerAction},
+    projectile::Weapon,
+};
+
+/// Settings for the predicted trajectory drawn while aiming a lobbed shot
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TrajectoryPreview {
+    pub enabled: bool,
+    /// Number of simulated steps
+    pub steps: usize,
+    /// Simulated time between two steps, in seconds
+    pub time_step: f32,
+    pub color: Color,
+}
+
+impl Default for TrajectoryPreview {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            steps: 40,
+            time_step: 1.0 / 30.0,
+            color: Color::srgba(1.0, 1.0, 1.0, 0.6),
+        }
+    }
+}
+
+/// Sample the positions of a projectile launched from `origin` with `velocity`,
+/// falling with `gravity`, at every `time_step` for `steps` steps.
+///
+/// The returned points include the origin, so there are `steps + 1` of them.
+pub fn sample_trajectory(
+    origin: Vec2,
+    velocity: Vec2,
+    gravity: f32,
+    time_step: f32,
+    steps: usize,
+) -> Vec<Vec2> {
+    (0..=steps)
+        .map(|step| {
+            let t = step as f32 * time_step;
+            origin + velocity * t + Vec2::new(0.0, -0.5 * gravity * t * t)
+        })
+        .collect()
+}
+
+/// Whether the player is taking aim, locked in place or with the aim stick tilted
+fn is_aiming(aim_mode: AimMode, stick: Vec2, deadzone: f32) -> bool {
+    aim_mode == AimMode::AimLock || stick.length() >= deadzone
+}
+
+/// Predicted path of the equipped lobbed weapon's shot, shown while aiming it
+fn draw_trajectory_preview(
+    mut gizmos: Gizmos,
+    spatial_query: SpatialQuery,
+    preview: Res<TrajectoryPreview>,
+    aim_assist: Res<AimAssist>,
+    query: Query<
+        (
+            &Transform,
+            &BarrelPosition,
+            &AimDirection,
+            &AimMode,
+            &Weapon,
+            &ActionState<PlayerAction>,
+        ),
+        With<Player>,
+    >,
+) {
+    if !preview.enabled {
+        return;
+    }
+
+    let filter = SpatialQueryFilter::from_mask(GameLayer::LevelGeometry.to_bits());
+
+    for (transform, barrel_position, aim_direction, aim_mode, weapon, action_state) in query.iter()
+    {
+        let Some(arcing_shot) = &weapon.arcing_shot else {
+            continue;
+        };
+        let stick = action_state.axis_pair(&PlayerAction::Aim);
+        if !is_aiming(*aim_mode, stick, aim_assist.deadzone) {
+            continue;
+        }
+
+        let origin = transform.translation.xy() + barrel_position.0;
+        let points = sample_trajectory(
+            origin,
+            aim_direction.0 * arcing_shot.speed,
+            arcing_shot.gravity,
+            preview.time_step,
+            preview.steps,
+        );
+
+        for (index, segment) in points.windows(2).enumerate() {
+            let (start, end) = (segment[0], segment[1]);
+            let Ok(direction) = Dir2::new(end - start) else {
+                continue;
+            };
+
+            let hit = spatial_query.cast_ray(start, direction, start.distance(end), true, &filter);
+            let end = hit.map_or(end, |hit| start + direction * hit.distance);
+
+            // Skip every other segment to get a dotted line
+            if index % 2 == 0 {
+                gizmos.line_2d(start, end, preview.color);
+            }
+
+            if let Some(_hit) = hit {
+                gizmos.circle_2d(end, 3.0, preview.color);
+                break;
+            }
+        }
+    }
+}
+
+pub struct TrajectoryPlugin;
+
+impl Plugin for TrajectoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TrajectoryPreview>()
+            .add_systems(Update, draw_trajectory_preview)
+            .register_type::<TrajectoryPreview>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_trajectory() {
+        let points = sample_trajectory(Vec2::ZERO, Vec2::new(100.0, 100.0), 200.0, 0.1, 10);
+
+        assert_eq!(points.len(), 11);
+        assert_eq!(points[0], Vec2::ZERO);
+
+        // Apex is at t = vy / g = 0.5s, height = vy^2 / 2g = 25
+        let apex = points[5];
+        assert!((apex.x - 50.0).abs() < 1e-3);
+        assert!((apex.y - 25.0).abs() < 1e-3);
+
+        // Symmetric around the apex, back at launch height at t = 1.0s
+        assert!((points[10].y).abs() < 1e-3);
+        assert!((points[4].y - points[6].y).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_preview_only_while_aiming() {
+        assert!(!is_aiming(AimMode::RunAndGun, Vec2::ZERO, 0.3));
+        // Barely touched stick
+        assert!(!is_aiming(AimMode::RunAndGun, Vec2::new(0.1, 0.1), 0.3));
+        assert!(is_aiming(AimMode::RunAndGun, Vec2::new(0.0, 0.8), 0.3));
+        assert!(is_aiming(AimMode::AimLock, Vec2::ZERO, 0.3));
+    }
+
+    #[test]
+    fn test_sample_trajectory_without_gravity_is_straight() {
+        let points = sample_trajectory(Vec2::new(5.0, 5.0), Vec2::X * 10.0, 0.0, 1.0, 3);
+
+        assert_eq!(
+            points,
+            vec![
+                Vec2::new(5.0, 5.0),
+                Vec2::new(15.0, 5.0),
+                Vec2::new(25.0, 5.0),
+                Vec2::new(35.0, 5.0)
+            ]
+        );
+    }
+}
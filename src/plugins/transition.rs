@@ -0,0 +1,305 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, ui::FocusPolicy, window::PrimaryWindow};
+
+use crate::states::GameState;
+
+/// How a transition covers the screen
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum TransitionKind {
+    /// The whole screen fades to black
+    #[default]
+    Fade,
+    /// Black closes in from the edges in a shrinking circle
+    CircleWipe,
+}
+
+/// Cover the screen, then reveal it again
+///
+/// Once covered a `TransitionCoveredEvent` is fired, which is the time to swap out whatever
+/// is on screen. A `TransitionFinishedEvent` follows once the screen is fully revealed.
+/// Starting a transition replaces any that's still running, one replaced before it covered
+/// the screen fires a `TransitionInterruptedEvent` instead.
+#[derive(Event, Clone, Debug)]
+pub struct TransitionEvent {
+    pub kind: TransitionKind,
+    /// Time for each of covering and revealing
+    pub duration: Duration,
+    /// Start out covered and only reveal, for changes that already happened, like a
+    /// state being entered
+    pub reveal_only: bool,
+}
+
+impl TransitionEvent {
+    pub fn cover_and_reveal(kind: TransitionKind, duration: Duration) -> Self {
+        Self {
+            kind,
+            duration,
+            reveal_only: false,
+        }
+    }
+
+    pub fn reveal(kind: TransitionKind, duration: Duration) -> Self {
+        Self {
+            kind,
+            duration,
+            reveal_only: true,
+        }
+    }
+}
+
+#[derive(Event, Clone, Debug)]
+pub struct TransitionCoveredEvent;
+
+#[derive(Event, Clone, Debug)]
+pub struct TransitionFinishedEvent;
+
+/// A transition was replaced before covering the screen, so its `TransitionCoveredEvent`
+/// never comes
+#[derive(Event, Clone, Debug)]
+pub struct TransitionInterruptedEvent;
+
+/// The overlay of a running transition, drawn over everything else
+#[derive(Component)]
+struct Transition {
+    kind: TransitionKind,
+    timer: Timer,
+    covering: bool,
+}
+
+/// The ring closing in for `TransitionKind::CircleWipe`, a child of the overlay
+#[derive(Component)]
+struct CircleWipe;
+
+const TRANSITION_COLOR: Color = Color::BLACK;
+const STATE_CHANGE_TRANSITION: Duration = Duration::from_millis(300);
+
+/// How much of the screen is covered, from 0.0 to 1.0, `progress` into a phase
+fn coverage(progress: f32, covering: bool) -> f32 {
+    if covering { progress } else { 1.0 - progress }
+}
+
+fn start_transitions(
+    mut commands: Commands,
+    mut event_reader: EventReader<TransitionEvent>,
+    transition_query: Query<(Entity, &Transition)>,
+    mut interrupted_writer: EventWriter<TransitionInterruptedEvent>,
+) {
+    let events: Vec<_> = event_reader.read().collect();
+    let Some((&event, replaced_events)) = events.split_last() else {
+        return;
+    };
+    // Those started in the same frame as another never even got to run
+    for replaced in replaced_events {
+        if !replaced.reveal_only {
+            interrupted_writer.write(TransitionInterruptedEvent);
+        }
+    }
+    for (entity, transition) in transition_query.iter() {
+        if transition.covering {
+            interrupted_writer.write(TransitionInterruptedEvent);
+        }
+        commands.entity(entity).despawn();
+    }
+
+    let mut overlay = commands.spawn((
+        Transition {
+            kind: event.kind,
+            timer: Timer::new(event.duration, TimerMode::Once),
+            covering: !event.reveal_only,
+        },
+        Node {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            overflow: Overflow::clip(),
+            ..default()
+        },
+        GlobalZIndex(i32::MAX),
+        // Clicks don't go through to buttons while the screen is (being) covered
+        FocusPolicy::Block,
+    ));
+    if event.kind == TransitionKind::CircleWipe {
+        overlay.with_child((
+            CircleWipe,
+            Node::default(),
+            BorderColor(TRANSITION_COLOR),
+            BorderRadius::MAX,
+        ));
+    }
+}
+
+fn update_transitions(
+    mut commands: Commands,
+    mut transition_query: Query<(Entity, &mut Transition, &mut BackgroundColor)>,
+    mut wipe_query: Query<&mut Node, With<CircleWipe>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    time: Res<Time<Real>>,
+    mut covered_writer: EventWriter<TransitionCoveredEvent>,
+    mut finished_writer: EventWriter<TransitionFinishedEvent>,
+) {
+    for (entity, mut transition, mut background) in transition_query.iter_mut() {
+        transition.timer.tick(time.delta());
+        let coverage = coverage(transition.timer.fraction(), transition.covering);
+
+        match transition.kind {
+            TransitionKind::Fade => {
+                background.0 = TRANSITION_COLOR.with_alpha(coverage);
+            }
+            TransitionKind::CircleWipe => {
+                // A ring thick enough to cover the screen around a hole shrinking to nothing
+                let diagonal = window_query
+                    .single()
+                    .map_or(0.0, |window| window.size().length());
+                let hole = diagonal * (1.0 - coverage);
+                for mut node in wipe_query.iter_mut() {
+                    node.width = Val::Px(hole + diagonal * 2.0);
+                    node.height = Val::Px(hole + diagonal * 2.0);
+                    node.border = UiRect::all(Val::Px(diagonal));
+                }
+            }
+        }
+
+        if !transition.timer.finished() {
+            continue;
+        }
+        if transition.covering {
+            covered_writer.write(TransitionCoveredEvent);
+            transition.covering = false;
+            transition.timer.reset();
+        } else {
+            finished_writer.write(TransitionFinishedEvent);
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Fade in whatever a state change put on screen
+fn reveal_state_changes(
+    mut state_events: EventReader<StateTransitionEvent<GameState>>,
+    mut event_writer: EventWriter<TransitionEvent>,
+) {
+    let changed = state_events
+        .read()
+        .any(|event| event.exited.is_some() && event.exited != event.entered);
+    if changed {
+        event_writer.write(TransitionEvent::reveal(
+            TransitionKind::Fade,
+            STATE_CHANGE_TRANSITION,
+        ));
+    }
+}
+
+/// Full screen transitions, covering up levels being loaded and states being switched
+pub struct TransitionPlugin;
+
+impl Plugin for TransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TransitionEvent>()
+            .add_event::<TransitionCoveredEvent>()
+            .add_event::<TransitionFinishedEvent>()
+            .add_event::<TransitionInterruptedEvent>()
+            .add_systems(
+                Update,
+                (reveal_state_changes, start_transitions, update_transitions).chain(),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn test_transition_covers_then_reveals() {
+        let mut world = World::new();
+        world.init_resource::<Events<TransitionEvent>>();
+        world.init_resource::<Events<TransitionCoveredEvent>>();
+        world.init_resource::<Events<TransitionFinishedEvent>>();
+        world.init_resource::<Events<TransitionInterruptedEvent>>();
+        world.init_resource::<Time<Real>>();
+
+        world.send_event(TransitionEvent::cover_and_reveal(
+            TransitionKind::Fade,
+            Duration::from_millis(100),
+        ));
+        world.run_system_once(start_transitions).unwrap();
+        world.flush();
+
+        let step = |world: &mut World, elapsed: Duration| {
+            world.resource_mut::<Time<Real>>().advance_by(elapsed);
+            world.run_system_once(update_transitions).unwrap();
+            world.flush();
+        };
+        let alpha = |world: &mut World| {
+            world
+                .query::<&BackgroundColor>()
+                .single(world)
+                .unwrap()
+                .0
+                .alpha()
+        };
+
+        step(&mut world, Duration::from_millis(50));
+        assert_eq!(alpha(&mut world), 0.5);
+        assert!(
+            world
+                .resource::<Events<TransitionCoveredEvent>>()
+                .is_empty()
+        );
+
+        step(&mut world, Duration::from_millis(50));
+        assert_eq!(alpha(&mut world), 1.0);
+        assert!(
+            !world
+                .resource::<Events<TransitionCoveredEvent>>()
+                .is_empty()
+        );
+
+        step(&mut world, Duration::from_millis(100));
+        assert!(
+            !world
+                .resource::<Events<TransitionFinishedEvent>>()
+                .is_empty()
+        );
+        assert!(world.query::<&Transition>().iter(&world).next().is_none());
+    }
+
+    #[test]
+    fn test_replacing_a_covering_transition_interrupts_it() {
+        let mut world = World::new();
+        world.init_resource::<Events<TransitionEvent>>();
+        world.init_resource::<Events<TransitionInterruptedEvent>>();
+        let interrupted =
+            |world: &World| world.resource::<Events<TransitionInterruptedEvent>>().len();
+
+        world.send_event(TransitionEvent::cover_and_reveal(
+            TransitionKind::CircleWipe,
+            Duration::from_millis(100),
+        ));
+        world.run_system_once(start_transitions).unwrap();
+        world.flush();
+        assert_eq!(interrupted(&world), 0);
+
+        // Like the player dying while walking through an exit door
+        world.send_event(TransitionEvent::reveal(
+            TransitionKind::Fade,
+            Duration::from_millis(100),
+        ));
+        world.run_system_once(start_transitions).unwrap();
+        world.flush();
+        assert_eq!(interrupted(&world), 1);
+
+        // Replacing one that only reveals is no interruption
+        world.send_event(TransitionEvent::reveal(
+            TransitionKind::Fade,
+            Duration::from_millis(100),
+        ));
+        world.run_system_once(start_transitions).unwrap();
+        assert_eq!(interrupted(&world), 1);
+    }
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+/// Steps of moving a character, run in this order every fixed timestep
+///
+/// `Animation` is the exception, it runs in `Update` once the frame's fixed timesteps are done.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MovementSet {
+    /// Player controls and AI deciding where to go
+    Input,
+    Gravity,
+    /// Finding the ground, walls, ledges and ceilings touched
+    CollisionChecks,
+    /// Moving transforms by their velocities
+    ApplyVelocity,
+    /// Picking and playing animations for how the character moved
+    Animation,
+}
+
+/// Orders the `MovementSet` steps, shared by the game and the headless simulation
+pub struct SchedulePlugin;
+
+impl Plugin for SchedulePlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            FixedUpdate,
+            (
+                MovementSet::Input,
+                MovementSet::Gravity,
+                MovementSet::CollisionChecks,
+                MovementSet::ApplyVelocity,
+            )
+                .chain(),
+        );
+    }
+}
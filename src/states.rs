@@ -2,6 +2,23 @@ use bevy::prelude::*;
 
 #[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub enum GameState {
+    /// Waiting for the LDtk project and the assets it needs
     #[default]
+    Loading,
+    /// Title screen, shown once loading is done
+    Menu,
     Game,
+    /// Out of lives, waiting for a retry
+    GameOver,
+    /// Reached the level exit, showing the summary
+    LevelComplete,
+}
+
+/// Whether the game is paused, only there while in `GameState::Game`
+#[derive(SubStates, Default, Debug, Clone, PartialEq, Eq, Hash)]
+#[source(GameState = GameState::Game)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
 }
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 
-use crate::bundles::level::TileCoords;
+use crate::bundles::level::{EdgeRole, SurfaceEdges, TileCoords};
 
 #[derive(Debug, Clone)]
 pub struct Rectangle {
@@ -8,6 +8,7 @@ pub struct Rectangle {
     pub y: i64,
     pub width: i64,
     pub height: i64,
+    pub edges: SurfaceEdges,
 }
 
 impl Rectangle {
@@ -17,6 +18,7 @@ impl Rectangle {
             y,
             width,
             height,
+            edges: SurfaceEdges::default(),
         }
     }
 
@@ -41,6 +43,28 @@ impl Rectangle {
         }
         tiles
     }
+
+    /// Classify each edge of this rectangle against the original tile set.
+    /// An edge counts as exposed if any tile along it borders an empty cell.
+    ///
+    /// Tile coordinates grow downwards (LDtk convention), so the top edge
+    /// borders row `y - 1`.
+    pub fn compute_edges(&self, tiles: &HashSet<TileCoords>) -> SurfaceEdges {
+        let row_exposed =
+            |y: i64| (self.x..self.x + self.width).any(|x| !tiles.contains(&TileCoords { x, y }));
+        let column_exposed =
+            |x: i64| (self.y..self.y + self.height).any(|y| !tiles.contains(&TileCoords { x, y }));
+        let role = |exposed: bool, role: EdgeRole| {
+            if exposed { role } else { EdgeRole::Interior }
+        };
+
+        SurfaceEdges {
+            top: role(row_exposed(self.y - 1), EdgeRole::FloorTop),
+            bottom: role(row_exposed(self.y + self.height), EdgeRole::Ceiling),
+            left: role(column_exposed(self.x - 1), EdgeRole::Wall),
+            right: role(column_exposed(self.x + self.width), EdgeRole::Wall),
+        }
+    }
 }
 
 pub struct TileMerger {
@@ -63,7 +87,9 @@ impl TileMerger {
 
         while !remaining_tiles.is_empty() {
             // Find the best rectangle that can be formed from remaining tiles
-            let best_rect = self.find_best_rectangle(&remaining_tiles);
+            let mut best_rect = self.find_best_rectangle(&remaining_tiles);
+            // Edges are classified against the full tile set, not just the remaining tiles
+            best_rect.edges = best_rect.compute_edges(tiles);
 
             // Remove all tiles covered by this rectangle
             for tile in best_rect.get_covered_tiles() {
@@ -203,9 +229,19 @@ impl TileMerger {
     }
 
     /// Helper method to create physics colliders from tile set
-    pub fn create_collider_data(&self, tiles: &HashSet<TileCoords>) -> Vec<(f32, f32, f32, f32)> {
+    /// Returns (center_x, center_y, width, height, edges) in world coordinates
+    pub fn create_collider_data(
+        &self,
+        tiles: &HashSet<TileCoords>,
+    ) -> Vec<(f32, f32, f32, f32, SurfaceEdges)> {
         let rectangles = self.merge_tiles(tiles);
         self.rectangles_to_world_coords(&rectangles)
+            .into_iter()
+            .zip(rectangles.iter())
+            .map(|((center_x, center_y, width, height), rect)| {
+                (center_x, center_y, width, height, rect.edges)
+            })
+            .collect()
     }
 }
 
@@ -230,11 +266,12 @@ mod bevy_integration {
             collider_data.len()
         );
 
-        for (center_x, center_y, width, height) in collider_data {
+        for (center_x, center_y, width, height, edges) in collider_data {
             commands.spawn((
                 RigidBody::Static,
                 Collider::rectangle(width, height),
                 Transform::from_translation(Vec3::new(center_x, center_y, 0.0)),
+                edges,
             ));
         }
     }
@@ -445,4 +482,37 @@ mod tests {
         assert!(optimized_count < original_count);
         assert!(optimized_count <= 4); // Should be very efficient for this layout
     }
+
+    #[test]
+    fn test_surface_edges() {
+        let merger = TileMerger::new(32.0);
+        let mut tiles = HashSet::new();
+
+        // A 4x1 platform resting on a wider 10x3 block:
+        //    XXXX
+        // XXXXXXXXXX
+        // XXXXXXXXXX
+        // XXXXXXXXXX
+        for x in 3..7 {
+            tiles.insert(TileCoords { x, y: 0 });
+        }
+        for x in 0..10 {
+            for y in 1..4 {
+                tiles.insert(TileCoords { x, y });
+            }
+        }
+
+        let rectangles = merger.merge_tiles(&tiles);
+        assert_eq!(rectangles.len(), 2);
+
+        let platform = rectangles.iter().find(|r| r.y == 0).unwrap();
+        assert!(platform.edges.is_walkable_top());
+        assert_eq!(platform.edges.bottom, EdgeRole::Interior);
+        assert_eq!(platform.edges.left, EdgeRole::Wall);
+        assert_eq!(platform.edges.right, EdgeRole::Wall);
+
+        let block = rectangles.iter().find(|r| r.y == 1).unwrap();
+        assert!(block.edges.is_walkable_top());
+        assert_eq!(block.edges.bottom, EdgeRole::Ceiling);
+    }
 }